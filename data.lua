@@ -71,6 +71,8 @@ return {
         image - Included as a comment only, this is the name of the item image
                 as stored in the game files. It should be renamed to the item's
                 name when uploaded.
+        icon - Path of the item icon, relative to the directory passed as
+               --icons-dir to dsp-tool. Only used for the graph.
     ]]
     game_items = {
         [1000] = {
@@ -1309,6 +1311,10 @@ return {
                      example).
         handcraft - Can it be made in the replicator?
         seconds - How long to craft at 1x speed.
+        non_productive - If true, sprayed inputs can only speed up the recipe, but
+                         not increase the number of products. Omitted when false.
+        productivity - Factor applied to the outputs, for mods that model bonus
+                       output this way. Omitted when 1.
         explicit - Whether this is an "explicit" recipe, as opposed to the
                    implicit or "primary" recipe for an item. You can tell the
                    difference in-game because hovering over an explicit recipe
@@ -1617,6 +1623,7 @@ return {
             grid_index=1410,
             handcraft=true,
             seconds=4,
+            non_productive=true,
         },{
             id=96,
             name='Logistics Vessel',
@@ -1626,6 +1633,7 @@ return {
             grid_index=1411,
             handcraft=true,
             seconds=6,
+            non_productive=true,
         },{
             id=97,
             name='Electric Motor',
@@ -1797,6 +1805,7 @@ return {
             grid_index=1407,
             handcraft=false,
             seconds='.017',
+            non_productive=true,
             explicit=true,
             description='The successful fractionation of deuterium from liquid hydrogen has greatly promoted the use of nuclear fuel.',
             --image='deuterium-formula'
@@ -2058,6 +2067,7 @@ return {
             grid_index=2104,
             handcraft=true,
             seconds=4,
+            non_productive=true,
         },{
             id=8,
             name='Tesla Tower',
@@ -2067,6 +2077,7 @@ return {
             grid_index=2101,
             handcraft=true,
             seconds=1,
+            non_productive=true,
         },{
             id=10,
             name='Matrix Lab',
@@ -2076,6 +2087,7 @@ return {
             grid_index=2408,
             handcraft=true,
             seconds=3,
+            non_productive=true,
         },{
             id=13,
             name='Wireless Power Tower',
@@ -2085,6 +2097,7 @@ return {
             grid_index=2102,
             handcraft=true,
             seconds=3,
+            non_productive=true,
         },{
             id=14,
             name='Oil Extractor',
@@ -2094,6 +2107,7 @@ return {
             grid_index=2307,
             handcraft=true,
             seconds=8,
+            non_productive=true,
         },{
             id=15,
             name='Oil Refinery',
@@ -2103,6 +2117,7 @@ return {
             grid_index=2308,
             handcraft=true,
             seconds=6,
+            non_productive=true,
         },{
             id=22,
             name='Chemical Plant',
@@ -2112,6 +2127,7 @@ return {
             grid_index=2407,
             handcraft=true,
             seconds=5,
+            non_productive=true,
         },{
             id=39,
             name='Miniature Particle Collider',
@@ -2121,6 +2137,7 @@ return {
             grid_index=2310,
             handcraft=true,
             seconds=15,
+            non_productive=true,
         },{
             id=43,
             name='Artificial Star',
@@ -2130,6 +2147,7 @@ return {
             grid_index=2112,
             handcraft=true,
             seconds=30,
+            non_productive=true,
         },{
             id=45,
             name='Assembling Machine Mk.I',
@@ -2139,6 +2157,7 @@ return {
             grid_index=2401,
             handcraft=true,
             seconds=2,
+            non_productive=true,
         },{
             id=46,
             name='Assembling Machine Mk.II',
@@ -2148,6 +2167,7 @@ return {
             grid_index=2402,
             handcraft=true,
             seconds=3,
+            non_productive=true,
         },{
             id=47,
             name='Assembling Machine Mk.III',
@@ -2157,6 +2177,7 @@ return {
             grid_index=2403,
             handcraft=true,
             seconds=4,
+            non_productive=true,
         },{
             id=48,
             name='Mining Machine',
@@ -2166,6 +2187,7 @@ return {
             grid_index=2304,
             handcraft=true,
             seconds=3,
+            non_productive=true,
         },{
             id=49,
             name='Water Pump',
@@ -2175,6 +2197,7 @@ return {
             grid_index=2306,
             handcraft=true,
             seconds=4,
+            non_productive=true,
         },{
             id=56,
             name='Arc Smelter',
@@ -2184,6 +2207,7 @@ return {
             grid_index=2404,
             handcraft=true,
             seconds=3,
+            non_productive=true,
         },{
             id=64,
             name='Thermal Power Plant',
@@ -2193,6 +2217,7 @@ return {
             grid_index=2105,
             handcraft=true,
             seconds=5,
+            non_productive=true,
         },{
             id=67,
             name='Solar Panel',
@@ -2202,6 +2227,7 @@ return {
             grid_index=2106,
             handcraft=true,
             seconds=6,
+            non_productive=true,
         },{
             id=71,
             name='EM-Rail Ejector',
@@ -2211,6 +2237,7 @@ return {
             grid_index=2311,
             handcraft=true,
             seconds=6,
+            non_productive=true,
         },{
             id=72,
             name='Ray Receiver',
@@ -2220,6 +2247,7 @@ return {
             grid_index=2111,
             handcraft=true,
             seconds=8,
+            non_productive=true,
         },{
             id=73,
             name='Satellite Substation',
@@ -2229,6 +2257,7 @@ return {
             grid_index=2103,
             handcraft=true,
             seconds=5,
+            non_productive=true,
         },{
             id=76,
             name='Accumulator',
@@ -2238,6 +2267,7 @@ return {
             grid_index=2107,
             handcraft=true,
             seconds=5,
+            non_productive=true,
         },{
             id=77,
             name='Energy Exchanger',
@@ -2247,6 +2277,7 @@ return {
             grid_index=2110,
             handcraft=true,
             seconds=15,
+            non_productive=true,
         },{
             id=82,
             name='Vertical Launching Silo',
@@ -2256,6 +2287,7 @@ return {
             grid_index=2312,
             handcraft=true,
             seconds=30,
+            non_productive=true,
         },{
             id=84,
             name='Conveyor Belt MK.I',
@@ -2265,6 +2297,7 @@ return {
             grid_index=2201,
             handcraft=true,
             seconds=1,
+            non_productive=true,
         },{
             id=89,
             name='Conveyor Belt MK.II',
@@ -2274,6 +2307,7 @@ return {
             grid_index=2202,
             handcraft=true,
             seconds=1,
+            non_productive=true,
         },{
             id=92,
             name='Conveyor Belt MK.III',
@@ -2283,6 +2317,7 @@ return {
             grid_index=2203,
             handcraft=true,
             seconds=1,
+            non_productive=true,
         },{
             id=85,
             name='Sorter MK.I',
@@ -2292,6 +2327,7 @@ return {
             grid_index=2301,
             handcraft=true,
             seconds=1,
+            non_productive=true,
         },{
             id=88,
             name='Sorter MK.II',
@@ -2301,6 +2337,7 @@ return {
             grid_index=2302,
             handcraft=true,
             seconds=1,
+            non_productive=true,
         },{
             id=90,
             name='Sorter MK.III',
@@ -2310,6 +2347,7 @@ return {
             grid_index=2303,
             handcraft=true,
             seconds=1,
+            non_productive=true,
         },{
             id=86,
             name='Storage MK.I',
@@ -2319,6 +2357,7 @@ return {
             grid_index=2207,
             handcraft=true,
             seconds=2,
+            non_productive=true,
         },{
             id=91,
             name='Storage MK.II',
@@ -2328,6 +2367,7 @@ return {
             grid_index=2208,
             handcraft=true,
             seconds=4,
+            non_productive=true,
         },{
             id=87,
             name='Splitter',
@@ -2337,6 +2377,7 @@ return {
             grid_index=2204,
             handcraft=true,
             seconds=2,
+            non_productive=true,
         },{
             id=93,
             name='Planetary Logistics Station',
@@ -2346,6 +2387,7 @@ return {
             grid_index=2210,
             handcraft=true,
             seconds=20,
+            non_productive=true,
         },{
             id=95,
             name='Interstellar Logistics Station',
@@ -2355,6 +2397,7 @@ return {
             grid_index=2211,
             handcraft=true,
             seconds=30,
+            non_productive=true,
         },{
             id=109,
             name='Spray Coater',
@@ -2364,6 +2407,7 @@ return {
             grid_index=2406,
             handcraft=true,
             seconds=3,
+            non_productive=true,
         },{
             id=110,
             name='Fractionator',
@@ -2373,6 +2417,7 @@ return {
             grid_index=2309,
             handcraft=true,
             seconds=3,
+            non_productive=true,
         },{
             id=111,
             name='Orbital Collector',
@@ -2382,6 +2427,7 @@ return {
             grid_index=2212,
             handcraft=true,
             seconds=30,
+            non_productive=true,
         },{
             id=113,
             name='Mini Fusion Power Plant',
@@ -2391,6 +2437,7 @@ return {
             grid_index=2109,
             handcraft=true,
             seconds=10,
+            non_productive=true,
         },{
             id=114,
             name='Storage Tank',
@@ -2400,6 +2447,7 @@ return {
             grid_index=2209,
             handcraft=true,
             seconds=2,
+            non_productive=true,
         },{
             id=116,
             name='Plane Smelter',
@@ -2409,6 +2457,7 @@ return {
             grid_index=2405,
             handcraft=true,
             seconds=5,
+            non_productive=true,
         },{
             id=117,
             name='Traffic Monitor',
@@ -2418,6 +2467,7 @@ return {
             grid_index=2206,
             handcraft=true,
             seconds=2,
+            non_productive=true,
         },{
             id=118,
             name='Geothermal Power Station',
@@ -2427,6 +2477,7 @@ return {
             grid_index=2108,
             handcraft=true,
             seconds=6,
+            non_productive=true,
         },{
             id=119,
             name='Advanced Mining Machine',
@@ -2436,6 +2487,7 @@ return {
             grid_index=2305,
             handcraft=true,
             seconds=20,
+            non_productive=true,
         },{
             id=120,
             name='Automatic Piler',
@@ -2445,6 +2497,7 @@ return {
             grid_index=2205,
             handcraft=true,
             seconds=4,
+            non_productive=true,
         },
     },
 
@@ -4741,6 +4794,24 @@ return {
         RESEARCH={name='Research Facility', power=480000, buildings={2901}},  --Matrix lab
     },
 
+    -- This is a map from the item id of a production building to its name, the
+    -- recipe types it is able to craft, its crafting speed and its power
+    -- consumption. The speed is relative to the recipe time (1 if omitted).
+    -- Power is measured in kW, work_power while the building is crafting and
+    -- idle_power while it is waiting for inputs.
+    game_buildings = {
+        [2302] = {name='Arc Smelter', types={'SMELT'}, speed=1, work_power=360, idle_power=12},
+        [2303] = {name='Assembling Machine Mk.I', types={'ASSEMBLE'}, speed=0.75, work_power=270, idle_power=12},
+        [2304] = {name='Assembling Machine Mk.II', types={'ASSEMBLE'}, speed=1, work_power=480, idle_power=15},
+        [2305] = {name='Assembling Machine Mk.III', types={'ASSEMBLE'}, speed=1.5, work_power=780, idle_power=18},
+        [2308] = {name='Oil Refinery', types={'REFINE'}, speed=1, work_power=960, idle_power=24},
+        [2309] = {name='Chemical Plant', types={'CHEMICAL'}, speed=1, work_power=720, idle_power=24},
+        [2310] = {name='Miniature Particle Collider', types={'PARTICLE'}, speed=1, work_power=12000, idle_power=120},
+        [2314] = {name='Fractionator', types={'FRACTIONATE'}, speed=1, work_power=720, idle_power=18},
+        [2315] = {name='Plane Smelter', types={'SMELT'}, speed=2, work_power=1440, idle_power=48},
+        [2901] = {name='Matrix Lab', types={'RESEARCH'}, speed=1, work_power=480, idle_power=12},
+    },
+
     -- This is just an array of what you start out being able to craft.
     -- (Both in the replicator, and in buildings once you get them.)
     starting_recipes = {1, 2, 3, 4, 5, 6, 50},
@@ -0,0 +1,297 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+
+use itertools::Itertools;
+
+use crate::data::{Data, ItemId, RecipeId};
+
+/* Node */
+
+/// Node of the bipartite item/recipe graph.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum Node {
+    Item(ItemId),
+    Recipe(RecipeId),
+}
+
+/* Tier */
+
+/// Minimum and maximum recipe chain depth of an item.
+///
+/// Items that are not produced by any recipe have tier 0.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Tier {
+    pub min: usize,
+    pub max: usize,
+}
+
+/// Calculate the tiers of all items that are used by the passed recipes.
+///
+/// Items that are part of the same cycle share the same tier.
+pub fn tiers(data: &Data, recipes: &BTreeSet<RecipeId>) -> BTreeMap<ItemId, Tier> {
+    let graph = RecipeGraph::new(data, recipes);
+
+    let mut producers = BTreeMap::<ItemId, Vec<RecipeId>>::new();
+    for rid in recipes {
+        if let Some(recipe) = data.recipes.get(rid) {
+            for output in &recipe.outputs {
+                producers.entry(output.id).or_default().push(*rid);
+            }
+        }
+    }
+
+    let mut ret = BTreeMap::<ItemId, Tier>::new();
+    for component in graph.strongly_connected_components().into_iter().rev() {
+        let members = component
+            .iter()
+            .filter_map(|node| match node {
+                Node::Item(iid) => Some(*iid),
+                Node::Recipe(_) => None,
+            })
+            .collect::<BTreeSet<_>>();
+
+        let mut tier: Option<Tier> = None;
+        for iid in &members {
+            for rid in producers.get(iid).into_iter().flatten() {
+                let recipe = &data.recipes[rid];
+                let mut depth = Tier { min: 0, max: 0 };
+                for input in &recipe.inputs {
+                    if let Some(t) = ret.get(&input.id).filter(|_| !members.contains(&input.id)) {
+                        depth.min = depth.min.max(t.min);
+                        depth.max = depth.max.max(t.max);
+                    }
+                }
+
+                tier = Some(match tier {
+                    Some(t) => Tier {
+                        min: t.min.min(depth.min + 1),
+                        max: t.max.max(depth.max + 1),
+                    },
+                    None => Tier {
+                        min: depth.min + 1,
+                        max: depth.max + 1,
+                    },
+                });
+            }
+        }
+
+        let tier = tier.unwrap_or(Tier { min: 0, max: 0 });
+        for iid in members {
+            ret.insert(iid, tier);
+        }
+    }
+
+    ret
+}
+
+/* Reachability */
+
+/// Items and recipes that can be produced from a set of available items.
+#[derive(Debug, Clone, Default)]
+pub struct Reachability {
+    /// Reachable items and the number of recipe steps that are needed to
+    /// produce them. Available items have 0 steps.
+    pub items: BTreeMap<ItemId, usize>,
+
+    /// Recipes whose inputs are all reachable.
+    pub recipes: BTreeSet<RecipeId>,
+
+    /// Recipes that are not reachable because exactly one of their inputs is
+    /// missing, with the missing input.
+    pub almost: BTreeMap<RecipeId, ItemId>,
+}
+
+/// Calculate which of the passed recipes can be crafted, starting with the
+/// available items.
+///
+/// In each step all recipes whose inputs are reachable are added, and their
+/// outputs become reachable, until no new recipe is found.
+pub fn reachable(
+    data: &Data,
+    available: &BTreeSet<ItemId>,
+    recipes: &BTreeSet<RecipeId>,
+) -> Reachability {
+    let mut ret = Reachability {
+        items: available.iter().map(|iid| (*iid, 0)).collect(),
+        ..Default::default()
+    };
+
+    for step in 1.. {
+        let next = recipes
+            .iter()
+            .filter(|rid| !ret.recipes.contains(rid))
+            .filter_map(|rid| data.recipes.get_key_value(rid))
+            .filter(|(_, r)| r.inputs.iter().all(|i| ret.items.contains_key(&i.id)))
+            .collect::<Vec<_>>();
+        if next.is_empty() {
+            break;
+        }
+
+        for (rid, recipe) in next {
+            ret.recipes.insert(*rid);
+            for output in &recipe.outputs {
+                ret.items.entry(output.id).or_insert(step);
+            }
+        }
+    }
+
+    for rid in recipes.iter().filter(|rid| !ret.recipes.contains(rid)) {
+        let recipe = match data.recipes.get(rid) {
+            Some(recipe) => recipe,
+            None => continue,
+        };
+
+        let mut missing = recipe
+            .inputs
+            .iter()
+            .map(|i| i.id)
+            .filter(|iid| !ret.items.contains_key(iid))
+            .unique();
+        if let (Some(iid), None) = (missing.next(), missing.next()) {
+            ret.almost.insert(*rid, iid);
+        }
+    }
+
+    ret
+}
+
+/* RecipeGraph */
+
+/// Directed graph of items and recipes.
+///
+/// Items point to the recipes that consume them, recipes point to the items
+/// they produce.
+#[derive(Debug, Default)]
+pub struct RecipeGraph {
+    pub edges: BTreeMap<Node, BTreeSet<Node>>,
+}
+
+impl RecipeGraph {
+    /// Create the graph that is formed by the passed recipes.
+    pub fn new(data: &Data, recipes: &BTreeSet<RecipeId>) -> Self {
+        let mut edges = BTreeMap::<Node, BTreeSet<Node>>::new();
+
+        for rid in recipes {
+            if let Some(recipe) = data.recipes.get(rid) {
+                let node = Node::Recipe(*rid);
+                edges.entry(node).or_default();
+
+                for input in &recipe.inputs {
+                    edges.entry(Node::Item(input.id)).or_default().insert(node);
+                }
+
+                for output in &recipe.outputs {
+                    edges.entry(Node::Item(output.id)).or_default();
+                    edges.get_mut(&node).unwrap().insert(Node::Item(output.id));
+                }
+            }
+        }
+
+        Self { edges }
+    }
+
+    /// Get the strongly connected components of the graph.
+    ///
+    /// The components are returned in reverse topological order, i.e. a
+    /// component is returned after all components that are reachable from it.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<Node>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            index: 0,
+            indices: BTreeMap::new(),
+            low_links: BTreeMap::new(),
+            stack: Vec::new(),
+            on_stack: BTreeSet::new(),
+            components: Vec::new(),
+        };
+
+        for node in self.edges.keys() {
+            if !tarjan.indices.contains_key(node) {
+                tarjan.visit(*node);
+            }
+        }
+
+        tarjan.components
+    }
+
+    /// Find the shortest cycle that starts and ends at `start` and only
+    /// passes nodes in `allowed`.
+    pub fn shortest_cycle(&self, start: Node, allowed: &BTreeSet<Node>) -> Option<Vec<Node>> {
+        let mut parents = BTreeMap::<Node, Node>::new();
+        let mut queue = VecDeque::from([start]);
+
+        while let Some(node) = queue.pop_front() {
+            for next in self.edges.get(&node).into_iter().flatten() {
+                if !allowed.contains(next) {
+                    continue;
+                }
+
+                if *next == start {
+                    let mut cycle = vec![node];
+                    let mut current = node;
+                    while current != start {
+                        current = parents[&current];
+                        cycle.push(current);
+                    }
+                    cycle.reverse();
+
+                    return Some(cycle);
+                }
+
+                if !parents.contains_key(next) {
+                    parents.insert(*next, node);
+                    queue.push_back(*next);
+                }
+            }
+        }
+
+        None
+    }
+}
+
+struct Tarjan<'a> {
+    graph: &'a RecipeGraph,
+    index: usize,
+    indices: BTreeMap<Node, usize>,
+    low_links: BTreeMap<Node, usize>,
+    stack: Vec<Node>,
+    on_stack: BTreeSet<Node>,
+    components: Vec<Vec<Node>>,
+}
+
+impl Tarjan<'_> {
+    fn visit(&mut self, node: Node) {
+        self.indices.insert(node, self.index);
+        self.low_links.insert(node, self.index);
+        self.index += 1;
+        self.stack.push(node);
+        self.on_stack.insert(node);
+
+        for next in self.graph.edges.get(&node).into_iter().flatten() {
+            if !self.indices.contains_key(next) {
+                self.visit(*next);
+
+                let low = self.low_links[&node].min(self.low_links[next]);
+                self.low_links.insert(node, low);
+            } else if self.on_stack.contains(next) {
+                let low = self.low_links[&node].min(self.indices[next]);
+                self.low_links.insert(node, low);
+            }
+        }
+
+        if self.low_links[&node] == self.indices[&node] {
+            let mut component = Vec::new();
+            loop {
+                let member = self.stack.pop().unwrap();
+                self.on_stack.remove(&member);
+                component.push(member);
+
+                if member == node {
+                    break;
+                }
+            }
+            component.sort();
+
+            self.components.push(component);
+        }
+    }
+}
@@ -1,9 +1,243 @@
+use std::env::var_os;
+use std::path::{Path, PathBuf};
+
+use log::LevelFilter;
 use structopt::StructOpt;
 
-use crate::commands::Command;
+use crate::{
+    commands::Command, data::DataArg, error::EXIT_CODES, logging::LogFormat, table::ColorChoice,
+};
 
 #[derive(Debug, StructOpt)]
+#[structopt(
+    about = "Production graphs, plans and statistics for the recipes of Dyson Sphere Program",
+    after_help = EXIT_CODES
+)]
 pub struct Args {
+    #[structopt(flatten)]
+    pub data: DataArgs,
+
+    /// Log more details, pass twice to log everything.
+    #[structopt(short = "v", long = "verbose", global = true, parse(from_occurrences))]
+    pub verbose: u8,
+
+    /// Only log warnings and errors.
+    #[structopt(short = "q", long = "quiet", global = true, conflicts_with = "verbose")]
+    pub quiet: bool,
+
+    /// Format of the log messages (text or json). With json every message
+    /// is written as one JSON object per line.
+    #[structopt(long = "log-format", global = true, default_value = "text")]
+    pub log_format: LogFormat,
+
+    /// Color the tables of the text output (auto, always or never). `auto`
+    /// uses colors if stdout is a terminal and `NO_COLOR` is not set.
+    #[structopt(long = "color", global = true, default_value = "auto")]
+    pub color: ColorChoice,
+
+    /// Do not truncate the tables to the width of the terminal.
+    #[structopt(long = "wide", global = true)]
+    pub wide: bool,
+
+    /// Config file to load the defaults from (`dsp-tool.toml` in the working
+    /// directory or the platform config directory if not set).
+    #[structopt(long = "config", global = true, conflicts_with = "no-config")]
+    pub config: Option<PathBuf>,
+
+    /// Do not load any config file.
+    #[structopt(long = "no-config", global = true)]
+    pub no_config: bool,
+
+    /// TOML file with named selector groups (`name = ["selector", ...]`)
+    /// that can be referenced as `@name`.
+    #[structopt(long = "groups", global = true)]
+    pub groups: Option<PathBuf>,
+
+    /// Only warn about selector patterns that do not match anything.
+    #[structopt(long = "allow-empty-match", global = true)]
+    pub allow_empty_match: bool,
+
+    /// File with selectors that are added to the `--ignore` selectors of the
+    /// command, one per line (`#` starts a comment line).
+    #[structopt(long = "ignore-file", global = true, number_of_values = 1)]
+    pub ignore_files: Vec<PathBuf>,
+
+    /// Print the ignored items and recipes of the command line and the
+    /// ignore files to stderr.
+    #[structopt(long = "print-effective-ignores", global = true)]
+    pub print_effective_ignores: bool,
+
     #[structopt(subcommand)]
     pub command: Command,
 }
+
+// Arguments that control how the data is loaded. No doc comment, structopt
+// would use it as the description of the whole tool.
+#[derive(Debug, StructOpt)]
+pub struct DataArgs {
+    /// Files to load the product data and recipes from (`$DSP_TOOL_DATA` or
+    /// the first `data.lua` that is found if not set). Later files add to and override the entries of earlier files.
+    /// With the `remote-data` feature this may also be an http(s) URL.
+    #[structopt(short = "d", long = "data", global = true, number_of_values = 1)]
+    pub data_paths: Vec<PathBuf>,
+
+    /// Game version to load from data files that contain multiple versions
+    /// (the newest version if not set).
+    #[structopt(long = "game-version", global = true)]
+    pub game_version: Option<String>,
+
+    /// Argument for data files that return a function, given as `key=value`.
+    /// The function is called with a table of all arguments. `true`, `false`
+    /// and numbers are passed as booleans and numbers, everything else as
+    /// string.
+    #[structopt(long = "data-arg", global = true, number_of_values = 1)]
+    pub data_args: Vec<DataArg>,
+
+    /// Never use the embedded data, even if no data file exists.
+    #[structopt(long = "no-embedded", global = true)]
+    pub no_embedded: bool,
+
+    /// Fail if recipes reference items that do not exist in the data.
+    #[structopt(long = "strict-data", global = true)]
+    pub strict_data: bool,
+
+    /// Fail if items or recipes have a type that is not known to the tool.
+    #[structopt(long = "strict-types", global = true)]
+    pub strict_types: bool,
+
+    /// Use the recipes that are marked as disabled in the data.
+    #[structopt(long = "include-disabled", global = true)]
+    pub include_disabled: bool,
+
+    /// File to load additional translations from.
+    #[structopt(long = "locale", global = true)]
+    pub locale_path: Option<PathBuf>,
+
+    /// Language to translate the item and recipe names to.
+    #[structopt(long = "language", global = true)]
+    pub language: Option<String>,
+
+    /// Cache the evaluated data files and use the cache as long as the files
+    /// do not change.
+    #[structopt(long = "cache", global = true)]
+    pub cache: bool,
+
+    /// Always evaluate the data files, do not read or write the cache (even
+    /// if the config enables it).
+    #[structopt(long = "no-cache", global = true, conflicts_with = "cache")]
+    pub no_cache: bool,
+
+    /// Evaluate the data files even if they are cached and update the cache.
+    /// Implies --cache.
+    #[structopt(long = "refresh-cache", global = true, conflicts_with = "no-cache")]
+    pub refresh_cache: bool,
+
+    /// Never download data files, only use the ones that were downloaded
+    /// before.
+    #[structopt(long = "offline", global = true)]
+    pub offline: bool,
+}
+
+impl Args {
+    /// Log level that is selected by the verbosity flags.
+    pub fn log_level(&self) -> LevelFilter {
+        match (self.quiet, self.verbose) {
+            (true, _) => LevelFilter::Warn,
+            (false, 0) => LevelFilter::Info,
+            (false, 1) => LevelFilter::Debug,
+            (false, _) => LevelFilter::Trace,
+        }
+    }
+}
+
+impl DataArgs {
+    /// Name of the data file that is searched if no `--data` is passed.
+    pub const DEFAULT_DATA_PATH: &'static str = "data.lua";
+
+    /// Environment variable that selects the data file if no `--data` is
+    /// passed.
+    pub const DATA_ENV: &'static str = "DSP_TOOL_DATA";
+
+    /// Files that are tried, in this order, if no `--data` is passed:
+    /// `$DSP_TOOL_DATA`, `data.lua` in the working directory and `data.lua`
+    /// in the platform config and data directories.
+    pub fn default_data_paths() -> Vec<PathBuf> {
+        let mut paths = Vec::new();
+
+        if let Some(path) = var_os(Self::DATA_ENV) {
+            paths.push(PathBuf::from(path));
+        }
+
+        paths.push(Self::DEFAULT_DATA_PATH.into());
+
+        for dir in [dirs::config_dir(), dirs::data_dir()].into_iter().flatten() {
+            let path = dir.join("dsp-tool").join(Self::DEFAULT_DATA_PATH);
+            if !paths.contains(&path) {
+                paths.push(path);
+            }
+        }
+
+        paths
+    }
+
+    /// First of the default data files that exists.
+    pub fn find_default_data() -> Option<PathBuf> {
+        Self::default_data_paths()
+            .into_iter()
+            .find(|path| path.is_file())
+    }
+
+    /// All local files the data is loaded from.
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut files = self
+            .data_paths
+            .iter()
+            .filter(|path| !Self::is_url(path))
+            .cloned()
+            .collect::<Vec<_>>();
+        if self.data_paths.is_empty() {
+            files.push(Self::find_default_data().unwrap_or_else(|| Self::DEFAULT_DATA_PATH.into()));
+        }
+
+        files.extend(self.locale_path.clone());
+
+        files
+    }
+
+    /// Whether the passed data path is an http(s) URL.
+    pub fn is_url(path: &Path) -> bool {
+        path.to_str()
+            .map(|s| s.starts_with("http://") || s.starts_with("https://"))
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn help_describes_the_tool_and_the_subcommands() {
+        let mut help = Vec::new();
+        Args::clap().write_help(&mut help).unwrap();
+        let help = String::from_utf8(help).unwrap();
+
+        assert_eq!(
+            help.lines().nth(1),
+            Some("Production graphs, plans and statistics for the recipes of Dyson Sphere Program")
+        );
+
+        let (_, subcommands) = help.split_once("SUBCOMMANDS:").unwrap();
+        let (subcommands, _) = subcommands.split_once("EXIT CODES:").unwrap();
+        let names = subcommands
+            .lines()
+            .filter(|line| line.starts_with("    ") && !line.starts_with("     "));
+        for line in names {
+            assert!(
+                line.split_whitespace().count() > 1,
+                "no help for {:?}",
+                line
+            );
+        }
+    }
+}
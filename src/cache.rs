@@ -0,0 +1,225 @@
+use std::fs::{create_dir_all, read, rename, write};
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+use bincode::{ErrorKind, Options};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    data::{fnv, Data, DataArg, Fnv},
+    error::Error,
+};
+
+/// Version of the encoding of the cached data, increased whenever it changes
+/// (e.g. the string keys that are stored with the data).
+const FORMAT: u32 = 5;
+
+/// Describes the data file a cache entry was created from. The entry is only
+/// used if all fields match the current data file.
+#[derive(Debug, Eq, PartialEq, Serialize, Deserialize)]
+struct Header {
+    format: u32,
+    version: String,
+    path: PathBuf,
+    size: u64,
+    hash: u64,
+}
+
+impl Header {
+    /// The selected game version and the data arguments are part of the
+    /// hash, so the entries of files with multiple versions or a data
+    /// function are only used for the same version and arguments.
+    fn new(path: &Path, source: &str, game_version: Option<&str>, data_args: &[DataArg]) -> Self {
+        // Every part ends with a byte that is never part of UTF-8, so the
+        // parts can not run into each other.
+        let mut hasher = Fnv::default();
+        let mut add = |part: &str| {
+            hasher.write(part.as_bytes());
+            hasher.write_u8(0xff);
+        };
+        add(source);
+        add(game_version.unwrap_or_default());
+        for arg in data_args {
+            add(&arg.to_string());
+        }
+
+        Self {
+            format: FORMAT,
+            version: env!("CARGO_PKG_VERSION").into(),
+            path: path.to_owned(),
+            size: source.len() as u64,
+            hash: hasher.finish(),
+        }
+    }
+}
+
+/// Load the cached data of the passed data file. Returns `None` if there is
+/// no cache entry, if it was created from a different version of the file or
+/// if it could not be read.
+pub fn load(
+    path: &Path,
+    source: &str,
+    game_version: Option<&str>,
+    data_args: &[DataArg],
+) -> Option<Data> {
+    let file = cache_file(path)?;
+    let bytes = match read(&file) {
+        Ok(bytes) => bytes,
+        Err(err) => {
+            log::debug!("  no cache file {:#?}: {}", file, err);
+
+            return None;
+        }
+    };
+
+    match decode(
+        &bytes,
+        &Header::new(&canonical(path), source, game_version, data_args),
+    ) {
+        Ok(None) => {
+            log::debug!("  cache file {:#?} is outdated", file);
+
+            None
+        }
+        Ok(data) => data,
+        Err(err) => {
+            log::warn!(path:% = file.display(); "Ignore invalid cache file {:#?}: {}", file, err);
+
+            None
+        }
+    }
+}
+
+/// Store the passed data in the cache of the passed data file. Errors are
+/// only logged, the cache is never required to run a command.
+pub fn store(
+    path: &Path,
+    source: &str,
+    game_version: Option<&str>,
+    data_args: &[DataArg],
+    data: &Data,
+) {
+    let file = match cache_file(path) {
+        Some(file) => file,
+        None => return,
+    };
+
+    match encode(
+        &file,
+        &Header::new(&canonical(path), source, game_version, data_args),
+        data,
+    ) {
+        Ok(()) => log::debug!("  stored data in cache file {:#?}", file),
+        Err(err) => {
+            log::warn!(path:% = file.display(); "Unable to write cache file {:#?}: {}", file, err)
+        }
+    }
+}
+
+fn decode(bytes: &[u8], expected: &Header) -> Result<Option<Data>, Error> {
+    let options = options(bytes.len() as u64);
+
+    let mut reader = bytes;
+    let header = options
+        .deserialize_from::<_, Header>(&mut reader)
+        .map_err(invalid)?;
+    if header != *expected {
+        return Ok(None);
+    }
+
+    let data = options.deserialize(reader).map_err(invalid)?;
+
+    Ok(Some(data))
+}
+
+fn invalid(err: bincode::Error) -> Error {
+    match err.as_ref() {
+        ErrorKind::Io(_) => Error::custom("unexpected end of file"),
+        _ => Error::custom(err),
+    }
+}
+
+fn encode(file: &Path, header: &Header, data: &Data) -> Result<(), Error> {
+    let options = options(u64::MAX);
+
+    let mut bytes = options.serialize(header).map_err(Error::custom)?;
+    bytes.extend(options.serialize(data).map_err(Error::custom)?);
+
+    if let Some(dir) = file.parent() {
+        create_dir_all(dir)?;
+    }
+
+    let tmp = file.with_extension("tmp");
+    write(&tmp, bytes)?;
+    rename(&tmp, file)?;
+
+    Ok(())
+}
+
+/// Encoding of the cache files, limited to `limit` bytes to not allocate huge
+/// buffers for corrupted length prefixes.
+fn options(limit: u64) -> impl Options + Copy {
+    bincode::DefaultOptions::new().with_limit(limit)
+}
+
+/// Path of the cache file for the passed data file.
+fn cache_file(path: &Path) -> Option<PathBuf> {
+    let hash = fnv(canonical(path).as_os_str().as_encoded_bytes());
+    let dir = dirs::cache_dir()?.join("dsp-tool");
+
+    Some(dir.join(format!("{:016x}.bin", hash)))
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs::remove_file;
+    use std::process::id;
+
+    use super::*;
+
+    const SOURCE: &str = include_str!("../tests/data/small.lua");
+
+    fn header(game_version: Option<&str>, data_args: &[&str]) -> Header {
+        let data_args = data_args
+            .iter()
+            .map(|arg| arg.parse().unwrap())
+            .collect::<Vec<DataArg>>();
+
+        Header::new(Path::new("small.lua"), SOURCE, game_version, &data_args)
+    }
+
+    #[test]
+    fn header_depends_on_the_inputs() {
+        let plain = header(None, &[]);
+        assert_eq!(plain, header(None, &[]));
+        assert_eq!(plain.size, SOURCE.len() as u64);
+
+        assert_ne!(plain.hash, header(Some("0.10"), &[]).hash);
+        assert_ne!(plain.hash, header(None, &["x=1"]).hash);
+        assert_ne!(header(Some("x=1"), &[]).hash, header(None, &["x=1"]).hash);
+    }
+
+    #[test]
+    fn cached_data_round_trips() {
+        let data = Data::from_lua_source("small", SOURCE, None, &[]).unwrap();
+        let file = temp_dir().join(format!("dsp-tool-cache-{}.bin", id()));
+
+        encode(&file, &header(None, &[]), &data).unwrap();
+        let bytes = read(&file).unwrap();
+        remove_file(&file).unwrap();
+
+        let cached = decode(&bytes, &header(None, &[])).unwrap().unwrap();
+        assert_eq!(cached.items.len(), data.items.len());
+        assert_eq!(cached.as_output, data.as_output);
+
+        assert!(decode(&bytes, &header(Some("0.10"), &[]))
+            .unwrap()
+            .is_none());
+        assert!(decode(&bytes[..bytes.len() / 2], &header(None, &[])).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use std::collections::BTreeSet;
+
+use itertools::Itertools;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    data::{Data, IdLabel, ItemId, RecipeId},
+    error::Error,
+    format::{csv_line, Format},
+    planner::{Objective, PlanOptions, ProductionPlan},
+    resolver::{PinnedList, PreferBasic, PreferExplicit, RecipeSelector},
+    selector::{parse_ids, parse_ignore, Selection, SelectorContext},
+    table::{Cell, Color, Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Alternatives {
+    /// Items to rank the producing recipes for (all items with multiple
+    /// recipes if not set).
+    #[structopt(short = "i", long = "items")]
+    pub items: Vec<String>,
+
+    /// Recipes to exclude from the calculation.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Output format (text, json or csv).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    item_id: IdLabel,
+    item: String,
+    recipe_id: IdLabel,
+    recipe: String,
+    rank: usize,
+
+    /// Raw resources per unit of the item, with the primary recipes
+    /// upstream. `None` if the plan can not be calculated.
+    cost: Option<f64>,
+
+    /// Difference to the cheapest recipe in percent.
+    difference: Option<f64>,
+
+    /// Raw resources per unit of the item, with the explicit recipes
+    /// upstream.
+    explicit_cost: Option<f64>,
+
+    /// Whether the rank changes if the explicit recipes are used upstream.
+    depends_on_upstream: bool,
+}
+
+impl Alternatives {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext, style: &Style) -> Result<(), Error> {
+        let Self {
+            items,
+            ignore,
+            format,
+        } = self;
+
+        log::info!("Parse items");
+        let items = if items.is_empty() {
+            data.as_output.keys().copied().collect::<BTreeSet<_>>()
+        } else {
+            parse_ids(data, ctx, &items, true)?.items
+        };
+        log::info!("  loaded {} items", items.len());
+
+        log::info!("Parse ignored recipes");
+        let ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        log::info!("Rank alternative recipes");
+        let rows = items
+            .iter()
+            .flat_map(|iid| item_rows(data, &ignore, *iid))
+            .collect::<Vec<_>>();
+        log::info!(
+            "  ranked {} recipes of {} items",
+            rows.len(),
+            rows.iter().map(|r| &r.item_id).unique().count()
+        );
+
+        let cost = |cost: Option<f64>| match cost {
+            Some(cost) => format!("{:.2}", cost),
+            None => "n/a".to_owned(),
+        };
+
+        match format {
+            Format::Text => {
+                for (i, (_, rows)) in rows
+                    .iter()
+                    .group_by(|row| row.item_id.clone())
+                    .into_iter()
+                    .enumerate()
+                {
+                    let rows = rows.collect::<Vec<_>>();
+                    if i > 0 {
+                        println!();
+                    }
+
+                    println!("{}:", rows[0].item);
+
+                    let mut table = Table::new([
+                        Column::right("#"),
+                        Column::left("Recipe"),
+                        Column::right("Raw/unit"),
+                        Column::right("Diff."),
+                        Column::right("Explicit upstream"),
+                        Column::left(""),
+                    ])
+                    .indent(4);
+                    for row in rows {
+                        table.row([
+                            Cell::new(row.rank),
+                            Cell::new(&row.recipe),
+                            Cell::new(cost(row.cost)),
+                            match row.difference {
+                                Some(d) if d > 0.0 => Cell::new(format!("+{:.1}%", d)),
+                                Some(_) => Cell::colored("best", Some(Color::Green)),
+                                None => Cell::new(""),
+                            },
+                            Cell::new(cost(row.explicit_cost)),
+                            if row.depends_on_upstream {
+                                Cell::colored("depends on upstream", Some(Color::Yellow))
+                            } else {
+                                Cell::new("")
+                            },
+                        ]);
+                    }
+
+                    table.print(style);
+                }
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+            Format::Csv => {
+                println!(
+                    "{}",
+                    csv_line([
+                        "item_id",
+                        "item",
+                        "recipe_id",
+                        "recipe",
+                        "rank",
+                        "cost",
+                        "difference",
+                        "explicit_cost",
+                        "depends_on_upstream",
+                    ])
+                );
+
+                let value = |value: Option<f64>| value.map(|v| format!("{:.4}", v));
+                for row in &rows {
+                    println!(
+                        "{}",
+                        csv_line([
+                            row.item_id.to_string(),
+                            row.item.clone(),
+                            row.recipe_id.to_string(),
+                            row.recipe.clone(),
+                            row.rank.to_string(),
+                            value(row.cost).unwrap_or_default(),
+                            value(row.difference).unwrap_or_default(),
+                            value(row.explicit_cost).unwrap_or_default(),
+                            row.depends_on_upstream.to_string(),
+                        ])
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Create the rows of the recipes that produce the passed item, cheapest
+/// first. Items with less than two recipes have no rows.
+fn item_rows(data: &Data, ignore: &Selection, iid: ItemId) -> Vec<Row> {
+    let candidates = data
+        .as_output
+        .get(&iid)
+        .into_iter()
+        .flatten()
+        .filter(|rid| !ignore.recipes.contains(rid) && data.recipes.contains_key(rid))
+        .copied()
+        .sorted()
+        .dedup()
+        .collect::<Vec<_>>();
+    if candidates.len() < 2 {
+        return Vec::new();
+    }
+
+    let item = data
+        .items
+        .get(&iid)
+        .map(|i| i.name.clone())
+        .unwrap_or_else(|| iid.label(&data.keys).to_string());
+
+    let basic = candidates
+        .iter()
+        .map(|rid| cost(data, ignore, iid, *rid, Box::new(PreferBasic)))
+        .collect::<Vec<_>>();
+    let explicit = candidates
+        .iter()
+        .map(|rid| cost(data, ignore, iid, *rid, Box::new(PreferExplicit)))
+        .collect::<Vec<_>>();
+
+    let basic_ranks = ranks(&basic);
+    let explicit_ranks = ranks(&explicit);
+    let best = basic.iter().flatten().copied().min_by(f64::total_cmp);
+
+    let mut rows = candidates
+        .iter()
+        .enumerate()
+        .map(|(i, rid)| Row {
+            item_id: iid.label(&data.keys),
+            item: item.clone(),
+            recipe_id: rid.label(&data.keys),
+            recipe: data.recipes[rid].name.clone(),
+            rank: basic_ranks[i],
+            cost: basic[i],
+            difference: basic[i]
+                .zip(best)
+                .filter(|(_, best)| *best > 0.0)
+                .map(|(cost, best)| 100.0 * (cost - best) / best),
+            explicit_cost: explicit[i],
+            depends_on_upstream: basic_ranks[i] != explicit_ranks[i],
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| (a.rank, &a.recipe_id).cmp(&(b.rank, &b.recipe_id)));
+
+    rows
+}
+
+/// Raw resources per unit of the item if it is produced by the passed
+/// recipe, and the other items by the recipes of the passed selector.
+fn cost(
+    data: &Data,
+    ignore: &Selection,
+    iid: ItemId,
+    rid: RecipeId,
+    upstream: Box<dyn RecipeSelector>,
+) -> Option<f64> {
+    let options = PlanOptions {
+        ignore: ignore.clone(),
+        selector: Box::new(PinnedList::new([rid], upstream)),
+        ..Default::default()
+    };
+    let targets = [(iid, 1.0)].into();
+
+    match ProductionPlan::new(data, &targets, options) {
+        Ok(plan) if plan.recipes.get(&rid).is_some_and(|crafts| *crafts > 0.0) => {
+            plan.cost(data, Objective::RawResources)
+        }
+        Ok(_) => {
+            log::debug!(recipe:% = rid; "  recipe {} is not used by the plan", rid);
+
+            None
+        }
+        Err(err) => {
+            log::debug!(recipe:% = rid; "  unable to plan recipe {}: {}", rid, err);
+
+            None
+        }
+    }
+}
+
+/// Rank of each cost, starting at 1 for the cheapest one. Equal costs share
+/// a rank, and missing costs are ranked last.
+fn ranks(costs: &[Option<f64>]) -> Vec<usize> {
+    costs
+        .iter()
+        .map(|cost| {
+            let cheaper = costs
+                .iter()
+                .filter(|other| match (cost, other) {
+                    (Some(cost), Some(other)) => *other < cost - EPSILON,
+                    (Some(_), None) => false,
+                    (None, Some(_)) => true,
+                    (None, None) => false,
+                })
+                .count();
+
+            cheaper + 1
+        })
+        .collect()
+}
+
+/// Tolerance for rounding errors of the costs.
+const EPSILON: f64 = 1e-9;
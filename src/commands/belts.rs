@@ -0,0 +1,335 @@
+use itertools::Itertools;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    commands::plan::{parse_belt, parse_buildings},
+    data::{Data, IdLabel, ItemId, Sorter},
+    error::Error,
+    format::Format,
+    planner::{PlanOptions, ProductionPlan, Proliferator, SprayMode},
+    resolver::{PinnedList, PreferBasic},
+    selector::{parse_ids, parse_ignore, parse_targets, SelectorContext},
+    table::{Cell, Color, Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Belts {
+    /// Items to produce, given as `<item>=<amount per minute>`.
+    #[structopt(short = "t", long = "target", required = true)]
+    pub targets: Vec<String>,
+
+    /// Recipes to exclude from the plan.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Recipes to use for the items they produce (instead of the primary
+    /// recipe of each item).
+    #[structopt(long = "prefer")]
+    pub prefer: Vec<String>,
+
+    /// Proliferator that is sprayed on the inputs (none, mk1, mk2 or mk3).
+    #[structopt(long = "proliferator", default_value = "none")]
+    pub proliferator: Proliferator,
+
+    /// How the proliferator is used (extra-products or speedup).
+    #[structopt(long = "spray-mode", default_value = "extra-products")]
+    pub spray_mode: SprayMode,
+
+    /// Buildings to use for the recipe types they can craft (instead of the
+    /// first building of each type).
+    #[structopt(short = "b", long = "building")]
+    pub buildings: Vec<String>,
+
+    /// Tier of the belts, starting at 1 for the slowest belt of the data
+    /// (the fastest belt if not set).
+    #[structopt(long = "belt-tier")]
+    pub belt_tier: Option<usize>,
+
+    /// Tier of the sorters, starting at 1 for the slowest sorter of the data
+    /// (the fastest sorter if not set).
+    #[structopt(long = "sorter-tier")]
+    pub sorter_tier: Option<usize>,
+
+    /// Output format (text or json).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    belt: String,
+
+    /// Items per second of one belt.
+    belt_speed: f64,
+
+    /// Sorter the sorters of the recipes are counted for, `None` if the data
+    /// does not contain any sorters.
+    sorter: Option<String>,
+    flows: Vec<Flow>,
+    recipes: Vec<RecipeSorters>,
+}
+
+/// Items per minute of an item that are produced or supplied by the plan.
+#[derive(Debug, Serialize)]
+struct Flow {
+    item_id: IdLabel,
+    item: String,
+    per_minute: f64,
+
+    /// Belts that are filled by the flow, including the partially used one.
+    load: f64,
+
+    /// Number of belts that are needed for the flow.
+    belts: usize,
+
+    /// Whether the flow does not fit on a single belt.
+    exceeds_belt: bool,
+}
+
+/// Sorters that are needed to feed one machine of a recipe and to take its
+/// products.
+#[derive(Debug, Serialize)]
+struct RecipeSorters {
+    recipe_id: IdLabel,
+    recipe: String,
+    machines: f64,
+    input_sorters: Option<usize>,
+    output_sorters: Option<usize>,
+}
+
+impl Belts {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext, style: &Style) -> Result<(), Error> {
+        let Self {
+            targets,
+            ignore,
+            prefer,
+            proliferator,
+            spray_mode,
+            buildings,
+            belt_tier,
+            sorter_tier,
+            format,
+        } = self;
+
+        log::info!("Parse targets");
+        let targets = parse_targets(data, ctx, &targets)?;
+        log::info!("  loaded {} targets", targets.len());
+
+        log::info!("Parse ignored recipes");
+        let ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        log::info!("Parse buildings");
+        let buildings = parse_buildings(data, ctx, &buildings)?;
+        log::info!("  loaded {} building selections", buildings.len());
+
+        log::info!("Parse preferred recipes");
+        let prefer = parse_ids(data, ctx, &prefer, false)?.recipes;
+        log::info!("  loaded {} preferred recipes", prefer.len());
+
+        let belt = parse_belt(data, belt_tier.unwrap_or(usize::MAX))?;
+        let sorter = parse_sorter(data, sorter_tier)?;
+
+        log::info!("Calculate plan");
+        let options = PlanOptions {
+            ignore,
+            proliferator,
+            spray_mode,
+            buildings,
+            selector: Box::new(PinnedList::new(
+                prefer.iter().copied(),
+                Box::new(PreferBasic),
+            )),
+        };
+        let plan = ProductionPlan::new(data, &targets, options)?;
+        log::info!("  use {} recipes", plan.recipes.len());
+
+        let item_name = |iid: &ItemId| {
+            data.items
+                .get(iid)
+                .map(|i| i.name.clone())
+                .unwrap_or_else(|| iid.label(&data.keys).to_string())
+        };
+
+        // Every item of the plan is either produced by its recipes or
+        // supplied as resource, so this is the flow of each item.
+        let mut flows = plan.production(data);
+        for (iid, rate) in &plan.resources {
+            *flows.entry(*iid).or_default() += rate;
+        }
+
+        let flows = flows
+            .into_iter()
+            .filter(|(_, rate)| *rate > 0.0)
+            .map(|(iid, per_minute)| {
+                let load = belt.belts(per_minute);
+
+                Flow {
+                    item_id: iid.label(&data.keys),
+                    item: item_name(&iid),
+                    per_minute,
+                    load,
+                    belts: ceil(load),
+                    exceeds_belt: load > 1.0 + EPSILON,
+                }
+            })
+            .sorted_by(|a, b| {
+                b.per_minute
+                    .total_cmp(&a.per_minute)
+                    .then(a.item_id.cmp(&b.item_id))
+            })
+            .collect::<Vec<_>>();
+
+        let recipes = plan
+            .recipes
+            .iter()
+            .filter_map(|(rid, crafts)| {
+                let recipe = data.recipes.get(rid)?;
+                let machines = plan.machines(data, *rid);
+
+                // Crafts per second of one machine running at full speed.
+                let per_machine = (machines > 0.0).then(|| crafts / machines / 60.0);
+                let multiplier = plan.options.output_multiplier(recipe);
+
+                Some(RecipeSorters {
+                    recipe_id: rid.label(&data.keys),
+                    recipe: recipe.name.clone(),
+                    machines,
+                    input_sorters: sorters(
+                        recipe.inputs.iter().map(|i| i.amount as f64),
+                        per_machine,
+                        sorter,
+                    ),
+                    output_sorters: sorters(
+                        recipe.outputs.iter().map(|o| o.amount as f64 * multiplier),
+                        per_machine,
+                        sorter,
+                    ),
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let summary = Summary {
+            belt: belt.name.clone(),
+            belt_speed: belt.speed,
+            sorter: sorter.map(|s| s.name.clone()),
+            flows,
+            recipes,
+        };
+
+        match format {
+            Format::Text => summary.print(style),
+            Format::Json => println!("{}", serde_json::to_string_pretty(&summary)?),
+            format => return Err(format.unsupported()),
+        }
+
+        Ok(())
+    }
+}
+
+impl Summary {
+    fn print(&self, style: &Style) {
+        println!("Belt: {} ({:.2} /min)", self.belt, self.belt_speed * 60.0);
+
+        println!();
+        let mut table = Table::new([
+            Column::left("Item"),
+            Column::right("Items/min"),
+            Column::right("Load"),
+            Column::right("Belts"),
+            Column::left(""),
+        ]);
+        for flow in &self.flows {
+            table.row([
+                Cell::new(&flow.item),
+                Cell::new(format!("{:.2}", flow.per_minute)),
+                Cell::new(format!("{:.2}", flow.load)),
+                Cell::new(flow.belts),
+                if flow.exceeds_belt {
+                    Cell::colored("exceeds one belt", Some(Color::Red))
+                } else {
+                    Cell::new("")
+                },
+            ]);
+        }
+        table.print(style);
+
+        let sorter = match &self.sorter {
+            Some(sorter) => sorter,
+            None => return,
+        };
+
+        println!();
+        println!("Sorters per machine: {}", sorter);
+        println!();
+        let mut table = Table::new([
+            Column::left("Recipe"),
+            Column::right("Machines"),
+            Column::right("Input sorters"),
+            Column::right("Output sorters"),
+        ]);
+        for recipe in &self.recipes {
+            let count = |count: Option<usize>| count.map(|c| c.to_string()).unwrap_or_default();
+
+            table.row([
+                Cell::new(&recipe.recipe),
+                Cell::new(format!("{:.2}", recipe.machines)),
+                Cell::new(count(recipe.input_sorters)),
+                Cell::new(count(recipe.output_sorters)),
+            ]);
+        }
+        table.print(style);
+    }
+}
+
+/// Tolerance of the belt and sorter counts, so a flow that exactly fills a
+/// belt is not rounded up to the next one.
+const EPSILON: f64 = 1e-9;
+
+fn ceil(value: f64) -> usize {
+    (value - EPSILON).ceil().max(0.0) as usize
+}
+
+/// Number of sorters that move the passed amounts per craft of one machine,
+/// each item needs its own sorters.
+fn sorters<I>(amounts: I, per_machine: Option<f64>, sorter: Option<&Sorter>) -> Option<usize>
+where
+    I: IntoIterator<Item = f64>,
+{
+    let (per_machine, sorter) = (per_machine?, sorter?);
+
+    Some(
+        amounts
+            .into_iter()
+            .map(|amount| ceil(amount * per_machine / sorter.speed))
+            .sum(),
+    )
+}
+
+/// Sorter of the passed tier (see [`crate::data::Logistics::sorter`]), the
+/// fastest sorter if no tier is passed. Returns `None` if the data does not
+/// contain any sorters and no tier is passed.
+fn parse_sorter(data: &Data, tier: Option<usize>) -> Result<Option<&Sorter>, Error> {
+    if tier == Some(0) {
+        return Err(Error::usage("Sorter tiers start at 1"));
+    }
+
+    match data.logistics.sorter(tier.unwrap_or(usize::MAX)) {
+        Some(sorter) => {
+            log::info!("Count sorters of {}", sorter.name);
+
+            Ok(Some(sorter))
+        }
+        None if tier.is_some() => Err(Error::data("Data file does not contain any sorter data")),
+        None => {
+            log::warn!("Data file does not contain any sorter data, sorters are not counted");
+
+            Ok(None)
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use structopt::StructOpt;
+
+use crate::{
+    commands::plan::parse_buildings,
+    data::{Data, ItemId, RecipeId},
+    error::Error,
+    planner::{PlanOptions, ProductionPlan, Proliferator, SprayMode},
+    selector::{parse_ids, parse_ignore, parse_machines, SelectorContext},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Bottleneck {
+    /// Item to calculate the sustained output for.
+    pub item: String,
+
+    /// Machines of the factory, given as `<recipe>=<machines>` or as files
+    /// with one such line per recipe. Items that are not produced by these
+    /// recipes are supplied from outside.
+    #[structopt(long = "have", required = true)]
+    pub have: Vec<String>,
+
+    /// Recipes to exclude from the calculation.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Proliferator that is sprayed on the inputs (none, mk1, mk2 or mk3).
+    #[structopt(long = "proliferator", default_value = "none")]
+    pub proliferator: Proliferator,
+
+    /// How the proliferator is used (extra-products or speedup).
+    #[structopt(long = "spray-mode", default_value = "extra-products")]
+    pub spray_mode: SprayMode,
+
+    /// Buildings to use for the recipe types they can craft (instead of the
+    /// first building of each type).
+    #[structopt(short = "b", long = "building")]
+    pub buildings: Vec<String>,
+}
+
+impl Bottleneck {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let Self {
+            item,
+            have,
+            ignore,
+            proliferator,
+            spray_mode,
+            buildings,
+        } = self;
+
+        log::info!("Parse item");
+        let items = parse_ids(data, ctx, std::slice::from_ref(&item), true)?.items;
+        let iid = match items.iter().exactly_one() {
+            Ok(iid) => *iid,
+            Err(_) => return Err(Error::usage(format!("Expected exactly one item: {}", item))),
+        };
+
+        log::info!("Parse machines");
+        let have = parse_machines(data, ctx, &have)?;
+        log::info!("  loaded {} recipes", have.len());
+
+        log::info!("Parse ignored recipes");
+        let mut ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+        ignore.recipes.extend(
+            data.recipes
+                .keys()
+                .filter(|rid| !have.contains_key(rid))
+                .copied(),
+        );
+
+        log::info!("Parse buildings");
+        let buildings = parse_buildings(data, ctx, &buildings)?;
+        log::info!("  loaded {} building selections", buildings.len());
+
+        // The steady state is linear in the target rate, so the plan of one
+        // item per minute is scaled until the first recipe runs out of
+        // machines.
+        log::info!("Calculate plan");
+        let options = PlanOptions {
+            ignore,
+            proliferator,
+            spray_mode,
+            buildings,
+            ..Default::default()
+        };
+        let targets = BTreeMap::from([(iid, 1.0)]);
+        let plan = ProductionPlan::new(data, &targets, options)?;
+        log::info!("  use {} recipes", plan.recipes.len());
+
+        let limits = plan
+            .recipes
+            .keys()
+            .map(|rid| {
+                let needed = plan.machines(data, *rid);
+
+                (*rid, needed, have[rid] / needed)
+            })
+            .filter(|(_, needed, _)| *needed > 0.0)
+            .sorted_by(|a, b| a.2.total_cmp(&b.2).then(a.0.cmp(&b.0)))
+            .collect::<Vec<_>>();
+
+        let (bottleneck, needed, rate) = match limits.first() {
+            Some(limit) => *limit,
+            None => {
+                return Err(Error::custom(format!(
+                    "None of the recipes produce {}",
+                    item
+                )))
+            }
+        };
+
+        let item_name = |iid: &ItemId| {
+            data.items
+                .get(iid)
+                .map(|i| i.name.clone())
+                .unwrap_or_else(|| iid.label(&data.keys).to_string())
+        };
+        let recipe_name = |rid: &RecipeId| {
+            data.recipes
+                .get(rid)
+                .map(|r| r.name.clone())
+                .unwrap_or_else(|| rid.label(&data.keys).to_string())
+        };
+
+        println!("Sustained output: {:.2} /min {}", rate, item_name(&iid));
+
+        println!();
+        println!("Recipes:");
+        for (rid, needed, _) in &limits {
+            let used = needed * rate;
+
+            println!(
+                "    {:<40} {:>8.2} of {:>6} machines {:>6.1}%",
+                recipe_name(rid),
+                used,
+                have[rid],
+                100.0 * used / have[rid]
+            );
+        }
+
+        let unused = have
+            .keys()
+            .filter(|rid| !plan.recipes.contains_key(rid))
+            .collect::<Vec<_>>();
+        if !unused.is_empty() {
+            println!();
+            println!("Unused recipes:");
+            for rid in unused {
+                println!("    {}", recipe_name(rid));
+            }
+        }
+
+        if !plan.resources.is_empty() {
+            println!();
+            println!("Supplied from outside:");
+            for (iid, amount) in &plan.resources {
+                println!("    {:<40} {:>10.2} /min", item_name(iid), amount * rate);
+            }
+        }
+
+        println!();
+        println!("Bottleneck: {}", recipe_name(&bottleneck));
+        match limits.get(1) {
+            Some((next, _, next_rate)) => {
+                let extra = (needed * next_rate - have[&bottleneck]).ceil();
+
+                if extra > 0.0 {
+                    println!(
+                        "    {} more machines move the bottleneck to {} at {:.2} /min",
+                        extra,
+                        recipe_name(next),
+                        next_rate
+                    );
+                } else {
+                    println!(
+                        "    {} is saturated at the same rate, both need more machines",
+                        recipe_name(next)
+                    );
+                }
+            }
+            None => println!("    the recipe is the only one of the production"),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,246 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use itertools::Itertools;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    analysis::{Node, RecipeGraph},
+    data::{Data, ItemId, RecipeId},
+    error::Error,
+    format::Format,
+    planner::{PlanOptions, ProductionPlan},
+    resolver::{PinnedList, PreferBasic},
+    selector::{parse_ids, parse_ignore, SelectorContext},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct BuildOrder {
+    /// Items to build the production lines for.
+    #[structopt(required = true)]
+    pub items: Vec<String>,
+
+    /// Recipes to exclude from the production. Ignored items are used as raw
+    /// resources.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Recipes to use for the items they produce (instead of the primary
+    /// recipe of each item).
+    #[structopt(long = "prefer")]
+    pub prefer: Vec<String>,
+
+    /// Output format (text or json).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+/// Production lines that can be built once the lines of all previous stages
+/// run.
+#[derive(Debug, Serialize)]
+struct Stage {
+    stage: usize,
+
+    /// Recipes of the stage, recipes of a loop form one group.
+    groups: Vec<Group>,
+
+    /// Raw resources that are needed by this stage, but by none of the
+    /// previous stages.
+    new_resources: Vec<ItemRef>,
+}
+
+#[derive(Debug, Serialize)]
+struct Group {
+    recipes: Vec<RecipeRef>,
+
+    #[serde(rename = "loop")]
+    is_loop: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct RecipeRef {
+    id: RecipeId,
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct ItemRef {
+    id: ItemId,
+    name: String,
+}
+
+impl BuildOrder {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let Self {
+            items,
+            ignore,
+            prefer,
+            format,
+        } = self;
+
+        log::info!("Parse items");
+        let items = parse_ids(data, ctx, &items, true)?.items;
+        log::info!("  loaded {} items", items.len());
+
+        log::info!("Parse ignored recipes");
+        let ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        log::info!("Parse preferred recipes");
+        let prefer = parse_ids(data, ctx, &prefer, false)?.recipes;
+        log::info!("  loaded {} preferred recipes", prefer.len());
+
+        // Only the recipes of the chain are of interest, so the rate of the
+        // targets does not matter.
+        log::info!("Resolve recipes");
+        let options = PlanOptions {
+            ignore,
+            selector: Box::new(PinnedList::new(
+                prefer.iter().copied(),
+                Box::new(PreferBasic),
+            )),
+            ..Default::default()
+        };
+        let targets = items.iter().map(|iid| (*iid, 1.0)).collect();
+        let plan = ProductionPlan::new(data, &targets, options)?;
+        log::info!("  use {} recipes", plan.recipes.len());
+
+        log::info!("Calculate stages");
+        let stages = stages(data, &plan.recipes.keys().copied().collect());
+        log::info!("  found {} stages", stages.len());
+
+        match format {
+            Format::Text => {
+                for (i, stage) in stages.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+
+                    println!("Stage {}:", stage.stage);
+                    for group in &stage.groups {
+                        let names = group.recipes.iter().map(|r| &r.name).join(" + ");
+                        if group.is_loop {
+                            println!("    loop: {} (build together)", names);
+                        } else {
+                            println!("    {}", names);
+                        }
+                    }
+
+                    if !stage.new_resources.is_empty() {
+                        println!(
+                            "  New resources: {}",
+                            stage.new_resources.iter().map(|i| &i.name).join(", ")
+                        );
+                    }
+                }
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&stages)?),
+            format => return Err(format.unsupported()),
+        }
+
+        Ok(())
+    }
+}
+
+/// Group the passed recipes into stages. Each recipe is placed one stage
+/// after the latest stage that produces one of its inputs, recipes that form
+/// a loop are placed together.
+fn stages(data: &Data, recipes: &BTreeSet<RecipeId>) -> Vec<Stage> {
+    let mut producers = BTreeMap::<ItemId, BTreeSet<RecipeId>>::new();
+    for rid in recipes {
+        for output in &data.recipes[rid].outputs {
+            producers.entry(output.id).or_default().insert(*rid);
+        }
+    }
+
+    // The components are returned consumers first, so the production order
+    // is reversed.
+    let components = RecipeGraph::new(data, recipes)
+        .strongly_connected_components()
+        .into_iter()
+        .rev()
+        .map(|component| {
+            component
+                .into_iter()
+                .filter_map(|node| match node {
+                    Node::Recipe(rid) => Some(rid),
+                    Node::Item(_) => None,
+                })
+                .sorted()
+                .collect::<Vec<_>>()
+        })
+        .filter(|component| !component.is_empty())
+        .collect::<Vec<_>>();
+
+    let mut stage_of = BTreeMap::<RecipeId, usize>::new();
+    let mut groups = BTreeMap::<usize, Vec<(Vec<RecipeId>, BTreeSet<ItemId>)>>::new();
+    for component in components {
+        let inputs = component
+            .iter()
+            .flat_map(|rid| data.recipes[rid].inputs.iter().map(|i| i.id))
+            .collect::<BTreeSet<_>>();
+
+        let stage = inputs
+            .iter()
+            .flat_map(|iid| producers.get(iid).into_iter().flatten())
+            .filter_map(|rid| stage_of.get(rid))
+            .max()
+            .map_or(1, |stage| stage + 1);
+        let resources = inputs
+            .into_iter()
+            .filter(|iid| !producers.contains_key(iid))
+            .collect();
+
+        stage_of.extend(component.iter().map(|rid| (*rid, stage)));
+        groups
+            .entry(stage)
+            .or_default()
+            .push((component, resources));
+    }
+
+    let item_ref = |iid: ItemId| ItemRef {
+        id: iid,
+        name: data
+            .items
+            .get(&iid)
+            .map(|i| i.name.clone())
+            .unwrap_or_else(|| iid.label(&data.keys).to_string()),
+    };
+
+    let mut seen = BTreeSet::new();
+    groups
+        .into_iter()
+        .map(|(stage, groups)| {
+            let new_resources = groups
+                .iter()
+                .flat_map(|(_, resources)| resources)
+                .filter(|iid| seen.insert(**iid))
+                .map(|iid| item_ref(*iid))
+                .collect();
+
+            let groups = groups
+                .into_iter()
+                .map(|(component, _)| Group {
+                    is_loop: component.len() > 1,
+                    recipes: component
+                        .into_iter()
+                        .map(|rid| RecipeRef {
+                            id: rid,
+                            name: data.recipes[&rid].name.clone(),
+                        })
+                        .collect(),
+                })
+                .collect();
+
+            Stage {
+                stage,
+                groups,
+                new_resources,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,116 @@
+use structopt::StructOpt;
+
+use crate::{
+    data::{Data, ItemAmount, ItemId, Recipe},
+    error::Error,
+    selector::{parse_ids, SelectorContext},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Byproducts {
+    /// Only list recipes that consume one of these items.
+    #[structopt(short = "c", long = "consuming")]
+    pub consuming: Vec<String>,
+
+    /// Also list recipes that produce one of their own inputs.
+    #[structopt(short = "l", long = "loops")]
+    pub loops: bool,
+}
+
+impl Byproducts {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let Self { consuming, loops } = self;
+
+        log::info!("Parse consumed items");
+        let consuming = parse_ids(data, ctx, &consuming, true)?.items;
+        log::info!("  loaded {} consumed items", consuming.len());
+
+        let mut recipes = data
+            .recipes
+            .iter()
+            .filter(|(_, r)| r.outputs.len() > 1 || (loops && is_loop(r)))
+            .filter(|(_, r)| {
+                consuming.is_empty() || r.inputs.iter().any(|i| consuming.contains(&i.id))
+            })
+            .collect::<Vec<_>>();
+        recipes.sort_by_key(|(rid, _)| **rid);
+        log::info!("  found {} recipes", recipes.len());
+
+        let item_name = |iid: &ItemId| {
+            data.items
+                .get(iid)
+                .map(|i| i.name.clone())
+                .unwrap_or_else(|| iid.label(&data.keys).to_string())
+        };
+        let per_minute = |recipe: &Recipe, amount: &ItemAmount| {
+            if recipe.seconds > 0.0 {
+                amount.amount as f64 * 60.0 / recipe.seconds
+            } else {
+                0.0
+            }
+        };
+
+        for (i, (_, recipe)) in recipes.into_iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+
+            let primary = primary_output(data, recipe);
+
+            println!("{}:", recipe.name);
+            for output in &recipe.outputs {
+                let kind = if Some(output.id) == primary {
+                    "primary"
+                } else {
+                    "byproduct"
+                };
+
+                println!(
+                    "    {:<10} {:<40} {:>10.2} /min",
+                    kind,
+                    item_name(&output.id),
+                    per_minute(recipe, output)
+                );
+            }
+
+            if loops && is_loop(recipe) {
+                for input in &recipe.inputs {
+                    if recipe.outputs.iter().any(|o| o.id == input.id) {
+                        println!(
+                            "    {:<10} {:<40} {:>10.2} /min",
+                            "consumed",
+                            item_name(&input.id),
+                            per_minute(recipe, input)
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Guess the primary output of the recipe.
+///
+/// This is the output that shares the name of the recipe, or the output with
+/// the largest amount if there is no such output.
+fn primary_output(data: &Data, recipe: &Recipe) -> Option<ItemId> {
+    let namesake = recipe.outputs.iter().find(|o| {
+        data.items
+            .get(&o.id)
+            .map(|i| i.name == recipe.name)
+            .unwrap_or(false)
+    });
+
+    namesake
+        .or_else(|| recipe.outputs.iter().rev().max_by_key(|o| o.amount))
+        .map(|o| o.id)
+}
+
+fn is_loop(recipe: &Recipe) -> bool {
+    recipe
+        .outputs
+        .iter()
+        .any(|o| recipe.inputs.iter().any(|i| i.id == o.id))
+}
@@ -0,0 +1,72 @@
+use std::io::{stdout, ErrorKind, Write};
+use std::path::PathBuf;
+
+use structopt::{clap::Shell, StructOpt};
+
+use crate::{args::Args, error::Error};
+
+use super::write_output;
+
+#[derive(Debug, StructOpt)]
+pub struct Completions {
+    /// Shell to generate the completion script for.
+    #[structopt(possible_values = &Shell::variants(), case_insensitive = true)]
+    pub shell: Shell,
+
+    /// File to write the completion script to (stdout if not set).
+    #[structopt(short = "o", long = "output")]
+    pub output: Option<PathBuf>,
+}
+
+impl Completions {
+    pub fn exec(self) -> Result<(), Error> {
+        let Self { shell, output } = self;
+
+        // clap panics if writing the script fails, so it is generated into a
+        // buffer first.
+        let mut app = Args::clap();
+        let name = app.get_name().to_owned();
+        let mut script = Vec::new();
+        app.gen_completions_to(name, shell, &mut script);
+
+        match output {
+            Some(path) => {
+                log::info!("Write {} completions to {:#?}", shell, &path);
+
+                write_output(&path, |out| Ok(out.write_all(&script)?))
+            }
+            None => {
+                let mut out = stdout().lock();
+                match out.write_all(&script).and_then(|()| out.flush()) {
+                    // The reader went away (like `completions bash | head`),
+                    // which is not an error of the command.
+                    Err(err) if err.kind() == ErrorKind::BrokenPipe => Ok(()),
+                    ret => Ok(ret?),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs::{read_to_string, remove_file};
+    use std::process::id;
+
+    use super::*;
+
+    #[test]
+    fn completions_are_written_to_the_output() {
+        let path = temp_dir().join(format!("dsp-tool-completions-{}.bash", id()));
+        let completions = Completions {
+            shell: Shell::Bash,
+            output: Some(path.clone()),
+        };
+        completions.exec().unwrap();
+
+        let script = read_to_string(&path).unwrap();
+        remove_file(&path).unwrap();
+        assert!(script.contains("create-production-graph"), "{}", script);
+    }
+}
@@ -1,22 +1,156 @@
-use std::collections::BTreeSet;
-use std::path::PathBuf;
-use std::{fs::read_to_string, str::FromStr};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::create_dir_all;
+use std::io::{stdout, Write};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::mpsc::channel;
+use std::time::{Duration, Instant};
 
-use rlua::Lua;
+use itertools::Itertools;
+use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebounceEventResult};
 use structopt::StructOpt;
 
-use crate::data::{ItemType, RecipeId};
 use crate::{
-    data::{Data, ItemId},
+    args::DataArgs,
+    commands::{load_data, write_output},
+    data::{Data, ItemId, ItemType, RecipeId},
     error::Error,
+    graph::{
+        write_aggregate_dot, write_aggregate_matrix_csv, write_dot, write_matrix_csv, AggregateBy,
+        AggregateGraph, ColorBy, DepthColors, DotOptions, GraphBuilder, GraphFormat, GraphIndex,
+        NodeSizes, ProductionGraph, RecipeLabel, Rgb, SizeBy, Summary,
+    },
+    meta::Meta,
+    picker::{is_interactive, pick_items},
+    planner::{PlanOptions, ProductionPlan, Proliferator, SprayMode},
+    resolver::PreferBasic,
+    selector::{
+        parse_each, parse_ignore, parse_items, parse_targets, parse_techs, split_selectors,
+        Selection, SelectorContext,
+    },
+    theme::Theme,
 };
 
 #[derive(Debug, StructOpt)]
 pub struct CreateProductionGraph {
+    #[structopt(flatten)]
+    pub options: GraphOptions,
+
+    /// Older data file to compare with. The same items are resolved in both
+    /// files, and the differences are highlighted in one graph.
+    #[structopt(long = "diff-against", conflicts_with = "targets")]
+    pub diff_against: Option<PathBuf>,
+
+    /// Item types (like logistics) to exclude from the graph. Items of these
+    /// types are not added to the graph and their recipes are not followed,
+    /// even if they are selected by `--items`.
+    #[structopt(long = "exclude-type")]
+    pub exclude_types: Vec<String>,
+
+    /// Files with item selectors to exclude, one per line (`#` starts a
+    /// comment line). Each selector removes its items from `--items`, as if
+    /// it was passed negated (`-i '!selector'`).
+    #[structopt(long = "exclude-file", number_of_values = 1)]
+    pub exclude_files: Vec<PathBuf>,
+
+    /// Output format (dot or matrix-csv). The matrix contains the amounts
+    /// that flow from the items in the rows to the items in the columns.
+    #[structopt(short = "f", long = "format", default_value = "dot")]
+    pub format: GraphFormat,
+
+    /// Use the items per minute instead of the amounts per craft as weights
+    /// of the matrix.
+    #[structopt(long = "rates", requires = "targets")]
+    pub rates: bool,
+
+    /// Do not write the metadata (version, data files, options and time)
+    /// at the top of the output.
+    #[structopt(long = "no-meta")]
+    pub no_meta: bool,
+
+    /// Do not add the current time to the metadata, so the output only
+    /// changes if the data or the options change.
+    #[structopt(long = "meta-no-timestamp")]
+    pub meta_no_timestamp: bool,
+
+    /// File to write the graph to (stdout if not set).
+    #[structopt(short = "o", long = "output")]
+    pub output: Option<PathBuf>,
+
+    /// Also write a JSON index of the written nodes and edges to this file,
+    /// which maps the node identifiers of the output to the ids, names and
+    /// types of the data.
+    #[structopt(long = "emit-index", conflicts_with = "split-per-item")]
+    pub emit_index: Option<PathBuf>,
+
+    /// Watch the data files and regenerate the graph whenever they change.
+    #[structopt(short = "w", long = "watch", requires = "output")]
+    pub watch: bool,
+
+    /// Write a separate graph for each requested item and target. The
+    /// output is a directory, or a file name template that contains
+    /// `{name}` (like `graph-{name}.dot`).
+    #[structopt(
+        long = "split-per-item",
+        requires = "output",
+        conflicts_with_all = &["watch", "diff-against"]
+    )]
+    pub split_per_item: bool,
+
+    /// Overwrite existing files when writing one graph per item.
+    #[structopt(long = "force", requires = "split-per-item")]
+    pub force: bool,
+
+    /// Validate the output before it is written: DOT is parsed by graphviz
+    /// (see `--dot-path`) and the index is parsed as JSON again. Fails with
+    /// the error of the validator if the output is invalid.
+    #[structopt(long = "check")]
+    pub check: bool,
+
+    /// Path of the `dot` command of graphviz that is used by `--check`.
+    #[structopt(long = "dot-path", default_value = "dot")]
+    pub dot_path: PathBuf,
+
+    /// Only resolve the selectors and the graph: print what each selector
+    /// matches and the number of nodes and edges, without writing any
+    /// output. Fails if a selector matches nothing or the graph is empty.
+    #[structopt(long = "dry-run", conflicts_with = "watch")]
+    pub dry_run: bool,
+
+    /// Fail if neither `--items` nor `--target` is passed, instead of picking
+    /// the items interactively on the terminal.
+    #[structopt(long = "no-interactive")]
+    pub no_interactive: bool,
+}
+
+/// Options of the production graph that are shared by the commands that
+/// create a graph (`create-production-graph` and `serve`).
+#[derive(Debug, Clone, StructOpt)]
+pub struct GraphOptions {
     /// Products to include in the graph.
     #[structopt(short = "i", long = "items")]
     pub items: Vec<String>,
 
+    /// Items to produce, given as `<item>=<amount per minute>`. The items are
+    /// added to the graph and the edges and recipes are labeled with the
+    /// rates that are needed.
+    #[structopt(short = "t", long = "target")]
+    pub targets: Vec<String>,
+
+    /// Add a table with the needed resources and machines to the graph. The
+    /// totals are calculated like the ones of `plan`.
+    #[structopt(long = "summary-node", requires = "targets")]
+    pub summary_node: bool,
+
+    /// Proliferator that is sprayed on the inputs for the rates of
+    /// `--target` (none, mk1, mk2 or mk3).
+    #[structopt(long = "proliferator", default_value = "none")]
+    pub proliferator: Proliferator,
+
+    /// How the proliferator is used (extra-products or speedup).
+    #[structopt(long = "spray-mode", default_value = "extra-products")]
+    pub spray_mode: SprayMode,
+
     /// Recipes to exclude from the graph.
     #[structopt(long = "ignore")]
     pub ignore: Vec<String>,
@@ -25,196 +159,1096 @@ pub struct CreateProductionGraph {
     #[structopt(short = "r", long = "resolve-deps")]
     pub resolve_deps: bool,
 
-    /// File to load the product data and recipes from.
-    #[structopt(short = "d", long = "data", default_value = "data.lua")]
-    pub data_path: PathBuf,
+    /// Only use recipes that are unlocked by these techs (ids, names or files
+    /// with one tech per line) and their prerequisites.
+    #[structopt(long = "unlocked-techs", use_delimiter = true)]
+    pub unlocked_techs: Vec<String>,
+
+    /// Only use recipes that can be crafted in the replicator.
+    #[structopt(long = "handcraft-only")]
+    pub handcraft_only: bool,
+
+    /// Draw recipes with the same inputs, outputs and crafting time as one
+    /// node.
+    #[structopt(long = "merge-duplicate-recipes")]
+    pub merge_duplicate_recipes: bool,
+
+    /// Place items of the same tier in the same rank of the graph.
+    #[structopt(long = "rank-by-tier")]
+    pub rank_by_tier: bool,
+
+    /// Show the icons of the items (see `--icons-dir`).
+    #[structopt(long = "icons")]
+    pub icons: bool,
+
+    /// Directory the icon paths of the items are relative to.
+    #[structopt(long = "icons-dir", default_value = "icons")]
+    pub icons_dir: PathBuf,
+
+    /// Draw items that are not produced by any recipe of the graph like all
+    /// other items.
+    #[structopt(long = "no-source-style")]
+    pub no_source_style: bool,
+
+    /// Fill the nodes with a color that depends on this property (depth).
+    #[structopt(long = "color-by")]
+    pub color_by: Option<ColorBy>,
+
+    /// Colors of the root items and of the deepest level for
+    /// `--color-by depth`, given as `<start>,<end>` (defaults to the colors
+    /// of the theme).
+    #[structopt(long = "depth-colors", use_delimiter = true, number_of_values = 2)]
+    pub depth_colors: Vec<Rgb>,
+
+    /// Deepest level of the depth colors, deeper nodes use the last color
+    /// (defaults to the deepest node of the graph).
+    #[structopt(long = "depth-cutoff")]
+    pub depth_cutoff: Option<usize>,
+
+    /// Scale the items by this property (throughput), using a log scale
+    /// from the smallest to the largest value. Items without a rate keep the
+    /// default size.
+    #[structopt(long = "node-size-by", requires = "targets")]
+    pub size_by: Option<SizeBy>,
+
+    /// Smallest and largest width and height of the scaled items in inches,
+    /// given as `<min>,<max>`.
+    #[structopt(
+        long = "node-size-range",
+        use_delimiter = true,
+        number_of_values = 2,
+        default_value = "0.75,3"
+    )]
+    pub node_size_range: Vec<f64>,
+
+    /// Smallest and largest font size of the scaled items in points, given
+    /// as `<min>,<max>`.
+    #[structopt(
+        long = "font-size-range",
+        use_delimiter = true,
+        number_of_values = 2,
+        default_value = "10,28"
+    )]
+    pub font_size_range: Vec<f64>,
+
+    /// Scale the recipes by their number of machines as well.
+    #[structopt(long = "scale-recipes", requires = "size-by")]
+    pub scale_recipes: bool,
+
+    /// Draw a separate copy of each item that is consumed by more than this
+    /// number of recipes next to every consumer.
+    #[structopt(long = "split-common")]
+    pub split_common: Option<usize>,
+
+    /// Theme of the graph: the name of a built-in theme (default or dark) or
+    /// the path of a TOML theme file.
+    #[structopt(long = "theme")]
+    pub theme: Option<String>,
+
+    /// Merge edges with the same endpoints into one edge with the summed
+    /// amounts, labeled with the number of merged edges.
+    #[structopt(long = "combine-edges")]
+    pub combine_edges: bool,
+
+    /// How the recipe nodes are drawn (point or table).
+    #[structopt(long = "recipe-label", default_value = "point")]
+    pub recipe_label: RecipeLabel,
+
+    /// Collapse the graph to one node per item or recipe type (item-type or
+    /// recipe-type), with the summed flows between the types as edges.
+    #[structopt(long = "aggregate-by")]
+    pub aggregate_by: Option<AggregateBy>,
+}
+
+enum WatchEvent {
+    Changed(DebounceEventResult),
+    Stop,
 }
 
 impl CreateProductionGraph {
-    pub fn exec(self) -> Result<(), Error> {
-        let Self {
-            items,
-            ignore,
-            resolve_deps,
-            data_path,
-        } = self;
+    /// Graph command with the passed options for the commands that render
+    /// a graph themselves (like `serve` and `report`): writes DOT without
+    /// metadata and never picks the items interactively.
+    pub(crate) fn embedded(options: GraphOptions) -> Self {
+        Self {
+            options,
+            diff_against: None,
+            exclude_types: Vec::new(),
+            exclude_files: Vec::new(),
+            format: GraphFormat::Dot,
+            rates: false,
+            no_meta: true,
+            meta_no_timestamp: false,
+            output: None,
+            emit_index: None,
+            watch: false,
+            split_per_item: false,
+            force: false,
+            check: false,
+            dot_path: "dot".into(),
+            dry_run: false,
+            no_interactive: true,
+        }
+    }
 
-        log::info!("Load data from {:#?}", &data_path);
-        let data = read_to_string(data_path)?;
-        let lua = Lua::new();
-        let data = lua.context(move |lua| lua.load(&data).eval::<Data>())?;
+    pub fn exec(mut self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        self.pick_items(data)?;
 
-        log::info!("  loaded {} items", data.items.len());
-        log::info!("  loaded {} recipes", data.recipes.len());
+        if self.dry_run {
+            return self.dry_run(data, ctx);
+        }
 
-        log::info!("Parse items");
-        let mut items = parse_ids(&data, &items, true)?
-            .into_iter()
-            .map(ItemId)
-            .collect::<BTreeSet<_>>();
-        log::info!("  loaded {} items", items.len());
+        self.write(data, ctx)
+    }
 
-        log::info!("Parse ignored recipes");
-        let ignore = parse_ids(&data, &ignore, false)?
+    /// Pick the items interactively if neither items nor targets are passed,
+    /// and use them as if they were passed as `--items`. Fails if the tool
+    /// does not run in a terminal or `--no-interactive` is passed.
+    fn pick_items(&mut self, data: &Data) -> Result<(), Error> {
+        if !self.options.items.is_empty() || !self.options.targets.is_empty() {
+            return Ok(());
+        }
+
+        if self.no_interactive {
+            return Err(Error::usage("No items selected, pass --items or --target"));
+        }
+
+        if !is_interactive() {
+            return Err(Error::usage(
+                "No items selected, pass --items or --target (the interactive picker needs a terminal)",
+            ));
+        }
+
+        self.options.items = pick_items(data)?
             .into_iter()
-            .collect::<BTreeSet<_>>();
-        log::info!("  loaded {} ignored recipes", ignore.len());
+            .map(|iid| format!("item:{}", iid.label(&data.keys)))
+            .collect();
+        log::info!("Picked items: {}", self.options.items.join(" "));
 
-        log::info!("Resolve recipes");
-        let mut recipes = BTreeSet::<RecipeId>::new();
-        for item in items.clone() {
-            resolve_item_dependencies(&data, &mut recipes, &mut items, &ignore, item, resolve_deps);
+        Ok(())
+    }
+
+    /// Resolve the selectors and the graph without writing any output, see
+    /// `--dry-run`.
+    fn dry_run(&self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let mut failed = 0;
+        let mut report = |label: String, result: Result<Option<String>, Error>| match result {
+            Ok(Some(matches)) => println!("{}: {}", label, matches),
+            Ok(None) => {
+                failed += 1;
+                println!("{}: matches nothing", label);
+            }
+            Err(err) => {
+                failed += 1;
+                println!("{}: {}", label, err);
+            }
+        };
+
+        let selectors = [
+            (
+                "items",
+                &self.options.items,
+                true,
+                &self.exclude_files,
+                true,
+            ),
+            (
+                "ignore",
+                &self.options.ignore,
+                false,
+                &ctx.ignore_files,
+                false,
+            ),
+        ];
+        for (flag, selectors, items_only, files, negate_files) in selectors {
+            let matches = parse_each(data, ctx, selectors, items_only, files, negate_files)?;
+
+            // Negated selectors only remove what the other selectors select,
+            // so they are reported with the entries they actually remove.
+            let mut selected = Selection::default();
+            for m in matches.iter().filter(|m| !m.negated) {
+                if let Ok(selection) = &m.selection {
+                    selected.extend(selection.clone());
+                }
+            }
+
+            for m in matches {
+                let label = match &m.origin {
+                    Some(origin) => format!("{}: {}", origin, m.selector),
+                    None => format!("--{} {}", flag, m.selector),
+                };
+
+                report(
+                    label,
+                    m.selection.map(|selection| {
+                        if selection.is_empty() {
+                            None
+                        } else if !m.negated {
+                            Some(describe_selection(data, &selection))
+                        } else {
+                            let removed = selection.intersection(&selected);
+                            Some(if removed.is_empty() {
+                                "removes nothing, none of its entries is selected".to_owned()
+                            } else {
+                                format!("removes {}", describe_selection(data, &removed))
+                            })
+                        }
+                    }),
+                );
+            }
         }
-        log::info!("  use {} items", items.len());
-        log::info!("  use {} recipes", recipes.len());
 
-        log::info!("Generate graph");
+        for target in &self.options.targets {
+            let targets = parse_targets(data, ctx, std::slice::from_ref(target)).map(|targets| {
+                (!targets.is_empty()).then(|| {
+                    targets
+                        .iter()
+                        .map(|(iid, rate)| format!("{} at {:.2} /min", item_name(data, *iid), rate))
+                        .join(", ")
+                })
+            });
+
+            report(format!("--target {}", target), targets);
+        }
 
-        println!("strict digraph DSP {{");
-        println!("    graph [ rankdir=LR ]");
+        if failed > 0 {
+            return Err(Error::usage(format!(
+                "Dry run failed: {} selectors match nothing",
+                failed
+            )));
+        }
+
+        let (items, targets, ignore) = self.parse(data, ctx)?;
+        log::info!("Resolve recipes");
+        let graph = self.build(data, items, &targets, ignore, &self.exclude_types()?)?;
 
         println!();
-        println!("    /* Recipes */");
-
-        for rid in &recipes {
-            if let Some(recipe) = data.recipes.get(rid) {
-                println!();
-                println!("    /* {} */", recipe.name);
-                println!(
-                    "    \"{}\" [ label=\"{}\" shape=point width=0.1 ]",
-                    rid.0, recipe.seconds
-                );
+        let nodes = graph.items.len() + graph.recipes.len();
+        let edges = graph.edges().count();
+        println!(
+            "Graph: {} node{} ({} item{}, {} recipe{}), {} edge{}",
+            nodes,
+            plural(nodes),
+            graph.items.len(),
+            plural(graph.items.len()),
+            graph.recipes.len(),
+            plural(graph.recipes.len()),
+            edges,
+            plural(edges)
+        );
 
-                for i in &recipe.inputs {
-                    if let Some(item) = data.items.get(&i.id) {
-                        println!(
-                            "    \"{}\" -> \"{}\" [ name=\"{}\" ]",
-                            item.name, rid.0, i.amount
-                        );
-                    }
-                }
+        if graph.items.is_empty() && graph.recipes.is_empty() {
+            return Err(Error::usage("Dry run failed: the graph is empty"));
+        }
 
-                for o in &recipe.outputs {
-                    if let Some(item) = data.items.get(&o.id) {
-                        println!(
-                            "    \"{}\" -> \"{}\" [ name=\"{}\" ]",
-                            rid.0, item.name, o.amount
-                        );
-                    }
-                }
+        Ok(())
+    }
+
+    fn write(&self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        match &self.output {
+            Some(path) if self.split_per_item => self.write_split(data, ctx, path),
+            Some(path) => {
+                log::info!("Write graph to {:#?}", path);
+
+                write_output(path, |out| self.write_graph(data, ctx, out))
+            }
+            None => self.write_graph(data, ctx, &mut stdout().lock()),
+        }
+    }
+
+    /// Write one graph for each requested item to the files derived from the
+    /// passed output path. The data is only parsed once.
+    fn write_split(&self, data: &Data, ctx: &SelectorContext, output: &Path) -> Result<(), Error> {
+        let (items, targets, ignore) = self.parse(data, ctx)?;
+
+        let roots = items
+            .iter()
+            .chain(targets.keys())
+            .copied()
+            .collect::<BTreeSet<_>>();
+
+        let template = output.to_str().filter(|path| path.contains("{name}"));
+        if template.is_none() {
+            create_dir_all(output)?;
+        }
+        let extension = match self.format {
+            GraphFormat::Dot => "dot",
+            GraphFormat::MatrixCsv => "csv",
+        };
+
+        let mut names = BTreeSet::new();
+        let mut files = Vec::with_capacity(roots.len());
+        for iid in roots {
+            let name = data
+                .items
+                .get(&iid)
+                .map(|item| sanitize_file_name(&item.name))
+                .filter(|name| !name.is_empty())
+                .unwrap_or_else(|| iid.label(&data.keys).to_string());
+            let name = if names.contains(&name) {
+                format!("{}-{}", name, iid.label(&data.keys))
+            } else {
+                name
+            };
+
+            let path = match template {
+                Some(template) => PathBuf::from(template.replace("{name}", &name)),
+                None => output.join(format!("{}.{}", name, extension)),
+            };
+            if path.exists() && !self.force {
+                return Err(Error::usage(format!(
+                    "File {:#?} already exists (use --force to overwrite)",
+                    path
+                )));
             }
+
+            names.insert(name);
+            files.push((iid, path));
         }
 
-        println!("}}");
+        let mut written = Vec::with_capacity(files.len());
+        for (iid, path) in files {
+            log::info!(item:% = iid; "Write graph of item {} to {:#?}", iid, path);
+
+            let items = items.iter().filter(|i| **i == iid).copied().collect();
+            let targets = targets
+                .iter()
+                .filter(|(i, _)| **i == iid)
+                .map(|(i, rate)| (*i, *rate))
+                .collect();
+
+            let mut index = None;
+            write_output(&path, |out| {
+                index = Some(self.emit(data, items, &targets, ignore.clone(), out)?);
+
+                Ok(())
+            })?;
+
+            written.extend(index.map(|index| (path, index)));
+        }
+
+        let plural = if written.len() == 1 { "" } else { "s" };
+        log::info!("Wrote {} graph{}", written.len(), plural);
+        for (path, index) in &written {
+            log::info!(
+                "  {}: {} nodes, {} edges",
+                path.display(),
+                index.nodes.len(),
+                index.edges.len()
+            );
+        }
 
         Ok(())
     }
-}
 
-fn parse_ids(data: &Data, items: &[String], items_only: bool) -> Result<Vec<usize>, Error> {
-    let mut ret = Vec::<usize>::new();
+    /// Regenerate the graph whenever the data file changes, until Ctrl-C
+    /// is pressed.
+    pub fn watch(mut self, args: &DataArgs, ctx: &SelectorContext) -> Result<(), Error> {
+        if self.options.items.is_empty() && self.options.targets.is_empty() {
+            self.pick_items(&load_data(args)?)?;
+        }
+
+        let (sender, receiver) = channel();
+
+        let watch_sender = sender.clone();
+        let mut debouncer = new_debouncer(Duration::from_millis(250), move |res| {
+            let _ = watch_sender.send(WatchEvent::Changed(res));
+        })?;
+
+        ctrlc::set_handler(move || {
+            let _ = sender.send(WatchEvent::Stop);
+        })
+        .map_err(Error::custom)?;
+
+        // Besides the data files, all files that are read while the graph is
+        // generated are watched.
+        let theme = self
+            .options
+            .theme
+            .iter()
+            .filter(|theme| Theme::is_file(theme));
+        let paths = args
+            .files()
+            .into_iter()
+            .chain(ctx.ignore_files.iter().cloned())
+            .chain(ctx.groups_file.iter().cloned())
+            .chain(self.exclude_files.iter().cloned())
+            .chain(theme.map(PathBuf::from))
+            .collect::<Vec<_>>();
+
+        // Watch the parent directories, because editors often replace the file
+        // instead of writing to it, which would end a watch on the file itself.
+        let mut files = BTreeSet::new();
+        for path in paths {
+            let dir = match path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir.canonicalize()?,
+                _ => Path::new(".").canonicalize()?,
+            };
+            let file_name = path
+                .file_name()
+                .ok_or_else(|| Error::custom(format!("Invalid file to watch: {:#?}", path)))?;
 
-    for item in items {
-        if let Ok(id) = usize::from_str(item) {
-            ret.push(id);
-        } else if let Some(id) = data.item_by_name.get(item) {
-            ret.push(id.0);
-        } else if let Some(id) = data.recipes_by_name.get(item) {
-            if !items_only {
-                ret.push(id.0);
+            if !files.iter().any(|f: &PathBuf| f.parent() == Some(&dir)) {
+                debouncer
+                    .watcher()
+                    .watch(&dir, RecursiveMode::NonRecursive)?;
             }
-        } else {
-            let item_type = match item.to_lowercase().as_str() {
-                "all" => {
-                    ret.extend(data.items.keys().map(|id| id.0));
 
-                    if !items_only {
-                        ret.extend(data.recipes.keys().map(|id| id.0));
-                    }
+            files.insert(dir.join(file_name));
+        }
+
+        self.regenerate(args, ctx);
+        log::info!(
+            "Watching {} files for changes (press Ctrl-C to exit)",
+            files.len()
+        );
 
-                    continue;
+        for event in receiver {
+            match event {
+                WatchEvent::Changed(Ok(events)) => {
+                    if events.iter().any(|e| files.contains(&e.path)) {
+                        self.regenerate(args, ctx);
+                    }
                 }
-                "explicit" if !items_only => {
-                    ret.extend(data.recipes.iter().filter_map(|(rid, r)| {
-                        if r.explicit {
-                            Some(rid.0)
-                        } else {
-                            None
-                        }
-                    }));
+                WatchEvent::Changed(Err(err)) => log::error!("Error while watching: {}", err),
+                WatchEvent::Stop => break,
+            }
+        }
+
+        log::info!("Stop watching");
+
+        Ok(())
+    }
+
+    fn regenerate(&self, args: &DataArgs, ctx: &SelectorContext) {
+        let start = Instant::now();
+
+        match load_data(args).and_then(|data| self.write(&data, ctx)) {
+            Ok(()) => log::info!("Regenerated graph in {:.2?}", start.elapsed()),
+            Err(err) => log::error!("Error while regenerating the graph: {}", err),
+        }
+    }
+
+    /// Write the graph in the selected format to the passed writer.
+    pub fn write_graph(
+        &self,
+        data: &Data,
+        ctx: &SelectorContext,
+        out: &mut dyn Write,
+    ) -> Result<(), Error> {
+        let (items, targets, ignore) = self.parse(data, ctx)?;
+
+        self.emit(data, items, &targets, ignore, out).map(|_| ())
+    }
+
+    /// Item types of `--exclude-type` that are excluded from the graph.
+    fn exclude_types(&self) -> Result<Vec<ItemType>, Error> {
+        Ok(split_selectors(&self.exclude_types)?
+            .iter()
+            .map(|t| ItemType::from(t.trim().to_uppercase().as_str()))
+            .collect())
+    }
+
+    /// Parse the requested items, the targets and the ignored items and
+    /// recipes.
+    fn parse(&self, data: &Data, ctx: &SelectorContext) -> Result<Request, Error> {
+        let GraphOptions {
+            items,
+            targets,
+            ignore,
+            unlocked_techs,
+            handcraft_only,
+            ..
+        } = &self.options;
+
+        log::info!("Parse items");
+        let mut items = parse_items(data, ctx, items, &self.exclude_files)?.items;
+        log::info!("  loaded {} items", items.len());
+
+        log::info!("Parse targets");
+        let targets = parse_targets(data, ctx, targets)?;
+        log::info!("  loaded {} targets", targets.len());
+
+        log::info!("Parse ignored recipes");
+        let mut ignore = parse_ignore(data, ctx, ignore, false)?;
+        if !unlocked_techs.is_empty() {
+            let techs = parse_techs(data, unlocked_techs)?;
+            ignore.recipes.extend(data.locked_recipes(&techs));
+        }
+        if *handcraft_only {
+            ignore.recipes.extend(
+                data.recipes
+                    .iter()
+                    .filter(|(_, r)| !r.handcraft)
+                    .map(|(rid, _)| *rid),
+            );
+        }
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
 
-                    continue;
+        let exclude_types = self.exclude_types()?;
+        if !exclude_types.is_empty() {
+            log::info!("Parse excluded item types");
+            for type_ in &exclude_types {
+                if !data.items.values().any(|item| item.type_ == *type_) {
+                    log::warn!("No item has the type {}", type_);
                 }
-                "advanced" if !items_only => {
-                    ret.extend(
-                        ADVANCED_RECIPES
-                            .iter()
-                            .filter_map(|name| data.recipes_by_name.get(*name).map(|rid| rid.0)),
+            }
+
+            items.retain(|iid| match data.items.get(iid) {
+                Some(item) if exclude_types.contains(&item.type_) => {
+                    log::warn!(
+                        item:% = iid;
+                        "Item {} is selected, but excluded by its type {}",
+                        item.name,
+                        item.type_
                     );
 
-                    continue;
+                    false
                 }
-                "material" => ItemType::Material,
-                "matrix" => ItemType::Matrix,
-                "product" => ItemType::Product,
-                "production" => ItemType::Production,
-                "resource" => ItemType::Resource,
-                "component" => ItemType::Component,
-                "logistics" => ItemType::Logistics,
-                s => {
-                    return Err(Error::custom(format!("Invalid or unknown item: {}", s)));
-                }
-            };
+                _ => true,
+            });
+            log::info!("  loaded {} excluded item types", exclude_types.len());
+        }
+
+        Ok((items, targets, ignore))
+    }
 
-            ret.extend(data.items.iter().filter_map(|(iid, i)| {
-                if i.type_ == item_type {
-                    Some(iid.0)
+    /// Resolve the graph of the passed items and targets, write it in the
+    /// selected format to the passed writer and return the index of the
+    /// written nodes and edges.
+    fn emit(
+        &self,
+        data: &Data,
+        items: BTreeSet<ItemId>,
+        targets: &BTreeMap<ItemId, f64>,
+        ignore: Selection,
+        out: &mut dyn Write,
+    ) -> Result<GraphIndex, Error> {
+        let GraphOptions {
+            resolve_deps,
+            merge_duplicate_recipes,
+            color_by,
+            size_by,
+            scale_recipes,
+            split_common,
+            combine_edges,
+            aggregate_by,
+            ..
+        } = &self.options;
+        let Self {
+            meta_no_timestamp,
+            diff_against,
+            format,
+            rates,
+            emit_index,
+            ..
+        } = self;
+        let exclude_types = self.exclude_types()?;
+        let plan_ignore = ignore.clone();
+        let build = |data: &Data, items: BTreeSet<ItemId>, ignore: Selection| {
+            self.build(data, items, targets, ignore, &exclude_types)
+        };
+
+        let old = match diff_against {
+            Some(path) => {
+                log::info!(path:% = path.display(); "Load old data from {:#?}", path);
+                let old = Data::load(path)?;
+
+                let items = match_items(data, &old, &items);
+                let ignore = Selection {
+                    items: match_items(data, &old, &ignore.items),
+                    recipes: match_recipes(data, &old, &ignore.recipes),
+                };
+
+                Some((old, items, ignore))
+            }
+            None => None,
+        };
+
+        log::info!("Resolve recipes");
+        let mut graph = build(data, items, ignore)?;
+        log::info!("  use {} items", graph.items.len());
+        log::info!("  use {} recipes", graph.recipes.len());
+
+        let diff = match old {
+            Some((old, items, ignore)) => {
+                log::info!("Resolve recipes of the old data");
+                // Items that are new are still drawn, as additions to an
+                // empty graph.
+                let old_graph = if items.is_empty() {
+                    ProductionGraph::default()
                 } else {
-                    None
+                    build(&old, items, ignore)?
+                };
+                log::info!("  use {} items", old_graph.items.len());
+                log::info!("  use {} recipes", old_graph.recipes.len());
+
+                let diff = graph.combine(old_graph);
+                log::info!(
+                    "  {} added, {} removed and {} changed recipes",
+                    diff.counts.recipes.added,
+                    diff.counts.recipes.removed,
+                    diff.counts.recipes.changed
+                );
+
+                Some(diff)
+            }
+            None => None,
+        };
+
+        if !targets.is_empty() {
+            log::info!("Propagate target rates");
+            graph.propagate_rates(data, targets, &self.plan_options(Selection::default()))?;
+        }
+
+        let summary = if self.options.summary_node && !targets.is_empty() {
+            log::info!("Calculate summary");
+            let plan = self.plan(data, &graph, targets, plan_ignore)?;
+
+            Some(Summary::new(data, &plan))
+        } else {
+            None
+        };
+
+        let aggregate = aggregate_by.map(|by| {
+            log::info!("Aggregate graph by {}", by);
+            let aggregate = graph.aggregate(by);
+            log::info!("  use {} groups", aggregate.groups.len());
+
+            aggregate
+        });
+
+        let meta = Meta::new(data, !*meta_no_timestamp)
+            .values("items", &self.options.items)
+            .values("target", &self.options.targets)
+            .values("ignore", &self.options.ignore)
+            .values("exclude-type", &self.exclude_types)
+            .values(
+                "exclude-file",
+                self.exclude_files.iter().map(|p| p.display()),
+            )
+            .values("unlocked-techs", &self.options.unlocked_techs)
+            .option("resolve-deps", resolve_deps)
+            .option("handcraft-only", self.options.handcraft_only)
+            .values(
+                "proliferator",
+                (self.options.proliferator != Proliferator::None).then(|| {
+                    format!(
+                        "{} ({})",
+                        self.options.proliferator, self.options.spray_mode
+                    )
+                }),
+            )
+            .option("merge-duplicate-recipes", merge_duplicate_recipes)
+            .values("color-by", color_by)
+            .values("node-size-by", size_by)
+            .option("scale-recipes", scale_recipes)
+            .values("split-common", split_common)
+            .values("theme", &self.options.theme)
+            .option("combine-edges", combine_edges)
+            .values("aggregate-by", aggregate_by)
+            .values("diff-against", diff_against.iter().map(|p| p.display()));
+
+        let mut index = self.write_checked(out, |out| {
+            if *format == GraphFormat::MatrixCsv {
+                log::info!("Generate adjacency matrix");
+
+                match &aggregate {
+                    Some(aggregate) => write_aggregate_matrix_csv(aggregate, *rates, out),
+                    None => write_matrix_csv(&graph, *rates, out),
                 }
-            }));
+            } else {
+                let resolved = DotOptions {
+                    summary,
+                    diff,
+                    meta: (!self.no_meta).then(|| meta.clone()),
+                    ..DotOptions::default()
+                };
+
+                self.emit_dot(data, &graph, aggregate.as_ref(), resolved, out)
+            }
+        })?;
+
+        if let Some(path) = emit_index {
+            log::info!("Write index to {:#?}", path);
+            index.meta = Some(meta);
+
+            let mut json = serde_json::to_vec_pretty(&index)?;
+            json.push(b'\n');
+            if self.check {
+                log::info!("Check index");
+                serde_json::from_slice::<serde_json::Value>(&json)
+                    .map_err(|err| Error::custom(format!("Invalid index: {}", err)))?;
+            }
+
+            write_output(path, |out| Ok(out.write_all(&json)?))?;
         }
+
+        Ok(index)
     }
 
-    Ok(ret)
-}
+    /// Resolve the graph of the passed items and targets.
+    fn build(
+        &self,
+        data: &Data,
+        items: BTreeSet<ItemId>,
+        targets: &BTreeMap<ItemId, f64>,
+        ignore: Selection,
+        exclude_types: &[ItemType],
+    ) -> Result<ProductionGraph, Error> {
+        GraphBuilder::new(data)
+            .targets(items)
+            .targets(targets.keys().copied())
+            .ignore(ignore)
+            .exclude_types(exclude_types.to_vec())
+            .resolve_deps(self.options.resolve_deps)
+            .tiers(self.options.rank_by_tier)
+            .merge_duplicates(self.options.merge_duplicate_recipes)
+            .build()
+    }
 
-fn resolve_item_dependencies(
-    data: &Data,
-    recipes: &mut BTreeSet<RecipeId>,
-    items: &mut BTreeSet<ItemId>,
-    exclude: &BTreeSet<usize>,
-    iid: ItemId,
-    resolve_deps: bool,
-) {
-    if let Some(rids) = data.as_output.get(&iid) {
-        for rid in rids {
-            if !exclude.contains(&rid.0) && recipes.insert(*rid) {
-                if let Some(r) = data.recipes.get(rid) {
-                    for input in &r.inputs {
-                        let iid = input.id;
-                        if !exclude.contains(&iid.0) {
-                            items.insert(iid);
-
-                            if resolve_deps {
-                                resolve_item_dependencies(data, recipes, items, exclude, iid, true);
-                            }
-                        }
-                    }
-                }
+    /// Options of the planner for the rates of the graph, like the options of
+    /// the `plan` command.
+    fn plan_options(&self, ignore: Selection) -> PlanOptions {
+        PlanOptions {
+            ignore,
+            proliferator: self.options.proliferator,
+            spray_mode: self.options.spray_mode,
+            selector: Box::new(PreferBasic),
+            ..PlanOptions::default()
+        }
+    }
+
+    /// Plan of the targets that only uses the recipes of the graph,
+    /// calculated by the planner of the `plan` command.
+    fn plan(
+        &self,
+        data: &Data,
+        graph: &ProductionGraph,
+        targets: &BTreeMap<ItemId, f64>,
+        mut ignore: Selection,
+    ) -> Result<ProductionPlan, Error> {
+        let used = graph
+            .recipes
+            .iter()
+            .flat_map(|(rid, node)| std::iter::once(rid).chain(&node.merged))
+            .collect::<BTreeSet<_>>();
+        ignore
+            .recipes
+            .extend(data.recipes.keys().filter(|rid| !used.contains(rid)));
+
+        ProductionPlan::new(data, targets, self.plan_options(ignore))
+    }
+
+    /// Pass the output of `f` to the writer. With `--check` the output is
+    /// buffered and only passed on if it is valid.
+    fn write_checked<F>(&self, out: &mut dyn Write, f: F) -> Result<GraphIndex, Error>
+    where
+        F: FnOnce(&mut dyn Write) -> Result<GraphIndex, Error>,
+    {
+        if !self.check {
+            return f(out);
+        }
+
+        let mut buffer = Vec::new();
+        let index = f(&mut buffer)?;
+
+        match self.format {
+            GraphFormat::Dot => {
+                log::info!("Check graph with {:#?}", self.dot_path);
+                check_dot(&self.dot_path, &buffer)?;
+            }
+            GraphFormat::MatrixCsv => {
+                log::info!("No validator for the {} format, skip check", self.format);
             }
         }
+
+        out.write_all(&buffer)?;
+
+        Ok(index)
+    }
+
+    /// Write the graph, or the aggregated graph, in the DOT format. The
+    /// passed options contain the parts that depend on the resolved graph
+    /// (the summary, the diff and the metadata), all other options are taken
+    /// from the command line.
+    fn emit_dot(
+        &self,
+        data: &Data,
+        graph: &ProductionGraph,
+        aggregate: Option<&AggregateGraph>,
+        resolved: DotOptions,
+        out: &mut dyn Write,
+    ) -> Result<GraphIndex, Error> {
+        let GraphOptions {
+            rank_by_tier,
+            icons,
+            icons_dir,
+            no_source_style,
+            color_by,
+            depth_colors,
+            depth_cutoff,
+            size_by,
+            node_size_range,
+            font_size_range,
+            scale_recipes,
+            split_common,
+            theme,
+            recipe_label,
+            combine_edges,
+            ..
+        } = &self.options;
+
+        let theme = match theme {
+            Some(theme) => Theme::load(theme)?,
+            None => Theme::default(),
+        };
+
+        log::info!("Generate graph");
+        let options = DotOptions {
+            icons_dir: icons.then(|| icons_dir.clone()),
+            source_style: !*no_source_style,
+            rank_by_tier: *rank_by_tier,
+            color_by: *color_by,
+            depth_colors: DepthColors {
+                start: depth_colors
+                    .first()
+                    .copied()
+                    .unwrap_or(theme.depth_colors.start),
+                end: depth_colors
+                    .get(1)
+                    .copied()
+                    .unwrap_or(theme.depth_colors.end),
+                cutoff: *depth_cutoff,
+            },
+            size_by: *size_by,
+            node_sizes: NodeSizes {
+                width: (node_size_range[0], node_size_range[1]),
+                font_size: (font_size_range[0], font_size_range[1]),
+                machines: scale_recipes
+                    .then(|| graph.machines(data, &self.plan_options(Selection::default()))),
+            },
+            split_common: *split_common,
+            recipe_label: *recipe_label,
+            combine_edges: *combine_edges,
+            theme,
+            ..resolved
+        };
+
+        match aggregate {
+            Some(aggregate) => write_aggregate_dot(aggregate, &options, out),
+            None => write_dot(graph, &options, out),
+        }
+    }
+}
+
+/// Requested items, targets and ignored items and recipes.
+type Request = (BTreeSet<ItemId>, BTreeMap<ItemId, f64>, Selection);
+
+/// Parse the passed graph with `dot -Tcanon`, and return the errors of
+/// graphviz if the graph is invalid.
+fn check_dot(dot_path: &Path, dot: &[u8]) -> Result<(), Error> {
+    let mut child = Command::new(dot_path)
+        .arg("-Tcanon")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| {
+            Error::tool(format!(
+                "Unable to execute {:#?}, --check needs graphviz: {}",
+                dot_path, err
+            ))
+        })?;
+    child.stdin.take().unwrap().write_all(dot)?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::tool(format!(
+            "Invalid graph: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Name of the passed item, or its id if the item is missing in the data.
+fn item_name(data: &Data, iid: ItemId) -> String {
+    data.items.get(&iid).map_or_else(
+        || iid.label(&data.keys).to_string(),
+        |item| item.name.clone(),
+    )
+}
+
+/// Number and first names of the entries of a selection, for `--dry-run`.
+fn describe_selection(data: &Data, selection: &Selection) -> String {
+    const NAMES: usize = 5;
+
+    let names = selection
+        .items
+        .iter()
+        .map(|iid| item_name(data, *iid))
+        .chain(selection.recipes.iter().map(|rid| {
+            let name = data.recipes.get(rid).map_or_else(
+                || rid.label(&data.keys).to_string(),
+                |recipe| recipe.name.clone(),
+            );
+
+            format!("recipe:{}", name)
+        }))
+        .collect::<Vec<_>>();
+
+    let mut ret = format!(
+        "{} item{}, {} recipe{} ({}",
+        selection.items.len(),
+        plural(selection.items.len()),
+        selection.recipes.len(),
+        plural(selection.recipes.len()),
+        names.iter().take(NAMES).join(", ")
+    );
+    if names.len() > NAMES {
+        ret += &format!(", ... {} more", names.len() - NAMES);
     }
+    ret.push(')');
+
+    ret
+}
+
+/// Plural suffix of a noun for the passed count.
+fn plural(count: usize) -> &'static str {
+    if count == 1 {
+        ""
+    } else {
+        "s"
+    }
+}
+
+/// Replace all characters of the passed item name that may not be portable
+/// in file names.
+fn sanitize_file_name(name: &str) -> String {
+    let name = name
+        .chars()
+        .map(|c| match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' => c,
+            _ => '_',
+        })
+        .collect::<String>();
+
+    name.trim_matches('_').to_owned()
+}
+
+/// Items of the old data that match the passed items of the new data, by id
+/// or by name.
+fn match_items(data: &Data, old: &Data, items: &BTreeSet<ItemId>) -> BTreeSet<ItemId> {
+    items
+        .iter()
+        .filter_map(|iid| {
+            if old.items.contains_key(iid) {
+                return Some(*iid);
+            }
+
+            let name = &data.items.get(iid)?.name;
+            let ret = old
+                .item_by_name
+                .get(name)
+                .and_then(|ids| ids.first())
+                .copied();
+            if ret.is_none() {
+                log::warn!(item:% = iid; "  item {} does not exist in the old data", name);
+            }
+
+            ret
+        })
+        .collect()
 }
 
-const ADVANCED_RECIPES: &[&str] = &[
-    "Casimir Crystal (Advanced)",
-    "Organic Crystal (Original)",
-    "Crystal Silicon (Advanced)",
-    "Photon Combiner (Advanced)",
-    "Space Warper (Advanced)",
-    "Particle Container (Advanced)",
-    "Graphene (Advanced)",
-    "Carbon Nanotube (Advanced)",
-    "Diamond (Advanced)",
-];
+/// Recipes of the old data that match the passed recipes of the new data, by
+/// id or by name.
+fn match_recipes(data: &Data, old: &Data, recipes: &BTreeSet<RecipeId>) -> BTreeSet<RecipeId> {
+    recipes
+        .iter()
+        .filter_map(|rid| {
+            if old.recipes.contains_key(rid) {
+                return Some(*rid);
+            }
+
+            let name = &data.recipes.get(rid)?.name;
+
+            old.recipes_by_name.get(name)?.first().copied()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::resolver::PinnedList;
+
+    /// Gear is made of ingots by its basic recipe, or cast from ore by an
+    /// alternative recipe.
+    const SOURCE: &str = r#"
+        return {
+          game_items = {
+            { id = 1, name = "Ore", type = "RESOURCE" },
+            { id = 2, name = "Ingot", type = "MATERIAL" },
+            { id = 3, name = "Gear", type = "COMPONENT" },
+          },
+          game_recipes = {
+            { id = 1, name = "Ingot", type = "SMELT", seconds = 1, inputs = { 1, 1 }, outputs = { 2, 1 } },
+            { id = 2, name = "Gear", type = "ASSEMBLE", seconds = 1, inputs = { 2, 2 }, outputs = { 3, 1 } },
+            { id = 3, name = "Cast Gear", type = "SMELT", seconds = 2, inputs = { 1, 3 }, outputs = { 3, 1 }, explicit = true },
+          },
+        }
+    "#;
+
+    #[test]
+    fn summary_uses_the_totals_of_the_plan() {
+        let data = Data::from_lua_source("gear", SOURCE, None, &[]).unwrap();
+        let cmd = CreateProductionGraph::from_iter_safe([
+            "create-production-graph",
+            "-t",
+            "Gear=60",
+            "-r",
+            "--summary-node",
+        ])
+        .unwrap();
+        let targets = BTreeMap::from([(ItemId(3), 60.0)]);
+
+        let mut graph = cmd
+            .build(&data, BTreeSet::new(), &targets, Selection::default(), &[])
+            .unwrap();
+        assert_eq!(graph.recipes.len(), 3);
+        graph
+            .propagate_rates(&data, &targets, &cmd.plan_options(Selection::default()))
+            .unwrap();
+
+        let plan = cmd
+            .plan(&data, &graph, &targets, Selection::default())
+            .unwrap();
+        let summary = Summary::new(&data, &plan);
+
+        // Options of the `plan` command without any flags.
+        let options = PlanOptions {
+            selector: Box::new(PinnedList::new([], Box::new(PreferBasic))),
+            ..PlanOptions::default()
+        };
+        let expected = ProductionPlan::new(&data, &targets, options).unwrap();
+
+        assert_eq!(plan.recipes, expected.recipes);
+        assert_eq!(plan.resources, expected.resources);
+        assert_eq!(summary.resources, vec![("Ore".to_owned(), 120.0)]);
+        assert_eq!(
+            summary.machines,
+            vec![("Ingot".to_owned(), 2.0), ("Gear".to_owned(), 1.0)]
+        );
+
+        // The rates of the graph are split across both recipes of gear.
+        assert_eq!(graph.recipes[&RecipeId(3)].rate, Some(30.0));
+    }
+}
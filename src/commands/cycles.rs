@@ -0,0 +1,232 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    analysis::{Node, RecipeGraph},
+    data::{Data, IdLabel, ItemId, RecipeId},
+    error::Error,
+    format::Format,
+    resolver::Resolver,
+    selector::{parse_ids, parse_ignore, SelectorContext},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Cycles {
+    /// Products to search for cycles (all recipes are used if empty).
+    #[structopt(short = "i", long = "items")]
+    pub items: Vec<String>,
+
+    /// Recipes to exclude from the search.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Resolve the dependencies of the products.
+    #[structopt(short = "r", long = "resolve-deps")]
+    pub resolve_deps: bool,
+
+    /// Output format (text or json).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Component {
+    items: Vec<Entry<IdLabel>>,
+    recipes: Vec<Entry<IdLabel>>,
+    cycles: Vec<Vec<Step>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Entry<Id> {
+    id: Id,
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Step {
+    item: Entry<IdLabel>,
+    consumed: usize,
+    recipe: Entry<IdLabel>,
+    produced: usize,
+}
+
+impl Cycles {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let Self {
+            items,
+            ignore,
+            resolve_deps,
+            format,
+        } = self;
+
+        log::info!("Parse ignored recipes");
+        let ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        let recipes = if items.is_empty() {
+            data.recipes
+                .keys()
+                .filter(|rid| !ignore.recipes.contains(rid))
+                .copied()
+                .collect::<BTreeSet<_>>()
+        } else {
+            log::info!("Parse items");
+            let items = parse_ids(data, ctx, &items, true)?.items;
+            log::info!("  loaded {} items", items.len());
+
+            log::info!("Resolve recipes");
+            let mut resolver = Resolver::new(data, &ignore, resolve_deps);
+            resolver.resolve(items);
+
+            resolver.recipe_ids()
+        };
+        log::info!("  use {} recipes", recipes.len());
+
+        log::info!("Search cycles");
+        let graph = RecipeGraph::new(data, &recipes);
+        let components = graph
+            .strongly_connected_components()
+            .into_iter()
+            .filter(|c| c.len() > 1)
+            .map(|c| make_component(data, &graph, c))
+            .collect::<Vec<_>>();
+        log::info!("  found {} cyclic components", components.len());
+
+        match format {
+            Format::Text => {
+                for (i, component) in components.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+
+                    println!(
+                        "Component {} ({} items, {} recipes):",
+                        i + 1,
+                        component.items.len(),
+                        component.recipes.len()
+                    );
+
+                    for cycle in &component.cycles {
+                        let mut line = String::new();
+                        for step in cycle {
+                            line += &format!(
+                                "{} -{}-> [{}] -{}-> ",
+                                step.item.name, step.consumed, step.recipe.name, step.produced
+                            );
+                        }
+                        line += &cycle[0].item.name;
+
+                        println!("    {}", line);
+                    }
+                }
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&components)?),
+            format => return Err(format.unsupported()),
+        }
+
+        Ok(())
+    }
+}
+
+fn make_component(data: &Data, graph: &RecipeGraph, nodes: Vec<Node>) -> Component {
+    let allowed = nodes.iter().copied().collect::<BTreeSet<_>>();
+
+    let mut cycles = BTreeSet::new();
+    for node in &nodes {
+        if let Some(mut cycle) = graph.shortest_cycle(*node, &allowed) {
+            if let Node::Recipe(_) = cycle[0] {
+                cycle.rotate_left(1);
+            }
+
+            let min = (0..cycle.len())
+                .step_by(2)
+                .min_by_key(|i| cycle[*i])
+                .unwrap_or(0);
+            cycle.rotate_left(min);
+
+            cycles.insert(cycle);
+        }
+    }
+
+    let mut items = Vec::new();
+    let mut recipes = Vec::new();
+    for node in nodes {
+        match node {
+            Node::Item(iid) => items.push(item_entry(data, iid)),
+            Node::Recipe(rid) => recipes.push(recipe_entry(data, rid)),
+        }
+    }
+
+    let cycles = cycles
+        .into_iter()
+        .map(|cycle| make_steps(data, &cycle))
+        .collect();
+
+    Component {
+        items,
+        recipes,
+        cycles,
+    }
+}
+
+fn make_steps(data: &Data, cycle: &[Node]) -> Vec<Step> {
+    let mut steps = Vec::new();
+
+    for (i, pair) in cycle.chunks(2).enumerate() {
+        let (iid, rid) = match pair {
+            [Node::Item(iid), Node::Recipe(rid)] => (*iid, *rid),
+            _ => continue,
+        };
+        let next = match cycle.get(2 * i + 2).unwrap_or(&cycle[0]) {
+            Node::Item(next) => *next,
+            Node::Recipe(_) => continue,
+        };
+
+        let recipe = data.recipes.get(&rid);
+        let consumed = recipe
+            .and_then(|r| r.inputs.iter().find(|i| i.id == iid))
+            .map(|i| i.amount)
+            .unwrap_or_default();
+        let produced = recipe
+            .and_then(|r| r.outputs.iter().find(|o| o.id == next))
+            .map(|o| o.amount)
+            .unwrap_or_default();
+
+        steps.push(Step {
+            item: item_entry(data, iid),
+            consumed,
+            recipe: recipe_entry(data, rid),
+            produced,
+        });
+    }
+
+    steps
+}
+
+fn item_entry(data: &Data, iid: ItemId) -> Entry<IdLabel> {
+    Entry {
+        id: iid.label(&data.keys),
+        name: data
+            .items
+            .get(&iid)
+            .map(|i| i.name.clone())
+            .unwrap_or_else(|| iid.label(&data.keys).to_string()),
+    }
+}
+
+fn recipe_entry(data: &Data, rid: RecipeId) -> Entry<IdLabel> {
+    Entry {
+        id: rid.label(&data.keys),
+        name: data
+            .recipes
+            .get(&rid)
+            .map(|r| r.name.clone())
+            .unwrap_or_else(|| rid.label(&data.keys).to_string()),
+    }
+}
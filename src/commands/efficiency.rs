@@ -0,0 +1,209 @@
+use std::collections::BTreeSet;
+
+use itertools::Itertools;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    data::{Data, ItemId, RecipeId},
+    error::Error,
+    format::{csv_line, Format},
+    selector::{parse_ids, SelectorContext},
+    table::{Cell, Color, Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Efficiency {
+    /// Items to compare the producing recipes for.
+    #[structopt(short = "i", long = "items", required_unless = "all")]
+    pub items: Vec<String>,
+
+    /// Compare the producing recipes of all items.
+    #[structopt(short = "a", long = "all")]
+    pub all: bool,
+
+    /// Output format (text, json or csv).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    item_id: ItemId,
+    item: String,
+    recipe_id: RecipeId,
+    recipe: String,
+    productivity: f64,
+    outputs_per_minute: f64,
+    inputs_per_minute: f64,
+    input_output_ratio: f64,
+    beats_base_recipe: bool,
+}
+
+impl Efficiency {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext, style: &Style) -> Result<(), Error> {
+        let Self { items, all, format } = self;
+
+        log::info!("Parse items");
+        let items = if all {
+            data.as_output.keys().copied().collect::<BTreeSet<_>>()
+        } else {
+            parse_ids(data, ctx, &items, true)?.items
+        };
+        log::info!("  loaded {} items", items.len());
+
+        let rows = items
+            .iter()
+            .flat_map(|iid| item_rows(data, *iid))
+            .collect::<Vec<_>>();
+
+        match format {
+            Format::Text => {
+                for (i, (_, rows)) in rows
+                    .iter()
+                    .group_by(|row| row.item_id)
+                    .into_iter()
+                    .enumerate()
+                {
+                    let rows = rows.collect::<Vec<_>>();
+                    if i > 0 {
+                        println!();
+                    }
+
+                    println!("{}:", rows[0].item);
+
+                    let mut table = Table::new([
+                        Column::left("Recipe"),
+                        Column::right("Out/min"),
+                        Column::right("In/min"),
+                        Column::right("In/out"),
+                        Column::right("Prod."),
+                        Column::left(""),
+                    ])
+                    .indent(4);
+                    for row in rows {
+                        table.row([
+                            Cell::new(&row.recipe),
+                            Cell::new(format!("{:.2}", row.outputs_per_minute)),
+                            Cell::new(format!("{:.2}", row.inputs_per_minute)),
+                            Cell::new(format!("{:.2}", row.input_output_ratio)),
+                            if row.productivity != 1.0 {
+                                Cell::new(format!("×{}", row.productivity))
+                            } else {
+                                Cell::new("")
+                            },
+                            if row.beats_base_recipe {
+                                Cell::colored("beats base recipe", Some(Color::Green))
+                            } else {
+                                Cell::new("")
+                            },
+                        ]);
+                    }
+
+                    table.print(style);
+                }
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+            Format::Csv => {
+                println!(
+                    "{}",
+                    csv_line([
+                        "item_id",
+                        "item",
+                        "recipe_id",
+                        "recipe",
+                        "productivity",
+                        "outputs_per_minute",
+                        "inputs_per_minute",
+                        "input_output_ratio",
+                        "beats_base_recipe",
+                    ])
+                );
+
+                for row in &rows {
+                    println!(
+                        "{}",
+                        csv_line([
+                            row.item_id.to_string(),
+                            row.item.clone(),
+                            row.recipe_id.to_string(),
+                            row.recipe.clone(),
+                            row.productivity.to_string(),
+                            format!("{:.4}", row.outputs_per_minute),
+                            format!("{:.4}", row.inputs_per_minute),
+                            format!("{:.4}", row.input_output_ratio),
+                            row.beats_base_recipe.to_string(),
+                        ])
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Create the rows of all recipes that produce the passed item, sorted
+/// best-first.
+fn item_rows(data: &Data, iid: ItemId) -> Vec<Row> {
+    let item = data
+        .items
+        .get(&iid)
+        .map(|i| i.name.clone())
+        .unwrap_or_else(|| iid.label(&data.keys).to_string());
+
+    let mut rows = data
+        .as_output
+        .get(&iid)
+        .into_iter()
+        .flatten()
+        .collect::<BTreeSet<&RecipeId>>()
+        .into_iter()
+        .filter_map(|rid| data.recipes.get(rid).map(|r| (rid, r)))
+        .filter(|(_, r)| r.seconds > 0.0)
+        .map(|(rid, recipe)| {
+            let produced = recipe
+                .outputs
+                .iter()
+                .filter(|o| o.id == iid)
+                .map(|o| o.amount)
+                .sum::<usize>() as f64
+                * recipe.productivity;
+            let inputs = recipe.inputs.iter().map(|i| i.amount).sum::<usize>() as f64;
+            let outputs =
+                recipe.outputs.iter().map(|o| o.amount).sum::<usize>() as f64 * recipe.productivity;
+
+            Row {
+                item_id: iid,
+                item: item.clone(),
+                recipe_id: *rid,
+                recipe: recipe.name.clone(),
+                productivity: recipe.productivity,
+                outputs_per_minute: produced * 60.0 / recipe.seconds,
+                inputs_per_minute: inputs * 60.0 / recipe.seconds,
+                input_output_ratio: if outputs > 0.0 {
+                    inputs / outputs
+                } else {
+                    f64::INFINITY
+                },
+                beats_base_recipe: false,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    rows.sort_by(|a, b| {
+        a.input_output_ratio
+            .total_cmp(&b.input_output_ratio)
+            .then(b.outputs_per_minute.total_cmp(&a.outputs_per_minute))
+            .then(a.recipe_id.cmp(&b.recipe_id))
+    });
+
+    let base = rows.iter().position(|r| !r.recipe.ends_with("(Advanced)"));
+    if let Some(base) = base {
+        for row in &mut rows[..base] {
+            row.beats_base_recipe = row.recipe.ends_with("(Advanced)");
+        }
+    }
+
+    rows
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeSet;
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+
+use structopt::StructOpt;
+
+use crate::{
+    data::{Data, ItemAmount, Recipe},
+    error::Error,
+    selector::{parse_ids, SelectorContext},
+};
+
+use super::write_output;
+
+#[derive(Debug, StructOpt)]
+pub struct ExportMarkdown {
+    /// Only export recipes that produce one of these items.
+    #[structopt(short = "p", long = "produces")]
+    pub produces: Vec<String>,
+
+    /// Only export recipes that consume one of these items.
+    #[structopt(short = "c", long = "consumes")]
+    pub consumes: Vec<String>,
+
+    /// File to write the markdown document to (stdout if not set).
+    #[structopt(short = "o", long = "output")]
+    pub output: Option<PathBuf>,
+}
+
+impl ExportMarkdown {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let Self {
+            produces,
+            consumes,
+            output,
+        } = self;
+
+        let produces = parse_ids(data, ctx, &produces, true)?.items;
+        let consumes = parse_ids(data, ctx, &consumes, true)?.items;
+
+        let mut recipes = data
+            .recipes
+            .iter()
+            .filter(|(_, r)| {
+                produces.is_empty() || r.outputs.iter().any(|o| produces.contains(&o.id))
+            })
+            .filter(|(_, r)| {
+                consumes.is_empty() || r.inputs.iter().any(|i| consumes.contains(&i.id))
+            })
+            .collect::<Vec<_>>();
+        recipes.sort_by(|(a_id, a), (b_id, b)| a.name.cmp(&b.name).then(a_id.cmp(b_id)));
+        log::info!("  export {} recipes", recipes.len());
+
+        let mut items = recipes
+            .iter()
+            .flat_map(|(_, r)| r.inputs.iter().chain(r.outputs.iter()).map(|i| i.id))
+            .collect::<BTreeSet<_>>()
+            .into_iter()
+            .filter_map(|iid| data.items.get(&iid).map(|i| (iid, i)))
+            .collect::<Vec<_>>();
+        items.sort_by(|(a_id, a), (b_id, b)| a.name.cmp(&b.name).then(a_id.cmp(b_id)));
+
+        let write = |out: &mut dyn Write| -> Result<(), Error> {
+            writeln!(out, "## Recipes")?;
+            writeln!(out)?;
+            writeln!(
+                out,
+                "| Name | Type | Time | Inputs | Outputs | Outputs per minute |"
+            )?;
+            writeln!(out, "| --- | --- | ---: | --- | --- | ---: |")?;
+            for (_, recipe) in &recipes {
+                writeln!(
+                    out,
+                    "| {} | {} | {}s | {} | {} | {} |",
+                    escape(&recipe.name),
+                    recipe.type_,
+                    recipe.seconds,
+                    amounts(data, &recipe.inputs),
+                    amounts(data, &recipe.outputs),
+                    rates(recipe),
+                )?;
+            }
+
+            writeln!(out)?;
+            writeln!(out, "## Items")?;
+            writeln!(out)?;
+            writeln!(out, "| Name | Type | Stack size |")?;
+            writeln!(out, "| --- | --- | ---: |")?;
+            for (iid, item) in &items {
+                writeln!(
+                    out,
+                    "| <a id=\"item-{}\"></a>{} | {} | {} |",
+                    iid.0,
+                    escape(&item.name),
+                    item.type_,
+                    item.stack_size.map(|s| s.to_string()).unwrap_or_default()
+                )?;
+            }
+
+            Ok(())
+        };
+
+        match output {
+            Some(path) => {
+                log::info!("Write markdown to {:#?}", &path);
+
+                write_output(&path, write)
+            }
+            None => write(&mut stdout().lock()),
+        }
+    }
+}
+
+fn amounts(data: &Data, amounts: &[ItemAmount]) -> String {
+    amounts
+        .iter()
+        .map(|a| match data.items.get(&a.id) {
+            Some(item) => format!("{}× [{}](#item-{})", a.amount, escape(&item.name), a.id.0),
+            None => format!("{}× {}", a.amount, a.id.label(&data.keys)),
+        })
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+fn rates(recipe: &Recipe) -> String {
+    recipe
+        .outputs
+        .iter()
+        .map(|o| {
+            if recipe.seconds > 0.0 {
+                let rate = o.amount as f64 * recipe.productivity * 60.0 / recipe.seconds;
+
+                match recipe.productivity_label() {
+                    Some(label) => format!("{:.2} ({})", rate, label),
+                    None => format!("{:.2}", rate),
+                }
+            } else {
+                "-".into()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("<br>")
+}
+
+pub(crate) fn escape(s: &str) -> String {
+    s.replace('|', "\\|")
+}
@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use structopt::StructOpt;
+
+use crate::{
+    data::{Data, ItemId},
+    error::Error,
+    planner::{PlanOptions, ProductionPlan},
+    selector::{parse_ids, parse_ignore, SelectorContext},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct MatrixCost {
+    /// Number of hashes to research (or hashes per minute if `--rate` is set).
+    pub hashes: f64,
+
+    /// Interpret the number of hashes as hashes per minute.
+    #[structopt(long = "rate")]
+    pub rate: bool,
+
+    /// Matrices that are needed for the research (colors, names or ids).
+    #[structopt(short = "m", long = "matrices", use_delimiter = true)]
+    pub matrices: Vec<String>,
+
+    /// Recipes to exclude from the calculation.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+}
+
+impl MatrixCost {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let Self {
+            hashes,
+            rate,
+            matrices,
+            ignore,
+        } = self;
+
+        log::info!("Parse matrices");
+        let matrices = parse_matrices(data, ctx, &matrices)?;
+        log::info!("  loaded {} matrices", matrices.len());
+
+        log::info!("Parse ignored recipes");
+        let ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        log::info!("Calculate matrix cost");
+        let targets = matrices
+            .iter()
+            .map(|iid| (*iid, hashes / HASH_PER_MATRIX))
+            .collect::<BTreeMap<_, _>>();
+        let options = PlanOptions {
+            ignore,
+            ..Default::default()
+        };
+        let plan = ProductionPlan::new(data, &targets, options)?;
+
+        let unit = if rate { " /min" } else { "" };
+        let item_name = |iid: &ItemId| {
+            data.items
+                .get(iid)
+                .map(|i| i.name.clone())
+                .unwrap_or_else(|| iid.label(&data.keys).to_string())
+        };
+
+        println!("Matrices:");
+        for (iid, amount) in &targets {
+            println!("    {:<40} {:>12.2}{}", item_name(iid), amount, unit);
+        }
+
+        println!();
+        println!("Items:");
+        for (iid, amount) in plan.production(data) {
+            println!("    {:<40} {:>12.2}{}", item_name(&iid), amount, unit);
+        }
+
+        println!();
+        println!("Resources:");
+        for (iid, amount) in &plan.resources {
+            println!("    {:<40} {:>12.2}{}", item_name(iid), amount, unit);
+        }
+
+        Ok(())
+    }
+}
+
+fn parse_matrices(
+    data: &Data,
+    ctx: &SelectorContext,
+    matrices: &[String],
+) -> Result<BTreeSet<ItemId>, Error> {
+    let mut ret = BTreeSet::new();
+
+    for matrix in matrices {
+        let color = MATRIX_COLORS
+            .iter()
+            .find(|(color, _)| color.eq_ignore_ascii_case(matrix.trim()));
+        let name = match color {
+            Some((_, name)) => name.to_string(),
+            None => matrix.trim().to_owned(),
+        };
+
+        ret.extend(parse_ids(data, ctx, &[name], true)?.items);
+    }
+
+    Ok(ret)
+}
+
+/// Hashes that are provided by a single matrix.
+const HASH_PER_MATRIX: f64 = 3600.0;
+
+const MATRIX_COLORS: &[(&str, &str)] = &[
+    ("blue", "Electromagnetic Matrix"),
+    ("red", "Energy Matrix"),
+    ("yellow", "Structure Matrix"),
+    ("purple", "Information Matrix"),
+    ("green", "Gravity Matrix"),
+    ("white", "Universe Matrix"),
+];
@@ -1,20 +1,620 @@
+pub mod alternatives;
+pub mod belts;
+pub mod bottleneck;
+pub mod build_order;
+pub mod byproducts;
+pub mod completions;
 pub mod create_production_graph;
+pub mod cycles;
+pub mod efficiency;
+pub mod export_markdown;
+pub mod matrix_cost;
+pub mod paths;
+pub mod plan;
+pub mod rates;
+pub mod ratio;
+pub mod reachable;
+pub mod report;
+pub mod serve;
+pub mod stats;
+pub mod techs;
+pub mod tiers;
+pub mod top;
+pub mod unused_items;
 
+use std::collections::{BTreeSet, HashMap};
+use std::fs::{read, read_to_string, remove_file, rename, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use itertools::Itertools;
+use rlua::Lua;
 use structopt::StructOpt;
 
-pub use create_production_graph::CreateProductionGraph;
+pub use alternatives::Alternatives;
+pub use belts::Belts;
+pub use bottleneck::Bottleneck;
+pub use build_order::BuildOrder;
+pub use byproducts::Byproducts;
+pub use completions::Completions;
+pub use create_production_graph::{CreateProductionGraph, GraphOptions};
+pub use cycles::Cycles;
+pub use efficiency::Efficiency;
+pub use export_markdown::ExportMarkdown;
+pub use matrix_cost::MatrixCost;
+pub use paths::Paths;
+pub use plan::Plan;
+pub use rates::Rates;
+pub use ratio::Ratio;
+pub use reachable::Reachable;
+pub use report::Report;
+pub use serve::Serve;
+pub use stats::Stats;
+pub use techs::Techs;
+pub use tiers::Tiers;
+pub use top::Top;
+pub use unused_items::UnusedItems;
 
-use crate::error::Error;
+use crate::{
+    args::DataArgs,
+    cache,
+    data::{decode_source, Data, DataSource, ItemId, Locale, RecipeId},
+    error::Error,
+    selector::SelectorContext,
+    table::Style,
+};
 
 #[derive(Debug, StructOpt)]
 pub enum Command {
+    /// Create the production graph of items.
     CreateProductionGraph(CreateProductionGraph),
+
+    /// Plan the machines and raw resources for items per minute.
+    Plan(Plan),
+
+    /// Calculate the cost of research in matrices.
+    MatrixCost(MatrixCost),
+
+    /// List recipe cycles.
+    Cycles(Cycles),
+
+    /// List the tiers of items.
+    Tiers(Tiers),
+
+    /// List recipes with byproducts.
+    Byproducts(Byproducts),
+
+    /// List the items that are never consumed, never produced or not used by
+    /// any recipe.
+    UnusedItems(UnusedItems),
+
+    /// Generate shell completions.
+    Completions(Completions),
+
+    /// Summarize a data file.
+    Stats(Stats),
+
+    /// Compare the efficiency of the recipes of items.
+    Efficiency(Efficiency),
+
+    /// Calculate the smallest integral machine ratios of a production chain.
+    Ratio(Ratio),
+
+    /// Export the recipes and items as Markdown tables.
+    ExportMarkdown(ExportMarkdown),
+
+    /// Preview the production graph in the browser.
+    Serve(Serve),
+
+    /// List the techs and the recipes they unlock.
+    Techs(Techs),
+
+    /// List the items that can be built from a set of resources.
+    Reachable(Reachable),
+
+    /// List the production routes of an item.
+    Paths(Paths),
+
+    /// Find the bottleneck of a factory.
+    Bottleneck(Bottleneck),
+
+    /// Rank alternative recipes by their raw resources.
+    Alternatives(Alternatives),
+
+    /// Write a production report with the plan, the ratios and the graph.
+    Report(Report),
+
+    /// List the rates of one machine of each recipe.
+    Rates(Rates),
+
+    /// List the order to build the production lines in.
+    BuildOrder(BuildOrder),
+
+    /// Rank items by their connectivity.
+    Top(Top),
+
+    /// Count the belts and sorters of a plan.
+    Belts(Belts),
 }
 
 impl Command {
-    pub fn exec(self) -> Result<(), Error> {
+    pub fn exec(self, args: &DataArgs, ctx: &SelectorContext, style: &Style) -> Result<(), Error> {
+        match self {
+            Self::Completions(cmd) => return cmd.exec(),
+            Self::Serve(cmd) => return cmd.exec(args, ctx),
+            Self::CreateProductionGraph(cmd) if cmd.watch => return cmd.watch(args, ctx),
+            _ => (),
+        }
+
+        let data = load_data(args)?;
+
         match self {
-            Self::CreateProductionGraph(cmd) => cmd.exec(),
+            Self::CreateProductionGraph(cmd) => cmd.exec(&data, ctx),
+            Self::Plan(cmd) => cmd.exec(&data, ctx),
+            Self::MatrixCost(cmd) => cmd.exec(&data, ctx),
+            Self::Cycles(cmd) => cmd.exec(&data, ctx),
+            Self::Tiers(cmd) => cmd.exec(&data, ctx, style),
+            Self::Byproducts(cmd) => cmd.exec(&data, ctx),
+            Self::UnusedItems(cmd) => cmd.exec(&data, style),
+            Self::Completions(_) | Self::Serve(_) => unreachable!(),
+            Self::Stats(cmd) => cmd.exec(&data, style),
+            Self::Efficiency(cmd) => cmd.exec(&data, ctx, style),
+            Self::Ratio(cmd) => cmd.exec(&data, ctx),
+            Self::ExportMarkdown(cmd) => cmd.exec(&data, ctx),
+            Self::Techs(cmd) => cmd.exec(&data, style),
+            Self::Reachable(cmd) => cmd.exec(&data, ctx, style),
+            Self::Paths(cmd) => cmd.exec(&data, ctx),
+            Self::Bottleneck(cmd) => cmd.exec(&data, ctx),
+            Self::Alternatives(cmd) => cmd.exec(&data, ctx, style),
+            Self::Report(cmd) => cmd.exec(&data, ctx),
+            Self::Rates(cmd) => cmd.exec(&data, ctx, style),
+            Self::BuildOrder(cmd) => cmd.exec(&data, ctx),
+            Self::Top(cmd) => cmd.exec(&data, ctx, style),
+            Self::Belts(cmd) => cmd.exec(&data, ctx, style),
+        }
+    }
+}
+
+/// Load and merge the data files, and translate the names if a language
+/// was selected.
+pub(crate) fn load_data(args: &DataArgs) -> Result<Data, Error> {
+    let mut data = if args.data_paths.is_empty() {
+        load_default_data(args)?
+    } else {
+        let mut paths = args.data_paths.iter();
+        let mut data = load_data_file(args, paths.next().unwrap())?;
+        for path in paths {
+            data.merge(load_data_file(args, path)?)?;
+        }
+
+        if args.data_paths.len() > 1 {
+            log::info!(files = args.data_paths.len(); "Merged {} data files", args.data_paths.len());
+            log_data(&data);
+        }
+
+        data
+    };
+
+    check_items(&data, args.strict_data)?;
+    check_types(&data, args.strict_types)?;
+    check_duplicate_recipes(&data);
+    check_logistics(&data);
+
+    if !args.include_disabled {
+        check_disabled_recipes(&data);
+
+        let disabled = data.recipes.values().filter(|r| r.disabled).count();
+        if disabled > 0 {
+            log::info!(recipes = disabled; "Skip {} disabled recipes", disabled);
+            data = data.filter(|_, _| true, |_, recipe| !recipe.disabled, true);
+        }
+    }
+
+    if let Some(path) = &args.locale_path {
+        log::info!(path:% = path.display(); "Load locale from {:#?}", path);
+        let locale = read_to_string(path)?;
+        let lua = Lua::new();
+        let locales =
+            lua.context(move |lua| lua.load(&locale).eval::<HashMap<String, Locale>>())?;
+
+        log::info!(languages = locales.len(); "  loaded {} languages", locales.len());
+
+        for (language, locale) in locales {
+            log::debug!(
+                "  {}: {} items, {} recipes",
+                language,
+                locale.items.len(),
+                locale.recipes.len()
+            );
+
+            let entry = data.locales.entry(language).or_default();
+            entry.items.extend(locale.items);
+            entry.recipes.extend(locale.recipes);
+        }
+    }
+
+    if let Some(language) = &args.language {
+        log::info!("Use language {}", language);
+        data.apply_locale(language)?;
+    }
+
+    check_names(&data);
+
+    Ok(data)
+}
+
+/// Write the output of `f` to the passed file. The output is written to a
+/// temporary file that only replaces the file if `f` succeeds, so a failed
+/// command never leaves a partially written file behind.
+pub(crate) fn write_output<F>(path: &Path, f: F) -> Result<(), Error>
+where
+    F: FnOnce(&mut dyn Write) -> Result<(), Error>,
+{
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let tmp = path.with_file_name(format!(".{}.tmp", name));
+
+    let mut file = BufWriter::new(File::create(&tmp)?);
+    let ret = f(&mut file)
+        .and_then(|()| Ok(file.flush()?))
+        .and_then(|()| Ok(rename(&tmp, path)?));
+    if ret.is_err() {
+        let _ = remove_file(&tmp);
+    }
+
+    ret
+}
+
+/// Check the data for recipes that reference missing items.
+fn check_items(data: &Data, strict: bool) -> Result<(), Error> {
+    let missing = data.missing_items();
+    if missing.is_empty() {
+        return Ok(());
+    }
+
+    let describe = |(iid, recipes): (&ItemId, &BTreeSet<RecipeId>)| {
+        format!(
+            "item {} (referenced by recipe {})",
+            iid.label(&data.keys),
+            recipes.iter().map(|rid| rid.label(&data.keys)).join(", ")
+        )
+    };
+
+    if strict {
+        return Err(Error::data(format!(
+            "Recipes reference missing items: {}",
+            missing.iter().map(describe).join("; ")
+        )));
+    }
+
+    for missing in &missing {
+        log::warn!(
+            item:% = missing.0.label(&data.keys),
+            recipes:% = missing.1.iter().map(|rid| rid.label(&data.keys)).join(",");
+            "Missing {}",
+            describe(missing)
+        );
+    }
+
+    Ok(())
+}
+
+/// Log the recipes that have the same inputs, outputs and crafting time.
+fn check_duplicate_recipes(data: &Data) {
+    for rids in data.duplicate_recipes() {
+        let labels = rids
+            .iter()
+            .map(|rid| rid.label(&data.keys))
+            .collect::<Vec<_>>();
+        log::warn!(
+            recipes:% = labels.iter().join(",");
+            "Recipes {} are duplicates (use --merge-duplicate-recipes to draw them as one)",
+            labels.iter().join(", ")
+        );
+    }
+}
+
+/// Log the disabled recipes that are the only producer of an item that is
+/// needed by an enabled recipe.
+fn check_disabled_recipes(data: &Data) {
+    for (iid, (rid, consumers)) in data.disabled_producers() {
+        let iid = iid.label(&data.keys);
+        let rid = rid.label(&data.keys);
+        let consumers = consumers
+            .iter()
+            .map(|rid| rid.label(&data.keys))
+            .collect::<Vec<_>>();
+        log::warn!(
+            item:% = iid,
+            recipe:% = rid,
+            recipes:% = consumers.iter().join(",");
+            "Recipe {} is disabled, but the only recipe of item {} that is needed by recipe {} (use --include-disabled to use it)",
+            rid,
+            iid,
+            consumers.iter().join(", ")
+        );
+    }
+}
+
+/// Log stack sizes, belts and sorters with values that are not positive, and
+/// belts that are not sorted by speed.
+fn check_logistics(data: &Data) {
+    for (iid, item) in data.items.iter().sorted_by_key(|(iid, _)| **iid) {
+        if item.stack_size == Some(0) {
+            let iid = iid.label(&data.keys);
+            log::warn!(item:% = iid; "Item {} has a stack size of 0", iid);
         }
     }
+
+    let logistics = &data.logistics;
+    let speeds = logistics
+        .belts
+        .iter()
+        .map(|b| ("Belt", &b.name, b.speed))
+        .chain(
+            logistics
+                .sorters
+                .iter()
+                .map(|s| ("Sorter", &s.name, s.speed)),
+        );
+    for (kind, name, speed) in speeds {
+        if speed.is_nan() || speed <= 0.0 {
+            log::warn!(
+                "{} {:?} has a speed that is not positive: {}",
+                kind,
+                name,
+                speed
+            );
+        }
+    }
+
+    for (slower, faster) in logistics.belts.iter().tuple_windows() {
+        if faster.speed < slower.speed {
+            log::warn!(
+                "Belts are not sorted by speed: {:?} ({}/s) is listed after {:?} ({}/s)",
+                faster.name,
+                faster.speed,
+                slower.name,
+                slower.speed
+            );
+        }
+    }
+}
+
+/// Check the data for items and recipes with unknown types.
+fn check_types(data: &Data, strict: bool) -> Result<(), Error> {
+    let unknown = data.unknown_types();
+    if unknown.is_empty() {
+        return Ok(());
+    }
+
+    let describe = |kind: &str, type_: &String, ids: Vec<String>| {
+        let examples = ids.iter().take(3).join(", ");
+        let more = if ids.len() > 3 { ", ..." } else { "" };
+
+        format!(
+            "{} type {} ({} {}s, e.g. {}{})",
+            kind,
+            type_,
+            ids.len(),
+            kind,
+            examples,
+            more
+        )
+    };
+
+    let types = unknown
+        .items
+        .iter()
+        .map(|(type_, ids)| {
+            describe(
+                "item",
+                type_,
+                ids.iter()
+                    .map(|iid| iid.label(&data.keys).to_string())
+                    .collect(),
+            )
+        })
+        .chain(unknown.recipes.iter().map(|(type_, ids)| {
+            describe(
+                "recipe",
+                type_,
+                ids.iter()
+                    .map(|rid| rid.label(&data.keys).to_string())
+                    .collect(),
+            )
+        }))
+        .join("; ");
+
+    if strict {
+        return Err(Error::data(format!("Data uses unknown types: {}", types)));
+    }
+
+    log::warn!("Unknown types: {}", types);
+
+    Ok(())
+}
+
+/// Log all names that are used by more than one item or recipe.
+fn check_names(data: &Data) {
+    for (name, ids) in data.item_by_name.iter().sorted() {
+        if ids.len() > 1 {
+            log::warn!(
+                "Multiple items are named {:?}: {}",
+                name,
+                ids.iter().map(|iid| iid.label(&data.keys)).join(", ")
+            );
+        }
+    }
+
+    for (name, ids) in data.recipes_by_name.iter().sorted() {
+        if ids.len() > 1 {
+            log::warn!(
+                "Multiple recipes are named {:?}: {}",
+                name,
+                ids.iter().map(|rid| rid.label(&data.keys)).join(", ")
+            );
+        }
+    }
+}
+
+/// Load the first default data file that exists, or the embedded data if
+/// there is none.
+fn load_default_data(args: &DataArgs) -> Result<Data, Error> {
+    if let Some(path) = DataArgs::find_default_data() {
+        log::info!(path:% = path.display(); "Found data file {:#?}", path);
+
+        return load_data_file(args, &path);
+    }
+
+    #[cfg(feature = "embedded-data")]
+    if !args.no_embedded {
+        let mut data = Data::from_lua_source(
+            "embedded data",
+            EMBEDDED_DATA,
+            args.game_version.as_deref(),
+            &args.data_args,
+        )?;
+        data.sources
+            .push(DataSource::new("<embedded>", EMBEDDED_DATA));
+
+        log::info!(
+            "No data file found, use embedded data (game version {})",
+            data.game_version.as_deref().unwrap_or("unknown")
+        );
+        log_data(&data);
+
+        return Ok(data);
+    }
+
+    Err(Error::usage(format!(
+        "Data file not found, tried {} (use --data to select one)",
+        DataArgs::default_data_paths()
+            .iter()
+            .map(|path| path.display())
+            .join(", ")
+    )))
+}
+
+fn load_data_file(args: &DataArgs, path: &Path) -> Result<Data, Error> {
+    let data = load_data_source(args, path)?;
+
+    if let Some(version) = &args.game_version {
+        match &data.game_version {
+            Some(game_version) if game_version == version => (),
+            Some(game_version) => log::warn!(
+                "Data file {:#?} only contains game version {}, ignore --game-version {}",
+                path,
+                game_version,
+                version
+            ),
+            None => log::warn!(
+                "Data file {:#?} does not contain multiple game versions, ignore --game-version {}",
+                path,
+                version
+            ),
+        }
+    }
+
+    Ok(data)
+}
+
+fn load_data_source(args: &DataArgs, path: &Path) -> Result<Data, Error> {
+    log::info!(path:% = path.display(); "Load data from {:#?}", path);
+    let source = decode_source(path, read_bytes(args, path)?)?;
+    log::debug!("  read {} bytes", source.len());
+
+    let game_version = args.game_version.as_deref();
+    let use_cache = (args.cache || args.refresh_cache) && !args.no_cache;
+    if use_cache && !args.refresh_cache {
+        if let Some(mut data) = cache::load(path, &source, game_version, &args.data_args) {
+            log::info!(cached = true; "  loaded from cache");
+            log_data(&data);
+
+            data.sources.push(DataSource::new(path.display(), &source));
+
+            return Ok(data);
+        }
+    }
+
+    let mut data = Data::from_lua_source(
+        &path.display().to_string(),
+        &source,
+        game_version,
+        &args.data_args,
+    )?;
+    log_data(&data);
+
+    if use_cache {
+        cache::store(path, &source, game_version, &args.data_args, &data);
+    }
+
+    data.sources.push(DataSource::new(path.display(), &source));
+
+    Ok(data)
+}
+
+#[cfg(feature = "remote-data")]
+fn read_bytes(args: &DataArgs, path: &Path) -> Result<Vec<u8>, Error> {
+    if DataArgs::is_url(path) {
+        return crate::download::fetch(&path.to_string_lossy(), args.offline);
+    }
+
+    Ok(read(path)?)
+}
+
+#[cfg(not(feature = "remote-data"))]
+fn read_bytes(_args: &DataArgs, path: &Path) -> Result<Vec<u8>, Error> {
+    if DataArgs::is_url(path) {
+        return Err(Error::usage(format!(
+            "Unable to load {}: loading data from URLs requires the `remote-data` feature",
+            path.display()
+        )));
+    }
+
+    Ok(read(path)?)
+}
+
+fn log_data(data: &Data) {
+    if let Some(version) = &data.game_version {
+        log::info!(game_version = version.as_str(); "  game version {}", version);
+    }
+
+    log::info!(items = data.items.len(); "  loaded {} items", data.items.len());
+    log::info!(recipes = data.recipes.len(); "  loaded {} recipes", data.recipes.len());
+    log::info!(buildings = data.buildings.len(); "  loaded {} buildings", data.buildings.len());
+}
+
+/// Data set that is used if no data file is available.
+#[cfg(feature = "embedded-data")]
+const EMBEDDED_DATA: &str = include_str!("../../data.lua");
+
+#[cfg(test)]
+mod tests {
+    use std::env::temp_dir;
+    use std::fs::{read_to_string, write};
+    use std::process::id;
+
+    use super::*;
+
+    #[test]
+    fn write_output_replaces_the_file_on_success() {
+        let path = temp_dir().join(format!("dsp-tool-output-{}.txt", id()));
+        write(&path, "old").unwrap();
+
+        write_output(&path, |out| Ok(out.write_all(b"new")?)).unwrap();
+        assert_eq!(read_to_string(&path).unwrap(), "new");
+
+        let err = write_output(&path, |out| {
+            out.write_all(b"partial")?;
+
+            Err(Error::custom("failed"))
+        });
+        assert!(err.is_err());
+        assert_eq!(read_to_string(&path).unwrap(), "new");
+        assert!(!path
+            .with_file_name(format!(".dsp-tool-output-{}.txt.tmp", id()))
+            .exists());
+
+        remove_file(&path).unwrap();
+    }
 }
@@ -0,0 +1,503 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use itertools::Itertools;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    analysis::{Node, RecipeGraph},
+    data::{Data, IdLabel, ItemId, RecipeId},
+    error::Error,
+    format::{csv_line, Format},
+    selector::{parse_ids, parse_ignore, Selection, SelectorContext},
+    solver::{self, Flow},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Paths {
+    /// Item to list the production routes for.
+    pub item: String,
+
+    /// Recipes to exclude from the routes. Ignored items are used as raw
+    /// resources.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Stop after this number of distinct routes was found.
+    #[structopt(short = "l", long = "limit", default_value = "20")]
+    pub limit: usize,
+
+    /// Only choose recipes up to this number of recipes below the item, the
+    /// deeper inputs are used as raw resources.
+    #[structopt(long = "max-depth")]
+    pub max_depth: Option<usize>,
+
+    /// Sort the routes by this key (resources, time or recipes).
+    #[structopt(short = "s", long = "sort-by", default_value = "resources")]
+    pub sort_by: SortBy,
+
+    /// Output format (text, json or csv).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+/* SortBy */
+
+/// Key the routes are sorted by, ascending.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SortBy {
+    /// Total raw resources per unit of the item.
+    Resources,
+
+    /// Total crafting time per unit of the item.
+    Time,
+
+    /// Number of recipes of the route.
+    Recipes,
+}
+
+impl FromStr for SortBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "resources" => Ok(Self::Resources),
+            "time" => Ok(Self::Time),
+            "recipes" => Ok(Self::Recipes),
+            s => Err(format!(
+                "Unknown sort key: {} (expected resources, time or recipes)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for SortBy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Resources => write!(f, "resources"),
+            Self::Time => write!(f, "time"),
+            Self::Recipes => write!(f, "recipes"),
+        }
+    }
+}
+
+/* Route */
+
+/// Production route of the item, with the amounts per unit of the item.
+#[derive(Debug, Serialize)]
+struct Route {
+    /// Steps in production order. Recipes that form a loop are one step.
+    steps: Vec<Step>,
+
+    /// Raw resources that are consumed.
+    resources: Vec<Amount>,
+
+    /// Byproducts that are left over.
+    byproducts: Vec<Amount>,
+
+    /// Sum of the raw resources.
+    total_resources: f64,
+
+    /// Sum of the crafting time of all recipes, in seconds.
+    crafting_time: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Step {
+    recipes: Vec<RecipeRef>,
+
+    #[serde(rename = "loop")]
+    is_loop: bool,
+}
+
+#[derive(Debug, Serialize)]
+struct RecipeRef {
+    id: IdLabel,
+    name: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Amount {
+    id: IdLabel,
+    name: String,
+    amount: f64,
+}
+
+impl Route {
+    fn recipe_count(&self) -> usize {
+        self.steps.iter().map(|s| s.recipes.len()).sum()
+    }
+
+    fn steps_label(&self) -> String {
+        self.steps
+            .iter()
+            .map(|step| {
+                let names = step.recipes.iter().map(|r| &r.name).join(" + ");
+
+                if step.is_loop {
+                    format!("loop({})", names)
+                } else {
+                    names
+                }
+            })
+            .join(" > ")
+    }
+}
+
+impl Paths {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let Self {
+            item,
+            ignore,
+            limit,
+            max_depth,
+            sort_by,
+            format,
+        } = self;
+
+        log::info!("Parse item");
+        let items = parse_ids(data, ctx, std::slice::from_ref(&item), true)?.items;
+        let iid = match items.iter().exactly_one() {
+            Ok(iid) => *iid,
+            Err(_) => return Err(Error::usage(format!("Expected exactly one item: {}", item))),
+        };
+
+        log::info!("Parse ignored recipes");
+        let ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        log::info!("Enumerate routes");
+        let mut enumeration = Enumeration {
+            data,
+            ignore: &ignore,
+            max_depth,
+            limit,
+            choices: Vec::new(),
+        };
+        enumeration.visit(Vec::new(), VecDeque::from([(iid, 0)]));
+        log::info!("  found {} routes", enumeration.choices.len());
+
+        // Different choices may end up using the same recipes, if the solver
+        // does not need some of them.
+        let mut routes = Vec::with_capacity(enumeration.choices.len());
+        let mut used = BTreeSet::new();
+        let mut unsolved = 0;
+        for choices in &enumeration.choices {
+            match route(data, iid, choices) {
+                Ok(route) => {
+                    let recipes = route
+                        .steps
+                        .iter()
+                        .flat_map(|s| s.recipes.iter().map(|r| r.id.clone()))
+                        .collect::<BTreeSet<_>>();
+                    if used.insert(recipes) {
+                        routes.push(route);
+                    }
+                }
+                Err(err) => {
+                    log::debug!("  skip route: {}", err);
+                    unsolved += 1;
+                }
+            }
+        }
+        if unsolved > 0 {
+            log::info!("  skip {} routes that can not be balanced", unsolved);
+        }
+        log::info!("  use {} distinct routes", routes.len());
+        if routes.is_empty() {
+            return Err(Error::custom(format!("No routes to produce {}", item)));
+        }
+
+        routes.sort_by(|a, b| match sort_by {
+            SortBy::Resources => a.total_resources.total_cmp(&b.total_resources),
+            SortBy::Time => a.crafting_time.total_cmp(&b.crafting_time),
+            SortBy::Recipes => a.recipe_count().cmp(&b.recipe_count()),
+        });
+
+        match format {
+            Format::Text => {
+                let name = data.items.get(&iid).map_or(&item, |i| &i.name);
+
+                for (i, route) in routes.iter().enumerate() {
+                    if i > 0 {
+                        println!();
+                    }
+
+                    println!(
+                        "Route {} for {} ({} recipes, {:.2} resources and {:.2}s per unit):",
+                        i + 1,
+                        name,
+                        route.recipe_count(),
+                        route.total_resources,
+                        route.crafting_time
+                    );
+                    for step in &route.steps {
+                        let names = step.recipes.iter().map(|r| &r.name).join(" + ");
+                        if step.is_loop {
+                            println!("    loop: {}", names);
+                        } else {
+                            println!("    {}", names);
+                        }
+                    }
+
+                    println!("  Resources:");
+                    for amount in &route.resources {
+                        println!("    {:<40} {:>12.2}", amount.name, amount.amount);
+                    }
+                    if !route.byproducts.is_empty() {
+                        println!("  Byproducts:");
+                        for amount in &route.byproducts {
+                            println!("    {:<40} {:>12.2}", amount.name, amount.amount);
+                        }
+                    }
+                }
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&routes)?),
+            Format::Csv => {
+                println!(
+                    "{}",
+                    csv_line([
+                        "route",
+                        "recipes",
+                        "steps",
+                        "total_resources",
+                        "crafting_time",
+                        "resources",
+                        "byproducts",
+                    ])
+                );
+
+                let amounts = |amounts: &[Amount]| {
+                    amounts
+                        .iter()
+                        .map(|a| format!("{}={:.4}", a.name, a.amount))
+                        .join(";")
+                };
+                for (i, route) in routes.iter().enumerate() {
+                    println!(
+                        "{}",
+                        csv_line([
+                            (i + 1).to_string(),
+                            route.recipe_count().to_string(),
+                            route.steps_label(),
+                            format!("{:.4}", route.total_resources),
+                            format!("{:.4}", route.crafting_time),
+                            amounts(&route.resources),
+                            amounts(&route.byproducts),
+                        ])
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/* Enumeration */
+
+/// Items of a route with the recipe that is chosen to produce them, in the
+/// order they were chosen.
+type Choices = Vec<(ItemId, RecipeId)>;
+
+/// Enumerates the distinct combinations of recipes that produce an item.
+///
+/// Each needed item is produced by one of its recipes, and each choice opens
+/// the inputs of the recipe. Items that already have a recipe are not opened
+/// again, so loops are closed instead of followed forever. The loop recipes
+/// stay in the route and are balanced by the solver.
+struct Enumeration<'a> {
+    data: &'a Data,
+    ignore: &'a Selection,
+    max_depth: Option<usize>,
+    limit: usize,
+
+    /// Chosen recipes of every route that was found.
+    choices: Vec<Choices>,
+}
+
+impl Enumeration<'_> {
+    fn visit(&mut self, mut choices: Choices, mut open: VecDeque<(ItemId, usize)>) {
+        if self.choices.len() >= self.limit {
+            return;
+        }
+
+        let (iid, depth) = loop {
+            match open.pop_front() {
+                Some((iid, depth)) if self.is_open(&choices, iid, depth) => break (iid, depth),
+                Some(_) => continue,
+                None => {
+                    let recipes =
+                        |c: &Choices| c.iter().map(|(_, rid)| *rid).collect::<BTreeSet<_>>();
+                    let known = self.choices.iter().any(|c| recipes(c) == recipes(&choices));
+                    if !known {
+                        self.choices.push(choices);
+                    }
+
+                    return;
+                }
+            }
+        };
+
+        let candidates = self.candidates(iid);
+        let last = candidates.len().saturating_sub(1);
+        for (i, rid) in candidates.into_iter().enumerate() {
+            let mut open = open.clone();
+            if let Some(recipe) = self.data.recipes.get(&rid) {
+                open.extend(recipe.inputs.iter().map(|input| (input.id, depth + 1)));
+            }
+
+            if i == last {
+                choices.push((iid, rid));
+                self.visit(choices, open);
+
+                return;
+            }
+
+            let mut choices = choices.clone();
+            choices.push((iid, rid));
+            self.visit(choices, open);
+        }
+    }
+
+    /// Whether a recipe has to be chosen for the passed item. Items without
+    /// recipes, ignored items and items below the maximum depth are raw
+    /// resources.
+    fn is_open(&self, choices: &Choices, iid: ItemId, depth: usize) -> bool {
+        choices.iter().all(|(i, _)| *i != iid)
+            && !self.ignore.items.contains(&iid)
+            && self.max_depth.is_none_or(|max| depth < max)
+            && !self.candidates(iid).is_empty()
+    }
+
+    /// Recipes that may produce the passed item, ordered by id.
+    fn candidates(&self, iid: ItemId) -> Vec<RecipeId> {
+        self.data
+            .as_output
+            .get(&iid)
+            .into_iter()
+            .flatten()
+            .filter(|rid| !self.ignore.recipes.contains(rid))
+            .filter(|rid| self.data.recipes.contains_key(rid))
+            .copied()
+            .sorted()
+            .dedup()
+            .collect()
+    }
+}
+
+/// Solve the steady state of the passed recipe choices for one unit of the
+/// item, and group the recipes into steps. Each recipe is driven by the item
+/// it was chosen for first.
+fn route(data: &Data, iid: ItemId, choices: &Choices) -> Result<Route, Error> {
+    let mut flows = BTreeMap::new();
+    let mut candidates = BTreeMap::<RecipeId, Vec<ItemId>>::new();
+    for (item, rid) in choices {
+        let recipe = &data.recipes[rid];
+
+        flows.entry(*rid).or_insert_with(|| Flow {
+            inputs: recipe
+                .inputs
+                .iter()
+                .map(|i| (i.id, i.amount as f64))
+                .collect(),
+            outputs: recipe
+                .outputs
+                .iter()
+                .map(|o| (o.id, o.amount as f64 * recipe.productivity))
+                .collect(),
+        });
+        candidates.entry(*rid).or_default().push(*item);
+    }
+
+    let item_name = |iid: ItemId| {
+        data.items
+            .get(&iid)
+            .map(|i| i.name.clone())
+            .unwrap_or_else(|| iid.label(&data.keys).to_string())
+    };
+    let targets = BTreeMap::from([(iid, 1.0)]);
+    let state = solver::solve(&flows, &candidates, &targets, item_name)?;
+
+    let recipes = state
+        .recipes
+        .iter()
+        .filter(|(_, crafts)| **crafts > 0.0)
+        .map(|(rid, _)| *rid)
+        .collect::<BTreeSet<_>>();
+
+    // The components are returned consumers first, so the production order
+    // is reversed.
+    let steps = RecipeGraph::new(data, &recipes)
+        .strongly_connected_components()
+        .into_iter()
+        .rev()
+        .filter_map(|component| {
+            let recipes = component
+                .iter()
+                .filter_map(|node| match node {
+                    Node::Recipe(rid) => Some(RecipeRef {
+                        id: rid.label(&data.keys),
+                        name: data.recipes[rid].name.clone(),
+                    }),
+                    Node::Item(_) => None,
+                })
+                .collect::<Vec<_>>();
+
+            (!recipes.is_empty()).then_some(Step {
+                is_loop: component.len() > 1,
+                recipes,
+            })
+        })
+        .collect();
+
+    // The solver may drop recipes, which turns their items into resources.
+    if let Some(iid) = state
+        .surplus
+        .iter()
+        .find(|(iid, rate)| **rate < 0.0 && choices.iter().any(|(i, _)| i == *iid))
+        .map(|(iid, _)| *iid)
+    {
+        return Err(Error::custom(format!(
+            "{} is not produced by the chosen recipe",
+            item_name(iid)
+        )));
+    }
+
+    let amount = |(iid, amount): (&ItemId, f64)| Amount {
+        id: iid.label(&data.keys),
+        name: item_name(*iid),
+        amount,
+    };
+    let resources = state
+        .surplus
+        .iter()
+        .filter(|(_, rate)| **rate < 0.0)
+        .map(|(iid, rate)| amount((iid, -rate)))
+        .collect::<Vec<_>>();
+    let byproducts = state
+        .surplus
+        .iter()
+        .filter(|(_, rate)| **rate > 0.0)
+        .map(|(iid, rate)| amount((iid, *rate)))
+        .collect();
+
+    Ok(Route {
+        steps,
+        total_resources: resources.iter().map(|a| a.amount).sum(),
+        resources,
+        byproducts,
+        crafting_time: state
+            .recipes
+            .iter()
+            .map(|(rid, crafts)| crafts * data.recipes[rid].seconds)
+            .sum(),
+    })
+}
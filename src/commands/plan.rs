@@ -0,0 +1,388 @@
+use std::collections::{BTreeMap, HashMap};
+
+use structopt::StructOpt;
+
+use crate::{
+    data::{Belt, Data, ItemId, RecipeId, RecipeType},
+    error::Error,
+    planner::{
+        optimize, Objective, PlanOptions, ProductionPlan, Proliferator, Rounding, ShoppingList,
+        SprayMode,
+    },
+    resolver::{PinnedList, PreferBasic},
+    selector::{parse_ids, parse_ignore, parse_targets, parse_techs, SelectorContext},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Plan {
+    /// Items to produce, given as `<item>=<amount per minute>`.
+    #[structopt(short = "t", long = "target")]
+    pub targets: Vec<String>,
+
+    /// Recipes to exclude from the plan.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Only use recipes that are unlocked by these techs (ids, names or files
+    /// with one tech per line) and their prerequisites.
+    #[structopt(long = "unlocked-techs", use_delimiter = true)]
+    pub unlocked_techs: Vec<String>,
+
+    /// Include the idle power of partially used machines in the power figures.
+    #[structopt(long = "idle-power")]
+    pub idle_power: bool,
+
+    /// Proliferator that is sprayed on the inputs (none, mk1, mk2 or mk3).
+    #[structopt(long = "proliferator", default_value = "none")]
+    pub proliferator: Proliferator,
+
+    /// How the proliferator is used (extra-products or speedup).
+    #[structopt(long = "spray-mode", default_value = "extra-products")]
+    pub spray_mode: SprayMode,
+
+    /// Buildings to use for the recipe types they can craft (instead of the
+    /// first building of each type).
+    #[structopt(short = "b", long = "building")]
+    pub buildings: Vec<String>,
+
+    /// Recipes to use for the items they produce (instead of the primary
+    /// recipe of each item).
+    #[structopt(long = "prefer")]
+    pub prefer: Vec<String>,
+
+    /// Tier of the belt the rates of the targets, resources and byproducts
+    /// are also expressed in, starting at 1 for the slowest belt of the
+    /// data. Higher tiers use the fastest belt.
+    #[structopt(long = "belt-tier")]
+    pub belt_tier: Option<usize>,
+
+    /// Choose the recipes of the items that minimize the objective
+    /// (raw-resources or power). Recipes passed to `--prefer` are kept.
+    #[structopt(long = "optimize")]
+    pub optimize: Option<Objective>,
+
+    /// Items to craft once, given as `<item>=<amount>`. Lists the total
+    /// amounts of all items instead of rates.
+    #[structopt(long = "amount", conflicts_with_all = &["targets", "optimize"])]
+    pub amounts: Vec<String>,
+
+    /// How the crafts of `--amount` are rounded (ceil or exact).
+    #[structopt(long = "rounding", default_value = "ceil")]
+    pub rounding: Rounding,
+
+    /// List the number of crafts of each recipe for `--amount`.
+    #[structopt(long = "crafts")]
+    pub crafts: bool,
+}
+
+impl Plan {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let Self {
+            targets,
+            ignore,
+            unlocked_techs,
+            idle_power,
+            proliferator,
+            spray_mode,
+            buildings,
+            prefer,
+            belt_tier,
+            optimize: objective,
+            amounts,
+            rounding,
+            crafts,
+        } = self;
+
+        if targets.is_empty() && amounts.is_empty() {
+            return Err(Error::usage("No targets passed, pass --target or --amount"));
+        }
+
+        log::info!("Parse targets");
+        let targets = parse_targets(data, ctx, &targets)?;
+        log::info!("  loaded {} targets", targets.len());
+
+        log::info!("Parse ignored recipes");
+        let mut ignore = parse_ignore(data, ctx, &ignore, false)?;
+        if !unlocked_techs.is_empty() {
+            let techs = parse_techs(data, &unlocked_techs)?;
+            ignore.recipes.extend(data.locked_recipes(&techs));
+        }
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        log::info!("Parse buildings");
+        let buildings = parse_buildings(data, ctx, &buildings)?;
+        log::info!("  loaded {} building selections", buildings.len());
+
+        log::info!("Parse preferred recipes");
+        let prefer = parse_ids(data, ctx, &prefer, false)?.recipes;
+        log::info!("  loaded {} preferred recipes", prefer.len());
+
+        let belt = belt_tier.map(|tier| parse_belt(data, tier)).transpose()?;
+
+        log::info!("Calculate plan");
+        let options = PlanOptions {
+            ignore,
+            proliferator,
+            spray_mode,
+            buildings,
+            selector: Box::new(PinnedList::new(
+                prefer.iter().copied(),
+                Box::new(PreferBasic),
+            )),
+        };
+        if !amounts.is_empty() {
+            log::info!("Parse amounts");
+            let amounts = parse_targets(data, ctx, &amounts)?;
+
+            let list = ShoppingList::new(data, &amounts, options, rounding)?;
+            log::info!("  use {} recipes", list.recipes.len());
+
+            print_shopping_list(data, &amounts, &list, crafts);
+
+            return Ok(());
+        }
+
+        let (plan, optimization) = match objective {
+            Some(objective) => {
+                let ret = optimize(data, &targets, &options, &prefer, objective)?;
+                log::info!("  optimized {} recipes", ret.choices.len());
+
+                (
+                    ret.plan,
+                    Some((objective, ret.choices, ret.default_cost, ret.cost)),
+                )
+            }
+            None => (ProductionPlan::new(data, &targets, options)?, None),
+        };
+        log::info!("  use {} recipes", plan.recipes.len());
+
+        let item_name = |iid: &ItemId| {
+            data.items
+                .get(iid)
+                .map(|i| i.name.clone())
+                .unwrap_or_else(|| iid.label(&data.keys).to_string())
+        };
+        let rate_label = |rate: f64| match belt {
+            Some(belt) => format!("{:>10.2} /min {:>8.2} belts", rate, belt.belts(rate)),
+            None => format!("{:>10.2} /min", rate),
+        };
+
+        if proliferator == Proliferator::None {
+            println!("Proliferator: none");
+        } else {
+            println!("Proliferator: {} ({})", proliferator, spray_mode);
+        }
+
+        println!();
+        println!("Targets:");
+        for (iid, rate) in &targets {
+            println!("    {:<40} {}", item_name(iid), rate_label(*rate));
+        }
+
+        println!();
+        println!("Recipes:");
+
+        let mut total_power = 0.0;
+        let mut power_available = !data.buildings.is_empty();
+        for (rid, crafts) in &plan.recipes {
+            let recipe = match data.recipes.get(rid) {
+                Some(recipe) => recipe,
+                None => continue,
+            };
+
+            let machines = plan.machines(data, *rid);
+            let power = match plan.power(data, *rid, idle_power) {
+                Some(power) => {
+                    total_power += power;
+
+                    format!("{:>10.2} kW", power)
+                }
+                None => {
+                    power_available = false;
+
+                    format!("{:>13}", "n/a")
+                }
+            };
+
+            let building = plan
+                .options
+                .building(data, recipe)
+                .map(|(_, b)| format!("   {}", b.name))
+                .unwrap_or_default();
+
+            let fallback = match plan.options.spray_mode(recipe) {
+                Some(mode) if mode != spray_mode => "   (speedup)",
+                _ => "",
+            };
+
+            let productivity = recipe
+                .productivity_label()
+                .map(|label| format!("   {}", label))
+                .unwrap_or_default();
+
+            println!(
+                "    {:<40} {:>10.2} crafts/min {:>8.2} machines {}{}{}{}",
+                recipe.name, crafts, machines, power, building, productivity, fallback
+            );
+        }
+
+        if let Some((objective, choices, default_cost, cost)) = &optimization {
+            let recipe_name = |rid: &RecipeId| {
+                data.recipes
+                    .get(rid)
+                    .map(|r| r.name.clone())
+                    .unwrap_or_else(|| rid.label(&data.keys).to_string())
+            };
+
+            println!();
+            println!("Optimized recipes ({}):", objective);
+            for choice in choices {
+                let default = choice
+                    .default
+                    .map(|rid| format!("   (instead of {})", recipe_name(&rid)))
+                    .unwrap_or_default();
+
+                println!(
+                    "    {:<40} {}{}",
+                    item_name(&choice.item),
+                    recipe_name(&choice.recipe),
+                    default
+                );
+            }
+            println!(
+                "    Savings: {:.2} {} ({:.2} -> {:.2} {})",
+                default_cost - cost,
+                objective.unit(),
+                default_cost,
+                cost,
+                objective.unit()
+            );
+        }
+
+        println!();
+        println!("Resources:");
+        for (iid, rate) in &plan.resources {
+            println!("    {:<40} {}", item_name(iid), rate_label(*rate));
+        }
+
+        if !plan.byproducts.is_empty() {
+            println!();
+            println!("Byproducts:");
+            for (iid, rate) in &plan.byproducts {
+                println!("    {:<40} {}", item_name(iid), rate_label(*rate));
+            }
+        }
+
+        println!();
+        if data.buildings.is_empty() {
+            log::warn!("Data file does not contain any building data");
+            println!("Power: unavailable (no building data)");
+        } else if !power_available {
+            log::warn!("Building data is incomplete, power figures are a lower bound");
+            println!("Power: {:.2} kW (incomplete building data)", total_power);
+        } else {
+            println!("Power: {:.2} kW", total_power);
+        }
+
+        Ok(())
+    }
+}
+
+fn print_shopping_list(
+    data: &Data,
+    amounts: &BTreeMap<ItemId, f64>,
+    list: &ShoppingList,
+    crafts: bool,
+) {
+    let item_name = |iid: &ItemId| {
+        data.items
+            .get(iid)
+            .map(|i| i.name.clone())
+            .unwrap_or_else(|| iid.label(&data.keys).to_string())
+    };
+    let print_items = |title: &str, items: &BTreeMap<ItemId, f64>| {
+        println!();
+        println!("{}:", title);
+        for (iid, amount) in items {
+            println!("    {:<40} {:>10}", item_name(iid), amount_label(*amount));
+        }
+    };
+
+    println!("Amounts:");
+    for (iid, amount) in amounts {
+        println!("    {:<40} {:>10}", item_name(iid), amount_label(*amount));
+    }
+
+    if crafts {
+        println!();
+        println!("Crafts:");
+        for (rid, crafts) in &list.recipes {
+            let name = data
+                .recipes
+                .get(rid)
+                .map(|r| r.name.clone())
+                .unwrap_or_else(|| rid.label(&data.keys).to_string());
+
+            println!("    {:<40} {:>10} crafts", name, amount_label(*crafts));
+        }
+    }
+
+    if !list.intermediates.is_empty() {
+        print_items("Intermediates", &list.intermediates);
+    }
+
+    print_items("Resources", &list.resources);
+
+    if !list.leftovers.is_empty() {
+        print_items("Leftovers", &list.leftovers);
+    }
+}
+
+/// Whole amounts are printed without decimals.
+fn amount_label(amount: f64) -> String {
+    if (amount - amount.round()).abs() < 1e-9 {
+        format!("{}", amount.round())
+    } else {
+        format!("{:.2}", amount)
+    }
+}
+
+/// Belt of the passed tier (see [`crate::data::Logistics::belt`]).
+pub(crate) fn parse_belt(data: &Data, tier: usize) -> Result<&Belt, Error> {
+    if tier == 0 {
+        return Err(Error::usage("Belt tiers start at 1"));
+    }
+
+    let belt = data
+        .logistics
+        .belt(tier)
+        .ok_or_else(|| Error::data("Data file does not contain any belt data"))?;
+    log::info!("Express rates in belts of {}", belt.name);
+
+    Ok(belt)
+}
+
+pub(crate) fn parse_buildings(
+    data: &Data,
+    ctx: &SelectorContext,
+    buildings: &[String],
+) -> Result<HashMap<RecipeType, ItemId>, Error> {
+    let mut ret = HashMap::new();
+
+    for bid in parse_ids(data, ctx, buildings, true)?.items {
+        let building = data
+            .buildings
+            .get(&bid)
+            .ok_or_else(|| Error::usage(format!("Not a production building: {}", bid)))?;
+
+        for type_ in &building.types {
+            ret.insert(type_.clone(), bid);
+        }
+    }
+
+    Ok(ret)
+}
@@ -0,0 +1,282 @@
+use itertools::Itertools;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    commands::plan::parse_belt,
+    data::{Belt, Building, Data, ItemAmount, ItemId, Recipe, RecipeId, RecipeType},
+    error::Error,
+    format::{csv_line, Format},
+    selector::{parse_ids, SelectorContext},
+    table::{Cell, Color, Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Rates {
+    /// Only list recipes of these types (like assemble or smelt).
+    #[structopt(long = "type", use_delimiter = true)]
+    pub types: Vec<String>,
+
+    /// Only list recipes that produce one of these items.
+    #[structopt(short = "p", long = "produces")]
+    pub produces: Vec<String>,
+
+    /// Tier of the buildings the rates are calculated for, starting at 1 for
+    /// the slowest building of each recipe type. Recipe types with less
+    /// buildings use their fastest one. Without a tier the rates are based
+    /// on the recipe time.
+    #[structopt(long = "building-tier")]
+    pub building_tier: Option<usize>,
+
+    /// Tier of the belt the rates are also expressed in, starting at 1 for
+    /// the slowest belt of the data. Higher tiers use the fastest belt.
+    #[structopt(long = "belt-tier")]
+    pub belt_tier: Option<usize>,
+
+    /// Output format (text, json or csv).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    recipe_id: RecipeId,
+    recipe: String,
+
+    #[serde(rename = "type")]
+    type_: String,
+
+    /// Building of the selected tier, `None` if no tier is selected or
+    /// there is no building data for the recipe type.
+    building: Option<String>,
+    speed: f64,
+    seconds: f64,
+    inputs: Vec<Rate>,
+    outputs: Vec<Rate>,
+}
+
+#[derive(Debug, Serialize)]
+struct Rate {
+    item_id: ItemId,
+    item: String,
+    amount: f64,
+
+    /// Items per minute of one machine.
+    per_minute: f64,
+
+    /// Belts of the selected tier that are filled by one machine, `None` if
+    /// no belt tier is selected.
+    belts: Option<f64>,
+}
+
+impl Rates {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext, style: &Style) -> Result<(), Error> {
+        let Self {
+            types,
+            produces,
+            building_tier,
+            belt_tier,
+            format,
+        } = self;
+
+        if building_tier == Some(0) {
+            return Err(Error::usage("Building tiers start at 1"));
+        }
+
+        let belt = belt_tier.map(|tier| parse_belt(data, tier)).transpose()?;
+
+        let types = types
+            .iter()
+            .map(|t| RecipeType::from(t.trim().to_uppercase().as_str()))
+            .collect::<Vec<_>>();
+
+        log::info!("Parse items");
+        let produces = parse_ids(data, ctx, &produces, true)?.items;
+        log::info!("  loaded {} items", produces.len());
+
+        if building_tier.is_some() && data.buildings.is_empty() {
+            log::warn!("Data file does not contain any building data");
+        }
+
+        let rows = data
+            .recipes
+            .iter()
+            .filter(|(_, r)| r.seconds > 0.0)
+            .filter(|(_, r)| types.is_empty() || types.contains(&r.type_))
+            .filter(|(_, r)| {
+                produces.is_empty() || r.outputs.iter().any(|o| produces.contains(&o.id))
+            })
+            .sorted_by(|(a_id, a), (b_id, b)| a.name.cmp(&b.name).then(a_id.cmp(b_id)))
+            .map(|(rid, recipe)| {
+                let building = building_tier.and_then(|tier| building(data, &recipe.type_, tier));
+                let speed = building.map(|b| b.speed).unwrap_or(1.0);
+
+                Row {
+                    recipe_id: *rid,
+                    recipe: recipe.name.clone(),
+                    type_: recipe.type_.to_string(),
+                    building: building.map(|b| b.name.clone()),
+                    speed,
+                    seconds: recipe.seconds,
+                    inputs: rates(data, recipe, &recipe.inputs, speed, 1.0, belt),
+                    outputs: rates(
+                        data,
+                        recipe,
+                        &recipe.outputs,
+                        speed,
+                        recipe.productivity,
+                        belt,
+                    ),
+                }
+            })
+            .collect::<Vec<_>>();
+        log::info!("  listed {} recipes", rows.len());
+
+        let amounts = |rates: &[Rate]| {
+            rates
+                .iter()
+                .map(|r| match r.belts {
+                    Some(belts) => format!("{:.2} {} ({:.2} belts)", r.per_minute, r.item, belts),
+                    None => format!("{:.2} {}", r.per_minute, r.item),
+                })
+                .join(", ")
+        };
+
+        match format {
+            Format::Text => {
+                // The building column is only of interest if a tier is
+                // selected.
+                let with_building = building_tier.is_some();
+
+                let mut table = Table::new(
+                    [
+                        Some(Column::left("Recipe")),
+                        Some(Column::left("Type")),
+                        with_building.then(|| Column::left("Building")),
+                        Some(Column::left("Outputs/min")),
+                        Some(Column::left("Inputs/min")),
+                    ]
+                    .into_iter()
+                    .flatten(),
+                );
+                for row in &rows {
+                    table.row(
+                        [
+                            Some(Cell::new(&row.recipe)),
+                            Some(Cell::colored(
+                                &row.type_,
+                                Color::recipe_type(&RecipeType::from(row.type_.as_str())),
+                            )),
+                            with_building.then(|| Cell::new(row.building.as_deref().unwrap_or(""))),
+                            Some(Cell::new(amounts(&row.outputs))),
+                            Some(Cell::new(amounts(&row.inputs))),
+                        ]
+                        .into_iter()
+                        .flatten(),
+                    );
+                }
+
+                table.print(style);
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+            Format::Csv => {
+                println!(
+                    "{}",
+                    csv_line([
+                        "recipe_id",
+                        "recipe",
+                        "type",
+                        "building",
+                        "speed",
+                        "seconds",
+                        "kind",
+                        "item_id",
+                        "item",
+                        "amount",
+                        "per_minute",
+                        "belts",
+                    ])
+                );
+
+                for row in &rows {
+                    let inputs = row.inputs.iter().map(|rate| ("input", rate));
+                    let outputs = row.outputs.iter().map(|rate| ("output", rate));
+
+                    for (kind, rate) in outputs.chain(inputs) {
+                        println!(
+                            "{}",
+                            csv_line([
+                                row.recipe_id.to_string(),
+                                row.recipe.clone(),
+                                row.type_.clone(),
+                                row.building.clone().unwrap_or_default(),
+                                row.speed.to_string(),
+                                row.seconds.to_string(),
+                                kind.to_owned(),
+                                rate.item_id.to_string(),
+                                rate.item.clone(),
+                                rate.amount.to_string(),
+                                format!("{:.4}", rate.per_minute),
+                                rate.belts.map(|b| format!("{:.4}", b)).unwrap_or_default(),
+                            ])
+                        );
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Building of the passed tier of the recipe type. The buildings of a type
+/// are ordered by their speed, and the fastest one is used if the tier is
+/// higher than the number of buildings.
+fn building<'a>(data: &'a Data, type_: &RecipeType, tier: usize) -> Option<&'a Building> {
+    let buildings = data
+        .buildings
+        .iter()
+        .filter(|(_, b)| b.types.contains(type_))
+        .sorted_by(|(a_id, a), (b_id, b)| a.speed.total_cmp(&b.speed).then(a_id.cmp(b_id)))
+        .map(|(_, b)| b)
+        .collect::<Vec<_>>();
+
+    buildings
+        .get(tier - 1)
+        .or_else(|| buildings.last())
+        .copied()
+}
+
+/// Items per minute of one machine running the recipe at the passed speed,
+/// and the share of the passed belt they fill.
+fn rates(
+    data: &Data,
+    recipe: &Recipe,
+    amounts: &[ItemAmount],
+    speed: f64,
+    productivity: f64,
+    belt: Option<&Belt>,
+) -> Vec<Rate> {
+    amounts
+        .iter()
+        .map(|a| {
+            let amount = a.amount as f64 * productivity;
+            let per_minute = amount * 60.0 * speed / recipe.seconds;
+
+            Rate {
+                item_id: a.id,
+                item: item_name(data, &a.id),
+                amount,
+                per_minute,
+                belts: belt.map(|b| b.belts(per_minute)),
+            }
+        })
+        .collect()
+}
+
+fn item_name(data: &Data, iid: &ItemId) -> String {
+    data.items
+        .get(iid)
+        .map(|i| i.name.clone())
+        .unwrap_or_else(|| iid.label(&data.keys).to_string())
+}
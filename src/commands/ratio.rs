@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+
+use itertools::Itertools;
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{One, ToPrimitive, Zero};
+use structopt::StructOpt;
+
+use crate::{
+    commands::plan::parse_buildings,
+    data::{Data, ItemId, RecipeId},
+    error::Error,
+    planner::{select_recipe, PlanOptions},
+    selector::{parse_ids, parse_ignore, SelectorContext},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Ratio {
+    /// Item to calculate the machine ratios for.
+    pub item: String,
+
+    /// Recipes to exclude from the calculation.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Buildings to use for the recipe types they can craft (instead of the
+    /// first building of each type).
+    #[structopt(short = "b", long = "building")]
+    pub buildings: Vec<String>,
+
+    /// Largest block size (relative to the smallest machine count) before
+    /// the ratio is approximated.
+    #[structopt(short = "m", long = "max-multiplier", default_value = "20")]
+    pub max_multiplier: u64,
+}
+
+impl Ratio {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        let Self {
+            item,
+            ignore,
+            buildings,
+            max_multiplier,
+        } = self;
+
+        log::info!("Parse item");
+        let items = parse_ids(data, ctx, std::slice::from_ref(&item), true)?.items;
+        let iid = match items.iter().exactly_one() {
+            Ok(iid) => *iid,
+            Err(_) => return Err(Error::usage(format!("Expected exactly one item: {}", item))),
+        };
+
+        log::info!("Parse ignored recipes");
+        let ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        log::info!("Parse buildings");
+        let options = PlanOptions {
+            ignore,
+            buildings: parse_buildings(data, ctx, &buildings)?,
+            ..PlanOptions::default()
+        };
+
+        log::info!("Calculate machine ratios");
+        let MachineRatio {
+            counts,
+            rate,
+            exact,
+        } = match machine_ratio(data, &options, iid, max_multiplier) {
+            Some(ratio) => ratio,
+            None => return Err(Error::custom(format!("No recipes to produce {}", item))),
+        };
+
+        println!(
+            "Machine ratio for {}{}:",
+            item,
+            if exact { "" } else { " (approximated)" }
+        );
+        for (rid, count) in &counts {
+            let name = data
+                .recipes
+                .get(rid)
+                .map(|r| r.name.as_str())
+                .unwrap_or("?");
+
+            println!("    {:<40} {:>6}", name, count);
+        }
+
+        println!();
+        println!("Output: {:.2} /min per block", rate);
+
+        Ok(())
+    }
+}
+
+/// Smallest whole numbers of machines of each recipe that produce the passed
+/// item without idle machines.
+pub(crate) struct MachineRatio {
+    /// Number of machines of each recipe in one block.
+    pub counts: BTreeMap<RecipeId, BigInt>,
+
+    /// Output of one block in items per minute.
+    pub rate: f64,
+
+    /// Whether the counts are exact, or approximated because the exact
+    /// block would be larger than the maximum multiplier.
+    pub exact: bool,
+}
+
+/// Calculate the machine ratio of the passed item, using the recipes and
+/// buildings of the passed options like the `plan` command. Returns `None` if
+/// no recipe produces the item.
+pub(crate) fn machine_ratio(
+    data: &Data,
+    options: &PlanOptions,
+    iid: ItemId,
+    max_multiplier: u64,
+) -> Option<MachineRatio> {
+    let mut machines = BTreeMap::new();
+    add_demand(
+        data,
+        options,
+        &mut Vec::new(),
+        &mut machines,
+        iid,
+        &BigRational::one(),
+    );
+    machines.retain(|_, m| !m.is_zero());
+
+    let min = machines.values().min()?.clone();
+    let ratios = machines
+        .iter()
+        .map(|(rid, m)| (*rid, m / &min))
+        .collect::<BTreeMap<_, _>>();
+
+    let exact = exact_multiplier(ratios.values());
+    let (multiplier, counts, exact) = match exact.to_u64() {
+        Some(m) if m <= max_multiplier => {
+            let counts = scale(&ratios, &BigRational::from_integer(exact));
+
+            (m, counts, true)
+        }
+        _ => {
+            let (m, counts) = approximate(&ratios, max_multiplier);
+
+            (m, counts, false)
+        }
+    };
+
+    let rate = BigRational::from_integer(multiplier.into()) / &min;
+
+    Some(MachineRatio {
+        counts,
+        rate: rate.to_f64().unwrap_or(f64::NAN),
+        exact,
+    })
+}
+
+/// Add the machines (per item per minute of the target) needed to produce
+/// `rate` of the passed item.
+fn add_demand(
+    data: &Data,
+    options: &PlanOptions,
+    stack: &mut Vec<ItemId>,
+    machines: &mut BTreeMap<RecipeId, BigRational>,
+    iid: ItemId,
+    rate: &BigRational,
+) {
+    let ignore = &options.ignore;
+    if stack.contains(&iid) || ignore.items.contains(&iid) {
+        return;
+    }
+
+    let selector = options.selector.as_ref();
+    let (rid, recipe) = match select_recipe(data, &ignore.recipes, selector, iid) {
+        Some(selected) => selected,
+        None => return,
+    };
+
+    let amount = recipe
+        .outputs
+        .iter()
+        .filter(|o| o.id == iid)
+        .map(|o| o.amount)
+        .sum::<usize>();
+    let multiplier = decimal(options.output_multiplier(recipe));
+    let crafts = rate / (BigRational::from_integer(amount.into()) * multiplier);
+
+    let seconds = decimal(recipe.seconds)
+        / BigRational::from_integer(60.into())
+        / decimal(options.crafting_speed(data, recipe));
+    *machines.entry(rid).or_insert_with(BigRational::zero) += &crafts * seconds;
+
+    stack.push(iid);
+    for input in &recipe.inputs {
+        let rate = &crafts * BigRational::from_integer(input.amount.into());
+        add_demand(data, options, stack, machines, input.id, &rate);
+    }
+    stack.pop();
+}
+
+/// Convert a decimal number from the data file into an exact rational.
+fn decimal(value: f64) -> BigRational {
+    let s = value.to_string();
+    let (int, frac) = s.split_once('.').unwrap_or((&s, ""));
+
+    let numer = format!("{}{}", int, frac)
+        .parse::<BigInt>()
+        .unwrap_or_default();
+    let denom = BigInt::from(10).pow(frac.len() as u32);
+
+    BigRational::new(numer, denom)
+}
+
+/// Smallest multiplier that turns all passed ratios into integers.
+fn exact_multiplier<'a, I>(ratios: I) -> BigInt
+where
+    I: IntoIterator<Item = &'a BigRational>,
+{
+    ratios
+        .into_iter()
+        .fold(BigInt::one(), |lcm, r| lcm.lcm(r.denom()))
+}
+
+fn scale(
+    ratios: &BTreeMap<RecipeId, BigRational>,
+    factor: &BigRational,
+) -> BTreeMap<RecipeId, BigInt> {
+    ratios
+        .iter()
+        .map(|(rid, r)| (*rid, (r * factor).ceil().to_integer()))
+        .collect()
+}
+
+/// Find the multiplier up to `max` that wastes the least machine capacity
+/// when the machine counts are rounded up.
+fn approximate(
+    ratios: &BTreeMap<RecipeId, BigRational>,
+    max: u64,
+) -> (u64, BTreeMap<RecipeId, BigInt>) {
+    let mut best: Option<(BigRational, u64, BTreeMap<RecipeId, BigInt>)> = None;
+
+    for m in 1..=max.max(1) {
+        let factor = BigRational::from_integer(m.into());
+        let counts = scale(ratios, &factor);
+
+        let needed = ratios
+            .values()
+            .fold(BigRational::zero(), |sum, r| sum + r * &factor);
+        let built = counts.values().fold(BigRational::zero(), |sum, c| {
+            sum + BigRational::from_integer(c.clone())
+        });
+        let waste = (built - &needed) / needed;
+
+        if best.as_ref().map(|(w, _, _)| waste < *w).unwrap_or(true) {
+            best = Some((waste, m, counts));
+        }
+    }
+
+    let (_, m, counts) = best.unwrap();
+
+    (m, counts)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    use crate::{data::RecipeType, planner::ProductionPlan};
+
+    /// Gears are assembled from ingots by assemblers that are slower than the
+    /// smelters.
+    const SOURCE: &str = r#"
+        return {
+          game_items = {
+            { id = 1, name = "Ore", type = "RESOURCE" },
+            { id = 2, name = "Ingot", type = "MATERIAL" },
+            { id = 3, name = "Gear", type = "COMPONENT" },
+          },
+          game_recipes = {
+            { id = 1, name = "Ingot", type = "SMELT", seconds = 1, inputs = { 1, 1 }, outputs = { 2, 1 } },
+            { id = 2, name = "Gear", type = "ASSEMBLE", seconds = 2, inputs = { 2, 2 }, outputs = { 3, 1 } },
+          },
+          game_buildings = {
+            { id = 11, name = "Smelter", types = { "SMELT" }, speed = 1, work_power = 360 },
+            { id = 12, name = "Assembler", types = { "ASSEMBLE" }, speed = 0.75, work_power = 270 },
+            { id = 13, name = "Fast Assembler", types = { "ASSEMBLE" }, speed = 1.5, work_power = 780 },
+          },
+        }
+    "#;
+
+    fn data() -> Data {
+        Data::from_lua_source("ratio", SOURCE, None, &[]).unwrap()
+    }
+
+    fn counts(ratio: &MachineRatio) -> Vec<(usize, u64)> {
+        ratio
+            .counts
+            .iter()
+            .map(|(rid, count)| (rid.0, count.to_u64().unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn ratio_uses_the_machines_of_the_plan() {
+        let data = data();
+
+        for buildings in [
+            HashMap::new(),
+            HashMap::from([(RecipeType::Assemble, ItemId(13))]),
+        ] {
+            let options = PlanOptions {
+                buildings: buildings.clone(),
+                ..PlanOptions::default()
+            };
+            let ratio = machine_ratio(&data, &options, ItemId(3), 20).unwrap();
+            assert!(ratio.exact);
+
+            let targets = BTreeMap::from([(ItemId(3), ratio.rate)]);
+            let options = PlanOptions {
+                buildings,
+                ..PlanOptions::default()
+            };
+            let plan = ProductionPlan::new(&data, &targets, options).unwrap();
+            for (rid, count) in &ratio.counts {
+                let machines = plan.machines(&data, *rid);
+                assert!(
+                    (machines - count.to_f64().unwrap()).abs() < 1e-9,
+                    "{:?}: {} machines, ratio {}",
+                    rid,
+                    machines,
+                    count
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn ratio_divides_by_the_building_speed() {
+        let data = data();
+
+        // 4 assemblers with a speed of 0.75 craft 90 gears per minute from
+        // the ingots of 3 smelters.
+        let ratio = machine_ratio(&data, &PlanOptions::default(), ItemId(3), 20).unwrap();
+        assert_eq!(counts(&ratio), vec![(1, 3), (2, 4)]);
+        assert!((ratio.rate - 90.0).abs() < 1e-9);
+
+        let options = PlanOptions {
+            buildings: HashMap::from([(RecipeType::Assemble, ItemId(13))]),
+            ..PlanOptions::default()
+        };
+        let ratio = machine_ratio(&data, &options, ItemId(3), 20).unwrap();
+        assert_eq!(counts(&ratio), vec![(1, 3), (2, 2)]);
+        assert!((ratio.rate - 90.0).abs() < 1e-9);
+    }
+
+    fn ratio(numer: i64, denom: i64) -> BigRational {
+        BigRational::new(numer.into(), denom.into())
+    }
+
+    #[test]
+    fn decimals_are_exact() {
+        assert_eq!(decimal(0.75), ratio(3, 4));
+        assert_eq!(decimal(1.5), ratio(3, 2));
+        assert_eq!(decimal(2.0), ratio(2, 1));
+        assert_eq!(decimal(0.1), ratio(1, 10));
+        assert_eq!(decimal(1.125), ratio(9, 8));
+    }
+
+    #[test]
+    fn exact_multiplier_is_the_lcm_of_the_denominators() {
+        let ratios = [ratio(1, 1), ratio(1, 2), ratio(2, 3), ratio(5, 4)];
+        assert_eq!(exact_multiplier(&ratios), BigInt::from(12));
+
+        let ratios = [ratio(1, 1), ratio(3, 1)];
+        assert_eq!(exact_multiplier(&ratios), BigInt::one());
+    }
+
+    #[test]
+    fn approximation_wastes_the_least_capacity() {
+        let ratios = BTreeMap::from([(RecipeId(1), ratio(1, 1)), (RecipeId(2), ratio(7, 5))]);
+
+        // A multiplier of 2 builds 2 and 3 machines for 2 and 2.8 needed
+        // ones, which wastes less than 1:2 or 3:5.
+        let (multiplier, counts) = approximate(&ratios, 3);
+        assert_eq!(multiplier, 2);
+        assert_eq!(
+            counts,
+            BTreeMap::from([
+                (RecipeId(1), BigInt::from(2)),
+                (RecipeId(2), BigInt::from(3))
+            ])
+        );
+    }
+
+    #[test]
+    fn ratio_is_approximated_above_the_max_multiplier() {
+        let data = data();
+
+        // The exact ratio of 3:4 needs a multiplier of 3.
+        let ratio = machine_ratio(&data, &PlanOptions::default(), ItemId(3), 3).unwrap();
+        assert!(ratio.exact);
+        assert_eq!(counts(&ratio), vec![(1, 3), (2, 4)]);
+
+        let ratio = machine_ratio(&data, &PlanOptions::default(), ItemId(3), 2).unwrap();
+        assert!(!ratio.exact);
+        assert_eq!(counts(&ratio), vec![(1, 2), (2, 3)]);
+        assert!((ratio.rate - 60.0).abs() < 1e-9);
+    }
+}
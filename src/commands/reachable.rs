@@ -0,0 +1,254 @@
+use std::collections::BTreeSet;
+use std::io::stdout;
+
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    analysis::reachable,
+    data::{Data, IdLabel, ItemId, ItemType, RecipeId},
+    error::Error,
+    format::Format,
+    graph::{write_dot, DotOptions, GraphBuilder},
+    meta::Meta,
+    selector::{parse_ids, parse_ignore, parse_techs, SelectorContext},
+    table::{Cell, Color, Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Reachable {
+    /// Items that are available, e.g. the resources of a planet.
+    #[structopt(short = "r", long = "resources", required = true, use_delimiter = true)]
+    pub resources: Vec<String>,
+
+    /// Recipes to exclude from the calculation.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Only use recipes that are unlocked by these techs (ids, names or files
+    /// with one tech per line) and their prerequisites.
+    #[structopt(long = "unlocked-techs", use_delimiter = true)]
+    pub unlocked_techs: Vec<String>,
+
+    /// Also list the items whose recipes only miss one input.
+    #[structopt(long = "almost")]
+    pub almost: bool,
+
+    /// Write the graph of the reachable recipes in DOT format instead of the
+    /// list of items.
+    #[structopt(short = "g", long = "graph")]
+    pub graph: bool,
+
+    /// Output format (text or json).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+
+    /// Do not write the metadata (version, data files, options and time)
+    /// to the JSON and DOT output.
+    #[structopt(long = "no-meta")]
+    pub no_meta: bool,
+
+    /// Do not add the current time to the metadata.
+    #[structopt(long = "meta-no-timestamp")]
+    pub meta_no_timestamp: bool,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    meta: Option<Meta>,
+
+    reachable: Vec<Row>,
+
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    almost: Vec<AlmostRow>,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    id: IdLabel,
+    name: String,
+    #[serde(rename = "type")]
+    type_: String,
+    steps: usize,
+}
+
+#[derive(Debug, Serialize)]
+struct AlmostRow {
+    id: IdLabel,
+    name: String,
+    recipe: String,
+    missing: String,
+}
+
+impl Reachable {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext, style: &Style) -> Result<(), Error> {
+        let Self {
+            resources,
+            ignore,
+            unlocked_techs,
+            almost,
+            graph,
+            format,
+            no_meta,
+            meta_no_timestamp,
+        } = self;
+
+        let meta = (!no_meta).then(|| {
+            Meta::new(data, !meta_no_timestamp)
+                .values("resources", &resources)
+                .values("ignore", &ignore)
+                .values("unlocked-techs", &unlocked_techs)
+                .option("almost", almost)
+        });
+
+        log::info!("Parse resources");
+        let resources = parse_ids(data, ctx, &resources, true)?.items;
+        log::info!("  loaded {} resources", resources.len());
+
+        log::info!("Parse ignored recipes");
+        let mut ignore = parse_ignore(data, ctx, &ignore, false)?.recipes;
+        if !unlocked_techs.is_empty() {
+            let techs = parse_techs(data, &unlocked_techs)?;
+            ignore.extend(data.locked_recipes(&techs));
+        }
+        log::info!("  loaded {} ignored recipes", ignore.len());
+
+        log::info!("Calculate reachable items");
+        let recipes = data
+            .recipes
+            .keys()
+            .filter(|rid| !ignore.contains(rid))
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let reachability = reachable(data, &resources, &recipes);
+        log::info!(
+            "  found {} reachable items and {} reachable recipes",
+            reachability.items.len(),
+            reachability.recipes.len()
+        );
+
+        if graph {
+            let produced = reachability
+                .items
+                .iter()
+                .filter(|(_, steps)| **steps > 0)
+                .map(|(iid, _)| *iid);
+            let unreachable = data
+                .recipes
+                .keys()
+                .filter(|rid| !reachability.recipes.contains(rid))
+                .copied();
+
+            let graph = GraphBuilder::new(data)
+                .targets(produced)
+                .ignore_recipes(unreachable)
+                .build()?;
+
+            let options = DotOptions {
+                source_style: true,
+                meta,
+                ..Default::default()
+            };
+
+            return write_dot(&graph, &options, &mut stdout().lock()).map(|_| ());
+        }
+
+        let item_name = |iid: &ItemId| {
+            data.items
+                .get(iid)
+                .map(|i| i.name.clone())
+                .unwrap_or_else(|| iid.label(&data.keys).to_string())
+        };
+        let recipe_name = |rid: &RecipeId| {
+            data.recipes
+                .get(rid)
+                .map(|r| r.name.clone())
+                .unwrap_or_else(|| rid.label(&data.keys).to_string())
+        };
+
+        let mut report = Report {
+            meta,
+            ..Default::default()
+        };
+        for (iid, steps) in &reachability.items {
+            report.reachable.push(Row {
+                id: iid.label(&data.keys),
+                name: item_name(iid),
+                type_: data
+                    .items
+                    .get(iid)
+                    .map(|i| i.type_.to_string())
+                    .unwrap_or_default(),
+                steps: *steps,
+            });
+        }
+        report
+            .reachable
+            .sort_by(|a, b| (a.steps, &a.name).cmp(&(b.steps, &b.name)));
+
+        if almost {
+            for (rid, missing) in &reachability.almost {
+                for output in &data.recipes[rid].outputs {
+                    if reachability.items.contains_key(&output.id) {
+                        continue;
+                    }
+
+                    report.almost.push(AlmostRow {
+                        id: output.id.label(&data.keys),
+                        name: item_name(&output.id),
+                        recipe: recipe_name(rid),
+                        missing: item_name(missing),
+                    });
+                }
+            }
+            report
+                .almost
+                .sort_by(|a, b| (&a.name, &a.recipe).cmp(&(&b.name, &b.recipe)));
+            log::info!("  found {} almost reachable items", report.almost.len());
+        }
+
+        match format {
+            Format::Text => {
+                println!("Reachable:");
+
+                let mut table = Table::new([
+                    Column::left("Item"),
+                    Column::left("Type"),
+                    Column::right("Steps"),
+                ])
+                .indent(4);
+                for row in &report.reachable {
+                    let color = Color::item_type(&ItemType::from(row.type_.as_str()));
+
+                    table.row([
+                        Cell::new(&row.name),
+                        Cell::colored(&row.type_, color),
+                        Cell::new(row.steps),
+                    ]);
+                }
+                table.print(style);
+
+                if almost {
+                    println!();
+                    println!("Almost reachable:");
+
+                    let mut table = Table::new([
+                        Column::left("Item"),
+                        Column::left("Recipe"),
+                        Column::left("Missing"),
+                    ])
+                    .indent(4);
+                    for row in &report.almost {
+                        table.row([&row.name, &row.recipe, &row.missing].map(Cell::new));
+                    }
+                    table.print(style);
+                }
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            format => return Err(format.unsupported()),
+        }
+
+        Ok(())
+    }
+}
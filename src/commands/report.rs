@@ -0,0 +1,675 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{stdout, Write};
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use structopt::StructOpt;
+
+use crate::{
+    commands::{plan::parse_buildings, ratio::machine_ratio},
+    data::{Data, ItemId, RecipeId},
+    error::Error,
+    graph::{escape_html, RecipeLabel},
+    planner::{PlanOptions, ProductionPlan, Proliferator, SprayMode},
+    resolver::{PinnedList, PreferBasic},
+    selector::{parse_ids, parse_ignore, parse_targets, parse_techs, SelectorContext},
+};
+
+use super::{export_markdown::escape, serve::render, write_output};
+use super::{CreateProductionGraph, GraphOptions};
+
+#[derive(Debug, StructOpt)]
+pub struct Report {
+    /// Items to produce, given as `<item>=<amount per minute>`.
+    #[structopt(short = "t", long = "target", required = true)]
+    pub targets: Vec<String>,
+
+    /// Recipes to exclude from the report.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Only use recipes that are unlocked by these techs (ids, names or files
+    /// with one tech per line) and their prerequisites.
+    #[structopt(long = "unlocked-techs", use_delimiter = true)]
+    pub unlocked_techs: Vec<String>,
+
+    /// Include the idle power of partially used machines in the power figures.
+    #[structopt(long = "idle-power")]
+    pub idle_power: bool,
+
+    /// Proliferator that is sprayed on the inputs (none, mk1, mk2 or mk3).
+    #[structopt(long = "proliferator", default_value = "none")]
+    pub proliferator: Proliferator,
+
+    /// How the proliferator is used (extra-products or speedup).
+    #[structopt(long = "spray-mode", default_value = "extra-products")]
+    pub spray_mode: SprayMode,
+
+    /// Buildings to use for the recipe types they can craft (instead of the
+    /// first building of each type).
+    #[structopt(short = "b", long = "building")]
+    pub buildings: Vec<String>,
+
+    /// Recipes to use for the items they produce (instead of the primary
+    /// recipe of each item).
+    #[structopt(long = "prefer")]
+    pub prefer: Vec<String>,
+
+    /// Largest block size of the machine ratios before they are approximated
+    /// (see the `ratio` command).
+    #[structopt(short = "m", long = "max-multiplier", default_value = "20")]
+    pub max_multiplier: u64,
+
+    /// Sections of the report in the order they are written (machines,
+    /// resources, power, ratios or graph).
+    #[structopt(
+        short = "s",
+        long = "sections",
+        use_delimiter = true,
+        default_value = "machines,resources,power,graph"
+    )]
+    pub sections: Vec<Section>,
+
+    /// Format of the report (markdown or html). The graph is embedded as
+    /// SVG in HTML reports if graphviz is installed, and as DOT code
+    /// otherwise.
+    #[structopt(short = "f", long = "format", default_value = "markdown")]
+    pub format: ReportFormat,
+
+    /// File to write the report to (stdout if not set).
+    #[structopt(short = "o", long = "output")]
+    pub output: Option<PathBuf>,
+}
+
+impl Report {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext) -> Result<(), Error> {
+        log::info!("Parse targets");
+        let targets = parse_targets(data, ctx, &self.targets)?;
+        log::info!("  loaded {} targets", targets.len());
+
+        log::info!("Parse ignored recipes");
+        let mut ignore = parse_ignore(data, ctx, &self.ignore, false)?;
+        if !self.unlocked_techs.is_empty() {
+            let techs = parse_techs(data, &self.unlocked_techs)?;
+            ignore.recipes.extend(data.locked_recipes(&techs));
+        }
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        log::info!("Parse buildings");
+        let buildings = parse_buildings(data, ctx, &self.buildings)?;
+        log::info!("  loaded {} building selections", buildings.len());
+
+        log::info!("Parse preferred recipes");
+        let prefer = parse_ids(data, ctx, &self.prefer, false)?.recipes;
+        log::info!("  loaded {} preferred recipes", prefer.len());
+
+        log::info!("Calculate plan");
+        let options = PlanOptions {
+            ignore,
+            proliferator: self.proliferator,
+            spray_mode: self.spray_mode,
+            buildings,
+            selector: Box::new(PinnedList::new(
+                prefer.iter().copied(),
+                Box::new(PreferBasic),
+            )),
+        };
+        let plan = ProductionPlan::new(data, &targets, options)?;
+        log::info!("  use {} recipes", plan.recipes.len());
+
+        let context = Context {
+            data,
+            selectors: ctx,
+            targets: &targets,
+            plan: &plan,
+            report: &self,
+        };
+
+        let write = |out: &mut dyn Write| -> Result<(), Error> {
+            let mut doc = Document {
+                format: self.format,
+                out,
+            };
+
+            context.write(&mut doc)
+        };
+
+        match &self.output {
+            Some(path) => {
+                log::info!("Write report to {:#?}", path);
+
+                write_output(path, write)
+            }
+            None => write(&mut stdout().lock()),
+        }
+    }
+
+    /// Graph of the targets, with the same recipes as the plan.
+    fn graph(&self) -> CreateProductionGraph {
+        CreateProductionGraph::embedded(GraphOptions {
+            items: Vec::new(),
+            targets: self.targets.clone(),
+            summary_node: false,
+            proliferator: self.proliferator,
+            spray_mode: self.spray_mode,
+            ignore: self.ignore.clone(),
+            resolve_deps: true,
+            unlocked_techs: self.unlocked_techs.clone(),
+            handcraft_only: false,
+            merge_duplicate_recipes: false,
+            rank_by_tier: false,
+            icons: false,
+            icons_dir: "icons".into(),
+            no_source_style: false,
+            color_by: None,
+            depth_colors: Vec::new(),
+            depth_cutoff: None,
+            size_by: None,
+            node_size_range: vec![0.75, 3.0],
+            font_size_range: vec![10.0, 28.0],
+            scale_recipes: false,
+            split_common: None,
+            theme: None,
+            recipe_label: RecipeLabel::Point,
+            combine_edges: false,
+            aggregate_by: None,
+        })
+    }
+}
+
+/* ReportFormat */
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "markdown" | "md" => Ok(Self::Markdown),
+            "html" => Ok(Self::Html),
+            s => Err(format!(
+                "Unknown report format: {} (expected markdown or html)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for ReportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Markdown => write!(f, "markdown"),
+            Self::Html => write!(f, "html"),
+        }
+    }
+}
+
+/* Section */
+
+/// Section of the report. New sections only need a variant and a function
+/// that writes their content to the document.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Section {
+    Machines,
+    Resources,
+    Power,
+    Ratios,
+    Graph,
+}
+
+impl Section {
+    fn title(self) -> &'static str {
+        match self {
+            Self::Machines => "Machines",
+            Self::Resources => "Resources",
+            Self::Power => "Power",
+            Self::Ratios => "Machine ratios",
+            Self::Graph => "Production graph",
+        }
+    }
+
+    fn write(self, context: &Context<'_>, doc: &mut Document<'_>) -> Result<(), Error> {
+        doc.heading(2, self.title())?;
+
+        match self {
+            Self::Machines => write_machines(context, doc),
+            Self::Resources => write_resources(context, doc),
+            Self::Power => write_power(context, doc),
+            Self::Ratios => write_ratios(context, doc),
+            Self::Graph => write_graph(context, doc),
+        }
+    }
+}
+
+impl FromStr for Section {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "machines" => Ok(Self::Machines),
+            "resources" => Ok(Self::Resources),
+            "power" => Ok(Self::Power),
+            "ratios" => Ok(Self::Ratios),
+            "graph" => Ok(Self::Graph),
+            s => Err(format!(
+                "Unknown section: {} (expected machines, resources, power, ratios or graph)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Section {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Machines => write!(f, "machines"),
+            Self::Resources => write!(f, "resources"),
+            Self::Power => write!(f, "power"),
+            Self::Ratios => write!(f, "ratios"),
+            Self::Graph => write!(f, "graph"),
+        }
+    }
+}
+
+/* Context */
+
+/// Everything the sections are generated from.
+struct Context<'a> {
+    data: &'a Data,
+    selectors: &'a SelectorContext,
+    targets: &'a BTreeMap<ItemId, f64>,
+    plan: &'a ProductionPlan,
+    report: &'a Report,
+}
+
+impl Context<'_> {
+    fn write(&self, doc: &mut Document<'_>) -> Result<(), Error> {
+        doc.begin("Production report")?;
+
+        let proliferator = match self.report.proliferator {
+            Proliferator::None => "none".to_owned(),
+            proliferator => format!("{} ({})", proliferator, self.report.spray_mode),
+        };
+        doc.paragraph(&format!("Proliferator: {}", proliferator))?;
+
+        doc.table(
+            &[("Target", Align::Left), ("Rate (/min)", Align::Right)],
+            self.targets
+                .iter()
+                .map(|(iid, rate)| vec![self.item_name(iid), format!("{:.2}", rate)])
+                .collect(),
+        )?;
+
+        for section in &self.report.sections {
+            log::info!("Write section {}", section);
+
+            section.write(self, doc)?;
+        }
+
+        doc.end()
+    }
+
+    fn item_name(&self, iid: &ItemId) -> String {
+        self.data
+            .items
+            .get(iid)
+            .map(|i| i.name.clone())
+            .unwrap_or_else(|| iid.label(&self.data.keys).to_string())
+    }
+
+    fn recipe_name(&self, rid: &RecipeId) -> String {
+        self.data
+            .recipes
+            .get(rid)
+            .map(|r| r.name.clone())
+            .unwrap_or_else(|| rid.label(&self.data.keys).to_string())
+    }
+
+    fn building_name(&self, rid: &RecipeId) -> String {
+        self.data
+            .recipes
+            .get(rid)
+            .and_then(|recipe| self.plan.options.building(self.data, recipe))
+            .map(|(_, b)| b.name.clone())
+            .unwrap_or_default()
+    }
+}
+
+fn write_machines(context: &Context<'_>, doc: &mut Document<'_>) -> Result<(), Error> {
+    let Context { data, plan, .. } = context;
+
+    doc.table(
+        &[
+            ("Recipe", Align::Left),
+            ("Building", Align::Left),
+            ("Crafts (/min)", Align::Right),
+            ("Machines", Align::Right),
+            ("Built", Align::Right),
+        ],
+        plan.recipes
+            .iter()
+            .map(|(rid, crafts)| {
+                let machines = plan.machines(data, *rid);
+
+                vec![
+                    context.recipe_name(rid),
+                    context.building_name(rid),
+                    format!("{:.2}", crafts),
+                    format!("{:.2}", machines),
+                    format!("{}", machines.ceil()),
+                ]
+            })
+            .collect(),
+    )
+}
+
+fn write_resources(context: &Context<'_>, doc: &mut Document<'_>) -> Result<(), Error> {
+    let items = |items: &BTreeMap<ItemId, f64>| {
+        items
+            .iter()
+            .map(|(iid, rate)| vec![context.item_name(iid), format!("{:.2}", rate)])
+            .collect()
+    };
+
+    doc.table(
+        &[("Resource", Align::Left), ("Rate (/min)", Align::Right)],
+        items(&context.plan.resources),
+    )?;
+
+    if !context.plan.byproducts.is_empty() {
+        doc.table(
+            &[("Byproduct", Align::Left), ("Rate (/min)", Align::Right)],
+            items(&context.plan.byproducts),
+        )?;
+    }
+
+    Ok(())
+}
+
+fn write_power(context: &Context<'_>, doc: &mut Document<'_>) -> Result<(), Error> {
+    let Context { data, plan, .. } = context;
+
+    if data.buildings.is_empty() {
+        log::warn!("Data file does not contain any building data");
+
+        return doc.paragraph("Power: unavailable (no building data)");
+    }
+
+    let mut total = 0.0;
+    let mut complete = true;
+    let rows = plan
+        .recipes
+        .keys()
+        .map(|rid| {
+            let power = match plan.power(data, *rid, context.report.idle_power) {
+                Some(power) => {
+                    total += power;
+
+                    format!("{:.2}", power)
+                }
+                None => {
+                    complete = false;
+
+                    "n/a".to_owned()
+                }
+            };
+
+            vec![context.recipe_name(rid), context.building_name(rid), power]
+        })
+        .collect();
+
+    doc.table(
+        &[
+            ("Recipe", Align::Left),
+            ("Building", Align::Left),
+            ("Power (kW)", Align::Right),
+        ],
+        rows,
+    )?;
+
+    if complete {
+        doc.paragraph(&format!("Total: {:.2} kW", total))
+    } else {
+        log::warn!("Building data is incomplete, power figures are a lower bound");
+
+        doc.paragraph(&format!(
+            "Total: {:.2} kW (incomplete building data)",
+            total
+        ))
+    }
+}
+
+fn write_ratios(context: &Context<'_>, doc: &mut Document<'_>) -> Result<(), Error> {
+    for iid in context.targets.keys() {
+        let name = context.item_name(iid);
+        doc.heading(3, &name)?;
+
+        let ratio = match machine_ratio(
+            context.data,
+            &context.plan.options,
+            *iid,
+            context.report.max_multiplier,
+        ) {
+            Some(ratio) => ratio,
+            None => {
+                doc.paragraph(&format!("No recipes to produce {}", name))?;
+
+                continue;
+            }
+        };
+
+        doc.table(
+            &[("Recipe", Align::Left), ("Machines", Align::Right)],
+            ratio
+                .counts
+                .iter()
+                .map(|(rid, count)| vec![context.recipe_name(rid), count.to_string()])
+                .collect(),
+        )?;
+
+        doc.paragraph(&format!(
+            "Output: {:.2} /min per block{}",
+            ratio.rate,
+            if ratio.exact { "" } else { " (approximated)" }
+        ))?;
+    }
+
+    Ok(())
+}
+
+fn write_graph(context: &Context<'_>, doc: &mut Document<'_>) -> Result<(), Error> {
+    let graph = context.report.graph();
+
+    if doc.format == ReportFormat::Html {
+        match render(context.data, context.selectors, &graph) {
+            Ok(svg) => return doc.svg(&svg),
+            Err(err) => log::warn!("Unable to render the graph, embed it as DOT: {}", err),
+        }
+    }
+
+    let mut dot = Vec::new();
+    graph.write_graph(context.data, context.selectors, &mut dot)?;
+
+    doc.code("dot", &String::from_utf8_lossy(&dot))
+}
+
+/* Document */
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+/// Writes the blocks of the report in the selected format.
+struct Document<'a> {
+    format: ReportFormat,
+    out: &'a mut dyn Write,
+}
+
+impl Document<'_> {
+    fn begin(&mut self, title: &str) -> Result<(), Error> {
+        match self.format {
+            ReportFormat::Markdown => {
+                writeln!(self.out, "# {}", escape(title))?;
+            }
+            ReportFormat::Html => {
+                writeln!(self.out, "<!DOCTYPE html>")?;
+                writeln!(self.out, "<html>")?;
+                writeln!(self.out, "<head>")?;
+                writeln!(self.out, "    <meta charset=\"utf-8\">")?;
+                writeln!(self.out, "    <title>{}</title>", escape_html(title))?;
+                writeln!(self.out, "</head>")?;
+                writeln!(self.out, "<body>")?;
+                writeln!(self.out, "<h1>{}</h1>", escape_html(title))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn end(&mut self) -> Result<(), Error> {
+        if self.format == ReportFormat::Html {
+            writeln!(self.out, "</body>")?;
+            writeln!(self.out, "</html>")?;
+        }
+
+        Ok(())
+    }
+
+    fn heading(&mut self, level: usize, text: &str) -> Result<(), Error> {
+        match self.format {
+            ReportFormat::Markdown => {
+                writeln!(self.out)?;
+                writeln!(self.out, "{} {}", "#".repeat(level), escape(text))?;
+            }
+            ReportFormat::Html => {
+                writeln!(self.out, "<h{0}>{1}</h{0}>", level, escape_html(text))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn paragraph(&mut self, text: &str) -> Result<(), Error> {
+        match self.format {
+            ReportFormat::Markdown => {
+                writeln!(self.out)?;
+                writeln!(self.out, "{}", escape(text))?;
+            }
+            ReportFormat::Html => {
+                writeln!(self.out, "<p>{}</p>", escape_html(text))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn table(&mut self, columns: &[(&str, Align)], rows: Vec<Vec<String>>) -> Result<(), Error> {
+        match self.format {
+            ReportFormat::Markdown => {
+                let line = |cells: Vec<String>| format!("| {} |", cells.join(" | "));
+
+                writeln!(self.out)?;
+                writeln!(
+                    self.out,
+                    "{}",
+                    line(columns.iter().map(|(title, _)| escape(title)).collect())
+                )?;
+                writeln!(
+                    self.out,
+                    "{}",
+                    line(
+                        columns
+                            .iter()
+                            .map(|(_, align)| match align {
+                                Align::Left => "---".to_owned(),
+                                Align::Right => "---:".to_owned(),
+                            })
+                            .collect()
+                    )
+                )?;
+                for row in rows {
+                    writeln!(
+                        self.out,
+                        "{}",
+                        line(row.iter().map(|cell| escape(cell)).collect())
+                    )?;
+                }
+            }
+            ReportFormat::Html => {
+                let cell = |tag: &str, align: Align, text: &str| match align {
+                    Align::Left => format!("<{0}>{1}</{0}>", tag, escape_html(text)),
+                    Align::Right => format!(
+                        "<{0} style=\"text-align: right\">{1}</{0}>",
+                        tag,
+                        escape_html(text)
+                    ),
+                };
+
+                writeln!(self.out, "<table>")?;
+                writeln!(
+                    self.out,
+                    "    <tr>{}</tr>",
+                    columns
+                        .iter()
+                        .map(|(title, align)| cell("th", *align, title))
+                        .collect::<String>()
+                )?;
+                for row in rows {
+                    writeln!(
+                        self.out,
+                        "    <tr>{}</tr>",
+                        row.iter()
+                            .zip(columns)
+                            .map(|(text, (_, align))| cell("td", *align, text))
+                            .collect::<String>()
+                    )?;
+                }
+                writeln!(self.out, "</table>")?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn code(&mut self, lang: &str, code: &str) -> Result<(), Error> {
+        match self.format {
+            ReportFormat::Markdown => {
+                writeln!(self.out)?;
+                writeln!(self.out, "```{}", lang)?;
+                write!(self.out, "{}", code)?;
+                if !code.ends_with('\n') {
+                    writeln!(self.out)?;
+                }
+                writeln!(self.out, "```")?;
+            }
+            ReportFormat::Html => {
+                writeln!(self.out, "<pre><code>{}</code></pre>", escape_html(code))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Embed an SVG image, only used for HTML reports. The XML prolog that
+    /// graphviz writes before the image is skipped.
+    fn svg(&mut self, svg: &[u8]) -> Result<(), Error> {
+        let svg = String::from_utf8_lossy(svg);
+        let start = svg.find("<svg").unwrap_or(0);
+
+        writeln!(self.out, "{}", svg[start..].trim_end())?;
+
+        Ok(())
+    }
+}
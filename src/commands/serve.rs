@@ -0,0 +1,348 @@
+use std::fs::metadata;
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::time::SystemTime;
+
+use structopt::StructOpt;
+use tiny_http::{Header, Request, Response, Server};
+
+use crate::{
+    args::DataArgs,
+    data::Data,
+    error::Error,
+    graph::escape_html,
+    selector::{split_selectors, SelectorContext},
+};
+
+use super::{load_data, CreateProductionGraph, GraphOptions};
+
+/// Serve the production graph of the graph options in the browser. The
+/// `items`, `ignore` and `resolve-deps` query parameters override the
+/// options of the command line.
+#[derive(Debug, StructOpt)]
+pub struct Serve {
+    #[structopt(flatten)]
+    pub options: GraphOptions,
+
+    /// Address to bind the HTTP server to.
+    #[structopt(short = "a", long = "address", default_value = "127.0.0.1:8080")]
+    pub address: String,
+}
+
+struct State<'a> {
+    args: &'a DataArgs,
+    ctx: &'a SelectorContext,
+    modified: Vec<Option<SystemTime>>,
+    data: Data,
+}
+
+impl Serve {
+    pub fn exec(self, args: &DataArgs, ctx: &SelectorContext) -> Result<(), Error> {
+        let mut state = State {
+            args,
+            ctx,
+            modified: modified(args),
+            data: load_data(args)?,
+        };
+
+        let server = Server::http(&self.address).map_err(Error::custom)?;
+        log::info!("Serve graph on http://{}/", &self.address);
+
+        for request in server.incoming_requests() {
+            log::info!("{} {}", request.method(), request.url());
+
+            if let Err(err) = self.handle(&mut state, request) {
+                log::error!("Error while handling the request: {}", err);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn handle(&self, state: &mut State<'_>, request: Request) -> Result<(), Error> {
+        let url = request.url().to_owned();
+        let (path, query) = url.split_once('?').unwrap_or((&url, ""));
+        let graph = match self.graph(query) {
+            Ok(graph) => graph,
+            Err(err) => {
+                log::warn!("Invalid query: {}", err);
+                request.respond(Response::from_string(err.to_string()).with_status_code(400))?;
+
+                return Ok(());
+            }
+        };
+
+        let response = match path {
+            "/" => Response::from_string(page(&graph, query))
+                .with_header(header("text/html; charset=utf-8")),
+            "/graph.svg" => match state
+                .reload()
+                .and_then(|()| render(&state.data, state.ctx, &graph))
+            {
+                Ok(svg) => Response::from_data(svg).with_header(header("image/svg+xml")),
+                Err(err) => {
+                    log::error!("Error while rendering the graph: {}", err);
+
+                    Response::from_string(err.to_string()).with_status_code(500)
+                }
+            },
+            _ => Response::from_string("Not Found").with_status_code(404),
+        };
+
+        request.respond(response)?;
+
+        Ok(())
+    }
+
+    /// Create the graph command for the passed query, using the command line
+    /// arguments for all parameters that are not part of the query. The
+    /// selectors of the query are checked with [`check_query_selectors`].
+    fn graph(&self, query: &str) -> Result<CreateProductionGraph, Error> {
+        let mut graph = CreateProductionGraph::embedded(self.options.clone());
+
+        let mut items = None;
+        let mut ignore = None;
+
+        for (key, value) in parse_query(query) {
+            let values = value
+                .lines()
+                .map(str::trim)
+                .filter(|s| !s.is_empty())
+                .map(ToOwned::to_owned);
+
+            match key.as_str() {
+                "items" => items.get_or_insert_with(Vec::new).extend(values),
+                "ignore" => ignore.get_or_insert_with(Vec::new).extend(values),
+                "resolve-deps" => {
+                    graph.options.resolve_deps = matches!(value.as_str(), "1" | "true" | "on")
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(items) = items {
+            check_query_selectors("items", &items)?;
+            graph.options.items = items;
+        }
+
+        if let Some(ignore) = ignore {
+            check_query_selectors("ignore", &ignore)?;
+            graph.options.ignore = ignore;
+        }
+
+        Ok(graph)
+    }
+}
+
+impl State<'_> {
+    /// Reload the data files if one of them was modified since they were
+    /// loaded.
+    fn reload(&mut self) -> Result<(), Error> {
+        let modified = modified(self.args);
+        if modified != self.modified {
+            log::info!("Data file was modified");
+
+            self.data = load_data(self.args)?;
+            self.modified = modified;
+        }
+
+        Ok(())
+    }
+}
+
+fn modified(args: &DataArgs) -> Vec<Option<SystemTime>> {
+    args.files()
+        .into_iter()
+        .map(|path| metadata(path).and_then(|m| m.modified()).ok())
+        .collect()
+}
+
+/// Render the passed graph to SVG using the `dot` command of graphviz.
+pub(crate) fn render(
+    data: &Data,
+    ctx: &SelectorContext,
+    graph: &CreateProductionGraph,
+) -> Result<Vec<u8>, Error> {
+    let mut dot = Vec::new();
+    graph.write_graph(data, ctx, &mut dot)?;
+
+    let mut child = Command::new("dot")
+        .arg("-Tsvg")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|err| Error::tool(format!("Unable to execute dot: {}", err)))?;
+    child.stdin.take().unwrap().write_all(&dot)?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::tool(format!(
+            "dot failed: {}",
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+
+    Ok(output.stdout)
+}
+
+fn page(graph: &CreateProductionGraph, query: &str) -> String {
+    let resolve_deps = |value: bool| {
+        if graph.options.resolve_deps == value {
+            " selected"
+        } else {
+            ""
+        }
+    };
+
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+    <meta charset="utf-8">
+    <title>DSP Production Graph</title>
+</head>
+<body>
+    <form method="get" action="/">
+        <label>Items<br><textarea name="items" rows="6" cols="40">{items}</textarea></label>
+        <label>Ignore<br><textarea name="ignore" rows="6" cols="40">{ignore}</textarea></label>
+        <label>Resolve dependencies
+            <select name="resolve-deps">
+                <option value="1"{yes}>yes</option>
+                <option value="0"{no}>no</option>
+            </select>
+        </label>
+        <button type="submit">Update</button>
+    </form>
+    <hr>
+    <img src="/graph.svg?{query}" alt="Production graph">
+</body>
+</html>
+"#,
+        items = escape_html(&graph.options.items.join("\n")),
+        ignore = escape_html(&graph.options.ignore.join("\n")),
+        yes = resolve_deps(true),
+        no = resolve_deps(false),
+        query = escape_html(query),
+    )
+}
+
+fn header(content_type: &str) -> Header {
+    Header::from_bytes(&b"Content-Type"[..], content_type.as_bytes()).unwrap()
+}
+
+fn parse_query(query: &str) -> Vec<(String, String)> {
+    query
+        .split('&')
+        .filter(|s| !s.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+
+            (decode(key), decode(value))
+        })
+        .collect()
+}
+
+/// Decode a percent encoded query component.
+fn decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut ret = Vec::with_capacity(bytes.len());
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+
+        match (bytes[i], hex) {
+            (b'+', _) => ret.push(b' '),
+            (b'%', Some(b)) => {
+                ret.push(b);
+                i += 2;
+            }
+            (b, _) => ret.push(b),
+        }
+
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&ret).into_owned()
+}
+
+/// Check the selectors of the query parameter `key` before they are parsed.
+/// The selectors of a request may only select from the data: `-` would read
+/// stdin of the server and `@name` would expand the groups of its config.
+fn check_query_selectors(key: &str, values: &[String]) -> Result<(), Error> {
+    for selector in split_selectors(values)? {
+        if selector == "-" {
+            return Err(Error::usage(format!(
+                "Invalid {} selector -: reading selectors from stdin is not supported by serve",
+                key
+            )));
+        }
+        let name = selector.strip_prefix(['!', '-']).unwrap_or(&selector);
+        if name.starts_with('@') {
+            return Err(Error::usage(format!(
+                "Invalid {} selector {}: groups are not supported by serve",
+                key, selector
+            )));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::error::EXIT_USAGE;
+
+    fn serve(args: &[&str]) -> Serve {
+        Serve::from_iter_safe(std::iter::once("serve").chain(args.iter().copied())).unwrap()
+    }
+
+    #[test]
+    fn query_overrides_the_options() {
+        let serve = serve(&["-i", "Gear", "--ignore", "Ore", "--rank-by-tier"]);
+
+        let graph = serve.graph("").unwrap();
+        assert_eq!(graph.options.items, vec!["Gear".to_owned()]);
+        assert_eq!(graph.options.ignore, vec!["Ore".to_owned()]);
+        assert!(graph.options.rank_by_tier);
+        assert!(!graph.options.resolve_deps);
+
+        let graph = serve.graph("items=Ingot%0D%0AGear&resolve-deps=1").unwrap();
+        assert_eq!(
+            graph.options.items,
+            vec!["Ingot".to_owned(), "Gear".to_owned()]
+        );
+        assert_eq!(graph.options.ignore, vec!["Ore".to_owned()]);
+        assert!(graph.options.resolve_deps);
+        assert!(graph.options.rank_by_tier);
+    }
+
+    #[test]
+    fn query_rejects_stdin_and_groups() {
+        let serve = serve(&[]);
+
+        for query in [
+            "items=-",
+            "ignore=Ore%2C-",
+            "items=%40metal",
+            "items=!%40metal",
+        ] {
+            let err = serve.graph(query).unwrap_err();
+            assert_eq!(err.exit_code(), EXIT_USAGE, "{}", query);
+        }
+
+        assert!(serve.graph("items=-Gear").is_ok());
+    }
+
+    #[test]
+    fn decode_percent_encoding() {
+        assert_eq!(decode("Iron+Ingot%2C%20Gear"), "Iron Ingot, Gear");
+        assert_eq!(decode("100%"), "100%");
+    }
+}
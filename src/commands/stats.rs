@@ -0,0 +1,289 @@
+use std::collections::{BTreeMap, HashMap};
+
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    data::{Data, IdLabel, ItemId, ItemType, RecipeId, RecipeType},
+    error::Error,
+    format::Format,
+    table::{Cell, Color, Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Stats {
+    /// Number of items to list for the most producing and consuming recipes.
+    #[structopt(short = "n", long = "top", default_value = "5")]
+    pub top: usize,
+
+    /// Output format (text or json).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Summary {
+    items: usize,
+    recipes: usize,
+    explicit_recipes: usize,
+    item_types: BTreeMap<String, usize>,
+    recipe_types: BTreeMap<String, usize>,
+    unknown_item_types: usize,
+    unknown_recipe_types: usize,
+    items_with_stack_size: usize,
+    seconds: Option<SecondsSummary>,
+    most_produced: Vec<ItemCount>,
+    most_consumed: Vec<ItemCount>,
+
+    /// Belts and sorters of the data, in items per second.
+    belts: Vec<Speed>,
+    sorters: Vec<Speed>,
+}
+
+#[derive(Debug, Serialize)]
+struct SecondsSummary {
+    min: f64,
+    avg: f64,
+    max: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct Speed {
+    name: String,
+    speed: f64,
+}
+
+#[derive(Debug, Serialize)]
+struct ItemCount {
+    id: IdLabel,
+    name: String,
+    recipes: usize,
+}
+
+impl Stats {
+    pub fn exec(self, data: &Data, style: &Style) -> Result<(), Error> {
+        let Self { top, format } = self;
+
+        let summary = Summary::new(data, top);
+
+        match format {
+            Format::Text => summary.print(style),
+            Format::Json => println!("{}", serde_json::to_string_pretty(&summary)?),
+            format => return Err(format.unsupported()),
+        }
+
+        Ok(())
+    }
+}
+
+impl Summary {
+    fn new(data: &Data, top: usize) -> Self {
+        let mut item_types = BTreeMap::<String, usize>::new();
+        for item in data.items.values() {
+            *item_types.entry(item.type_.to_string()).or_default() += 1;
+        }
+
+        let mut recipe_types = BTreeMap::<String, usize>::new();
+        for recipe in data.recipes.values() {
+            *recipe_types.entry(recipe.type_.to_string()).or_default() += 1;
+        }
+
+        let unknown_item_types = data
+            .items
+            .values()
+            .filter(|i| matches!(i.type_, ItemType::Unknown(_)))
+            .count();
+        let unknown_recipe_types = data
+            .recipes
+            .values()
+            .filter(|r| matches!(r.type_, RecipeType::Unknown(_)))
+            .count();
+
+        let seconds = if data.recipes.is_empty() {
+            None
+        } else {
+            let (min, max, sum) = data.recipes.values().fold(
+                (f64::INFINITY, f64::NEG_INFINITY, 0.0),
+                |(min, max, sum), r| (min.min(r.seconds), max.max(r.seconds), sum + r.seconds),
+            );
+
+            Some(SecondsSummary {
+                min,
+                avg: sum / data.recipes.len() as f64,
+                max,
+            })
+        };
+
+        Self {
+            items: data.items.len(),
+            recipes: data.recipes.len(),
+            explicit_recipes: data.recipes.values().filter(|r| r.explicit).count(),
+            item_types,
+            recipe_types,
+            unknown_item_types,
+            unknown_recipe_types,
+            items_with_stack_size: data
+                .items
+                .values()
+                .filter(|i| i.stack_size.is_some())
+                .count(),
+            seconds,
+            most_produced: top_items(data, &data.as_output, top),
+            most_consumed: top_items(data, &data.as_input, top),
+            belts: speeds(data.logistics.belts.iter().map(|b| (&b.name, b.speed))),
+            sorters: speeds(data.logistics.sorters.iter().map(|s| (&s.name, s.speed))),
+        }
+    }
+
+    fn print(&self, style: &Style) {
+        println!("{:<30} {:>8}", "Items", self.items);
+        println!("{:<30} {:>8}", "Recipes", self.recipes);
+        println!("{:<30} {:>8}", "Explicit recipes", self.explicit_recipes);
+        println!(
+            "{:<30} {:>8}",
+            "Items with unknown type", self.unknown_item_types
+        );
+        println!(
+            "{:<30} {:>8}",
+            "Recipes with unknown type", self.unknown_recipe_types
+        );
+        println!(
+            "{:<30} {:>8}",
+            "Items with stack size", self.items_with_stack_size
+        );
+
+        println!();
+        println!("Item types:");
+        let mut table = Table::new([Column::left(""), Column::right("")]).indent(4);
+        for (type_, count) in &self.item_types {
+            let color = Color::item_type(&ItemType::from(type_.as_str()));
+
+            table.row([Cell::colored(type_, color), Cell::new(count)]);
+        }
+        table.print(style);
+
+        println!();
+        println!("Recipe types:");
+        let mut table = Table::new([Column::left(""), Column::right("")]).indent(4);
+        for (type_, count) in &self.recipe_types {
+            let color = Color::recipe_type(&RecipeType::from(type_.as_str()));
+
+            table.row([Cell::colored(type_, color), Cell::new(count)]);
+        }
+        table.print(style);
+
+        if let Some(seconds) = &self.seconds {
+            println!();
+            println!("Crafting time:");
+            println!("    {:<26} {:>8.2} s", "min", seconds.min);
+            println!("    {:<26} {:>8.2} s", "avg", seconds.avg);
+            println!("    {:<26} {:>8.2} s", "max", seconds.max);
+        }
+
+        println!();
+        println!("Most producing recipes:");
+        for item in &self.most_produced {
+            println!("    {:<26} {:>8}", item.name, item.recipes);
+        }
+
+        println!();
+        println!("Most consuming recipes:");
+        for item in &self.most_consumed {
+            println!("    {:<26} {:>8}", item.name, item.recipes);
+        }
+
+        for (title, speeds) in [("Belts", &self.belts), ("Sorters", &self.sorters)] {
+            if !speeds.is_empty() {
+                println!();
+                println!("{}:", title);
+                for speed in speeds {
+                    println!("    {:<26} {:>8.2} /s", speed.name, speed.speed);
+                }
+            }
+        }
+    }
+}
+
+fn speeds<'a>(speeds: impl Iterator<Item = (&'a String, f64)>) -> Vec<Speed> {
+    speeds
+        .map(|(name, speed)| Speed {
+            name: name.clone(),
+            speed,
+        })
+        .collect()
+}
+
+fn top_items(data: &Data, index: &HashMap<ItemId, Vec<RecipeId>>, top: usize) -> Vec<ItemCount> {
+    let mut items = index
+        .iter()
+        .map(|(iid, rids)| ItemCount {
+            id: iid.label(&data.keys),
+            name: data
+                .items
+                .get(iid)
+                .map(|i| i.name.clone())
+                .unwrap_or_else(|| iid.label(&data.keys).to_string()),
+            recipes: rids.len(),
+        })
+        .collect::<Vec<_>>();
+
+    items.sort_by(|a, b| b.recipes.cmp(&a.recipes).then(a.id.cmp(&b.id)));
+    items.truncate(top);
+
+    items
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summary_counts_the_data() {
+        let source = r#"
+            return {
+              game_items = {
+                { id = 1, name = "Ore", type = "RESOURCE" },
+                { id = 2, name = "Ingot", type = "MATERIAL", stack_size = 100 },
+                { id = 3, name = "Gear", type = "GADGET" },
+              },
+              game_recipes = {
+                { id = 1, name = "Ingot", type = "SMELT", seconds = 1,
+                  inputs = { 1, 1 }, outputs = { 2, 1 } },
+                { id = 2, name = "Fast Ingot", type = "SMELT", seconds = 2, explicit = true,
+                  inputs = { 1, 2 }, outputs = { 2, 2 } },
+                { id = 3, name = "Gear", type = "ASSEMBLE", seconds = 6,
+                  inputs = { 2, 2 }, outputs = { 3, 1 } },
+              },
+            }
+        "#;
+        let data = Data::from_lua_source("stats", source, None, &[]).unwrap();
+
+        let summary = Summary::new(&data, 1);
+        assert_eq!(summary.items, 3);
+        assert_eq!(summary.recipes, 3);
+        assert_eq!(summary.explicit_recipes, 1);
+        assert_eq!(summary.item_types["RESOURCE"], 1);
+        assert_eq!(summary.recipe_types["SMELT"], 2);
+        assert_eq!(summary.unknown_item_types, 1);
+        assert_eq!(summary.unknown_recipe_types, 0);
+        assert_eq!(summary.items_with_stack_size, 1);
+
+        let seconds = summary.seconds.as_ref().unwrap();
+        assert_eq!((seconds.min, seconds.avg, seconds.max), (1.0, 3.0, 6.0));
+
+        assert_eq!(summary.most_produced.len(), 1);
+        assert_eq!(summary.most_produced[0].name, "Ingot");
+        assert_eq!(summary.most_produced[0].recipes, 2);
+        assert_eq!(summary.most_consumed[0].name, "Ore");
+    }
+
+    #[test]
+    fn summary_of_empty_data_has_no_seconds() {
+        let source = "return { game_items = {}, game_recipes = {} }";
+        let data = Data::from_lua_source("empty", source, None, &[]).unwrap();
+
+        let summary = Summary::new(&data, 5);
+        assert!(summary.seconds.is_none());
+        assert!(summary.most_produced.is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    data::{Data, RecipeId},
+    error::Error,
+    format::Format,
+    table::{Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Techs {
+    /// List all techs, including the ones that do not unlock any recipe.
+    #[structopt(short = "a", long = "all")]
+    pub all: bool,
+
+    /// Output format (text or json).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Report {
+    techs: Vec<TechRow>,
+    starting_recipes: Vec<RecipeRow>,
+}
+
+#[derive(Debug, Serialize)]
+struct TechRow {
+    id: usize,
+    name: String,
+    pre_techs: Vec<usize>,
+    recipes: Vec<RecipeRow>,
+}
+
+#[derive(Debug, Serialize)]
+struct RecipeRow {
+    id: RecipeId,
+    name: String,
+}
+
+impl Techs {
+    pub fn exec(self, data: &Data, style: &Style) -> Result<(), Error> {
+        let Self { all, format } = self;
+
+        let recipe_row = |rid: &RecipeId| RecipeRow {
+            id: *rid,
+            name: data
+                .recipes
+                .get(rid)
+                .map(|r| r.name.clone())
+                .unwrap_or_else(|| rid.label(&data.keys).to_string()),
+        };
+
+        let mut techs = data.techs.iter().collect::<Vec<_>>();
+        techs.sort_by_key(|(tid, _)| **tid);
+
+        let techs = techs
+            .into_iter()
+            .filter(|(_, t)| all || !t.recipes.is_empty())
+            .map(|(tid, tech)| TechRow {
+                id: tid.0,
+                name: tech.name.clone(),
+                pre_techs: tech.pre_techs.iter().map(|tid| tid.0).collect(),
+                recipes: tech.recipes.iter().map(recipe_row).collect(),
+            })
+            .collect::<Vec<_>>();
+        log::info!("  found {} techs", techs.len());
+
+        let starting_recipes = data
+            .unlocked_recipes(&BTreeSet::new())
+            .iter()
+            .map(recipe_row)
+            .collect::<Vec<_>>();
+        log::info!(
+            "  found {} recipes that are not unlocked by any tech",
+            starting_recipes.len()
+        );
+
+        let report = Report {
+            techs,
+            starting_recipes,
+        };
+
+        match format {
+            Format::Text => {
+                let mut table = Table::new([
+                    Column::right("Id"),
+                    Column::left("Tech"),
+                    Column::right("Recipe"),
+                    Column::left("Name"),
+                ]);
+                for tech in &report.techs {
+                    table.row([tech.id.to_string(), tech.name.clone()]);
+
+                    for recipe in &tech.recipes {
+                        table.row([
+                            String::new(),
+                            String::new(),
+                            recipe.id.to_string(),
+                            recipe.name.clone(),
+                        ]);
+                    }
+                }
+
+                table.print(style);
+
+                println!();
+                println!("Available from the start:");
+
+                let mut table =
+                    Table::new([Column::right("Recipe"), Column::left("Name")]).indent(4);
+                for recipe in &report.starting_recipes {
+                    table.row([recipe.id.to_string(), recipe.name.clone()]);
+                }
+
+                table.print(style);
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            format => return Err(format.unsupported()),
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    analysis::tiers,
+    data::{Data, ItemId},
+    error::Error,
+    format::Format,
+    selector::{parse_ids, parse_ignore, Selection, SelectorContext},
+    table::{Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Tiers {
+    /// Items to print the tiers for (all items are printed if empty).
+    #[structopt(short = "i", long = "items")]
+    pub items: Vec<String>,
+
+    /// Recipes to exclude from the calculation.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Output format (text or json).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    id: ItemId,
+    name: String,
+    min: usize,
+    max: usize,
+}
+
+impl Tiers {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext, style: &Style) -> Result<(), Error> {
+        let Self {
+            items,
+            ignore,
+            format,
+        } = self;
+
+        log::info!("Parse items");
+        let items = parse_ids(data, ctx, &items, true)?.items;
+        log::info!("  loaded {} items", items.len());
+
+        log::info!("Parse ignored recipes");
+        let ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        log::info!("Calculate tiers");
+        let rows = rows(data, &items, &ignore);
+
+        match format {
+            Format::Text => {
+                let mut table = Table::new([
+                    Column::left("Item"),
+                    Column::right("Min"),
+                    Column::right("Max"),
+                ]);
+                for row in &rows {
+                    table.row([row.name.clone(), row.min.to_string(), row.max.to_string()]);
+                }
+
+                table.print(style);
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+            format => return Err(format.unsupported()),
+        }
+
+        Ok(())
+    }
+}
+
+/// Tiers of the passed items (or all items if empty), sorted by tier. Items
+/// that are not produced by any of the not ignored recipes have tier 0.
+fn rows(data: &Data, items: &BTreeSet<ItemId>, ignore: &Selection) -> Vec<Row> {
+    let recipes = data
+        .recipes
+        .keys()
+        .filter(|rid| !ignore.recipes.contains(rid))
+        .copied()
+        .collect::<BTreeSet<_>>();
+    let tiers = tiers(data, &recipes);
+
+    let mut rows = data
+        .items
+        .iter()
+        .filter(|(iid, _)| items.is_empty() || items.contains(iid))
+        .map(|(iid, item)| {
+            let tier = tiers.get(iid);
+
+            Row {
+                id: *iid,
+                name: item.name.clone(),
+                min: tier.map(|t| t.min).unwrap_or_default(),
+                max: tier.map(|t| t.max).unwrap_or_default(),
+            }
+        })
+        .collect::<Vec<_>>();
+    rows.sort_by(|a, b| (a.min, a.max, &a.name).cmp(&(b.min, b.max, &b.name)));
+
+    rows
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::data::RecipeId;
+
+    /// Gears are assembled from ingots, or cast from ore directly. Sand is
+    /// not used by any recipe.
+    const SOURCE: &str = r#"
+        return {
+          game_items = {
+            { id = 1, name = "Ore", type = "RESOURCE" },
+            { id = 2, name = "Ingot", type = "MATERIAL" },
+            { id = 3, name = "Gear", type = "COMPONENT" },
+            { id = 4, name = "Sand", type = "RESOURCE" },
+          },
+          game_recipes = {
+            { id = 1, name = "Ingot", type = "SMELT", seconds = 1, inputs = { 1, 1 }, outputs = { 2, 1 } },
+            { id = 2, name = "Gear", type = "ASSEMBLE", seconds = 1, inputs = { 2, 2 }, outputs = { 3, 1 } },
+            { id = 3, name = "Cast Gear", type = "SMELT", seconds = 2, inputs = { 1, 3 }, outputs = { 3, 1 }, explicit = true },
+          },
+        }
+    "#;
+
+    fn tier_rows(items: &[usize], ignore: Selection) -> Vec<(String, usize, usize)> {
+        let data = Data::from_lua_source("tiers", SOURCE, None, &[]).unwrap();
+        let items = items.iter().map(|iid| ItemId(*iid)).collect();
+
+        rows(&data, &items, &ignore)
+            .into_iter()
+            .map(|row| (row.name, row.min, row.max))
+            .collect()
+    }
+
+    #[test]
+    fn chain_has_increasing_tiers() {
+        let mut ignore = Selection::default();
+        ignore.recipes.insert(RecipeId(3));
+
+        assert_eq!(
+            tier_rows(&[], ignore),
+            vec![
+                ("Ore".to_owned(), 0, 0),
+                ("Sand".to_owned(), 0, 0),
+                ("Ingot".to_owned(), 1, 1),
+                ("Gear".to_owned(), 2, 2),
+            ]
+        );
+    }
+
+    #[test]
+    fn alternative_recipes_widen_the_tier() {
+        assert_eq!(
+            tier_rows(&[1, 3], Selection::default()),
+            vec![("Ore".to_owned(), 0, 0), ("Gear".to_owned(), 1, 2)]
+        );
+    }
+}
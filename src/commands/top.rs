@@ -0,0 +1,328 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use itertools::Itertools;
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    data::{Data, ItemId, RecipeId},
+    error::Error,
+    format::{csv_line, Format},
+    planner::{PlanOptions, ProductionPlan},
+    selector::{parse_ignore, parse_targets, SelectorContext},
+    table::{Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct Top {
+    /// Property to rank the items by (consumers, producers, degree or flow).
+    #[structopt(long = "by", default_value = "degree")]
+    pub by: TopBy,
+
+    /// Number of items to list.
+    #[structopt(short = "n", long = "limit", default_value = "10")]
+    pub limit: usize,
+
+    /// Items to produce, given as `<item>=<amount per minute>`. The flow of
+    /// the items through the plan of these targets is listed as well.
+    #[structopt(short = "t", long = "target")]
+    pub targets: Vec<String>,
+
+    /// Recipes to exclude from the ranking.
+    #[structopt(long = "ignore")]
+    pub ignore: Vec<String>,
+
+    /// Output format (text, json or csv).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+/* TopBy */
+
+/// Property the items are ranked by, largest first.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum TopBy {
+    /// Number of recipes that consume the item.
+    Consumers,
+
+    /// Number of recipes that produce the item.
+    Producers,
+
+    /// Number of recipes that consume or produce the item.
+    Degree,
+
+    /// Items per minute that are consumed in the plan of the targets.
+    Flow,
+}
+
+impl FromStr for TopBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "consumers" => Ok(Self::Consumers),
+            "producers" => Ok(Self::Producers),
+            "degree" => Ok(Self::Degree),
+            "flow" => Ok(Self::Flow),
+            s => Err(format!(
+                "Unknown ranking: {} (expected consumers, producers, degree or flow)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for TopBy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Consumers => write!(f, "consumers"),
+            Self::Producers => write!(f, "producers"),
+            Self::Degree => write!(f, "degree"),
+            Self::Flow => write!(f, "flow"),
+        }
+    }
+}
+
+/* Row */
+
+#[derive(Debug, Serialize)]
+struct Row {
+    rank: usize,
+    id: ItemId,
+    name: String,
+    consumers: usize,
+    producers: usize,
+    degree: usize,
+
+    /// Items per minute that are consumed in the plan of the targets,
+    /// including the targets themselves. `None` if no target is passed.
+    flow: Option<f64>,
+
+    /// Names of the recipes the item is ranked for.
+    recipes: Vec<String>,
+}
+
+/// Number of leading rows that are printed with their recipes.
+const LEADERS: usize = 3;
+
+impl Top {
+    pub fn exec(self, data: &Data, ctx: &SelectorContext, style: &Style) -> Result<(), Error> {
+        let Self {
+            by,
+            limit,
+            targets,
+            ignore,
+            format,
+        } = self;
+
+        if by == TopBy::Flow && targets.is_empty() {
+            return Err(Error::usage("Ranking by flow needs at least one --target"));
+        }
+
+        log::info!("Parse targets");
+        let targets = parse_targets(data, ctx, &targets)?;
+        log::info!("  loaded {} targets", targets.len());
+
+        log::info!("Parse ignored recipes");
+        let ignore = parse_ignore(data, ctx, &ignore, false)?;
+        log::info!(
+            "  loaded {} ignored items and {} ignored recipes",
+            ignore.items.len(),
+            ignore.recipes.len()
+        );
+
+        let plan = if targets.is_empty() {
+            None
+        } else {
+            log::info!("Calculate plan");
+            let options = PlanOptions {
+                ignore: ignore.clone(),
+                ..Default::default()
+            };
+            let plan = ProductionPlan::new(data, &targets, options)?;
+            log::info!("  use {} recipes", plan.recipes.len());
+
+            Some(plan)
+        };
+
+        let flows = plan.as_ref().map(|plan| flows(data, &targets, plan));
+
+        log::info!("Rank items by {}", by);
+        let recipes = |index: &HashMap<ItemId, Vec<RecipeId>>, iid: &ItemId| {
+            index
+                .get(iid)
+                .into_iter()
+                .flatten()
+                .filter(|rid| !ignore.recipes.contains(rid) && data.recipes.contains_key(rid))
+                .copied()
+                .collect::<BTreeSet<_>>()
+        };
+
+        let mut rows = data
+            .items
+            .keys()
+            .filter(|iid| !ignore.items.contains(iid))
+            .map(|iid| {
+                let consumers = recipes(&data.as_input, iid);
+                let producers = recipes(&data.as_output, iid);
+                let flow = flows
+                    .as_ref()
+                    .map(|f| f.get(iid).copied().unwrap_or_default());
+
+                let ranked = match by {
+                    TopBy::Consumers => consumers.clone(),
+                    TopBy::Producers => producers.clone(),
+                    TopBy::Degree => consumers.union(&producers).copied().collect(),
+                    TopBy::Flow => plan_consumers(data, plan.as_ref(), iid),
+                };
+
+                Row {
+                    rank: 0,
+                    id: *iid,
+                    name: data.items[iid].name.clone(),
+                    consumers: consumers.len(),
+                    producers: producers.len(),
+                    degree: consumers.len() + producers.len(),
+                    flow,
+                    recipes: ranked
+                        .iter()
+                        .map(|rid| data.recipes[rid].name.clone())
+                        .sorted()
+                        .collect(),
+                }
+            })
+            .filter(|row| key(by, row) > 0.0)
+            .sorted_by(|a, b| {
+                key(by, b)
+                    .total_cmp(&key(by, a))
+                    .then_with(|| a.name.cmp(&b.name))
+                    .then(a.id.cmp(&b.id))
+            })
+            .take(limit)
+            .collect::<Vec<_>>();
+        for (i, row) in rows.iter_mut().enumerate() {
+            row.rank = i + 1;
+        }
+        log::info!("  ranked {} items", rows.len());
+
+        let flow = |flow: Option<f64>| flow.map(|f| format!("{:.2}", f)).unwrap_or_default();
+
+        match format {
+            Format::Text => {
+                let mut columns = vec![
+                    Column::right("#"),
+                    Column::left("Item"),
+                    Column::right("Consumers"),
+                    Column::right("Producers"),
+                    Column::right("Degree"),
+                ];
+                if flows.is_some() {
+                    columns.push(Column::right("Flow/min"));
+                }
+
+                let mut table = Table::new(columns);
+                for row in &rows {
+                    let mut cells = vec![
+                        row.rank.to_string(),
+                        row.name.clone(),
+                        row.consumers.to_string(),
+                        row.producers.to_string(),
+                        row.degree.to_string(),
+                    ];
+                    if flows.is_some() {
+                        cells.push(flow(row.flow));
+                    }
+
+                    table.row(cells);
+                }
+
+                table.print(style);
+
+                for row in rows.iter().take(LEADERS) {
+                    println!();
+                    println!("{} ({} recipes):", row.name, row.recipes.len());
+                    for recipe in &row.recipes {
+                        println!("    {}", recipe);
+                    }
+                }
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&rows)?),
+            Format::Csv => {
+                println!(
+                    "{}",
+                    csv_line([
+                        "rank",
+                        "id",
+                        "name",
+                        "consumers",
+                        "producers",
+                        "degree",
+                        "flow",
+                        "recipes",
+                    ])
+                );
+
+                for row in &rows {
+                    println!(
+                        "{}",
+                        csv_line([
+                            row.rank.to_string(),
+                            row.id.to_string(),
+                            row.name.clone(),
+                            row.consumers.to_string(),
+                            row.producers.to_string(),
+                            row.degree.to_string(),
+                            row.flow.map(|f| format!("{:.4}", f)).unwrap_or_default(),
+                            row.recipes.join("; "),
+                        ])
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Value of the row the items are ranked by.
+fn key(by: TopBy, row: &Row) -> f64 {
+    match by {
+        TopBy::Consumers => row.consumers as f64,
+        TopBy::Producers => row.producers as f64,
+        TopBy::Degree => row.degree as f64,
+        TopBy::Flow => row.flow.unwrap_or_default(),
+    }
+}
+
+/// Items per minute that are consumed by the recipes of the plan, or that
+/// are requested as targets.
+fn flows(
+    data: &Data,
+    targets: &BTreeMap<ItemId, f64>,
+    plan: &ProductionPlan,
+) -> BTreeMap<ItemId, f64> {
+    let mut ret = targets.clone();
+
+    for (rid, crafts) in &plan.recipes {
+        for input in data.recipes.get(rid).into_iter().flat_map(|r| &r.inputs) {
+            *ret.entry(input.id).or_default() += crafts * input.amount as f64;
+        }
+    }
+
+    ret
+}
+
+/// Recipes of the plan that consume the passed item.
+fn plan_consumers(data: &Data, plan: Option<&ProductionPlan>, iid: &ItemId) -> BTreeSet<RecipeId> {
+    plan.into_iter()
+        .flat_map(|plan| plan.recipes.keys())
+        .filter(|rid| {
+            data.recipes
+                .get(rid)
+                .is_some_and(|r| r.inputs.iter().any(|i| i.id == *iid))
+        })
+        .copied()
+        .collect()
+}
@@ -0,0 +1,113 @@
+use serde::Serialize;
+use structopt::StructOpt;
+
+use crate::{
+    data::{Data, IdLabel, ItemType},
+    error::Error,
+    format::Format,
+    table::{Cell, Color, Column, Style, Table},
+};
+
+#[derive(Debug, StructOpt)]
+pub struct UnusedItems {
+    /// Output format (text or json).
+    #[structopt(short = "f", long = "format", default_value = "text")]
+    pub format: Format,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct Report {
+    never_consumed: Vec<Row>,
+    never_produced: Vec<Row>,
+    unused: Vec<Row>,
+}
+
+#[derive(Debug, Serialize)]
+struct Row {
+    id: IdLabel,
+    name: String,
+    #[serde(rename = "type")]
+    type_: String,
+}
+
+impl UnusedItems {
+    pub fn exec(self, data: &Data, style: &Style) -> Result<(), Error> {
+        let Self { format } = self;
+
+        let mut items = data.items.iter().collect::<Vec<_>>();
+        items.sort_by_key(|(iid, _)| **iid);
+
+        let mut report = Report::default();
+        for (iid, item) in items {
+            let consumed = data
+                .as_input
+                .get(iid)
+                .map(|r| !r.is_empty())
+                .unwrap_or(false);
+            let produced = data
+                .as_output
+                .get(iid)
+                .map(|r| !r.is_empty())
+                .unwrap_or(false);
+
+            let row = Row {
+                id: iid.label(&data.keys),
+                name: item.name.clone(),
+                type_: item.type_.to_string(),
+            };
+
+            match (consumed, produced) {
+                (false, false) => report.unused.push(row),
+                (false, true) => report.never_consumed.push(row),
+                (true, false) => report.never_produced.push(row),
+                (true, true) => (),
+            }
+        }
+
+        log::info!(
+            "  found {} items that are never consumed",
+            report.never_consumed.len()
+        );
+        log::info!(
+            "  found {} items that are never produced",
+            report.never_produced.len()
+        );
+        log::info!("  found {} unused items", report.unused.len());
+
+        match format {
+            Format::Text => {
+                print_rows("Never consumed", &report.never_consumed, style);
+                println!();
+                print_rows("Never produced", &report.never_produced, style);
+                println!();
+                print_rows("Unused", &report.unused, style);
+            }
+            Format::Json => println!("{}", serde_json::to_string_pretty(&report)?),
+            format => return Err(format.unsupported()),
+        }
+
+        Ok(())
+    }
+}
+
+fn print_rows(title: &str, rows: &[Row], style: &Style) {
+    println!("{}:", title);
+
+    let mut table = Table::new([
+        Column::right("Id"),
+        Column::left("Name"),
+        Column::left("Type"),
+    ])
+    .indent(4);
+    for row in rows {
+        let color = Color::item_type(&ItemType::from(row.type_.as_str()));
+
+        table.row([
+            Cell::new(&row.id),
+            Cell::new(&row.name),
+            Cell::colored(&row.type_, color),
+        ]);
+    }
+
+    table.print(style);
+}
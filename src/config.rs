@@ -0,0 +1,367 @@
+use std::collections::BTreeMap;
+use std::env::current_dir;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+use structopt::clap::ArgMatches;
+
+use crate::{
+    args::{Args, DataArgs},
+    commands::{Command, CreateProductionGraph, Serve},
+    error::Error,
+    format::Format,
+};
+
+/// Name of the config file that is searched in the working directory and the
+/// platform config directory.
+pub const CONFIG_FILE_NAME: &str = "dsp-tool.toml";
+
+/// Defaults for the command line arguments, loaded from `dsp-tool.toml`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Data files that are used if `--data` is not passed.
+    pub data: Vec<PathBuf>,
+
+    /// Selectors that are ignored if `--ignore` is not passed.
+    pub ignore: Vec<String>,
+
+    /// Output format of the commands that support `--format`.
+    pub format: Option<Format>,
+
+    /// Cache the evaluated data files, like `--cache`.
+    pub cache: bool,
+
+    /// Styling of the production graph.
+    pub graph: GraphConfig,
+
+    /// Named lists of selectors that can be referenced as `@name`.
+    pub groups: Groups,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+/// Named selector groups, mapping the names to lists of selectors.
+pub type Groups = BTreeMap<String, Vec<String>>;
+
+/// Defaults for the styling options of `create-production-graph` and
+/// `serve`.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct GraphConfig {
+    pub rank_by_tier: Option<bool>,
+    pub icons: Option<bool>,
+    pub icons_dir: Option<PathBuf>,
+    pub no_source_style: Option<bool>,
+
+    /// Name of a built-in theme or path of a theme file.
+    pub theme: Option<String>,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl Config {
+    /// Load the config file that is selected by the arguments. Without
+    /// `--config` the file is searched in the working directory and then in
+    /// the platform config directory, and it is fine if there is none. The
+    /// groups of `--groups` are loaded by the selectors, see
+    /// [`SelectorContext::groups_file`](crate::selector::SelectorContext::groups_file).
+    pub fn load(args: &Args) -> Result<Self, Error> {
+        match Self::path(args) {
+            Some(path) => Self::load_file(&path),
+            None => Ok(Self::default()),
+        }
+    }
+
+    fn path(args: &Args) -> Option<PathBuf> {
+        if args.no_config {
+            return None;
+        }
+
+        args.config.clone().or_else(Self::find)
+    }
+
+    fn load_file(path: &Path) -> Result<Self, Error> {
+        log::info!("Load config from {:#?}", path);
+        let mut config = parse_file::<Self>(path)?;
+
+        for key in config.unknown_keys() {
+            log::warn!("Unknown key in config file {:#?}: {}", path, key);
+        }
+
+        // Relative data paths are relative to the config file.
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        for data in &mut config.data {
+            *data = expand_path(dir, data);
+        }
+
+        Ok(config)
+    }
+
+    /// Keys of the file that are not part of the config, with the section
+    /// they are in.
+    fn unknown_keys(&self) -> Vec<String> {
+        let graph = self
+            .graph
+            .unknown
+            .keys()
+            .map(|key| format!("graph.{}", key));
+
+        self.unknown.keys().cloned().chain(graph).collect()
+    }
+
+    fn find() -> Option<PathBuf> {
+        let local = current_dir().ok()?.join(CONFIG_FILE_NAME);
+        if local.is_file() {
+            return Some(local);
+        }
+
+        let global = dirs::config_dir()?.join("dsp-tool").join(CONFIG_FILE_NAME);
+        global.is_file().then_some(global)
+    }
+
+    /// Use the values of the config for all arguments that were not passed
+    /// on the command line.
+    pub fn apply(&self, args: &mut Args, matches: &ArgMatches<'_>) {
+        if !self.data.is_empty() && !is_set(matches, "data-paths") {
+            args.data.data_paths = self.data.clone();
+        }
+        if self.cache {
+            args.data.cache = true;
+        }
+
+        let matches = match matches.subcommand() {
+            (_, Some(matches)) => matches,
+            (_, None) => return,
+        };
+
+        match &mut args.command {
+            Command::CreateProductionGraph(CreateProductionGraph { options, .. })
+            | Command::Serve(Serve { options, .. }) => {
+                self.apply_ignore(&mut options.ignore, matches);
+                self.graph.apply(
+                    &mut options.rank_by_tier,
+                    &mut options.icons,
+                    &mut options.icons_dir,
+                    &mut options.no_source_style,
+                    &mut options.theme,
+                    matches,
+                );
+            }
+            Command::Cycles(cmd) => {
+                self.apply_ignore(&mut cmd.ignore, matches);
+                self.apply_format(&mut cmd.format, matches);
+            }
+            Command::Tiers(cmd) => {
+                self.apply_ignore(&mut cmd.ignore, matches);
+                self.apply_format(&mut cmd.format, matches);
+            }
+            Command::Reachable(cmd) => {
+                self.apply_ignore(&mut cmd.ignore, matches);
+                self.apply_format(&mut cmd.format, matches);
+            }
+            Command::Alternatives(cmd) => {
+                self.apply_ignore(&mut cmd.ignore, matches);
+                self.apply_format(&mut cmd.format, matches);
+            }
+            Command::Paths(cmd) => {
+                self.apply_ignore(&mut cmd.ignore, matches);
+                self.apply_format(&mut cmd.format, matches);
+            }
+            Command::Top(cmd) => {
+                self.apply_ignore(&mut cmd.ignore, matches);
+                self.apply_format(&mut cmd.format, matches);
+            }
+            Command::BuildOrder(cmd) => {
+                self.apply_ignore(&mut cmd.ignore, matches);
+                self.apply_format(&mut cmd.format, matches);
+            }
+            Command::Belts(cmd) => {
+                self.apply_ignore(&mut cmd.ignore, matches);
+                self.apply_format(&mut cmd.format, matches);
+            }
+            Command::Plan(cmd) => self.apply_ignore(&mut cmd.ignore, matches),
+            Command::MatrixCost(cmd) => self.apply_ignore(&mut cmd.ignore, matches),
+            Command::Ratio(cmd) => self.apply_ignore(&mut cmd.ignore, matches),
+            Command::Bottleneck(cmd) => self.apply_ignore(&mut cmd.ignore, matches),
+            Command::Report(cmd) => self.apply_ignore(&mut cmd.ignore, matches),
+            Command::Efficiency(cmd) => self.apply_format(&mut cmd.format, matches),
+            Command::Stats(cmd) => self.apply_format(&mut cmd.format, matches),
+            Command::Rates(cmd) => self.apply_format(&mut cmd.format, matches),
+            Command::Techs(cmd) => self.apply_format(&mut cmd.format, matches),
+            Command::UnusedItems(cmd) => self.apply_format(&mut cmd.format, matches),
+            Command::Byproducts(_) | Command::Completions(_) | Command::ExportMarkdown(_) => (),
+        }
+    }
+
+    fn apply_ignore(&self, ignore: &mut Vec<String>, matches: &ArgMatches<'_>) {
+        if !self.ignore.is_empty() && !is_set(matches, "ignore") {
+            ignore.clone_from(&self.ignore);
+        }
+    }
+
+    fn apply_format(&self, format: &mut Format, matches: &ArgMatches<'_>) {
+        apply_value(format, &self.format, "format", matches);
+    }
+}
+
+impl GraphConfig {
+    fn apply(
+        &self,
+        rank_by_tier: &mut bool,
+        icons: &mut bool,
+        icons_dir: &mut PathBuf,
+        no_source_style: &mut bool,
+        theme: &mut Option<String>,
+        matches: &ArgMatches<'_>,
+    ) {
+        apply_value(rank_by_tier, &self.rank_by_tier, "rank-by-tier", matches);
+        apply_value(icons, &self.icons, "icons", matches);
+        apply_value(icons_dir, &self.icons_dir, "icons-dir", matches);
+        apply_value(
+            no_source_style,
+            &self.no_source_style,
+            "no-source-style",
+            matches,
+        );
+        if self.theme.is_some() && !is_set(matches, "theme") {
+            theme.clone_from(&self.theme);
+        }
+    }
+}
+
+/// Use the config value if there is one and the argument was not passed.
+fn apply_value<T: Clone>(arg: &mut T, value: &Option<T>, name: &str, matches: &ArgMatches<'_>) {
+    if let Some(value) = value.as_ref().filter(|_| !is_set(matches, name)) {
+        arg.clone_from(value);
+    }
+}
+
+pub(crate) fn parse_file<T>(path: &Path) -> Result<T, Error>
+where
+    T: for<'de> Deserialize<'de>,
+{
+    let content = read_to_string(path)
+        .map_err(|err| Error::custom(format!("Unable to read {:#?}: {}", path, err)))?;
+
+    toml::from_str(&content)
+        .map_err(|err| Error::usage(format!("Invalid file {:#?}: {}", path, err)))
+}
+
+fn is_set(matches: &ArgMatches<'_>, name: &str) -> bool {
+    matches.occurrences_of(name) > 0
+}
+
+/// Expand a leading `~` to the home directory and make relative paths
+/// relative to `dir`.
+fn expand_path(dir: &Path, path: &Path) -> PathBuf {
+    if let (Ok(rest), Some(home)) = (path.strip_prefix("~"), dirs::home_dir()) {
+        return home.join(rest);
+    }
+
+    if path.is_relative() && !DataArgs::is_url(path) {
+        return dir.join(path);
+    }
+
+    path.to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use structopt::StructOpt;
+
+    fn fixture() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/config.toml")
+    }
+
+    fn parse_args(argv: &[&str]) -> (Args, ArgMatches<'static>) {
+        let matches = Args::clap().get_matches_from_safe(argv).unwrap();
+
+        (Args::from_clap(&matches), matches)
+    }
+
+    #[test]
+    fn config_file_is_parsed() {
+        let path = fixture();
+        let config = Config::load_file(&path).unwrap();
+
+        assert_eq!(config.data, vec![path.parent().unwrap().join("small.lua")]);
+        assert_eq!(config.ignore, vec!["Ingot".to_owned()]);
+        assert_eq!(config.format, Some(Format::Json));
+        assert!(config.cache);
+        assert_eq!(config.graph.rank_by_tier, Some(true));
+        assert_eq!(config.graph.icons, None);
+        assert_eq!(config.graph.icons_dir, Some(PathBuf::from("/tmp/icons")));
+        assert_eq!(config.graph.theme.as_deref(), Some("dark"));
+        assert_eq!(
+            config.groups["metals"],
+            vec!["Ore".to_owned(), "Ingot".to_owned()]
+        );
+    }
+
+    #[test]
+    fn unknown_keys_do_not_fail() {
+        let config = Config::load_file(&fixture()).unwrap();
+
+        assert_eq!(config.unknown_keys(), vec!["colour", "graph.arrows"]);
+    }
+
+    #[test]
+    fn explicit_arguments_win() {
+        let config = Config::load_file(&fixture()).unwrap();
+
+        let (mut args, matches) = parse_args(&["dsp-tool", "tiers"]);
+        config.apply(&mut args, &matches);
+        assert_eq!(args.data.data_paths, config.data);
+        assert!(args.data.cache);
+        match &args.command {
+            Command::Tiers(cmd) => {
+                assert_eq!(cmd.ignore, config.ignore);
+                assert_eq!(cmd.format, Format::Json);
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+
+        let (mut args, matches) = parse_args(&[
+            "dsp-tool",
+            "-d",
+            "other.lua",
+            "tiers",
+            "--ignore",
+            "Gear",
+            "--format",
+            "csv",
+        ]);
+        config.apply(&mut args, &matches);
+        assert_eq!(args.data.data_paths, vec![PathBuf::from("other.lua")]);
+        match &args.command {
+            Command::Tiers(cmd) => {
+                assert_eq!(cmd.ignore, vec!["Gear".to_owned()]);
+                assert_eq!(cmd.format, Format::Csv);
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+
+    #[test]
+    fn graph_options_use_the_config() {
+        let config = Config::load_file(&fixture()).unwrap();
+
+        let (mut args, matches) =
+            parse_args(&["dsp-tool", "create-production-graph", "--theme", "light"]);
+        config.apply(&mut args, &matches);
+        match &args.command {
+            Command::CreateProductionGraph(cmd) => {
+                assert!(cmd.options.rank_by_tier);
+                assert_eq!(cmd.options.icons_dir, PathBuf::from("/tmp/icons"));
+                assert_eq!(cmd.options.theme.as_deref(), Some("light"));
+            }
+            command => panic!("unexpected command: {:?}", command),
+        }
+    }
+}
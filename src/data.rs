@@ -1,288 +1,2433 @@
-use std::{collections::HashMap, str::FromStr};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::fs::read;
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Instant;
 
+use flate2::read::MultiGzDecoder;
 use itertools::Itertools;
-use rlua::{Context, Error as LuaError, FromLua, Table, Value};
+use rlua::{Context, Error as LuaError, FromLua, Lua, Scope, Table, ToLua, Value};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::Error;
 
 /* Data */
 
-#[derive(Debug)]
-pub struct Data {
-    pub items: HashMap<ItemId, Item>,
-    pub recipes: HashMap<RecipeId, Recipe>,
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "DataTables")]
+pub struct Data {
+    pub game_version: Option<String>,
+
+    /// Schema version that is declared by the data file (see [`Schema`]).
+    pub schema_version: Option<u32>,
+
+    pub items: HashMap<ItemId, Item>,
+    pub recipes: HashMap<RecipeId, Recipe>,
+    pub buildings: HashMap<ItemId, Building>,
+    pub techs: HashMap<TechId, Tech>,
+    pub locales: HashMap<String, Locale>,
+    pub logistics: Logistics,
+
+    /// String keys of the keyed item and recipe ids.
+    pub keys: Keys,
+
+    #[serde(skip)]
+    pub as_input: HashMap<ItemId, Vec<RecipeId>>,
+    #[serde(skip)]
+    pub as_output: HashMap<ItemId, Vec<RecipeId>>,
+
+    #[serde(skip)]
+    pub item_by_name: HashMap<String, Vec<ItemId>>,
+    #[serde(skip)]
+    pub recipes_by_name: HashMap<String, Vec<RecipeId>>,
+
+    /// Name indices keyed by the normalized names (see [`normalize_name`]).
+    #[serde(skip)]
+    pub item_by_normalized_name: HashMap<String, Vec<ItemId>>,
+    #[serde(skip)]
+    pub recipes_by_normalized_name: HashMap<String, Vec<RecipeId>>,
+
+    /// Buildings that are able to craft each recipe type, sorted by speed.
+    #[serde(skip)]
+    pub buildings_by_type: HashMap<RecipeType, Vec<ItemId>>,
+
+    /// Files the data was loaded from, in the order they were merged.
+    #[serde(skip)]
+    pub sources: Vec<DataSource>,
+}
+
+/// File the data was loaded from.
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+pub struct DataSource {
+    pub path: String,
+
+    /// FNV-1a hash of the decoded source, as 16 hex digits.
+    pub hash: String,
+}
+
+impl DataSource {
+    pub fn new(path: impl Display, source: &str) -> Self {
+        Self {
+            path: path.to_string(),
+            hash: format!("{:016x}", fnv(source.as_bytes())),
+        }
+    }
+}
+
+/* Fnv */
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// FNV-1a hasher. Unlike the `DefaultHasher` of the standard library its
+/// hashes do not change between runs or Rust versions, so they can be used
+/// for anything that is persisted, like cache keys and file names.
+///
+/// Only [`Hasher::write`] is stable, the `Hash` implementations of the
+/// standard types may change the bytes they write.
+#[derive(Debug, Clone, Copy)]
+pub struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Self(FNV_OFFSET)
+    }
+}
+
+impl Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 = (self.0 ^ *byte as u64).wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// FNV-1a hash of the passed bytes, see [`Fnv`].
+pub fn fnv(bytes: &[u8]) -> u64 {
+    let mut hasher = Fnv::default();
+    hasher.write(bytes);
+
+    hasher.finish()
+}
+
+/// Tables of the data that are serialized, the indices are rebuilt after
+/// deserialization.
+#[derive(Deserialize)]
+struct DataTables {
+    game_version: Option<String>,
+    schema_version: Option<u32>,
+    items: HashMap<ItemId, Item>,
+    recipes: HashMap<RecipeId, Recipe>,
+    buildings: HashMap<ItemId, Building>,
+    techs: HashMap<TechId, Tech>,
+    locales: HashMap<String, Locale>,
+    logistics: Logistics,
+    keys: Keys,
+}
+
+impl From<DataTables> for Data {
+    fn from(tables: DataTables) -> Self {
+        let mut data = Self::new(tables.items, tables.recipes, tables.buildings);
+        data.game_version = tables.game_version;
+        data.schema_version = tables.schema_version;
+        data.techs = tables.techs;
+        data.locales = tables.locales;
+        data.logistics = tables.logistics;
+        data.keys = tables.keys;
+
+        data
+    }
+}
+
+impl<'lua> FromLua<'lua> for Data {
+    fn from_lua(value: Value<'lua>, lua: Context<'lua>) -> Result<Self, LuaError> {
+        match value {
+            Value::Table(table) => {
+                let items = entries(lua, field(&table, "game_items")?, "item")?;
+                let recipes = entries(lua, field(&table, "game_recipes")?, "recipe")?;
+                let buildings = match field(&table, "game_buildings")? {
+                    Some(buildings) => entries(lua, buildings, "building")?,
+                    None => HashMap::new(),
+                };
+                let techs = match field(&table, "game_techs")? {
+                    Some(techs) => entries(lua, techs, "tech")?,
+                    None => HashMap::new(),
+                };
+                let locales = field::<Option<HashMap<String, Locale>>>(&table, "game_locale")?
+                    .unwrap_or_default();
+                let logistics =
+                    field::<Option<Logistics>>(&table, "game_logistics")?.unwrap_or_default();
+
+                let mut data = Self::new(items, recipes, buildings);
+                data.game_version = field(&table, "game_version")?;
+                data.techs = techs;
+                data.locales = locales;
+                data.logistics = logistics;
+
+                Ok(data)
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Data",
+                message: Some("expected a table".into()),
+            }),
+        }
+    }
+}
+
+impl Data {
+    /// Create a new data set from the passed items, recipes and buildings.
+    pub fn new(
+        items: HashMap<ItemId, Item>,
+        recipes: HashMap<RecipeId, Recipe>,
+        buildings: HashMap<ItemId, Building>,
+    ) -> Self {
+        let mut data = Self {
+            game_version: None,
+            schema_version: None,
+            items,
+            recipes,
+            buildings,
+            techs: HashMap::new(),
+            locales: HashMap::new(),
+            logistics: Logistics::default(),
+            keys: Keys::default(),
+            as_input: HashMap::new(),
+            as_output: HashMap::new(),
+            item_by_name: HashMap::new(),
+            recipes_by_name: HashMap::new(),
+            item_by_normalized_name: HashMap::new(),
+            recipes_by_normalized_name: HashMap::new(),
+            buildings_by_type: HashMap::new(),
+            sources: Vec::new(),
+        };
+        data.update_index();
+
+        data
+    }
+
+    /// Load the data from the passed Lua file, which may be gzipped. Files
+    /// with multiple game versions load the newest one.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let path = path.as_ref();
+        let source = decode_source(path, read(path)?)?;
+
+        let mut data = Self::from_lua_source(&path.display().to_string(), &source, None, &[])?;
+        data.sources.push(DataSource::new(path.display(), &source));
+
+        Ok(data)
+    }
+
+    /// Evaluate the passed Lua source, which has to return the data table.
+    /// The name of the source is used in the Lua error messages.
+    ///
+    /// The source may also return a function that returns the data table.
+    /// The function is called with a table of the passed data arguments.
+    ///
+    /// Items and recipes can also be added by the `register_item` and
+    /// `register_recipe` functions, see [`Registry`]. The registered entries
+    /// are added to the returned table, which is optional then.
+    ///
+    /// The table may also contain the data of multiple game versions as
+    /// `game_data = { ["<version>"] = { ... }, ... }`. Then the passed game
+    /// version is loaded, or the newest one if no version is passed. Files
+    /// with a single version ignore the passed version.
+    pub fn from_lua_source(
+        name: &str,
+        source: &str,
+        game_version: Option<&str>,
+        data_args: &[DataArg],
+    ) -> Result<Self, Error> {
+        let start = Instant::now();
+
+        let lua = Lua::new();
+        let data = lua.context(move |lua| -> Result<Self, Error> {
+            let mut registry = Registry::default();
+
+            let table = lua.scope(|scope| -> Result<Option<Table>, Error> {
+                registry.install(lua, scope)?;
+
+                let value = lua
+                    .load(source)
+                    .set_name(&format!("@{}", name))?
+                    .eval::<Value>()?;
+                if !matches!(value, Value::Function(_)) && !data_args.is_empty() {
+                    log::warn!("Data does not return a function, ignore the --data-arg options");
+                }
+
+                let value = match value {
+                    Value::Function(function) => {
+                        let args = lua.create_table()?;
+                        for arg in data_args {
+                            args.set(arg.key.as_str(), arg.value.clone())?;
+                        }
+
+                        match function.call::<_, Value>(args)? {
+                            Value::Table(table) => Value::Table(table),
+                            Value::Nil => Value::Nil,
+                            value => {
+                                return Err(LuaError::FromLuaConversionError {
+                                    from: value.type_name(),
+                                    to: "Data",
+                                    message: Some("the data function has to return a table".into()),
+                                }
+                                .into())
+                            }
+                        }
+                    }
+                    value => value,
+                };
+
+                match value {
+                    Value::Table(table) => Ok(Some(table)),
+                    Value::Nil => Ok(None),
+                    value => Err(LuaError::FromLuaConversionError {
+                        from: value.type_name(),
+                        to: "Data",
+                        message: Some("the data has to return a table or a function".into()),
+                    }
+                    .into()),
+                }
+            })?;
+
+            let (mut data, schema) = match table {
+                Some(table) => {
+                    let schema = Schema::from_table(&table)?;
+                    let data = Self::from_table(lua, table, game_version, &schema)?;
+
+                    (data, schema)
+                }
+                None if registry.is_empty() => {
+                    return Err(LuaError::FromLuaConversionError {
+                        from: "nil",
+                        to: "Data",
+                        message: Some(
+                            "the data has to return a table or register items and recipes".into(),
+                        ),
+                    }
+                    .into())
+                }
+                None => (
+                    Self::new(HashMap::new(), HashMap::new(), HashMap::new()),
+                    Schema::default(),
+                ),
+            };
+            data.keys = Keys::from_lua_registry(lua)?;
+            registry.apply(&mut data)?;
+            schema.apply(&mut data);
+
+            Ok(data)
+        })?;
+        log::debug!("  evaluated the data in {:.2?}", start.elapsed());
+
+        Ok(data)
+    }
+
+    /// Convert the returned data table, which may contain multiple game
+    /// versions.
+    fn from_table<'lua>(
+        lua: Context<'lua>,
+        table: Table<'lua>,
+        game_version: Option<&str>,
+        schema: &Schema,
+    ) -> Result<Self, Error> {
+        let versions = match field::<Option<Table>>(&table, "game_data")? {
+            Some(versions) => versions,
+            None => return Ok(Self::from_lua(Value::Table(table), lua)?),
+        };
+
+        let mut versions = versions
+            .pairs::<String, Value>()
+            .collect::<Result<BTreeMap<_, _>, _>>()
+            .map_err(|err| with_context(err, "field 'game_data'"))?;
+        let available = versions
+            .keys()
+            .sorted_by(|a, b| compare_versions(a, b))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        let version = match game_version {
+            Some(version) if versions.contains_key(version) => version.to_owned(),
+            Some(version) => {
+                return Err(Error::usage(format!(
+                    "Game version {} not found in the data file (available: {})",
+                    version,
+                    available.join(", ")
+                )))
+            }
+            None => {
+                let version = available
+                    .last()
+                    .cloned()
+                    .ok_or_else(|| Error::data("Data file does not contain any game version"))?;
+                log::info!(
+                    "  data file contains {} game versions, use the newest {}",
+                    available.len(),
+                    version
+                );
+
+                version
+            }
+        };
+
+        let value = versions.remove(&version).unwrap();
+        if let Value::Table(table) = &value {
+            schema.check_keys(table, &format!("game version {}", version));
+        }
+        let mut data = Self::from_lua(value, lua)
+            .map_err(|err| with_context(err, &format!("game version {}", version)))?;
+        data.game_version.get_or_insert(version);
+
+        Ok(data)
+    }
+
+    /// Keep only the items and recipes that match the passed predicates.
+    ///
+    /// Recipes that use or produce a removed item are removed as well, unless
+    /// `keep_dangling` is set. Buildings of removed items are removed, and
+    /// removed recipes are dropped from the techs. All indices are rebuilt.
+    pub fn filter<I, R>(mut self, mut item_pred: I, mut recipe_pred: R, keep_dangling: bool) -> Self
+    where
+        I: FnMut(&ItemId, &Item) -> bool,
+        R: FnMut(&RecipeId, &Recipe) -> bool,
+    {
+        self.items.retain(|iid, item| item_pred(iid, item));
+
+        let items = &self.items;
+        self.recipes.retain(|rid, recipe| {
+            recipe_pred(rid, recipe)
+                && (keep_dangling
+                    || recipe
+                        .inputs
+                        .iter()
+                        .chain(&recipe.outputs)
+                        .all(|a| items.contains_key(&a.id)))
+        });
+
+        self.buildings.retain(|bid, _| items.contains_key(bid));
+
+        let recipes = &self.recipes;
+        for tech in self.techs.values_mut() {
+            tech.recipes.retain(|rid| recipes.contains_key(rid));
+        }
+
+        self.update_index();
+
+        self
+    }
+
+    /// Merge the passed data into this data set.
+    ///
+    /// Items, recipes and buildings of `other` are added, entries with an
+    /// already known id are overwritten. The belts and sorters of `other`
+    /// replace the known ones, if it has any. Fails if a string key of
+    /// `other` has the same id as a different key of this data set.
+    pub fn merge(&mut self, other: Data) -> Result<(), Error> {
+        if self.game_version.is_none() {
+            self.game_version = other.game_version;
+        }
+        if self.schema_version.is_none() {
+            self.schema_version = other.schema_version;
+        }
+
+        for (iid, item) in other.items {
+            if let Some(old) = self.items.get(&iid).filter(|old| old.name != item.name) {
+                log::warn!(
+                    item:% = iid;
+                    "Item {} is overwritten with a different name: {} => {}",
+                    iid,
+                    old.name,
+                    item.name
+                );
+            }
+
+            self.items.insert(iid, item);
+        }
+
+        for (rid, recipe) in other.recipes {
+            if let Some(old) = self.recipes.get(&rid).filter(|old| old.name != recipe.name) {
+                log::warn!(
+                    recipe:% = rid;
+                    "Recipe {} is overwritten with a different name: {} => {}",
+                    rid,
+                    old.name,
+                    recipe.name
+                );
+            }
+
+            self.recipes.insert(rid, recipe);
+        }
+
+        for (bid, building) in other.buildings {
+            if let Some(old) = self
+                .buildings
+                .get(&bid)
+                .filter(|old| old.name != building.name)
+            {
+                log::warn!(
+                    building:% = bid;
+                    "Building {} is overwritten with a different name: {} => {}",
+                    bid,
+                    old.name,
+                    building.name
+                );
+            }
+
+            self.buildings.insert(bid, building);
+        }
+
+        for (tid, tech) in other.techs {
+            if let Some(old) = self.techs.get(&tid).filter(|old| old.name != tech.name) {
+                log::warn!(
+                    tech = tid.0;
+                    "Tech {} is overwritten with a different name: {} => {}",
+                    tid.0,
+                    old.name,
+                    tech.name
+                );
+            }
+
+            self.techs.insert(tid, tech);
+        }
+
+        for (language, locale) in other.locales {
+            let entry = self.locales.entry(language).or_default();
+            entry.items.extend(locale.items);
+            entry.recipes.extend(locale.recipes);
+        }
+
+        if !other.logistics.belts.is_empty() {
+            self.logistics.belts = other.logistics.belts;
+        }
+        if !other.logistics.sorters.is_empty() {
+            self.logistics.sorters = other.logistics.sorters;
+        }
+
+        self.keys.merge(other.keys)?;
+        self.sources.extend(other.sources);
+        self.update_index();
+
+        Ok(())
+    }
+
+    /// Translate the names of the items and recipes to the passed language.
+    ///
+    /// Items and recipes without a translation keep their original name.
+    /// Both the translated and the original names can be used to look up
+    /// items and recipes. This should be called after all data was merged,
+    /// because merging rebuilds the name index.
+    pub fn apply_locale(&mut self, language: &str) -> Result<(), Error> {
+        let locale = match self.locales.get(language) {
+            Some(locale) => locale,
+            None => {
+                return Err(Error::usage(format!(
+                    "Unknown language: {} (available: {})",
+                    language,
+                    self.locales.keys().sorted().join(", ")
+                )))
+            }
+        };
+
+        for (iid, name) in &locale.items {
+            if let Some(item) = self.items.get_mut(iid) {
+                item.name = name.clone();
+
+                let ids = self.item_by_name.entry(name.clone()).or_default();
+                if !ids.contains(iid) {
+                    ids.push(*iid);
+                    ids.sort();
+                }
+            }
+        }
+
+        for (rid, name) in &locale.recipes {
+            if let Some(recipe) = self.recipes.get_mut(rid) {
+                recipe.name = name.clone();
+
+                let ids = self.recipes_by_name.entry(name.clone()).or_default();
+                if !ids.contains(rid) {
+                    ids.push(*rid);
+                    ids.sort();
+                }
+            }
+        }
+
+        self.update_normalized_index();
+
+        Ok(())
+    }
+
+    /// Get the items that are referenced by recipes but do not exist, with
+    /// the recipes that reference them.
+    pub fn missing_items(&self) -> BTreeMap<ItemId, BTreeSet<RecipeId>> {
+        let mut ret = BTreeMap::<ItemId, BTreeSet<RecipeId>>::new();
+
+        for (rid, recipe) in &self.recipes {
+            for iid in recipe.inputs.iter().chain(&recipe.outputs).map(|i| i.id) {
+                if !self.items.contains_key(&iid) {
+                    ret.entry(iid).or_default().insert(*rid);
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Get the items that are produced by a single recipe that is disabled,
+    /// but consumed by recipes that are enabled, with the disabled producer
+    /// and the enabled consumers.
+    pub fn disabled_producers(&self) -> BTreeMap<ItemId, (RecipeId, BTreeSet<RecipeId>)> {
+        let mut ret = BTreeMap::new();
+
+        for (iid, producers) in &self.as_output {
+            let rid = match producers.as_slice() {
+                [rid] if self.recipes[rid].disabled => *rid,
+                _ => continue,
+            };
+
+            let consumers = self
+                .as_input
+                .get(iid)
+                .into_iter()
+                .flatten()
+                .filter(|rid| !self.recipes[rid].disabled)
+                .copied()
+                .collect::<BTreeSet<_>>();
+            if !consumers.is_empty() {
+                ret.insert(*iid, (rid, consumers));
+            }
+        }
+
+        ret
+    }
+
+    /// Get the groups of recipes that have the same inputs, outputs and
+    /// crafting time (see [`Recipe::signature`]). Each group is sorted by id
+    /// and has at least two recipes.
+    pub fn duplicate_recipes(&self) -> Vec<Vec<RecipeId>> {
+        let mut groups = BTreeMap::<RecipeSignature, Vec<RecipeId>>::new();
+        for (rid, recipe) in &self.recipes {
+            groups.entry(recipe.signature()).or_default().push(*rid);
+        }
+
+        let mut ret = groups
+            .into_values()
+            .filter(|rids| rids.len() > 1)
+            .map(|mut rids| {
+                rids.sort();
+
+                rids
+            })
+            .collect::<Vec<_>>();
+        ret.sort();
+
+        ret
+    }
+
+    /// Get the item and recipe type strings that are not known to the tool,
+    /// with the items and recipes that use them.
+    pub fn unknown_types(&self) -> UnknownTypes {
+        let mut ret = UnknownTypes::default();
+
+        for (iid, item) in &self.items {
+            if let ItemType::Unknown(type_) = &item.type_ {
+                ret.items.entry(type_.clone()).or_default().insert(*iid);
+            }
+        }
+
+        for (rid, recipe) in &self.recipes {
+            if let RecipeType::Unknown(type_) = &recipe.type_ {
+                ret.recipes.entry(type_.clone()).or_default().insert(*rid);
+            }
+        }
+
+        ret
+    }
+
+    /// Get the recipes that are available after researching the passed techs
+    /// and all their prerequisites. Recipes that are not unlocked by any tech
+    /// are always available.
+    pub fn unlocked_recipes(&self, techs: &BTreeSet<TechId>) -> BTreeSet<RecipeId> {
+        let mut researched = BTreeSet::new();
+        let mut stack = techs.iter().copied().collect::<Vec<_>>();
+        while let Some(tid) = stack.pop() {
+            if researched.insert(tid) {
+                if let Some(tech) = self.techs.get(&tid) {
+                    stack.extend(tech.pre_techs.iter().copied());
+                }
+            }
+        }
+
+        let locked = self
+            .techs
+            .values()
+            .flat_map(|t| t.recipes.iter().copied())
+            .collect::<BTreeSet<_>>();
+
+        self.recipes
+            .keys()
+            .filter(|rid| !locked.contains(rid))
+            .chain(
+                researched
+                    .iter()
+                    .filter_map(|tid| self.techs.get(tid))
+                    .flat_map(|t| t.recipes.iter()),
+            )
+            .copied()
+            .collect()
+    }
+
+    /// Get the recipes that are not available after researching the passed
+    /// techs (see [`Data::unlocked_recipes`]).
+    pub fn locked_recipes(&self, techs: &BTreeSet<TechId>) -> BTreeSet<RecipeId> {
+        let unlocked = self.unlocked_recipes(techs);
+
+        self.recipes
+            .keys()
+            .filter(|rid| !unlocked.contains(rid))
+            .copied()
+            .collect()
+    }
+
+    /// Get the building that is used to craft recipes of the passed type.
+    ///
+    /// If multiple buildings are able to handle the recipe type, the one with
+    /// the lowest id is returned.
+    pub fn building_for(&self, type_: &RecipeType) -> Option<(ItemId, &Building)> {
+        self.buildings
+            .iter()
+            .filter(|(_, b)| b.types.contains(type_))
+            .min_by_key(|(bid, _)| **bid)
+            .map(|(bid, b)| (*bid, b))
+    }
+
+    fn update_index(&mut self) {
+        self.as_input.clear();
+        for (iid, rid) in self
+            .recipes
+            .iter()
+            .flat_map(|(rid, r)| r.inputs.iter().map(|i| (i.id, *rid)))
+        {
+            self.as_input.entry(iid).or_default().push(rid);
+        }
+
+        self.as_output.clear();
+        for (iid, rid) in self
+            .recipes
+            .iter()
+            .flat_map(|(rid, r)| r.outputs.iter().map(|i| (i.id, *rid)))
+        {
+            self.as_output.entry(iid).or_default().push(rid);
+        }
+
+        self.item_by_name = name_index(self.items.iter().map(|(iid, i)| (iid, &i.name)));
+        self.recipes_by_name = name_index(self.recipes.iter().map(|(rid, r)| (rid, &r.name)));
+        self.update_normalized_index();
+
+        self.buildings_by_type.clear();
+        for (bid, building) in &self.buildings {
+            for type_ in &building.types {
+                self.buildings_by_type
+                    .entry(type_.clone())
+                    .or_default()
+                    .push(*bid);
+            }
+        }
+
+        let buildings = &self.buildings;
+        for ids in self.buildings_by_type.values_mut() {
+            ids.sort_by(|a, b| {
+                buildings[a]
+                    .speed
+                    .total_cmp(&buildings[b].speed)
+                    .then(a.cmp(b))
+            });
+        }
+    }
+
+    fn update_normalized_index(&mut self) {
+        self.item_by_normalized_name = normalized_index(&self.item_by_name);
+        self.recipes_by_normalized_name = normalized_index(&self.recipes_by_name);
+    }
+}
+
+/// Decode the content of the passed data file, decompressing it if it is
+/// gzipped. All members of the gzip stream are read, up to
+/// [`MAX_DECOMPRESSED_SIZE`] bytes.
+pub(crate) fn decode_source(path: &Path, bytes: Vec<u8>) -> Result<String, Error> {
+    let is_gzip = path.extension().is_some_and(|ext| ext == "gz") || bytes.starts_with(GZIP_MAGIC);
+    let bytes = if is_gzip {
+        let mut decompressed = Vec::new();
+        MultiGzDecoder::new(bytes.as_slice())
+            .take(MAX_DECOMPRESSED_SIZE + 1)
+            .read_to_end(&mut decompressed)
+            .map_err(|err| {
+                Error::data(format!(
+                    "Unable to decompress data file {}: {}",
+                    path.display(),
+                    err
+                ))
+            })?;
+
+        if decompressed.len() as u64 > MAX_DECOMPRESSED_SIZE {
+            return Err(Error::data(format!(
+                "Data file {} is larger than {} MiB when decompressed",
+                path.display(),
+                MAX_DECOMPRESSED_SIZE >> 20
+            )));
+        }
+
+        decompressed
+    } else {
+        bytes
+    };
+
+    String::from_utf8(bytes).map_err(|err| {
+        Error::data(format!(
+            "Data file {} is not valid UTF-8: {}",
+            path.display(),
+            err
+        ))
+    })
+}
+
+/// First bytes of a gzip stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Largest size of a decompressed data file, so a small gzip file can not
+/// exhaust the memory.
+const MAX_DECOMPRESSED_SIZE: u64 = 256 << 20;
+
+/// Normalize a name for lookups that ignore case, punctuation and
+/// whitespace: `"conveyor-belt  MK.III"` becomes `"conveyor belt mk iii"`.
+pub fn normalize_name(name: &str) -> String {
+    name.split(|c: char| !c.is_alphanumeric())
+        .filter(|s| !s.is_empty())
+        .map(str::to_lowercase)
+        .join(" ")
+}
+
+/// Build the index from normalized names to ids out of the name index.
+fn normalized_index<T>(index: &HashMap<String, Vec<T>>) -> HashMap<String, Vec<T>>
+where
+    T: Copy + Ord,
+{
+    let mut ret = HashMap::<String, Vec<T>>::new();
+    for (name, ids) in index {
+        ret.entry(normalize_name(name)).or_default().extend(ids);
+    }
+
+    for ids in ret.values_mut() {
+        ids.sort();
+        ids.dedup();
+    }
+
+    ret
+}
+
+/// Build the index from names to ids.
+fn name_index<'a, I, T>(entries: I) -> HashMap<String, Vec<T>>
+where
+    I: IntoIterator<Item = (&'a T, &'a String)>,
+    T: Copy + Ord + 'a,
+{
+    let mut index = HashMap::<String, Vec<T>>::new();
+    for (id, name) in entries {
+        index.entry(name.clone()).or_default().push(*id);
+    }
+
+    for ids in index.values_mut() {
+        ids.sort();
+    }
+
+    index
+}
+
+/* Schema */
+
+/// Newest version of the data schema that is known to the tool.
+pub const SCHEMA_VERSION: u32 = 2;
+
+/// Top level keys of the first schema version.
+const SCHEMA_KEYS: &[&str] = &[
+    "schema_version",
+    "game_version",
+    "game_data",
+    "game_items",
+    "game_recipes",
+    "game_buildings",
+    "game_techs",
+    "game_locale",
+];
+
+/// Fields that were added after the first schema version, with the version
+/// they were added in. Top level keys are given by their name, fields of
+/// the entries as `<entry>.<field>`. Files that declare an older version
+/// load these fields with their default value.
+const SCHEMA_FIELDS: &[(&str, u32)] = &[
+    ("game_logistics", 2),
+    ("item.stack_size", 2),
+    ("recipe.disabled", 2),
+];
+
+/// Schema of a data file, as declared by its `schema_version` field.
+///
+/// Files without a version use the newest schema. Every field that is added
+/// to the schema is listed in [`SCHEMA_FIELDS`], and [`Schema::apply`]
+/// resets the fields that are newer than the declared version.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Schema {
+    declared: Option<u32>,
+}
+
+impl Schema {
+    /// Read the schema version of the passed data table and report the keys
+    /// of the table that are not part of the schema.
+    fn from_table(table: &Table<'_>) -> Result<Self, LuaError> {
+        let declared = field::<Option<u32>>(table, "schema_version")?;
+        if declared == Some(0) {
+            return Err(LuaError::FromLuaConversionError {
+                from: "integer",
+                to: "Data",
+                message: Some("field 'schema_version': versions start at 1".into()),
+            });
+        }
+
+        let schema = Self { declared };
+        schema.check_keys(table, "data");
+
+        Ok(schema)
+    }
+
+    /// Schema version the file is loaded with.
+    pub fn version(&self) -> u32 {
+        self.declared.unwrap_or(SCHEMA_VERSION)
+    }
+
+    /// Whether the passed field of [`SCHEMA_FIELDS`] is part of the schema
+    /// version of the file.
+    pub fn supports(&self, field: &str) -> bool {
+        SCHEMA_FIELDS
+            .iter()
+            .find(|(name, _)| *name == field)
+            .is_none_or(|(_, version)| *version <= self.version())
+    }
+
+    /// Report the keys of the passed table that are ignored. If the file
+    /// declares a newer version than the tool knows, they are logged as
+    /// warning, otherwise once each at debug level.
+    fn check_keys(&self, table: &Table<'_>, context: &str) {
+        let unknown = table
+            .clone()
+            .pairs::<Value, Value>()
+            .filter_map(|pair| match pair {
+                Ok((Value::String(key), _)) => key.to_str().ok().map(ToOwned::to_owned),
+                _ => None,
+            })
+            .filter(|key| {
+                let added = SCHEMA_FIELDS.iter().find(|(name, _)| name == key);
+
+                !SCHEMA_KEYS.contains(&key.as_str())
+                    && added.is_none_or(|(_, version)| *version > self.version())
+            })
+            .collect::<BTreeSet<_>>();
+
+        if self.version() > SCHEMA_VERSION {
+            log::warn!(
+                "Data file uses schema version {}, but only version {} is known{}",
+                self.version(),
+                SCHEMA_VERSION,
+                if unknown.is_empty() {
+                    String::new()
+                } else {
+                    format!(
+                        ", ignore the keys of the {}: {}",
+                        context,
+                        unknown.iter().join(", ")
+                    )
+                }
+            );
+
+            return;
+        }
+
+        for key in unknown {
+            log::debug!(
+                "Ignore key {:?} of the {}, it is not part of schema version {}",
+                key,
+                context,
+                self.version()
+            );
+        }
+    }
+
+    /// Record the schema version in the passed data and reset the fields
+    /// that are newer than the declared version.
+    fn apply(self, data: &mut Data) {
+        data.schema_version = self.declared;
+
+        if !self.supports("game_logistics") {
+            data.logistics = Logistics::default();
+        }
+        if !self.supports("item.stack_size") {
+            for item in data.items.values_mut() {
+                item.stack_size = None;
+            }
+        }
+        if !self.supports("recipe.disabled") {
+            for recipe in data.recipes.values_mut() {
+                recipe.disabled = false;
+            }
+        }
+    }
+}
+
+/* Registry */
+
+/// Installs the `register_item` and `register_recipe` functions. They pass
+/// the entry and the location of the caller to the registry and raise the
+/// returned error at the caller.
+const REGISTER_SOURCE: &str = r#"
+local register = ...
+local function wrap(kind)
+    return function(entry)
+        local _, location = pcall(error, "", 3)
+        local err = register(kind, entry, location)
+        if err then
+            error(err, 2)
+        end
+    end
+end
+register_item = wrap("item")
+register_recipe = wrap("recipe")
+"#;
+
+/// Items and recipes that are registered by the data file instead of being
+/// part of the returned table:
+///
+/// ```lua
+/// register_item { id = 1101, name = "Iron Ingot", type = "MATERIAL" }
+/// ```
+///
+/// The entries have the same fields as the entries of the data tables, plus
+/// their `id`. The location of each registration is kept for the errors.
+#[derive(Debug, Default)]
+struct Registry {
+    items: HashMap<ItemId, (Item, String)>,
+    recipes: HashMap<RecipeId, (Recipe, String)>,
+}
+
+impl Registry {
+    fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.recipes.is_empty()
+    }
+
+    /// Install the registration functions into the globals of the passed
+    /// context. They are valid until the scope ends.
+    fn install<'lua, 'scope>(
+        &'scope mut self,
+        lua: Context<'lua>,
+        scope: &Scope<'lua, 'scope>,
+    ) -> Result<(), LuaError> {
+        let register = scope.create_function_mut(
+            move |lua, (kind, entry, location): (String, Value, String)| {
+                let location = location.trim_end_matches(": ");
+                let ret = match kind.as_str() {
+                    "item" => register(lua, &mut self.items, "item", entry, location),
+                    _ => register(lua, &mut self.recipes, "recipe", entry, location),
+                };
+
+                Ok(ret.err())
+            },
+        )?;
+
+        lua.load(REGISTER_SOURCE)
+            .set_name("=register")?
+            .call::<_, ()>(register)
+    }
+
+    /// Add the registered entries to the passed data. Entries that are also
+    /// part of the returned table are an error.
+    fn apply(self, data: &mut Data) -> Result<(), Error> {
+        if self.is_empty() {
+            return Ok(());
+        }
+
+        for (iid, (item, location)) in self.items {
+            if data.items.contains_key(&iid) {
+                return Err(Error::data(format!(
+                    "Item {} is registered at {} and also part of the returned data",
+                    iid.label(&data.keys),
+                    location
+                )));
+            }
+
+            data.items.insert(iid, item);
+        }
+
+        for (rid, (recipe, location)) in self.recipes {
+            if data.recipes.contains_key(&rid) {
+                return Err(Error::data(format!(
+                    "Recipe {} is registered at {} and also part of the returned data",
+                    rid.label(&data.keys),
+                    location
+                )));
+            }
+
+            data.recipes.insert(rid, recipe);
+        }
+
+        data.update_index();
+
+        Ok(())
+    }
+}
+
+/// Validate and add one registered entry.
+fn register<'lua, K, T>(
+    lua: Context<'lua>,
+    entries: &mut HashMap<K, (T, String)>,
+    kind: &str,
+    entry: Value<'lua>,
+    location: &str,
+) -> Result<(), String>
+where
+    K: FromLua<'lua> + Eq + Hash,
+    T: FromLua<'lua>,
+{
+    let function = format!("register_{}", kind);
+    let table = match entry {
+        Value::Table(table) => table,
+        value => {
+            return Err(format!(
+                "{} expects a table, got a {}",
+                function,
+                value.type_name()
+            ))
+        }
+    };
+
+    let required: &[&str] = match kind {
+        "item" => &["id", "name", "type"],
+        _ => &["id", "name", "type", "seconds", "inputs", "outputs"],
+    };
+    for key in required {
+        if let Value::Nil = table.get::<_, Value>(*key).unwrap_or(Value::Nil) {
+            return Err(format!("{}: missing field '{}'", function, key));
+        }
+    }
+
+    // Numbers and string keys both convert to a string, so the messages show
+    // the id as it is written in the data.
+    let id = field::<K>(&table, "id").map_err(|err| format!("{}: {}", function, err))?;
+    let label = table.get::<_, String>("id").unwrap_or_default();
+    if let Some((_, other)) = entries.get(&id) {
+        return Err(format!(
+            "{}: {} {} is already registered at {}",
+            function, kind, label, other
+        ));
+    }
+
+    let value = T::from_lua(Value::Table(table), lua)
+        .map_err(|err| format!("{}: {} {}: {}", function, kind, label, err))?;
+    entries.insert(id, (value, location.to_owned()));
+
+    Ok(())
+}
+
+/* DataArg */
+
+/// Argument of data files that return a function, given as `key=value`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataArg {
+    pub key: String,
+    pub value: DataArgValue,
+}
+
+impl FromStr for DataArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (key, value) = s
+            .split_once('=')
+            .ok_or_else(|| format!("Invalid data argument: {} (expected key=value)", s))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("Invalid data argument: {} (the key is empty)", s));
+        }
+
+        Ok(Self {
+            key: key.into(),
+            value: DataArgValue::from(value.trim()),
+        })
+    }
+}
+
+impl Display for DataArg {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}={}", self.key, self.value)
+    }
+}
+
+/// Value of a data argument. `true` and `false` are booleans, numbers are
+/// integers or floats and everything else is a string.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DataArgValue {
+    Bool(bool),
+    Integer(i64),
+    Number(f64),
+    String(String),
+}
+
+impl From<&str> for DataArgValue {
+    fn from(s: &str) -> Self {
+        match s {
+            "true" => Self::Bool(true),
+            "false" => Self::Bool(false),
+            s => match (i64::from_str(s), f64::from_str(s)) {
+                (Ok(i), _) => Self::Integer(i),
+                (_, Ok(n)) if n.is_finite() => Self::Number(n),
+                _ => Self::String(s.into()),
+            },
+        }
+    }
+}
+
+impl Display for DataArgValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Bool(b) => write!(f, "{}", b),
+            Self::Integer(i) => write!(f, "{}", i),
+            Self::Number(n) => write!(f, "{}", n),
+            Self::String(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl<'lua> ToLua<'lua> for DataArgValue {
+    fn to_lua(self, lua: Context<'lua>) -> Result<Value<'lua>, LuaError> {
+        match self {
+            Self::Bool(b) => Ok(Value::Boolean(b)),
+            Self::Integer(i) => Ok(Value::Integer(i)),
+            Self::Number(n) => Ok(Value::Number(n)),
+            Self::String(s) => s.to_lua(lua),
+        }
+    }
+}
+
+/* UnknownTypes */
+
+/// Type strings of the data that are not known to the tool.
+#[derive(Debug, Default)]
+pub struct UnknownTypes {
+    pub items: BTreeMap<String, BTreeSet<ItemId>>,
+    pub recipes: BTreeMap<String, BTreeSet<RecipeId>>,
+}
+
+impl UnknownTypes {
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.recipes.is_empty()
+    }
+}
+
+/* Keys */
+
+/// Bit that is set in the ids that were created from a string key.
+const KEYED: usize = 1 << (usize::BITS - 1);
+
+/// Name of the Lua registry table that collects the string keys of the ids
+/// while the data is converted, see [`Keys::from_lua_registry`].
+const KEYS_REGISTRY: &str = "dsp_tool_keys";
+
+/// String keys (like `mymod:super_magnet`) of the keyed ids of a data set,
+/// so the ids can be displayed and selected by their key.
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Keys(BTreeMap<usize, String>);
+
+impl Keys {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Add a key and return its id, see [`key_id`]. Fails if a different key
+    /// has the same id.
+    pub fn insert(&mut self, key: &str) -> Result<usize, Error> {
+        let id = key_id(key);
+
+        match self.0.get(&id) {
+            Some(other) if other != key => Err(Error::data(format!(
+                "Key {} has the same internal id as key {}",
+                key, other
+            ))),
+            Some(_) => Ok(id),
+            None => {
+                self.0.insert(id, key.to_owned());
+
+                Ok(id)
+            }
+        }
+    }
+
+    /// Add the keys of another data set. Fails if a key of it has the same
+    /// id as a different key of this one.
+    pub fn merge(&mut self, other: Keys) -> Result<(), Error> {
+        for key in other.0.values() {
+            self.insert(key)?;
+        }
+
+        Ok(())
+    }
+
+    /// Get the string key of the passed id, `None` for numeric ids.
+    pub fn get(&self, id: usize) -> Option<&str> {
+        self.0.get(&id).map(String::as_str)
+    }
+
+    /// Get the id of a known string key.
+    pub fn id(&self, key: &str) -> Option<usize> {
+        let id = key_id(key);
+
+        (self.get(id) == Some(key)).then_some(id)
+    }
+
+    /// Collect the keys that were converted by [`id_from_lua`].
+    fn from_lua_registry(lua: Context<'_>) -> Result<Self, Error> {
+        let mut ret = Self::default();
+
+        for key in keys_registry(lua)?.pairs::<String, bool>() {
+            let (key, _) = key?;
+            ret.insert(&key)?;
+        }
+
+        Ok(ret)
+    }
+}
+
+/// Get the internal id of a string key: the FNV-1a hash of the key with the
+/// [`KEYED`] bit set, so it is stable between runs and does not depend on
+/// the order the data is loaded in.
+pub fn key_id(key: &str) -> usize {
+    fnv(key.as_bytes()) as usize | KEYED
+}
+
+/// Get the Lua registry table with the converted keys, see
+/// [`KEYS_REGISTRY`]. The table is created if it does not exist yet.
+fn keys_registry(lua: Context<'_>) -> Result<Table<'_>, LuaError> {
+    match lua.named_registry_value::<_, Option<Table>>(KEYS_REGISTRY)? {
+        Some(table) => Ok(table),
+        None => {
+            let table = lua.create_table()?;
+            lua.set_named_registry_value(KEYS_REGISTRY, table.clone())?;
+
+            Ok(table)
+        }
+    }
+}
+
+/// Id as it is shown to the user: the string key of keyed ids, and the number
+/// otherwise. JSON outputs write it the same way, so the original identifier
+/// round-trips.
+#[derive(Debug, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub enum IdLabel {
+    Id(usize),
+    Key(String),
+}
+
+impl IdLabel {
+    fn new(id: usize, keys: &Keys) -> Self {
+        match keys.get(id) {
+            Some(key) => Self::Key(key.to_owned()),
+            None => Self::Id(id),
+        }
+    }
+}
+
+impl Display for IdLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Id(id) => fmt_id(*id, f),
+            Self::Key(key) => write!(f, "{}", key),
+        }
+    }
+}
+
+impl Serialize for IdLabel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Self::Id(id) => serializer.serialize_u64(*id as u64),
+            Self::Key(key) => serializer.serialize_str(key),
+        }
+    }
+}
+
+/// Parse a number, or a string key that is one of the passed keys.
+fn parse_id(s: &str, keys: &Keys) -> Result<usize, String> {
+    if let Ok(id) = usize::from_str(s) {
+        return Ok(id);
+    }
+
+    keys.id(s)
+        .ok_or_else(|| format!("Invalid id: {} (not a number or a known key)", s))
+}
+
+/// Keyed ids are displayed as the hash of their key if the keys of the data
+/// are not at hand, see [`IdLabel`].
+fn fmt_id(id: usize, f: &mut Formatter<'_>) -> FmtResult {
+    if id & KEYED == 0 {
+        write!(f, "{}", id)
+    } else {
+        write!(f, "#{:016x}", id & !KEYED)
+    }
+}
+
+/// Convert a Lua number or string into an id. Strings that are not numbers
+/// are string keys, they are collected in the Lua registry (see
+/// [`KEYS_REGISTRY`]) and end up in the [`Keys`] of the data.
+fn id_from_lua(value: Value<'_>, lua: Context<'_>, to: &'static str) -> Result<usize, LuaError> {
+    let error = |from, message: String| LuaError::FromLuaConversionError {
+        from,
+        to,
+        message: Some(message),
+    };
+
+    match value {
+        Value::Integer(id) if id >= 0 => Ok(id as usize),
+        Value::Number(id) if id >= 0.0 && id.fract() == 0.0 => Ok(id as usize),
+        Value::String(s) => {
+            let s = s.to_str()?.trim();
+            match usize::from_str(s) {
+                Ok(id) => Ok(id),
+                Err(_) if s.is_empty() => Err(error("string", "expected a non-empty key".into())),
+                Err(_) => {
+                    keys_registry(lua)?.set(s, true)?;
+
+                    Ok(key_id(s))
+                }
+            }
+        }
+        value => Err(error(
+            value.type_name(),
+            "expected a positive integer or a string key".into(),
+        )),
+    }
+}
+
+/* ItemId */
+
+/// Id of an item. Ids are numbers or string keys, see [`Keys`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct ItemId(pub usize);
+
+impl ItemId {
+    /// Parse a number or one of the passed string keys.
+    pub fn parse(s: &str, keys: &Keys) -> Result<Self, String> {
+        parse_id(s, keys).map(Self)
+    }
+
+    /// Label of the id with its string key, if it has one.
+    pub fn label(&self, keys: &Keys) -> IdLabel {
+        IdLabel::new(self.0, keys)
+    }
+}
+
+impl FromStr for ItemId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, &Keys::default())
+    }
+}
+
+impl Display for ItemId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        fmt_id(self.0, f)
+    }
+}
+
+impl<'lua> FromLua<'lua> for ItemId {
+    fn from_lua(value: Value<'lua>, lua: Context<'lua>) -> Result<Self, LuaError> {
+        id_from_lua(value, lua, "ItemId").map(Self)
+    }
+}
+
+/* Item */
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Item {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: ItemType,
+    pub icon: Option<String>,
+
+    /// Number of items that fit into one inventory slot.
+    pub stack_size: Option<usize>,
+}
+
+/// Formats the item as its name and type: `Copper Ingot (MATERIAL)`.
+impl Display for Item {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{} ({})", self.name, self.type_)
+    }
+}
+
+impl<'lua> FromLua<'lua> for Item {
+    fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
+        match value {
+            Value::Table(table) => {
+                let name = field(&table, "name")?;
+                let type_ = field(&table, "type")?;
+                let icon = field(&table, "icon")?;
+                let stack_size = field(&table, "stack_size")?;
+
+                Ok(Self {
+                    name,
+                    type_,
+                    icon,
+                    stack_size,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Item",
+                message: Some("expected a table".into()),
+            }),
+        }
+    }
+}
+
+/* ItemType */
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum ItemType {
+    Material,
+    Matrix,
+    Product,
+    Production,
+    Resource,
+    Component,
+    Logistics,
+    Unknown(String),
+}
+
+impl From<&str> for ItemType {
+    fn from(s: &str) -> Self {
+        match s {
+            "MATERIAL" => Self::Material,
+            "MATRIX" => Self::Matrix,
+            "PRODUCT" => Self::Product,
+            "PRODUCTION" => Self::Production,
+            "RESOURCE" => Self::Resource,
+            "COMPONENT" => Self::Component,
+            "LOGISTICS" => Self::Logistics,
+            s => Self::Unknown(s.into()),
+        }
+    }
+}
+
+impl<'lua> FromLua<'lua> for ItemType {
+    fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
+        match value {
+            Value::String(s) => Ok(Self::from(s.to_str()?)),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "ItemType",
+                message: Some("expected a string".into()),
+            }),
+        }
+    }
+}
+
+impl Display for ItemType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Material => write!(f, "MATERIAL"),
+            Self::Matrix => write!(f, "MATRIX"),
+            Self::Product => write!(f, "PRODUCT"),
+            Self::Production => write!(f, "PRODUCTION"),
+            Self::Resource => write!(f, "RESOURCE"),
+            Self::Component => write!(f, "COMPONENT"),
+            Self::Logistics => write!(f, "LOGISTICS"),
+            Self::Unknown(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl Serialize for ItemType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ItemType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from(String::deserialize(deserializer)?.as_str()))
+    }
+}
+
+/* RecipeId */
+
+/// Id of a recipe. Ids are numbers or string keys, see [`Keys`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+pub struct RecipeId(pub usize);
+
+impl RecipeId {
+    /// Parse a number or one of the passed string keys.
+    pub fn parse(s: &str, keys: &Keys) -> Result<Self, String> {
+        parse_id(s, keys).map(Self)
+    }
+
+    /// Label of the id with its string key, if it has one.
+    pub fn label(&self, keys: &Keys) -> IdLabel {
+        IdLabel::new(self.0, keys)
+    }
+}
+
+impl FromStr for RecipeId {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::parse(s, &Keys::default())
+    }
+}
+
+impl Display for RecipeId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        fmt_id(self.0, f)
+    }
+}
+
+impl<'lua> FromLua<'lua> for RecipeId {
+    fn from_lua(value: Value<'lua>, lua: Context<'lua>) -> Result<Self, LuaError> {
+        id_from_lua(value, lua, "RecipeId").map(Self)
+    }
+}
+
+/* Recipe */
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Recipe {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub type_: RecipeType,
+    pub seconds: f64,
+    pub explicit: bool,
+
+    /// Recipe can not receive the extra products bonus of proliferators.
+    pub non_productive: bool,
+
+    /// Recipe can be crafted in the replicator.
+    pub handcraft: bool,
+
+    /// Recipe is not used, unless `--include-disabled` is passed.
+    pub disabled: bool,
+
+    /// Factor that is applied to the outputs of the recipe.
+    pub productivity: f64,
+
+    pub inputs: Vec<ItemAmount>,
+    pub outputs: Vec<ItemAmount>,
+}
+
+/// Inputs, outputs and crafting time of a recipe, independent of the order
+/// of the items.
+#[derive(Debug, Clone, Eq, PartialEq, Ord, PartialOrd)]
+pub struct RecipeSignature {
+    inputs: Vec<(ItemId, usize)>,
+    outputs: Vec<(ItemId, usize)>,
+    seconds: u64,
+}
+
+impl RecipeSignature {
+    pub fn new(inputs: &[ItemAmount], outputs: &[ItemAmount], seconds: f64) -> Self {
+        let sorted = |amounts: &[ItemAmount]| {
+            let mut ret = amounts.iter().map(|a| (a.id, a.amount)).collect::<Vec<_>>();
+            ret.sort();
+
+            ret
+        };
+
+        Self {
+            inputs: sorted(inputs),
+            outputs: sorted(outputs),
+            seconds: seconds.to_bits(),
+        }
+    }
+}
+
+impl Recipe {
+    /// Signature of the recipe, which is equal for recipes that only differ
+    /// in their name, type or flags.
+    pub fn signature(&self) -> RecipeSignature {
+        RecipeSignature::new(&self.inputs, &self.outputs, self.seconds)
+    }
 
-    pub as_input: HashMap<ItemId, Vec<RecipeId>>,
-    pub as_output: HashMap<ItemId, Vec<RecipeId>>,
+    /// Annotation of the productivity, if the recipe has one.
+    pub fn productivity_label(&self) -> Option<String> {
+        if self.productivity != 1.0 {
+            Some(format!("×{}", self.productivity))
+        } else {
+            None
+        }
+    }
 
-    pub item_by_name: HashMap<String, ItemId>,
-    pub recipes_by_name: HashMap<String, RecipeId>,
+    /// Format the recipe with the names of its items.
+    pub fn display_with<'a>(&'a self, data: &'a Data) -> RecipeDisplay<'a> {
+        RecipeDisplay { recipe: self, data }
+    }
 }
 
-impl<'lua> FromLua<'lua> for Data {
-    fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
-        match value {
-            Value::Table(table) => {
-                let items = table.get::<_, HashMap<ItemId, Item>>("game_items")?;
-                let recipes = table
-                    .get::<_, Vec<RecipeTuple>>("game_recipes")?
-                    .into_iter()
-                    .map(|RecipeTuple(id, recipe)| (id, recipe))
-                    .collect::<HashMap<RecipeId, Recipe>>();
-
-                let mut as_input = HashMap::<ItemId, Vec<RecipeId>>::new();
-                for (iid, rid) in recipes
-                    .iter()
-                    .flat_map(|(rid, r)| r.inputs.iter().map(|i| (i.id, *rid)))
-                {
-                    as_input.entry(iid).or_default().push(rid);
-                }
+/// Formats a recipe as its inputs, outputs, duration and type:
+/// `2× Copper Ingot + 1× Iron Ingot → 1× Circuit Board (2s, ASSEMBLE)`.
+/// Items that are missing in the data are shown by their id.
+pub struct RecipeDisplay<'a> {
+    recipe: &'a Recipe,
+    data: &'a Data,
+}
 
-                let mut as_output = HashMap::<ItemId, Vec<RecipeId>>::new();
-                for (iid, rid) in recipes
-                    .iter()
-                    .flat_map(|(rid, r)| r.outputs.iter().map(|i| (i.id, *rid)))
-                {
-                    as_output.entry(iid).or_default().push(rid);
-                }
+impl Display for RecipeDisplay<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let amounts = |amounts: &[ItemAmount]| {
+            amounts
+                .iter()
+                .map(|a| match self.data.items.get(&a.id) {
+                    Some(item) => format!("{}× {}", a.amount, item.name),
+                    None => format!("{}× {}", a.amount, a.id.label(&self.data.keys)),
+                })
+                .join(" + ")
+        };
 
-                let item_by_name = items
-                    .iter()
-                    .map(|(iid, i)| (i.name.clone(), *iid))
-                    .collect();
+        write!(
+            f,
+            "{} → {} ({}s, {})",
+            amounts(&self.recipe.inputs),
+            amounts(&self.recipe.outputs),
+            self.recipe.seconds,
+            self.recipe.type_
+        )
+    }
+}
 
-                let recipes_by_name = recipes
-                    .iter()
-                    .map(|(rid, r)| (r.name.clone(), *rid))
-                    .collect();
+impl<'lua> FromLua<'lua> for Recipe {
+    fn from_lua(value: Value<'lua>, lua: Context<'lua>) -> Result<Self, LuaError> {
+        match value {
+            Value::Table(table) => {
+                let name = field(&table, "name")?;
+                let type_ = field(&table, "type")?;
+                let seconds = field(&table, "seconds")?;
+                let explicit = table.get("explicit").unwrap_or(false);
+                let non_productive = table.get("non_productive").unwrap_or(false);
+                let handcraft = table.get("handcraft").unwrap_or(false);
+                let disabled = table.get("disabled").unwrap_or(false);
+                let productivity = field::<Option<f64>>(&table, "productivity")?.unwrap_or(1.0);
+                let inputs = amounts(lua, &table, "inputs")?;
+                let outputs = amounts(lua, &table, "outputs")?;
 
                 Ok(Self {
-                    items,
-                    recipes,
-                    as_input,
-                    as_output,
-                    item_by_name,
-                    recipes_by_name,
+                    name,
+                    type_,
+                    seconds,
+                    explicit,
+                    non_productive,
+                    handcraft,
+                    disabled,
+                    productivity,
+                    inputs,
+                    outputs,
                 })
             }
-            _ => Err(LuaError::FromLuaConversionError {
-                from: "Data",
-                to: "Data",
-                message: None,
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Recipe",
+                message: Some("expected a table".into()),
             }),
         }
     }
 }
 
-/* ItemId */
+/* ItemAmount */
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
-pub struct ItemId(pub usize);
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ItemAmount {
+    pub id: ItemId,
+    pub amount: usize,
+}
 
-impl FromStr for ItemId {
-    type Err = <usize as FromStr>::Err;
+impl ItemAmount {
+    fn from_tuple<'lua>(
+        lua: Context<'lua>,
+        (id, amount): (Result<Value<'lua>, LuaError>, Result<Value<'lua>, LuaError>),
+    ) -> Result<Self, LuaError> {
+        Ok(Self {
+            id: ItemId::from_lua(id?, lua)?,
+            amount: usize::from_lua(amount?, lua)?,
+        })
+    }
+}
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(usize::from_str(s)?))
+/* RecipeType */
+
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub enum RecipeType {
+    Assemble,
+    Chemical,
+    Fractionate,
+    Particle,
+    Refine,
+    Research,
+    Smelt,
+    Unknown(String),
+}
+
+impl RecipeType {
+    /// All known recipe types. Their names in the data are the displayed
+    /// names, and the lowercase names are the selector keywords of the types.
+    pub const KNOWN: [RecipeType; 7] = [
+        Self::Assemble,
+        Self::Chemical,
+        Self::Fractionate,
+        Self::Particle,
+        Self::Refine,
+        Self::Research,
+        Self::Smelt,
+    ];
+
+    /// Get the known recipe type with the passed keyword, ignoring case.
+    pub fn from_keyword(keyword: &str) -> Option<Self> {
+        Self::KNOWN
+            .into_iter()
+            .find(|type_| type_.to_string().eq_ignore_ascii_case(keyword))
     }
 }
 
-impl<'lua> FromLua<'lua> for ItemId {
-    fn from_lua(value: Value<'lua>, lua: Context<'lua>) -> Result<Self, LuaError> {
-        Ok(Self(usize::from_lua(value, lua)?))
+impl From<&str> for RecipeType {
+    fn from(s: &str) -> Self {
+        Self::KNOWN
+            .into_iter()
+            .find(|type_| type_.to_string() == s)
+            .unwrap_or_else(|| Self::Unknown(s.into()))
     }
 }
 
-/* Item */
+impl<'lua> FromLua<'lua> for RecipeType {
+    fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
+        match value {
+            Value::String(s) => Ok(Self::from(s.to_str()?)),
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "RecipeType",
+                message: Some("expected a string".into()),
+            }),
+        }
+    }
+}
 
-#[derive(Debug)]
-pub struct Item {
+impl Display for RecipeType {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Assemble => write!(f, "ASSEMBLE"),
+            Self::Chemical => write!(f, "CHEMICAL"),
+            Self::Fractionate => write!(f, "FRACTIONATE"),
+            Self::Particle => write!(f, "PARTICLE"),
+            Self::Refine => write!(f, "REFINE"),
+            Self::Research => write!(f, "RESEARCH"),
+            Self::Smelt => write!(f, "SMELT"),
+            Self::Unknown(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl Serialize for RecipeType {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for RecipeType {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(Self::from(String::deserialize(deserializer)?.as_str()))
+    }
+}
+
+/* Building */
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Building {
     pub name: String,
-    pub type_: ItemType,
+    pub types: Vec<RecipeType>,
+
+    /// Power consumption while working in kW.
+    pub work_power: f64,
+
+    /// Power consumption while idle in kW.
+    pub idle_power: f64,
+
+    /// Crafting speed relative to the recipe time.
+    pub speed: f64,
 }
 
-impl<'lua> FromLua<'lua> for Item {
+impl<'lua> FromLua<'lua> for Building {
     fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
         match value {
             Value::Table(table) => {
-                let name = table.get("name")?;
-                let type_ = table.get("type")?;
+                let name = field(&table, "name")?;
+                let types = field(&table, "types")?;
+                let work_power = field(&table, "work_power")?;
+                let idle_power = field::<Option<f64>>(&table, "idle_power")?.unwrap_or(0.0);
+                let speed = field::<Option<f64>>(&table, "speed")?.unwrap_or(1.0);
 
-                Ok(Self { name, type_ })
+                Ok(Self {
+                    name,
+                    types,
+                    work_power,
+                    idle_power,
+                    speed,
+                })
             }
-            _ => Err(LuaError::FromLuaConversionError {
-                from: "Item",
-                to: "Item",
-                message: None,
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Building",
+                message: Some("expected a table".into()),
             }),
         }
     }
 }
 
-/* ItemType */
+/* Logistics */
 
-#[derive(Debug, Eq, PartialEq)]
-pub enum ItemType {
-    Material,
-    Matrix,
-    Product,
-    Production,
-    Resource,
-    Component,
-    Logistics,
-    Unknown(String),
+/// Belt tiers and sorters of the game, as given by the `game_logistics`
+/// table.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Logistics {
+    /// Belt tiers, which are expected to be sorted by speed.
+    pub belts: Vec<Belt>,
+    pub sorters: Vec<Sorter>,
 }
 
-impl<'lua> FromLua<'lua> for ItemType {
+impl Logistics {
+    /// Belt of the passed tier, starting at 1 for the slowest belt. The
+    /// fastest belt is used if the tier is higher than the number of belts.
+    pub fn belt(&self, tier: usize) -> Option<&Belt> {
+        self.belts
+            .get(tier.saturating_sub(1))
+            .or_else(|| self.belts.last())
+    }
+
+    /// Sorter of the passed tier, like [`Logistics::belt`].
+    pub fn sorter(&self, tier: usize) -> Option<&Sorter> {
+        self.sorters
+            .get(tier.saturating_sub(1))
+            .or_else(|| self.sorters.last())
+    }
+}
+
+impl<'lua> FromLua<'lua> for Logistics {
     fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
         match value {
-            Value::String(s) => Ok(match s.to_str()? {
-                "MATERIAL" => Self::Material,
-                "MATRIX" => Self::Matrix,
-                "PRODUCT" => Self::Product,
-                "PRODUCTION" => Self::Production,
-                "RESOURCE" => Self::Resource,
-                "COMPONENT" => Self::Component,
-                "LOGISTICS" => Self::Logistics,
-                s => Self::Unknown(s.into()),
-            }),
-            _ => Err(LuaError::FromLuaConversionError {
-                from: "ItemType",
-                to: "ItemType",
-                message: None,
+            Value::Table(table) => {
+                let belts = field::<Option<_>>(&table, "belts")?.unwrap_or_default();
+                let sorters = field::<Option<_>>(&table, "sorters")?.unwrap_or_default();
+
+                Ok(Self { belts, sorters })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Logistics",
+                message: Some("expected a table".into()),
             }),
         }
     }
 }
 
-/* RecipeTuple */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Belt {
+    pub name: String,
+
+    /// Items per second that are transported by the belt.
+    pub speed: f64,
+}
 
-pub struct RecipeTuple(pub RecipeId, pub Recipe);
+impl Belt {
+    /// Number of belts that are needed for the passed items per minute.
+    pub fn belts(&self, per_minute: f64) -> f64 {
+        per_minute / (self.speed * 60.0)
+    }
+}
 
-impl<'lua> FromLua<'lua> for RecipeTuple {
+impl<'lua> FromLua<'lua> for Belt {
     fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
         match value {
             Value::Table(table) => {
-                let id = table.get("id")?;
+                let name = field(&table, "name")?;
+                let speed = field(&table, "speed")?;
 
-                let name = table.get("name")?;
-                let type_ = table.get("type")?;
-                let seconds = table.get("seconds")?;
-                let explicit = table.get("explicit").unwrap_or(false);
-                let inputs = table
-                    .get::<_, Table>("inputs")?
-                    .sequence_values::<usize>()
-                    .tuples::<(_, _)>()
-                    .map(ItemAmount::from_tuple)
-                    .collect::<Result<_, _>>()?;
-                let outputs = table
-                    .get::<_, Table>("outputs")?
-                    .sequence_values::<usize>()
-                    .tuples::<(_, _)>()
-                    .map(ItemAmount::from_tuple)
-                    .collect::<Result<_, _>>()?;
-
-                Ok(Self(
-                    id,
-                    Recipe {
-                        name,
-                        type_,
-                        seconds,
-                        explicit,
-                        inputs,
-                        outputs,
-                    },
-                ))
+                Ok(Self { name, speed })
             }
-            _ => Err(LuaError::FromLuaConversionError {
-                from: "ItemType",
-                to: "ItemType",
-                message: None,
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Belt",
+                message: Some("expected a table".into()),
             }),
         }
     }
 }
 
-/* RecipeId */
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Sorter {
+    pub name: String,
 
-#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd)]
-pub struct RecipeId(pub usize);
+    /// Items per second that are moved by the sorter.
+    pub speed: f64,
+}
 
-impl FromStr for RecipeId {
-    type Err = <usize as FromStr>::Err;
+impl<'lua> FromLua<'lua> for Sorter {
+    fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
+        match value {
+            Value::Table(table) => {
+                let name = field(&table, "name")?;
+                let speed = field(&table, "speed")?;
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        Ok(Self(usize::from_str(s)?))
+                Ok(Self { name, speed })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Sorter",
+                message: Some("expected a table".into()),
+            }),
+        }
     }
 }
 
-impl<'lua> FromLua<'lua> for RecipeId {
+/* TechId */
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash, Ord, PartialOrd, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TechId(pub usize);
+
+impl Display for TechId {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl<'lua> FromLua<'lua> for TechId {
     fn from_lua(value: Value<'lua>, lua: Context<'lua>) -> Result<Self, LuaError> {
         Ok(Self(usize::from_lua(value, lua)?))
     }
 }
 
-/* Recipe */
+/* Tech */
 
-#[derive(Debug)]
-pub struct Recipe {
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Tech {
     pub name: String,
-    pub type_: RecipeType,
-    pub seconds: f64,
-    pub explicit: bool,
-    pub inputs: Vec<ItemAmount>,
-    pub outputs: Vec<ItemAmount>,
-}
 
-/* ItemAmount */
+    /// Recipes that are unlocked by the tech.
+    pub recipes: Vec<RecipeId>,
 
-#[derive(Debug)]
-pub struct ItemAmount {
-    pub id: ItemId,
-    pub amount: usize,
+    /// Techs that need to be researched before this tech.
+    pub pre_techs: Vec<TechId>,
 }
 
-impl ItemAmount {
-    fn from_tuple(
-        (id, amount): (Result<usize, LuaError>, Result<usize, LuaError>),
-    ) -> Result<Self, LuaError> {
-        Ok(Self {
-            id: ItemId(id?),
-            amount: amount?,
-        })
+impl<'lua> FromLua<'lua> for Tech {
+    fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
+        match value {
+            Value::Table(table) => {
+                let name = field(&table, "name")?;
+                let recipes = field::<Option<_>>(&table, "recipes")?.unwrap_or_default();
+                let pre_techs = field::<Option<_>>(&table, "pre_techs")?.unwrap_or_default();
+
+                Ok(Self {
+                    name,
+                    recipes,
+                    pre_techs,
+                })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Tech",
+                message: Some("expected a table".into()),
+            }),
+        }
     }
 }
 
-/* RecipeType */
+/* Locale */
 
-#[derive(Debug)]
-pub enum RecipeType {
-    Assemble,
-    Chemical,
-    Fractionate,
-    Particle,
-    Refine,
-    Research,
-    Smelt,
-    Unknown(String),
+/// Translated names of the items and recipes.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Locale {
+    pub items: HashMap<ItemId, String>,
+    pub recipes: HashMap<RecipeId, String>,
 }
 
-impl<'lua> FromLua<'lua> for RecipeType {
+impl<'lua> FromLua<'lua> for Locale {
     fn from_lua(value: Value<'lua>, _lua: Context<'lua>) -> Result<Self, LuaError> {
         match value {
-            Value::String(s) => Ok(match s.to_str()? {
-                "ASSEMBLE" => Self::Assemble,
-                "CHEMICAL" => Self::Chemical,
-                "FRACTIONATE" => Self::Fractionate,
-                "PARTICLE" => Self::Particle,
-                "REFINE" => Self::Refine,
-                "RESEARCH" => Self::Research,
-                "SMELT" => Self::Smelt,
-                s => Self::Unknown(dbg!(s.into())),
-            }),
-            _ => Err(LuaError::FromLuaConversionError {
-                from: "ItemType",
-                to: "ItemType",
-                message: None,
+            Value::Table(table) => {
+                let items = field::<Option<_>>(&table, "items")?.unwrap_or_default();
+                let recipes = field::<Option<_>>(&table, "recipes")?.unwrap_or_default();
+
+                Ok(Self { items, recipes })
+            }
+            value => Err(LuaError::FromLuaConversionError {
+                from: value.type_name(),
+                to: "Locale",
+                message: Some("expected a table".into()),
             }),
         }
     }
 }
+
+/* Helper */
+
+/// Compare two game versions by their numeric parts, so `0.10.30` is newer
+/// than `0.9.27`. Parts that are not numbers are compared as text.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let parts = |s: &str| {
+        s.split(['.', '-'])
+            .map(|part| (part.parse::<u64>().ok(), part.to_owned()))
+            .collect::<Vec<_>>()
+    };
+
+    parts(a).cmp(&parts(b)).then_with(|| a.cmp(b))
+}
+
+/// Get the passed field of a table and add the name of the field to the
+/// error if the conversion fails.
+fn field<'lua, T: FromLua<'lua>>(table: &Table<'lua>, key: &str) -> Result<T, LuaError> {
+    table
+        .get(key)
+        .map_err(|err| with_context(err, &format!("field '{}'", key)))
+}
+
+/// Parse a table of entries. The table may either be a map keyed by the id
+/// of the entries or a list of entries that contain their id in the `id`
+/// field. If both are present they need to match. Ids may be numbers or
+/// string keys.
+fn entries<'lua, K, T>(
+    lua: Context<'lua>,
+    table: Table<'lua>,
+    kind: &str,
+) -> Result<HashMap<K, T>, LuaError>
+where
+    K: FromLua<'lua> + Display + Eq + Hash,
+    T: FromLua<'lua>,
+{
+    let pairs = table
+        .pairs::<Value, Value>()
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|err| with_context(err, &format!("{} id", kind)))?;
+    let is_list = pairs.iter().all(|(key, _)| match key {
+        Value::Integer(key) => (1..=pairs.len() as i64).contains(key),
+        _ => false,
+    });
+
+    pairs
+        .into_iter()
+        .map(|(key, value)| {
+            let key =
+                K::from_lua(key, lua).map_err(|err| with_context(err, &format!("{} id", kind)))?;
+            let inner = match &value {
+                Value::Table(table) => table
+                    .get::<_, Option<K>>("id")
+                    .map_err(|err| with_context(err, &format!("{} {} field 'id'", kind, key)))?,
+                _ => None,
+            };
+
+            let key = match inner {
+                Some(inner) if is_list => inner,
+                Some(inner) if inner != key => {
+                    return Err(LuaError::FromLuaConversionError {
+                        from: "table",
+                        to: "entry",
+                        message: Some(format!(
+                            "{} {}: id {} does not match the key of the entry",
+                            kind, key, inner
+                        )),
+                    })
+                }
+                _ => key,
+            };
+
+            let value = T::from_lua(value, lua)
+                .map_err(|err| with_context(err, &format!("{} {}", kind, key)))?;
+
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// Parse a list of item ids and amounts.
+fn amounts<'lua>(
+    lua: Context<'lua>,
+    table: &Table<'lua>,
+    key: &str,
+) -> Result<Vec<ItemAmount>, LuaError> {
+    field::<Table>(table, key)?
+        .sequence_values::<Value>()
+        .tuples::<(_, _)>()
+        .map(|tuple| ItemAmount::from_tuple(lua, tuple))
+        .collect::<Result<_, _>>()
+        .map_err(|err| with_context(err, &format!("field '{}'", key)))
+}
+
+/// Prefix the message of the passed error with a description of the value
+/// that was converted.
+fn with_context(err: LuaError, context: &str) -> LuaError {
+    match err {
+        LuaError::FromLuaConversionError { from, to, message } => {
+            LuaError::FromLuaConversionError {
+                from,
+                to,
+                message: Some(match message {
+                    Some(message) => format!("{}: {}", context, message),
+                    None => context.to_owned(),
+                }),
+            }
+        }
+        err => LuaError::RuntimeError(format!("{}: {}", context, err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    use crate::error::EXIT_DATA;
+
+    const KEYED_SOURCE: &str = r#"
+        return {
+          game_items = {
+            { id = 1, name = "Ore", type = "RESOURCE" },
+            { id = "mymod:magnet", name = "Magnet", type = "COMPONENT" },
+          },
+          game_recipes = {
+            { id = "mymod:magnet", name = "Magnet", type = "ASSEMBLE", seconds = 1,
+              inputs = { 1, 2 }, outputs = { "mymod:magnet", 1 } },
+          },
+        }
+    "#;
+
+    #[test]
+    fn string_keys_resolve_and_display() {
+        let data = Data::from_lua_source("keyed", KEYED_SOURCE, None, &[]).unwrap();
+
+        let iid = ItemId::parse("mymod:magnet", &data.keys).unwrap();
+        let rid = RecipeId::parse("mymod:magnet", &data.keys).unwrap();
+        assert_eq!(data.items[&iid].name, "Magnet");
+        assert_eq!(data.recipes[&rid].outputs[0].id, iid);
+        assert_eq!(iid.label(&data.keys).to_string(), "mymod:magnet");
+
+        assert_eq!(ItemId::parse("1", &data.keys).unwrap(), ItemId(1));
+        assert!(ItemId::parse("mymod:unknown", &data.keys).is_err());
+        assert!(iid.to_string().starts_with('#'));
+    }
+
+    #[test]
+    fn keys_are_stable_between_loads() {
+        let a = Data::from_lua_source("a", KEYED_SOURCE, None, &[]).unwrap();
+        let b = Data::from_lua_source("b", KEYED_SOURCE, None, &[]).unwrap();
+
+        assert_eq!(a.keys.id("mymod:magnet"), b.keys.id("mymod:magnet"));
+        assert_eq!(key_id("mymod:magnet") & KEYED, KEYED);
+    }
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests/data")
+            .join(name)
+    }
+
+    #[test]
+    fn load_reads_data_files() {
+        let data = Data::load(fixture("small.lua")).unwrap();
+
+        assert_eq!(data.items.len(), 3);
+        assert_eq!(data.recipes.len(), 2);
+        assert_eq!(data.as_output[&ItemId(3)], [RecipeId(2)]);
+        assert_eq!(data.sources.len(), 1);
+    }
+
+    #[test]
+    fn load_maps_the_errors() {
+        let err = Data::load(fixture("missing.lua")).unwrap_err();
+        assert!(matches!(err, Error::IoError(_)), "{:?}", err);
+
+        let err = Data::load(fixture("syntax_error.lua")).unwrap_err();
+        assert!(matches!(err, Error::LuaError(_)), "{:?}", err);
+        assert!(err.to_string().contains("syntax_error.lua"), "{}", err);
+    }
+
+    #[test]
+    fn load_reads_gzipped_data_files() {
+        // The fixture consists of two gzip members.
+        let data = Data::load(fixture("small.lua.gz")).unwrap();
+
+        assert_eq!(data.items.len(), 3);
+        assert_eq!(data.recipes.len(), 2);
+        assert_eq!(data.items[&ItemId(3)].name, "Gear");
+    }
+
+    #[test]
+    fn load_rejects_corrupt_gzip_files() {
+        let err = Data::load(fixture("corrupt.lua.gz")).unwrap_err();
+
+        assert_eq!(err.exit_code(), EXIT_DATA);
+        assert!(
+            err.to_string()
+                .starts_with("Unable to decompress data file"),
+            "{}",
+            err
+        );
+        assert!(err.to_string().contains("corrupt.lua.gz"), "{}", err);
+    }
+
+    #[test]
+    fn data_functions_get_the_arguments() {
+        let source = r#"
+            return function(args)
+              local items = {}
+              for i = 1, args.count do
+                items[i] = { id = i, name = args.prefix .. i, type = "COMPONENT" }
+              end
+              return { game_items = items, game_recipes = {} }
+            end
+        "#;
+        let args = ["count=2".parse().unwrap(), "prefix=Item-".parse().unwrap()];
+        let data = Data::from_lua_source("function", source, None, &args).unwrap();
+
+        assert_eq!(data.items.len(), 2);
+        assert_eq!(data.items[&ItemId(2)].name, "Item-2");
+    }
+
+    #[test]
+    fn registered_entries_are_added() {
+        let source = r#"
+            register_item { id = 1, name = "Ore", type = "RESOURCE" }
+            register_item { id = "mymod:magnet", name = "Magnet", type = "COMPONENT" }
+            register_recipe { id = 1, name = "Magnet", type = "ASSEMBLE", seconds = 1,
+              inputs = { 1, 1 }, outputs = { "mymod:magnet", 1 } }
+        "#;
+        let data = Data::from_lua_source("registered", source, None, &[]).unwrap();
+
+        let iid = ItemId::parse("mymod:magnet", &data.keys).unwrap();
+        assert_eq!(data.items[&iid].name, "Magnet");
+        assert_eq!(data.as_output[&iid], [RecipeId(1)]);
+    }
+
+    #[test]
+    fn registered_entries_are_checked() {
+        let error = |source: &str| {
+            Data::from_lua_source("registered", source, None, &[])
+                .unwrap_err()
+                .to_string()
+        };
+
+        let err = error(
+            r#"
+                register_item { id = 1, name = "Ore", type = "RESOURCE" }
+                register_item { id = 2, name = "Ingot" }
+            "#,
+        );
+        assert!(
+            err.contains("register_item: missing field 'type'"),
+            "{}",
+            err
+        );
+        assert!(err.contains("registered:3:"), "{}", err);
+
+        let err = error(
+            r#"
+                register_item { id = "mymod:ore", name = "Ore", type = "RESOURCE" }
+                register_item { id = "mymod:ore", name = "Ore", type = "RESOURCE" }
+            "#,
+        );
+        assert!(
+            err.contains("item mymod:ore is already registered"),
+            "{}",
+            err
+        );
+
+        let err = error(
+            r#"
+                register_item { id = 1, name = "Ore", type = "RESOURCE" }
+                return {
+                  game_items = { { id = 1, name = "Ore", type = "RESOURCE" } },
+                  game_recipes = {},
+                }
+            "#,
+        );
+        assert!(err.contains("Item 1 is registered at"), "{}", err);
+    }
+
+    #[test]
+    fn entries_display_their_names() {
+        let data = Data::load(fixture("small.lua")).unwrap();
+
+        assert_eq!(ItemId(3).to_string(), "3");
+        assert_eq!(RecipeId(2).to_string(), "2");
+        assert_eq!(data.items[&ItemId(2)].to_string(), "Ingot (MATERIAL)");
+        assert_eq!(
+            data.recipes[&RecipeId(2)].display_with(&data).to_string(),
+            "2× Ingot → 1× Gear (1s, ASSEMBLE)"
+        );
+    }
+
+    #[test]
+    fn recipes_display_missing_items_by_id() {
+        let source = r#"
+            return {
+              game_items = { { id = 1, name = "Ore", type = "RESOURCE" } },
+              game_recipes = {
+                { id = 1, name = "Magnet", type = "SMELT", seconds = 1.5,
+                  inputs = { 1, 1 }, outputs = { "mymod:magnet", 1 } },
+              },
+            }
+        "#;
+        let data = Data::from_lua_source("missing", source, None, &[]).unwrap();
+
+        assert_eq!(
+            data.recipes[&RecipeId(1)].display_with(&data).to_string(),
+            "1× Ore → 1× mymod:magnet (1.5s, SMELT)"
+        );
+    }
+
+    #[test]
+    fn filter_drops_dangling_recipes() {
+        let data = Data::load(fixture("small.lua")).unwrap();
+
+        let filtered = data.filter(|iid, _| iid.0 != 1, |_, _| true, false);
+        assert_eq!(filtered.items.len(), 2);
+        assert_eq!(filtered.recipes.keys().collect::<Vec<_>>(), [&RecipeId(2)]);
+        assert!(!filtered.as_input.contains_key(&ItemId(1)));
+        assert!(!filtered.as_output.contains_key(&ItemId(2)));
+        assert!(!filtered.item_by_name.contains_key("Ore"));
+
+        let data = Data::load(fixture("small.lua")).unwrap();
+        let filtered = data.filter(|iid, _| iid.0 != 1, |rid, _| rid.0 != 2, true);
+        assert_eq!(filtered.recipes.keys().collect::<Vec<_>>(), [&RecipeId(1)]);
+        assert_eq!(filtered.as_output[&ItemId(2)], [RecipeId(1)]);
+    }
+
+    #[test]
+    fn key_collision_fails() {
+        let mut keys = Keys(BTreeMap::from([(key_id("mymod:a"), "mymod:b".to_owned())]));
+
+        assert!(keys.insert("mymod:a").is_err());
+        assert!(keys.insert("mymod:c").is_ok());
+
+        let mut other = Keys::default();
+        other.insert("mymod:a").unwrap();
+        assert!(keys.merge(other).is_err());
+    }
+}
@@ -0,0 +1,108 @@
+use std::fs::{create_dir_all, read, write};
+use std::io::Read;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::{data::fnv, error::Error};
+
+/// Time to wait for the server before the download is aborted.
+const TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Largest data file that is downloaded.
+const MAX_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Download the data file from the passed URL.
+///
+/// Downloaded files are stored in the download cache. If `offline` is set, the
+/// file is only taken from the cache and the network is never used.
+pub fn fetch(url: &str, offline: bool) -> Result<Vec<u8>, Error> {
+    let file = cache_file(url);
+
+    if offline {
+        return match file.as_ref().map(read) {
+            Some(Ok(source)) => Ok(source),
+            _ => Err(Error::HttpError(format!(
+                "{} was not downloaded yet and --offline is set",
+                url
+            ))),
+        };
+    }
+
+    log::info!("  download {}", url);
+    let source = download(url)?;
+
+    if let Some(file) = file {
+        let stored = file
+            .parent()
+            .map_or(Ok(()), create_dir_all)
+            .and_then(|()| write(&file, &source));
+        if let Err(err) = stored {
+            log::warn!("Unable to write download cache {:#?}: {}", file, err);
+        }
+    }
+
+    Ok(source)
+}
+
+fn download(url: &str) -> Result<Vec<u8>, Error> {
+    let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+    let response = agent.get(url).call().map_err(|err| match err {
+        ureq::Error::Status(status, response) => Error::HttpError(format!(
+            "{} returned {} {}",
+            url,
+            status,
+            response.status_text()
+        )),
+        ureq::Error::Transport(err) => Error::HttpError(err.to_string()),
+    })?;
+
+    if response.status() != 200 {
+        return Err(Error::HttpError(format!(
+            "{} returned {} {}",
+            url,
+            response.status(),
+            response.status_text()
+        )));
+    }
+
+    let mut source = Vec::new();
+    response
+        .into_reader()
+        .take(MAX_SIZE + 1)
+        .read_to_end(&mut source)
+        .map_err(|err| Error::HttpError(format!("{}: {}", url, err)))?;
+
+    if source.len() as u64 > MAX_SIZE {
+        return Err(Error::HttpError(format!(
+            "{} is larger than {} MiB",
+            url,
+            MAX_SIZE / 1024 / 1024
+        )));
+    }
+
+    Ok(source)
+}
+
+/// Path of the download cache file for the passed URL.
+fn cache_file(url: &str) -> Option<PathBuf> {
+    let dir = dirs::cache_dir()?.join("dsp-tool").join("downloads");
+
+    Some(dir.join(format!("{:016x}", fnv(url.as_bytes()))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_files_are_named_by_the_url() {
+        let Some(file) = cache_file("https://example.com/data.lua") else {
+            return;
+        };
+
+        assert_eq!(file, cache_file("https://example.com/data.lua").unwrap());
+        assert_ne!(file, cache_file("https://example.com/data.lua.gz").unwrap());
+        assert_eq!(file.file_name().unwrap().len(), 16);
+        assert!(file.parent().unwrap().ends_with("dsp-tool/downloads"));
+    }
+}
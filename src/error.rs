@@ -1,6 +1,8 @@
 use std::io::Error as IoError;
 
+use notify_debouncer_mini::notify::Error as NotifyError;
 use rlua::Error as LuaError;
+use serde_json::Error as JsonError;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,6 +13,30 @@ pub enum Error {
     #[error("Lua Error: {0}")]
     LuaError(#[from] LuaError),
 
+    #[error("JSON Error: {0}")]
+    JsonError(#[from] JsonError),
+
+    #[error("HTTP Error: {0}")]
+    HttpError(String),
+
+    #[error("Graph Error: {0}")]
+    GraphError(String),
+
+    #[error("Watch Error: {0}")]
+    NotifyError(#[from] NotifyError),
+
+    /// Invalid arguments or selectors.
+    #[error("{0}")]
+    UsageError(String),
+
+    /// Data file that can not be loaded or fails a validation.
+    #[error("{0}")]
+    DataError(String),
+
+    /// Failure of an external tool like graphviz.
+    #[error("{0}")]
+    ToolError(String),
+
     #[error("{0}")]
     Custom(String),
 }
@@ -19,4 +45,52 @@ impl Error {
     pub fn custom<T: ToString>(msg: T) -> Self {
         Self::Custom(msg.to_string())
     }
+
+    pub fn usage<T: ToString>(msg: T) -> Self {
+        Self::UsageError(msg.to_string())
+    }
+
+    pub fn data<T: ToString>(msg: T) -> Self {
+        Self::DataError(msg.to_string())
+    }
+
+    pub fn tool<T: ToString>(msg: T) -> Self {
+        Self::ToolError(msg.to_string())
+    }
+
+    /// Exit code of the tool if a command fails with this error, see
+    /// [`EXIT_CODES`].
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::UsageError(_) => EXIT_USAGE,
+            Self::LuaError(_) | Self::DataError(_) => EXIT_DATA,
+            Self::IoError(_) | Self::HttpError(_) | Self::NotifyError(_) => EXIT_IO,
+            Self::ToolError(_) => EXIT_TOOL,
+            Self::JsonError(_) | Self::GraphError(_) | Self::Custom(_) => EXIT_FAILURE,
+        }
+    }
 }
+
+/// Exit code of errors without a more specific category.
+pub const EXIT_FAILURE: i32 = 1;
+
+/// Exit code of invalid arguments and selectors.
+pub const EXIT_USAGE: i32 = 2;
+
+/// Exit code of data files that can not be loaded or are invalid.
+pub const EXIT_DATA: i32 = 3;
+
+/// Exit code of failed file, network or watch operations.
+pub const EXIT_IO: i32 = 4;
+
+/// Exit code of failed external tools like graphviz.
+pub const EXIT_TOOL: i32 = 5;
+
+/// Description of the exit codes for the help of the tool.
+pub const EXIT_CODES: &str = "EXIT CODES:
+    0    Success
+    1    Generic failure
+    2    Invalid arguments or selectors
+    3    Data file can not be loaded or is invalid
+    4    File, network or watch error
+    5    External tool (like graphviz) failed";
@@ -0,0 +1,2828 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::Write;
+use std::iter;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use itertools::Itertools;
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+    analysis::{tiers, Node},
+    data::{
+        Data, IdLabel, ItemAmount, ItemId, ItemType, Keys, Recipe, RecipeId, RecipeSignature,
+        RecipeType,
+    },
+    error::Error,
+    format::csv_line,
+    meta::Meta,
+    planner::{PlanOptions, ProductionPlan},
+    resolver::{Direction, Origin, RecipeSelector, Resolver},
+    selector::Selection,
+    solver::{self, Flow},
+    theme::{Attributes, Theme},
+};
+
+/* ProductionGraph */
+
+/// Items and recipes that are needed to produce a set of root items, and the
+/// edges between them.
+#[derive(Debug, Clone, Default)]
+pub struct ProductionGraph {
+    pub items: BTreeMap<ItemId, ItemNode>,
+    pub recipes: BTreeMap<RecipeId, RecipeNode>,
+
+    /// Names of the ignored items that are still used by the recipes of the
+    /// graph.
+    pub ignored_items: BTreeMap<ItemId, String>,
+
+    /// String keys of the keyed ids, to write the ids with their keys.
+    pub keys: Keys,
+}
+
+/// Item of the production graph.
+#[derive(Debug, Clone)]
+pub struct ItemNode {
+    /// Name of the item, or a placeholder if the item is missing in the data.
+    pub name: String,
+
+    /// Icon of the item, relative to the icons directory.
+    pub icon: Option<String>,
+
+    /// Whether the item is not produced by any recipe of the graph.
+    pub source: bool,
+
+    /// Minimum tier of the item, if tiers were requested.
+    pub tier: Option<usize>,
+
+    /// Type of the item, if the item exists in the data.
+    pub type_: Option<ItemType>,
+
+    /// Root item and depth the item was reached with.
+    pub origin: Origin,
+
+    /// Items per minute that are needed, if rates were propagated (see
+    /// [`ProductionGraph::propagate_rates`]).
+    pub rate: Option<f64>,
+}
+
+/// Recipe of the production graph.
+#[derive(Debug, Clone)]
+pub struct RecipeNode {
+    pub name: String,
+    pub seconds: f64,
+    pub inputs: Vec<ItemAmount>,
+    pub outputs: Vec<ItemAmount>,
+
+    /// Factor that is applied to the outputs of the recipe, including the
+    /// proliferator once the rates are propagated.
+    pub productivity: f64,
+
+    pub type_: RecipeType,
+
+    /// Flags that are set for the recipe.
+    pub flags: Vec<RecipeFlag>,
+
+    /// Item the recipe was added for.
+    pub item: ItemId,
+
+    /// Root item and depth the recipe was reached with.
+    pub origin: Origin,
+
+    /// Crafts per minute that are needed, if rates were propagated.
+    pub rate: Option<f64>,
+
+    /// Duplicates of the recipe that were merged into this node (see
+    /// [`ProductionGraph::merge_duplicate_recipes`]).
+    pub merged: Vec<RecipeId>,
+}
+
+/// Edge of the production graph, from an input item to a recipe or from a
+/// recipe to an output item.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Edge {
+    pub from: Node,
+    pub to: Node,
+    pub amount: usize,
+
+    /// Items per minute that flow across the edge, if rates were propagated.
+    pub rate: Option<f64>,
+}
+
+/// Boolean property of a recipe. Every flag that is set is written as
+/// attribute of the recipe node and styled by the theme (see
+/// [`Theme::recipe_flags`]).
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Ord, PartialOrd)]
+pub enum RecipeFlag {
+    /// Alternative recipe (see [`Recipe::explicit`]).
+    Explicit,
+
+    /// Recipe can be crafted in the replicator.
+    Handcraft,
+
+    /// Recipe can receive the extra products bonus of proliferators.
+    Productive,
+
+    /// Recipe is disabled in the data (see `--include-disabled`).
+    Disabled,
+}
+
+impl RecipeFlag {
+    pub const ALL: [Self; 4] = [
+        Self::Explicit,
+        Self::Handcraft,
+        Self::Productive,
+        Self::Disabled,
+    ];
+
+    /// Name of the flag in attributes and themes.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Explicit => "explicit",
+            Self::Handcraft => "handcraft",
+            Self::Productive => "productive",
+            Self::Disabled => "disabled",
+        }
+    }
+
+    /// Whether the flag is set for the passed recipe.
+    pub fn is_set(&self, recipe: &Recipe) -> bool {
+        match self {
+            Self::Explicit => recipe.explicit,
+            Self::Handcraft => recipe.handcraft,
+            Self::Productive => !recipe.non_productive,
+            Self::Disabled => recipe.disabled,
+        }
+    }
+}
+
+/// Options that control which recipes are added to the graph.
+#[derive(Debug, Default)]
+pub struct GraphOptions {
+    /// Items and recipes that are not added to the graph.
+    pub ignore: Selection,
+
+    /// Add the recipes of the inputs, recursively, instead of only the
+    /// recipes of the root items.
+    pub resolve_deps: bool,
+
+    /// Only add recipes up to this depth while resolving the dependencies.
+    /// The recipes of the root items have a depth of 0.
+    pub max_depth: Option<usize>,
+
+    /// Direction the recipes are followed in.
+    pub direction: Direction,
+
+    /// Selects the recipes that are used for items with multiple producing
+    /// recipes.
+    pub selector: Box<dyn RecipeSelector>,
+
+    /// Calculate the tier of each item. The tiers are calculated from all
+    /// recipes that are not ignored, not only the ones of the graph.
+    pub tiers: bool,
+
+    /// Merge recipes with the same inputs, outputs and crafting time into
+    /// one node.
+    pub merge_duplicates: bool,
+
+    /// Item types that are removed from the graph, including the edges of
+    /// their items. The items also have to be ignored, so their recipes are
+    /// not followed (see [`GraphBuilder::exclude_types`]).
+    pub exclude_types: Vec<ItemType>,
+}
+
+impl ProductionGraph {
+    /// Create the graph of the recipes that produce the passed root items.
+    pub fn new<I>(data: &Data, roots: I, options: &GraphOptions) -> Self
+    where
+        I: IntoIterator<Item = ItemId>,
+    {
+        let mut resolver = Resolver::new(data, &options.ignore, options.resolve_deps)
+            .max_depth(options.max_depth)
+            .direction(options.direction)
+            .selector(options.selector.as_ref());
+        resolver.resolve(roots);
+
+        let mut graph = Self {
+            keys: data.keys.clone(),
+            ..Self::default()
+        };
+
+        let amounts = |amounts: &[ItemAmount]| {
+            amounts
+                .iter()
+                .filter(|a| {
+                    data.items
+                        .get(&a.id)
+                        .is_none_or(|item| !options.exclude_types.contains(&item.type_))
+                })
+                .copied()
+                .collect::<Vec<_>>()
+        };
+
+        for (rid, origin) in &resolver.recipes {
+            if let Some(recipe) = data.recipes.get(rid) {
+                graph.recipes.insert(
+                    *rid,
+                    RecipeNode {
+                        name: recipe.name.clone(),
+                        seconds: recipe.seconds,
+                        inputs: amounts(&recipe.inputs),
+                        outputs: amounts(&recipe.outputs),
+                        productivity: recipe.productivity,
+                        type_: recipe.type_.clone(),
+                        flags: RecipeFlag::ALL
+                            .into_iter()
+                            .filter(|flag| flag.is_set(recipe))
+                            .collect(),
+                        item: resolver.recipe_items[rid],
+                        origin: *origin,
+                        rate: None,
+                        merged: Vec::new(),
+                    },
+                );
+            }
+        }
+
+        if options.merge_duplicates {
+            graph.merge_duplicate_recipes();
+        }
+
+        let produced = graph
+            .recipes
+            .values()
+            .flat_map(|r| r.outputs.iter().map(|o| o.id))
+            .collect::<BTreeSet<_>>();
+
+        let tiers = options.tiers.then(|| {
+            let recipes = data
+                .recipes
+                .keys()
+                .filter(|rid| !options.ignore.recipes.contains(rid))
+                .copied()
+                .collect::<BTreeSet<_>>();
+
+            tiers(data, &recipes)
+        });
+
+        for (iid, origin) in &resolver.items {
+            let item = data.items.get(iid);
+
+            graph.items.insert(
+                *iid,
+                ItemNode {
+                    name: item_name(data, *iid),
+                    icon: item.and_then(|i| i.icon.clone()),
+                    source: !produced.contains(iid),
+                    tier: tiers
+                        .as_ref()
+                        .filter(|_| item.is_some())
+                        .and_then(|tiers| tiers.get(iid))
+                        .map(|tier| tier.min),
+                    type_: item.map(|i| i.type_.clone()),
+                    origin: *origin,
+                    rate: None,
+                },
+            );
+        }
+
+        let used = graph
+            .edges()
+            .flat_map(|edge| [edge.from, edge.to])
+            .filter_map(|node| match node {
+                Node::Item(iid) => Some(iid),
+                Node::Recipe(_) => None,
+            })
+            .collect::<BTreeSet<_>>();
+        graph.ignored_items = used
+            .into_iter()
+            .filter(|iid| !graph.items.contains_key(iid))
+            .map(|iid| (iid, item_name(data, iid)))
+            .collect();
+
+        graph
+    }
+
+    /// Merge the recipes that have the same inputs, outputs and crafting time
+    /// (see [`Recipe::signature`](crate::data::Recipe::signature)) into the
+    /// recipe with the smallest id. Returns the number of removed recipes.
+    pub fn merge_duplicate_recipes(&mut self) -> usize {
+        let mut groups = BTreeMap::<RecipeSignature, Vec<RecipeId>>::new();
+        for (rid, recipe) in &self.recipes {
+            let signature = RecipeSignature::new(&recipe.inputs, &recipe.outputs, recipe.seconds);
+            groups.entry(signature).or_default().push(*rid);
+        }
+
+        let mut ret = 0;
+        for rids in groups.into_values().filter(|rids| rids.len() > 1) {
+            let (kept, merged) = rids.split_first().unwrap();
+            log::info!(
+                recipe = kept.0,
+                merged:% = merged.iter().join(",");
+                "  merge duplicate recipes {} into recipe {}",
+                merged.iter().join(", "),
+                kept.0
+            );
+
+            let mut rate = self.recipes[kept].rate;
+            for rid in merged {
+                let recipe = self.recipes.remove(rid).unwrap();
+                if let Some(r) = recipe.rate {
+                    rate = Some(rate.unwrap_or_default() + r);
+                }
+            }
+
+            let recipe = self.recipes.get_mut(kept).unwrap();
+            recipe.merged.extend(merged);
+            recipe.rate = rate;
+            ret += merged.len();
+        }
+
+        ret
+    }
+
+    /// Add the items and recipes of an older version of the graph that do
+    /// not exist in this graph, and return the differences between both.
+    ///
+    /// The elements of both graphs are matched by their id, or by their name
+    /// if the id does not exist in this graph.
+    pub fn combine(&mut self, old: ProductionGraph) -> GraphDiff {
+        let old = old.remap(self);
+        let mut diff = GraphDiff::default();
+
+        for (rid, recipe) in &old.recipes {
+            let new = match self.recipes.get(rid) {
+                Some(new) => new,
+                None => {
+                    diff.recipes.insert(*rid, Change::Removed);
+
+                    continue;
+                }
+            };
+
+            let signature = |r: &RecipeNode| RecipeSignature::new(&r.inputs, &r.outputs, r.seconds);
+            if signature(new) != signature(recipe) || new.name != recipe.name {
+                diff.recipes.insert(*rid, Change::Changed);
+            }
+
+            let old_edges = old
+                .recipe_edges(*rid)
+                .map(|e| ((e.from, e.to), e))
+                .collect::<BTreeMap<_, _>>();
+            let new_edges = self
+                .recipe_edges(*rid)
+                .map(|e| ((e.from, e.to), e))
+                .collect::<BTreeMap<_, _>>();
+
+            for (key, edge) in &new_edges {
+                match old_edges.get(key) {
+                    None => {
+                        diff.added_edges.insert(*key);
+                    }
+                    Some(old) if old.amount != edge.amount => {
+                        diff.changed_edges.insert(*key, old.amount);
+                    }
+                    Some(_) => (),
+                }
+            }
+            for (key, edge) in old_edges {
+                if !new_edges.contains_key(&key) {
+                    diff.removed_edges.entry(*rid).or_default().push(edge);
+                }
+            }
+        }
+        for rid in self.recipes.keys() {
+            if !old.recipes.contains_key(rid) {
+                diff.recipes.insert(*rid, Change::Added);
+            }
+        }
+
+        for (iid, item) in &old.items {
+            match self.items.get(iid) {
+                None => {
+                    diff.items.insert(*iid, Change::Removed);
+                }
+                Some(new) if new.name != item.name => {
+                    diff.items.insert(*iid, Change::Changed);
+                }
+                Some(_) => (),
+            }
+        }
+        for iid in self.items.keys() {
+            if !old.items.contains_key(iid) {
+                diff.items.insert(*iid, Change::Added);
+            }
+        }
+
+        for (iid, item) in old.items {
+            self.items.entry(iid).or_insert(item);
+        }
+        for (rid, recipe) in old.recipes {
+            self.recipes.entry(rid).or_insert(recipe);
+        }
+        for (iid, name) in old.ignored_items {
+            if !self.items.contains_key(&iid) {
+                self.ignored_items.entry(iid).or_insert(name);
+            }
+        }
+        // Keys of the old data that collide with a key of this data keep the
+        // key of this data.
+        if let Err(err) = self.keys.merge(old.keys) {
+            log::warn!("Unable to add all keys of the old data: {}", err);
+        }
+
+        for change in diff.items.values() {
+            diff.counts.items.add(*change);
+        }
+        for change in diff.recipes.values() {
+            diff.counts.recipes.add(*change);
+        }
+        for (rid, edge) in self
+            .recipes
+            .keys()
+            .flat_map(|rid| self.recipe_edges(*rid).map(move |edge| (*rid, edge)))
+        {
+            if let Some(change) = diff.edge(rid, &edge) {
+                diff.counts.edges.add(change);
+            }
+        }
+        diff.counts.edges.removed += diff.removed_edges.values().map(Vec::len).sum::<usize>();
+
+        diff
+    }
+
+    /// Change the ids of the items and recipes to the ids of the matching
+    /// elements of `other`, see [`ProductionGraph::combine`].
+    fn remap(self, other: &ProductionGraph) -> Self {
+        let items = self
+            .items
+            .iter()
+            .map(|(iid, item)| (*iid, item.name.as_str()))
+            .chain(
+                self.ignored_items
+                    .iter()
+                    .map(|(iid, name)| (*iid, name.as_str())),
+            )
+            .map(|(iid, name)| {
+                let known =
+                    other.items.contains_key(&iid) || other.ignored_items.contains_key(&iid);
+                let by_name = other
+                    .items
+                    .iter()
+                    .find(|(_, item)| item.name == name)
+                    .map(|(iid, _)| *iid);
+
+                match (known, by_name) {
+                    (false, Some(id)) => (iid, id),
+                    (_, _) => (iid, iid),
+                }
+            })
+            .collect::<BTreeMap<_, _>>();
+        let item = |iid: ItemId| items.get(&iid).copied().unwrap_or(iid);
+
+        let recipe_id = |rid: RecipeId, name: &str| {
+            if other.recipes.contains_key(&rid) {
+                return rid;
+            }
+
+            other
+                .recipes
+                .iter()
+                .find(|(_, recipe)| recipe.name == name)
+                .map(|(rid, _)| *rid)
+                .unwrap_or(rid)
+        };
+
+        let remap_amounts = |amounts: Vec<ItemAmount>| {
+            amounts
+                .into_iter()
+                .map(|a| ItemAmount {
+                    id: item(a.id),
+                    amount: a.amount,
+                })
+                .collect()
+        };
+
+        Self {
+            items: self
+                .items
+                .into_iter()
+                .map(|(iid, node)| (item(iid), node))
+                .collect(),
+            recipes: self
+                .recipes
+                .into_iter()
+                .map(|(rid, mut node)| {
+                    node.inputs = remap_amounts(node.inputs);
+                    node.outputs = remap_amounts(node.outputs);
+                    node.item = item(node.item);
+
+                    (recipe_id(rid, &node.name), node)
+                })
+                .collect(),
+            ignored_items: self
+                .ignored_items
+                .into_iter()
+                .map(|(iid, name)| (item(iid), name))
+                .collect(),
+            keys: self.keys,
+        }
+    }
+
+    /// Items that are consumed by more than `threshold` recipes of the graph.
+    pub fn common_items(&self, threshold: usize) -> BTreeSet<ItemId> {
+        let mut consumers = BTreeMap::<ItemId, usize>::new();
+        for recipe in self.recipes.values() {
+            for iid in recipe.inputs.iter().map(|i| i.id).unique() {
+                *consumers.entry(iid).or_default() += 1;
+            }
+        }
+
+        consumers
+            .into_iter()
+            .filter(|(_, count)| *count > threshold)
+            .map(|(iid, _)| iid)
+            .collect()
+    }
+
+    /// Depth of the deepest item or recipe of the graph.
+    pub fn max_depth(&self) -> usize {
+        let items = self.items.values().map(|i| i.origin.depth);
+        let recipes = self.recipes.values().map(|r| r.origin.depth);
+
+        items.chain(recipes).max().unwrap_or_default()
+    }
+
+    /// Name of the passed item, which may also be an ignored item.
+    pub fn item_name(&self, iid: ItemId) -> Option<&str> {
+        self.items
+            .get(&iid)
+            .map(|i| i.name.as_str())
+            .or_else(|| self.ignored_items.get(&iid).map(String::as_str))
+    }
+
+    /// Edges of the passed recipe, the inputs first.
+    pub fn recipe_edges(&self, rid: RecipeId) -> impl Iterator<Item = Edge> + '_ {
+        let recipe = self.recipes.get(&rid);
+
+        let inputs = recipe.into_iter().flat_map(move |r| {
+            r.inputs.iter().map(move |i| Edge {
+                from: Node::Item(i.id),
+                to: Node::Recipe(rid),
+                amount: i.amount,
+                rate: r.rate.map(|crafts| crafts * i.amount as f64),
+            })
+        });
+        let outputs = recipe.into_iter().flat_map(move |r| {
+            r.outputs.iter().map(move |o| Edge {
+                from: Node::Recipe(rid),
+                to: Node::Item(o.id),
+                amount: o.amount,
+                rate: r
+                    .rate
+                    .map(|crafts| crafts * o.amount as f64 * r.productivity),
+            })
+        });
+
+        inputs.chain(outputs)
+    }
+
+    /// Number of machines of each recipe with a positive rate, calculated
+    /// like the machines of the `plan` command, using the passed options.
+    pub fn machines(&self, data: &Data, options: &PlanOptions) -> BTreeMap<RecipeId, f64> {
+        self.recipes
+            .iter()
+            .filter_map(|(rid, node)| {
+                let crafts = node.rate.filter(|crafts| *crafts > 0.0)?;
+                let recipe = data.recipes.get(rid)?;
+
+                Some((*rid, options.machines(data, recipe, crafts)))
+            })
+            .collect()
+    }
+
+    /// All edges of the graph, ordered by recipe.
+    pub fn edges(&self) -> impl Iterator<Item = Edge> + '_ {
+        self.recipes.keys().flat_map(|rid| self.recipe_edges(*rid))
+    }
+
+    /// Calculate the rates that are needed to produce the passed targets,
+    /// given in items per minute, and attach them to the items and recipes.
+    ///
+    /// The demand of an item is split evenly across the recipes of the graph
+    /// that produce it, and the demands of items with multiple consumers are
+    /// summed up. If the recipes contain a cycle, or a byproduct of a recipe
+    /// is consumed by another one, the steady state of the recipes is solved
+    /// instead (see [`solver::solve`]). Fails if a target is not produced by
+    /// any recipe of the graph.
+    ///
+    /// The outputs of the recipes are multiplied like the ones of the `plan`
+    /// command, so the proliferator of the options is applied to the rates.
+    pub fn propagate_rates(
+        &mut self,
+        data: &Data,
+        targets: &BTreeMap<ItemId, f64>,
+        options: &PlanOptions,
+    ) -> Result<(), Error> {
+        for (rid, node) in &mut self.recipes {
+            if let Some(recipe) = data.recipes.get(rid) {
+                node.productivity = options.output_multiplier(recipe);
+            }
+        }
+
+        let mut producers = BTreeMap::<ItemId, Vec<RecipeId>>::new();
+        for (rid, recipe) in &self.recipes {
+            for output in &recipe.outputs {
+                if output.amount as f64 * recipe.productivity > 0.0 {
+                    producers.entry(output.id).or_default().push(*rid);
+                }
+            }
+        }
+
+        let mut rates = Rates::default();
+        for (iid, rate) in targets {
+            if !producers.contains_key(iid) {
+                return Err(Error::GraphError(format!(
+                    "Target item {} is not produced by any recipe of the graph",
+                    self.item_name(*iid).unwrap_or(&iid.to_string())
+                )));
+            }
+
+            self.add_demand(&producers, &mut rates, *iid, *rate);
+        }
+
+        if rates.cycle || self.has_shared_byproducts(targets) {
+            log::debug!(
+                recipes = self.recipes.len();
+                "  solve the steady state of {} recipes",
+                self.recipes.len()
+            );
+            rates = self.solve_rates(targets)?;
+        }
+
+        for (iid, item) in &mut self.items {
+            item.rate = Some(rates.items.get(iid).copied().unwrap_or_default());
+        }
+        for (rid, recipe) in &mut self.recipes {
+            recipe.rate = Some(rates.recipes.get(rid).copied().unwrap_or_default());
+        }
+
+        Ok(())
+    }
+
+    fn add_demand(
+        &self,
+        producers: &BTreeMap<ItemId, Vec<RecipeId>>,
+        rates: &mut Rates,
+        iid: ItemId,
+        rate: f64,
+    ) {
+        *rates.items.entry(iid).or_default() += rate;
+
+        let producers_of = match producers.get(&iid) {
+            Some(_) if rates.stack.contains(&iid) => {
+                rates.cycle = true;
+
+                return;
+            }
+            Some(rids) => rids,
+            None => return,
+        };
+
+        let share = rate / producers_of.len() as f64;
+
+        rates.stack.push(iid);
+        for rid in producers_of {
+            let recipe = &self.recipes[rid];
+            let amount = recipe
+                .outputs
+                .iter()
+                .filter(|o| o.id == iid)
+                .map(|o| o.amount)
+                .sum::<usize>() as f64;
+            let crafts = share / (amount * recipe.productivity);
+            *rates.recipes.entry(*rid).or_default() += crafts;
+
+            for input in &recipe.inputs {
+                self.add_demand(producers, rates, input.id, crafts * input.amount as f64);
+            }
+        }
+        rates.stack.pop();
+    }
+
+    /// Whether an output of a recipe other than the item it was added for is
+    /// consumed by another recipe, or is a target.
+    fn has_shared_byproducts(&self, targets: &BTreeMap<ItemId, f64>) -> bool {
+        let consumed = self
+            .recipes
+            .values()
+            .flat_map(|r| r.inputs.iter().map(|i| i.id))
+            .chain(targets.keys().copied())
+            .collect::<BTreeSet<_>>();
+
+        self.recipes.values().any(|r| {
+            r.outputs
+                .iter()
+                .any(|o| o.id != r.item && consumed.contains(&o.id))
+        })
+    }
+
+    /// Solve the steady state of all recipes. Each recipe is driven by the
+    /// item it was added for, or by its other outputs if they are missing.
+    fn solve_rates(&self, targets: &BTreeMap<ItemId, f64>) -> Result<Rates, Error> {
+        let mut flows = BTreeMap::new();
+        let mut candidates = BTreeMap::new();
+        for (rid, recipe) in &self.recipes {
+            flows.insert(
+                *rid,
+                Flow {
+                    inputs: recipe
+                        .inputs
+                        .iter()
+                        .map(|i| (i.id, i.amount as f64))
+                        .collect(),
+                    outputs: recipe
+                        .outputs
+                        .iter()
+                        .map(|o| (o.id, o.amount as f64 * recipe.productivity))
+                        .collect(),
+                },
+            );
+
+            let items = std::iter::once(recipe.item)
+                .chain(recipe.outputs.iter().map(|o| o.id))
+                .filter(|iid| self.items.contains_key(iid))
+                .unique()
+                .collect::<Vec<_>>();
+            candidates.insert(*rid, items);
+        }
+
+        let item_name = |iid: ItemId| {
+            self.item_name(iid)
+                .map(ToOwned::to_owned)
+                .unwrap_or_else(|| iid.to_string())
+        };
+        let state = solver::solve(&flows, &candidates, targets, item_name)?;
+
+        let mut rates = Rates::default();
+        for (iid, rate) in targets {
+            *rates.items.entry(*iid).or_default() += rate;
+        }
+        for (rid, crafts) in &state.recipes {
+            for input in &self.recipes[rid].inputs {
+                *rates.items.entry(input.id).or_default() += crafts * input.amount as f64;
+            }
+        }
+        rates.recipes = state.recipes;
+
+        Ok(rates)
+    }
+}
+
+/// State of the rate propagation.
+#[derive(Default)]
+struct Rates {
+    items: BTreeMap<ItemId, f64>,
+    recipes: BTreeMap<RecipeId, f64>,
+    stack: Vec<ItemId>,
+
+    /// An item is needed to produce itself.
+    cycle: bool,
+}
+
+/// Name of the passed item. Items that are missing in the data get a
+/// placeholder name, so their edges are still visible.
+fn item_name(data: &Data, iid: ItemId) -> String {
+    match data.items.get(&iid) {
+        Some(item) => item.name.clone(),
+        None => format!("unknown item {}", iid),
+    }
+}
+
+/* GraphBuilder */
+
+/// Builder of a [`ProductionGraph`].
+///
+/// ```ignore
+/// let graph = GraphBuilder::new(&data)
+///     .targets([ItemId(1101)])
+///     .resolve_deps(true)
+///     .max_depth(3)
+///     .build()?;
+/// ```
+#[derive(Debug)]
+pub struct GraphBuilder<'a> {
+    data: &'a Data,
+    targets: BTreeSet<ItemId>,
+    options: GraphOptions,
+}
+
+impl<'a> GraphBuilder<'a> {
+    pub fn new(data: &'a Data) -> Self {
+        Self {
+            data,
+            targets: BTreeSet::new(),
+            options: GraphOptions::default(),
+        }
+    }
+
+    /// Add root items of the graph.
+    pub fn targets<I>(mut self, targets: I) -> Self
+    where
+        I: IntoIterator<Item = ItemId>,
+    {
+        self.targets.extend(targets);
+
+        self
+    }
+
+    /// Ignore the passed recipes.
+    pub fn ignore_recipes<I>(mut self, recipes: I) -> Self
+    where
+        I: IntoIterator<Item = RecipeId>,
+    {
+        self.options.ignore.recipes.extend(recipes);
+
+        self
+    }
+
+    /// Do not add the passed items, and do not follow their recipes.
+    pub fn exclude_items<I>(mut self, items: I) -> Self
+    where
+        I: IntoIterator<Item = ItemId>,
+    {
+        self.options.ignore.items.extend(items);
+
+        self
+    }
+
+    /// Remove the items of the passed types from the graph, see
+    /// [`GraphOptions::exclude_types`].
+    pub fn exclude_types<I>(mut self, types: I) -> Self
+    where
+        I: IntoIterator<Item = ItemType>,
+    {
+        self.options.exclude_types.extend(types);
+
+        let types = &self.options.exclude_types;
+        self.options.ignore.items.extend(
+            self.data
+                .items
+                .iter()
+                .filter(|(_, item)| types.contains(&item.type_))
+                .map(|(iid, _)| *iid),
+        );
+
+        self
+    }
+
+    /// Ignore the items and recipes of the passed selection.
+    pub fn ignore(self, selection: Selection) -> Self {
+        self.ignore_recipes(selection.recipes)
+            .exclude_items(selection.items)
+    }
+
+    /// See [`GraphOptions::resolve_deps`].
+    pub fn resolve_deps(mut self, resolve_deps: bool) -> Self {
+        self.options.resolve_deps = resolve_deps;
+
+        self
+    }
+
+    /// See [`GraphOptions::max_depth`].
+    pub fn max_depth(mut self, max_depth: usize) -> Self {
+        self.options.max_depth = Some(max_depth);
+
+        self
+    }
+
+    /// See [`GraphOptions::direction`].
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.options.direction = direction;
+
+        self
+    }
+
+    /// See [`GraphOptions::selector`].
+    pub fn selector(mut self, selector: Box<dyn RecipeSelector>) -> Self {
+        self.options.selector = selector;
+
+        self
+    }
+
+    /// See [`GraphOptions::merge_duplicates`].
+    pub fn merge_duplicates(mut self, merge_duplicates: bool) -> Self {
+        self.options.merge_duplicates = merge_duplicates;
+
+        self
+    }
+
+    /// See [`GraphOptions::tiers`].
+    pub fn tiers(mut self, tiers: bool) -> Self {
+        self.options.tiers = tiers;
+
+        self
+    }
+
+    /// Build the graph. Fails if no target is set or a target is excluded.
+    pub fn build(self) -> Result<ProductionGraph, Error> {
+        if self.targets.is_empty() {
+            return Err(Error::usage("No target items selected"));
+        }
+
+        if let Some(iid) = self
+            .targets
+            .iter()
+            .find(|iid| self.options.ignore.items.contains(iid))
+        {
+            return Err(Error::usage(format!(
+                "Target item {} is excluded",
+                item_name(self.data, *iid)
+            )));
+        }
+
+        Ok(ProductionGraph::new(self.data, self.targets, &self.options))
+    }
+}
+
+/* GraphDiff */
+
+/// How an element of a combined graph differs between the two graphs.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Change {
+    /// Only in the new graph.
+    Added,
+
+    /// Only in the old graph.
+    Removed,
+
+    /// In both graphs, with different names or amounts.
+    Changed,
+}
+
+impl Change {
+    fn fill_color(&self) -> &'static str {
+        match self {
+            Self::Added => "#b6d7a8",
+            Self::Removed => "#f4cccc",
+            Self::Changed => "#f9cb9c",
+        }
+    }
+
+    fn line_color(&self) -> &'static str {
+        match self {
+            Self::Added => "#38761d",
+            Self::Removed => "#cc0000",
+            Self::Changed => "#e69138",
+        }
+    }
+
+    fn edge_style(&self, attribs: &mut DotAttribs) {
+        attribs.set("color", format!("\"{}\"", self.line_color()));
+        if *self == Self::Removed {
+            attribs.set("style", "dashed");
+        }
+    }
+}
+
+/// Differences between two graphs, see [`ProductionGraph::combine`].
+#[derive(Debug, Clone, Default)]
+pub struct GraphDiff {
+    /// Items that are not the same in both graphs.
+    pub items: BTreeMap<ItemId, Change>,
+
+    /// Recipes that are not the same in both graphs. All edges of added and
+    /// removed recipes are added or removed as well.
+    pub recipes: BTreeMap<RecipeId, Change>,
+
+    /// Edges of recipes in both graphs that only exist in the new graph.
+    pub added_edges: BTreeSet<(Node, Node)>,
+
+    /// Edges of recipes in both graphs that only exist in the old graph.
+    pub removed_edges: BTreeMap<RecipeId, Vec<Edge>>,
+
+    /// Edges of recipes in both graphs whose amount changed, with the old
+    /// amount.
+    pub changed_edges: BTreeMap<(Node, Node), usize>,
+
+    pub counts: DiffCounts,
+}
+
+impl GraphDiff {
+    /// Change of the passed edge of the combined graph, if any.
+    pub fn edge(&self, rid: RecipeId, edge: &Edge) -> Option<Change> {
+        let key = (edge.from, edge.to);
+
+        match self.recipes.get(&rid) {
+            Some(Change::Added) => Some(Change::Added),
+            Some(Change::Removed) => Some(Change::Removed),
+            _ if self.added_edges.contains(&key) => Some(Change::Added),
+            _ if self.changed_edges.contains_key(&key) => Some(Change::Changed),
+            _ => None,
+        }
+    }
+
+    fn edge_attribs(&self, rid: RecipeId, edge: &Edge, attribs: &mut DotAttribs) {
+        let change = match self.edge(rid, edge) {
+            Some(change) => change,
+            None => return,
+        };
+
+        change.edge_style(attribs);
+        if let Some(old) = self.changed_edges.get(&(edge.from, edge.to)) {
+            attribs.set("label", format!("\"{} → {}\"", old, edge.amount));
+        }
+    }
+
+    fn write_comments(&self, out: &mut dyn Write) -> Result<(), Error> {
+        let counts = [
+            ("items", &self.counts.items),
+            ("recipes", &self.counts.recipes),
+            ("edges", &self.counts.edges),
+        ];
+        for (kind, counts) in counts {
+            writeln!(
+                out,
+                "// diff: {} added, {} removed and {} changed {}",
+                counts.added, counts.removed, counts.changed, kind
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Number of changed elements of a [`GraphDiff`].
+#[derive(Debug, Clone, Default)]
+pub struct DiffCounts {
+    pub items: ChangeCount,
+    pub recipes: ChangeCount,
+    pub edges: ChangeCount,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChangeCount {
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+}
+
+impl ChangeCount {
+    fn add(&mut self, change: Change) {
+        match change {
+            Change::Added => self.added += 1,
+            Change::Removed => self.removed += 1,
+            Change::Changed => self.changed += 1,
+        }
+    }
+}
+
+/* Summary */
+
+/// Totals of the production plan of a graph.
+#[derive(Debug, Clone, Default)]
+pub struct Summary {
+    /// Items that are supplied from outside, with the items per minute that
+    /// are needed.
+    pub resources: Vec<(String, f64)>,
+
+    /// Recipes of the plan with the number of machines that are needed.
+    pub machines: Vec<(String, f64)>,
+}
+
+impl Summary {
+    /// Create the summary of the passed plan. The plan is calculated by the
+    /// planner of the `plan` command, so both show the same totals.
+    pub fn new(data: &Data, plan: &ProductionPlan) -> Self {
+        let resources = plan
+            .resources
+            .iter()
+            .filter(|(_, rate)| **rate > 0.0)
+            .map(|(iid, rate)| (item_name(data, *iid), *rate))
+            .collect();
+
+        let machines = plan
+            .recipes
+            .keys()
+            .filter_map(|rid| {
+                let recipe = data.recipes.get(rid)?;
+
+                Some((recipe.name.clone(), plan.machines(data, *rid)))
+            })
+            .collect();
+
+        Self {
+            resources,
+            machines,
+        }
+    }
+}
+
+/* Aggregate */
+
+/// Property the nodes of an aggregated graph are grouped by.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum AggregateBy {
+    ItemType,
+    RecipeType,
+}
+
+impl FromStr for AggregateBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "item-type" => Ok(Self::ItemType),
+            "recipe-type" => Ok(Self::RecipeType),
+            s => Err(format!(
+                "Unknown aggregation: {} (expected item-type or recipe-type)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for AggregateBy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::ItemType => write!(f, "item-type"),
+            Self::RecipeType => write!(f, "recipe-type"),
+        }
+    }
+}
+
+/// Production graph that is collapsed to one node per item or recipe type,
+/// see [`ProductionGraph::aggregate`].
+#[derive(Debug, Clone)]
+pub struct AggregateGraph {
+    pub by: AggregateBy,
+
+    /// Number of items or recipes of each group.
+    pub groups: BTreeMap<String, usize>,
+
+    /// Summed flows between the groups, including flows within a group.
+    pub edges: BTreeMap<(String, String), AggregateEdge>,
+
+    /// String keys of the keyed ids, see [`ProductionGraph::keys`].
+    pub keys: Keys,
+}
+
+/// Flow between two groups of an aggregated graph.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AggregateEdge {
+    /// Summed amounts per craft.
+    pub amount: usize,
+
+    /// Summed items per minute, if rates were propagated.
+    pub rate: Option<f64>,
+
+    /// Names of the recipes the flow passes through.
+    pub recipes: BTreeMap<RecipeId, String>,
+}
+
+impl AggregateEdge {
+    fn add(&mut self, rid: RecipeId, recipe: &RecipeNode, edge: &Edge) {
+        self.amount += edge.amount;
+        if let Some(rate) = edge.rate {
+            *self.rate.get_or_insert(0.0) += rate;
+        }
+
+        self.recipes.insert(rid, recipe.name.clone());
+    }
+}
+
+impl ProductionGraph {
+    /// Collapse the graph to one node per item or recipe type.
+    ///
+    /// By item type, the outputs of each recipe flow from the types of its
+    /// inputs to the types of the outputs. Like in the adjacency matrix, only
+    /// the items of the graph are used. By recipe type, the inputs of each
+    /// recipe flow from the types of the recipes that produce them to the
+    /// type of the recipe; items that are produced by recipes of multiple
+    /// types are counted for each of them, and source items are skipped.
+    pub fn aggregate(&self, by: AggregateBy) -> AggregateGraph {
+        let item_group = |iid: ItemId| {
+            let item = self.items.get(&iid)?;
+
+            Some(match &item.type_ {
+                Some(type_) => type_.to_string(),
+                None => "UNKNOWN".to_owned(),
+            })
+        };
+
+        let mut groups = BTreeMap::<String, usize>::new();
+        let mut edges = BTreeMap::<(String, String), AggregateEdge>::new();
+
+        match by {
+            AggregateBy::ItemType => {
+                for group in self.items.keys().filter_map(|iid| item_group(*iid)) {
+                    *groups.entry(group).or_default() += 1;
+                }
+
+                for (rid, recipe) in &self.recipes {
+                    let inputs = recipe
+                        .inputs
+                        .iter()
+                        .filter_map(|i| item_group(i.id))
+                        .collect::<BTreeSet<_>>();
+
+                    for edge in self.recipe_edges(*rid) {
+                        let to = match edge.to {
+                            Node::Item(iid) => item_group(iid),
+                            Node::Recipe(_) => None,
+                        };
+                        let to = match to {
+                            Some(to) => to,
+                            None => continue,
+                        };
+
+                        for from in &inputs {
+                            edges
+                                .entry((from.clone(), to.clone()))
+                                .or_default()
+                                .add(*rid, recipe, &edge);
+                        }
+                    }
+                }
+            }
+            AggregateBy::RecipeType => {
+                let mut producers = BTreeMap::<ItemId, BTreeSet<String>>::new();
+                for recipe in self.recipes.values() {
+                    let group = recipe.type_.to_string();
+
+                    *groups.entry(group.clone()).or_default() += 1;
+                    for output in &recipe.outputs {
+                        producers
+                            .entry(output.id)
+                            .or_default()
+                            .insert(group.clone());
+                    }
+                }
+
+                for (rid, recipe) in &self.recipes {
+                    let to = recipe.type_.to_string();
+
+                    for edge in self.recipe_edges(*rid) {
+                        let from = match edge.from {
+                            Node::Item(iid) => producers.get(&iid).into_iter().flatten(),
+                            Node::Recipe(_) => continue,
+                        };
+
+                        for from in from {
+                            edges
+                                .entry((from.clone(), to.clone()))
+                                .or_default()
+                                .add(*rid, recipe, &edge);
+                        }
+                    }
+                }
+            }
+        }
+
+        AggregateGraph {
+            by,
+            groups,
+            edges,
+            keys: self.keys.clone(),
+        }
+    }
+}
+
+/* Matrix */
+
+/// Output format of the production graph.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum GraphFormat {
+    /// DOT format of graphviz.
+    #[default]
+    Dot,
+
+    /// Item × item adjacency matrix as CSV.
+    MatrixCsv,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "dot" => Ok(Self::Dot),
+            "matrix-csv" => Ok(Self::MatrixCsv),
+            s => Err(format!(
+                "Unknown graph format: {} (expected dot or matrix-csv)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for GraphFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Dot => write!(f, "dot"),
+            Self::MatrixCsv => write!(f, "matrix-csv"),
+        }
+    }
+}
+
+/// Write the item × item adjacency matrix of the passed graph as CSV.
+///
+/// The first row and column contain the item names, ordered by id. The cell
+/// in the row of item `a` and the column of item `b` sums the amounts of `b`
+/// that are produced per craft by the recipes that consume `a`. If `rates` is
+/// set, the items per minute are used instead, which requires propagated
+/// rates (see [`ProductionGraph::propagate_rates`]).
+///
+/// The rows are written one by one, so only one row is kept in memory.
+/// Returns the index of the items and of the cells that are not empty.
+pub fn write_matrix_csv(
+    graph: &ProductionGraph,
+    rates: bool,
+    out: &mut dyn Write,
+) -> Result<GraphIndex, Error> {
+    let columns = graph
+        .items
+        .keys()
+        .enumerate()
+        .map(|(i, iid)| (*iid, i))
+        .collect::<BTreeMap<_, _>>();
+
+    let mut consumers = BTreeMap::<ItemId, BTreeSet<RecipeId>>::new();
+    for (rid, recipe) in &graph.recipes {
+        for input in &recipe.inputs {
+            consumers.entry(input.id).or_default().insert(*rid);
+        }
+    }
+
+    writeln!(
+        out,
+        "{}",
+        csv_line(iter::once("").chain(graph.items.values().map(|i| i.name.as_str())))
+    )?;
+
+    let mut index = GraphIndex::default();
+    let mut row = vec![0.0; columns.len()];
+    for (iid, item) in &graph.items {
+        row.iter_mut().for_each(|weight| *weight = 0.0);
+        index.add_item(&item.name, graph, *iid);
+
+        let mut cells = BTreeMap::<usize, IndexEdge>::new();
+        for rid in consumers.get(iid).into_iter().flatten() {
+            for edge in graph.recipe_edges(*rid) {
+                let (to, column) = match edge.to {
+                    Node::Item(to) => match columns.get(&to) {
+                        Some(column) => (to, *column),
+                        None => continue,
+                    },
+                    Node::Recipe(_) => continue,
+                };
+
+                row[column] += if rates {
+                    edge.rate.unwrap_or_default()
+                } else {
+                    edge.amount as f64
+                };
+
+                let cell = cells.entry(column).or_insert_with(|| IndexEdge {
+                    from: item.name.clone(),
+                    to: graph.items[&to].name.clone(),
+                    item: Some(to.label(&graph.keys)),
+                    recipes: Vec::new(),
+                    amount: 0,
+                    rate: None,
+                    removed: false,
+                });
+                cell.recipes.push(rid.label(&graph.keys));
+                cell.amount += edge.amount;
+                if let Some(rate) = edge.rate {
+                    *cell.rate.get_or_insert(0.0) += rate;
+                }
+            }
+        }
+        index.edges.extend(cells.into_values());
+
+        let weights = row.iter().map(|weight| {
+            if rates {
+                format!("{:.4}", weight)
+            } else {
+                weight.to_string()
+            }
+        });
+
+        writeln!(
+            out,
+            "{}",
+            csv_line(iter::once(item.name.clone()).chain(weights))
+        )?;
+    }
+
+    Ok(index)
+}
+
+/// Write the group × group adjacency matrix of the passed aggregated graph as
+/// CSV, like [`write_matrix_csv`].
+pub fn write_aggregate_matrix_csv(
+    graph: &AggregateGraph,
+    rates: bool,
+    out: &mut dyn Write,
+) -> Result<GraphIndex, Error> {
+    writeln!(
+        out,
+        "{}",
+        csv_line(iter::once("").chain(graph.groups.keys().map(String::as_str)))
+    )?;
+
+    for from in graph.groups.keys() {
+        let weights = graph.groups.keys().map(|to| {
+            let edge = graph.edges.get(&(from.clone(), to.clone()));
+
+            if rates {
+                format!("{:.4}", edge.and_then(|e| e.rate).unwrap_or_default())
+            } else {
+                edge.map(|e| e.amount).unwrap_or_default().to_string()
+            }
+        });
+
+        writeln!(out, "{}", csv_line(iter::once(from.clone()).chain(weights)))?;
+    }
+
+    Ok(graph.index())
+}
+
+/* Index */
+
+/// Nodes and edges that were written by one of the graph writers, keyed by
+/// the node identifiers of the output, so the rendered graph can be mapped
+/// back to the data.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GraphIndex {
+    /// Metadata of the output, set by the caller.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+
+    /// Written nodes, by their identifier.
+    pub nodes: BTreeMap<String, IndexNode>,
+
+    /// Written edges, in the order they were written.
+    pub edges: Vec<IndexEdge>,
+}
+
+/// Node of the [`GraphIndex`].
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexNode {
+    pub kind: NodeKind,
+
+    /// Item of the node, also set for the copies of an item.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<IdLabel>,
+
+    /// Recipe of the node, or the consumer of a copy.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recipe: Option<IdLabel>,
+
+    /// Recipes that were merged into the recipe of the node.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub merged: Vec<IdLabel>,
+
+    pub name: String,
+
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub type_: Option<String>,
+}
+
+/// Kind of an [`IndexNode`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NodeKind {
+    Item,
+
+    /// Copy of an item that is drawn next to one of its consumers.
+    ItemCopy,
+
+    Recipe,
+
+    /// Item or recipe type of an aggregated graph.
+    Group,
+
+    Summary,
+}
+
+/// Edge of the [`GraphIndex`].
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexEdge {
+    /// Identifiers of the connected nodes.
+    pub from: String,
+    pub to: String,
+
+    /// Item that flows across the edge.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item: Option<IdLabel>,
+
+    /// Recipes the edge belongs to.
+    pub recipes: Vec<IdLabel>,
+
+    pub amount: usize,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rate: Option<f64>,
+
+    /// Whether the edge only exists in the old graph of a diff.
+    #[serde(skip_serializing_if = "is_false")]
+    pub removed: bool,
+}
+
+impl GraphIndex {
+    /// Add an item node, unless a node with the passed identifier exists.
+    fn add_item(&mut self, id: &str, graph: &ProductionGraph, iid: ItemId) {
+        let type_ = graph
+            .items
+            .get(&iid)
+            .and_then(|item| item.type_.as_ref())
+            .map(ToString::to_string);
+
+        self.nodes
+            .entry(id.to_owned())
+            .or_insert_with(|| IndexNode {
+                kind: NodeKind::Item,
+                item: Some(iid.label(&graph.keys)),
+                recipe: None,
+                merged: Vec::new(),
+                name: graph.item_name(iid).unwrap_or_default().to_owned(),
+                type_,
+            });
+    }
+
+    /// Add an edge of the passed recipe between the passed nodes. Item nodes
+    /// that were not written explicitly (like ignored items) are added.
+    fn add_edge(
+        &mut self,
+        graph: &ProductionGraph,
+        rid: RecipeId,
+        (from, to): (String, String),
+        edge: &Edge,
+        removed: bool,
+    ) {
+        let item = match (edge.from, edge.to) {
+            (Node::Item(iid), _) | (_, Node::Item(iid)) => Some(iid.label(&graph.keys)),
+            _ => None,
+        };
+        for (id, node) in [(&from, edge.from), (&to, edge.to)] {
+            if let Node::Item(iid) = node {
+                self.add_item(id, graph, iid);
+            }
+        }
+
+        self.edges.push(IndexEdge {
+            from,
+            to,
+            item,
+            recipes: vec![rid.label(&graph.keys)],
+            amount: edge.amount,
+            rate: edge.rate,
+            removed,
+        });
+    }
+}
+
+impl AggregateGraph {
+    /// Index of the groups and of the flows between them, which are written
+    /// completely by all writers of the aggregated graph.
+    fn index(&self) -> GraphIndex {
+        let nodes = self
+            .groups
+            .keys()
+            .map(|group| {
+                let node = IndexNode {
+                    kind: NodeKind::Group,
+                    item: None,
+                    recipe: None,
+                    merged: Vec::new(),
+                    name: group.clone(),
+                    type_: Some(group.clone()),
+                };
+
+                (group.clone(), node)
+            })
+            .collect();
+
+        let edges = self
+            .edges
+            .iter()
+            .map(|((from, to), edge)| IndexEdge {
+                from: from.clone(),
+                to: to.clone(),
+                item: None,
+                recipes: edge
+                    .recipes
+                    .keys()
+                    .map(|rid| rid.label(&self.keys))
+                    .collect(),
+                amount: edge.amount,
+                rate: edge.rate,
+                removed: false,
+            })
+            .collect();
+
+        GraphIndex {
+            meta: None,
+            nodes,
+            edges,
+        }
+    }
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/* DOT */
+
+/// Options of the DOT output.
+#[derive(Debug, Clone, Default)]
+pub struct DotOptions {
+    /// Draw the items with their icons, relative to this directory.
+    pub icons_dir: Option<PathBuf>,
+
+    /// Draw the source items as green houses.
+    pub source_style: bool,
+
+    /// Place items of the same tier in the same rank. Requires the tiers
+    /// (see [`GraphOptions::tiers`]).
+    pub rank_by_tier: bool,
+
+    /// Add a table node with the summary, in its own rank.
+    pub summary: Option<Summary>,
+
+    /// Fill the nodes with a color that depends on this property.
+    pub color_by: Option<ColorBy>,
+
+    /// Colors that are used for [`ColorBy::Depth`].
+    pub depth_colors: DepthColors,
+
+    /// Attributes of the nodes and edges.
+    pub theme: Theme,
+
+    /// Scale the nodes by this property.
+    pub size_by: Option<SizeBy>,
+
+    /// Sizes that are used for [`DotOptions::size_by`].
+    pub node_sizes: NodeSizes,
+
+    /// Draw a separate copy of an item for each recipe that consumes it, if
+    /// it is consumed by more than this number of recipes (see
+    /// [`ProductionGraph::common_items`]).
+    pub split_common: Option<usize>,
+
+    /// Write the metadata as comments at the top of the graph.
+    pub meta: Option<Meta>,
+
+    /// How the recipe nodes are drawn.
+    pub recipe_label: RecipeLabel,
+
+    /// Merge edges with the same endpoints into one edge, instead of drawing
+    /// a bundle of edges (which `strict` graphs reduce to a single one).
+    pub combine_edges: bool,
+
+    /// Highlight the differences to another graph (see
+    /// [`ProductionGraph::combine`]).
+    pub diff: Option<GraphDiff>,
+}
+
+/// How the recipe nodes of the graph are drawn.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum RecipeLabel {
+    /// Small point, labeled with the crafting time.
+    #[default]
+    Point,
+
+    /// Table with the name, the inputs and outputs and the crafting time.
+    Table,
+}
+
+impl FromStr for RecipeLabel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "point" => Ok(Self::Point),
+            "table" => Ok(Self::Table),
+            s => Err(format!(
+                "Unknown recipe label: {} (expected point or table)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for RecipeLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Point => write!(f, "point"),
+            Self::Table => write!(f, "table"),
+        }
+    }
+}
+
+/// Property the nodes of the graph are colored by.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ColorBy {
+    /// Distance of the node from the root items (see [`Origin::depth`]).
+    Depth,
+}
+
+impl FromStr for ColorBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "depth" => Ok(Self::Depth),
+            s => Err(format!("Unknown color property: {} (expected depth)", s)),
+        }
+    }
+}
+
+impl Display for ColorBy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Depth => write!(f, "depth"),
+        }
+    }
+}
+
+/// Gradient from the color of the root items to the color of the deepest
+/// level. Nodes beyond the cutoff use the color of the deepest level.
+#[derive(Debug, Clone, Copy)]
+pub struct DepthColors {
+    pub start: Rgb,
+    pub end: Rgb,
+
+    /// Deepest level of the gradient. Defaults to the deepest node of the
+    /// graph.
+    pub cutoff: Option<usize>,
+}
+
+impl Default for DepthColors {
+    fn default() -> Self {
+        Self {
+            start: Rgb(0xea, 0x99, 0x99),
+            end: Rgb(0x9f, 0xc5, 0xe8),
+            cutoff: None,
+        }
+    }
+}
+
+impl DepthColors {
+    /// Color of the passed depth, with `max` as the deepest level.
+    fn color(&self, depth: usize, max: usize) -> Rgb {
+        let max = self.cutoff.unwrap_or(max);
+        if max == 0 {
+            return self.start;
+        }
+
+        self.start.mix(self.end, depth.min(max) as f64 / max as f64)
+    }
+}
+
+/// Property the size of the nodes of the graph depends on.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SizeBy {
+    /// Items per minute that are needed, requires propagated rates (see
+    /// [`ProductionGraph::propagate_rates`]).
+    Throughput,
+}
+
+impl FromStr for SizeBy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "throughput" => Ok(Self::Throughput),
+            s => Err(format!(
+                "Unknown size property: {} (expected throughput)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for SizeBy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Throughput => write!(f, "throughput"),
+        }
+    }
+}
+
+/// Bounds the values of [`SizeBy`] are mapped onto, using a log scale from
+/// the smallest to the largest value of the graph.
+#[derive(Debug, Clone, Default)]
+pub struct NodeSizes {
+    /// Smallest and largest width and height of the items, in inches.
+    pub width: (f64, f64),
+
+    /// Smallest and largest font size of the items, in points.
+    pub font_size: (f64, f64),
+
+    /// Number of machines of the recipes, if the recipes are scaled as well
+    /// (see [`ProductionGraph::machines`]).
+    pub machines: Option<BTreeMap<RecipeId, f64>>,
+}
+
+/// Logarithmic scale from the range of a set of positive values.
+#[derive(Debug, Clone, Copy)]
+struct LogScale {
+    min: f64,
+    max: f64,
+}
+
+impl LogScale {
+    /// Scale of the passed values, `None` if none of them is positive.
+    fn new<I>(values: I) -> Option<Self>
+    where
+        I: IntoIterator<Item = f64>,
+    {
+        values
+            .into_iter()
+            .filter(|value| *value > 0.0)
+            .map(f64::ln)
+            .fold(None, |ret: Option<Self>, value| {
+                Some(match ret {
+                    Some(ret) => Self {
+                        min: ret.min.min(value),
+                        max: ret.max.max(value),
+                    },
+                    None => Self {
+                        min: value,
+                        max: value,
+                    },
+                })
+            })
+    }
+
+    /// Map the passed value onto the passed bounds. If all values are equal
+    /// the upper bound is used.
+    fn map(&self, value: f64, (lower, upper): (f64, f64)) -> f64 {
+        let t = if self.max > self.min {
+            ((value.ln() - self.min) / (self.max - self.min)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+
+        lower + (upper - lower) * t
+    }
+}
+
+/// Color in the `#rrggbb` format.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Rgb(pub u8, pub u8, pub u8);
+
+impl Rgb {
+    /// Linear interpolation to `other`, `t` is between 0 and 1.
+    fn mix(self, other: Self, t: f64) -> Self {
+        let mix = |a: u8, b: u8| (a as f64 + (b as f64 - a as f64) * t).round() as u8;
+
+        Self(
+            mix(self.0, other.0),
+            mix(self.1, other.1),
+            mix(self.2, other.2),
+        )
+    }
+}
+
+impl FromStr for Rgb {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.is_ascii())
+            .ok_or_else(|| format!("Invalid color: {} (expected #rrggbb)", s))?;
+        let channel = |i: usize| {
+            u8::from_str_radix(&hex[i..i + 2], 16)
+                .map_err(|_| format!("Invalid color: {} (expected #rrggbb)", s))
+        };
+
+        Ok(Self(channel(0)?, channel(2)?, channel(4)?))
+    }
+}
+
+impl Display for Rgb {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "#{:02x}{:02x}{:02x}", self.0, self.1, self.2)
+    }
+}
+
+impl<'de> Deserialize<'de> for Rgb {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+
+        Self::from_str(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Write the passed graph in the DOT format of graphviz. Returns the index
+/// of the written nodes and edges.
+pub fn write_dot(
+    graph: &ProductionGraph,
+    options: &DotOptions,
+    out: &mut dyn Write,
+) -> Result<GraphIndex, Error> {
+    if let Some(meta) = &options.meta {
+        meta.write_comments("//", out)?;
+    }
+    if let Some(diff) = &options.diff {
+        diff.write_comments(out)?;
+    }
+
+    let theme = &options.theme;
+
+    writeln!(out, "strict digraph DSP {{")?;
+
+    let mut attribs = DotAttribs::default();
+    attribs.apply(&theme.graph);
+    writeln!(out, "    graph [ {} ]", attribs)?;
+
+    let split = options
+        .split_common
+        .map(|threshold| graph.common_items(threshold))
+        .unwrap_or_default();
+
+    let mut index = GraphIndex::default();
+    write_items(graph, options, &split, &mut index, out)?;
+
+    writeln!(out)?;
+    writeln!(out, "    /* Recipes */")?;
+
+    let node_name = |node: Node| match node {
+        Node::Item(iid) => graph.item_name(iid).unwrap_or_default().to_owned(),
+        Node::Recipe(rid) => rid.label(&graph.keys).to_string(),
+    };
+
+    let max_depth = graph.max_depth();
+    let machines = options.size_by.and(options.node_sizes.machines.as_ref());
+    let machine_scale = machines.and_then(|m| LogScale::new(m.values().copied()));
+    for (rid, recipe) in &graph.recipes {
+        let count = if recipe.merged.is_empty() {
+            String::new()
+        } else {
+            format!(" ×{}", recipe.merged.len() + 1)
+        };
+        let mut attribs = DotAttribs::default();
+        match options.recipe_label {
+            RecipeLabel::Point => {
+                attribs.set("label", format!("\"{}{}\"", recipe.seconds, count));
+            }
+            RecipeLabel::Table => {
+                attribs.set("label", format!("<{}>", recipe_table(graph, recipe)));
+            }
+        }
+        if let Some(rate) = recipe.rate {
+            attribs.set("xlabel", format!("\"{}\"", rate_label(rate)));
+        }
+        match options.recipe_label {
+            RecipeLabel::Point => {
+                attribs.set("shape", "point");
+                attribs.set("width", "0.1");
+            }
+            RecipeLabel::Table => attribs.set("shape", "plain"),
+        }
+        attribs.apply(&theme.recipe);
+        if let Some(type_attribs) = theme.recipe_type(&recipe.type_) {
+            attribs.apply(type_attribs);
+        }
+        for flag in &recipe.flags {
+            attribs.set(flag.name(), "true");
+            if let Some(flag_attribs) = theme.recipe_flags.get(flag.name()) {
+                attribs.apply(flag_attribs);
+            }
+        }
+        if options.color_by == Some(ColorBy::Depth) {
+            let depth = recipe.origin.depth;
+            let color = options.depth_colors.color(depth, max_depth);
+
+            attribs.set("color", format!("\"{}\"", color));
+            attribs.set("depth", depth);
+        }
+
+        if let (Some(machines), Some(scale)) = (
+            machines.and_then(|m| m.get(rid)).filter(|m| **m > 0.0),
+            machine_scale,
+        ) {
+            match options.recipe_label {
+                RecipeLabel::Point => {
+                    attribs.set(
+                        "width",
+                        format!("{:.2}", scale.map(*machines, RECIPE_WIDTH)),
+                    );
+                }
+                RecipeLabel::Table => {
+                    let sizes = &options.node_sizes;
+
+                    attribs.set(
+                        "fontsize",
+                        format!("{:.1}", scale.map(*machines, sizes.font_size)),
+                    );
+                }
+            }
+            attribs.set("machines", format!("{:.4}", machines));
+        }
+
+        if let Some(change) = options.diff.as_ref().and_then(|d| d.recipes.get(rid)) {
+            attribs.set("color", format!("\"{}\"", change.line_color()));
+        }
+        let label = rid.label(&graph.keys);
+        let merged = recipe
+            .merged
+            .iter()
+            .map(|rid| rid.label(&graph.keys))
+            .collect::<Vec<_>>();
+        if !merged.is_empty() {
+            let ids = iter::once(&label).chain(&merged).join(",");
+            attribs.set("merged", format!("\"{}\"", ids));
+        }
+
+        writeln!(out)?;
+        if merged.is_empty() {
+            writeln!(out, "    /* {} */", recipe.name)?;
+        } else {
+            writeln!(
+                out,
+                "    /* {} (merged with {}) */",
+                recipe.name,
+                merged.iter().join(", ")
+            )?;
+        }
+        writeln!(out, "    \"{}\" [ {} ]", label, attribs)?;
+        index.nodes.insert(
+            label.to_string(),
+            IndexNode {
+                kind: NodeKind::Recipe,
+                item: None,
+                recipe: Some(label.clone()),
+                merged,
+                name: recipe.name.clone(),
+                type_: Some(recipe.type_.to_string()),
+            },
+        );
+
+        let edges = if options.combine_edges {
+            combine_edges(graph.recipe_edges(*rid))
+        } else {
+            graph.recipe_edges(*rid).map(|edge| (edge, 1)).collect()
+        };
+        for (edge, count) in edges {
+            let from = match edge.from {
+                Node::Item(iid) if split.contains(&iid) => {
+                    let name = graph.item_name(iid).unwrap_or_default();
+                    let item = graph.items.get(&iid);
+
+                    let mut attribs = DotAttribs::default();
+                    attribs.set("label", format!("\"{}\"", name));
+                    attribs.apply(&theme.item);
+                    if let Some(type_attribs) = item
+                        .and_then(|i| i.type_.as_ref())
+                        .and_then(|t| theme.item_type(t))
+                    {
+                        attribs.apply(type_attribs);
+                    }
+                    // Copies of source items keep the shape of the source.
+                    if let Some(shape) = item
+                        .filter(|i| options.source_style && i.source)
+                        .and_then(|_| theme.source.get("shape"))
+                    {
+                        attribs.set("shape", dot_id(shape));
+                    }
+                    attribs.apply(&theme.copy);
+
+                    let copy = copy_name(name, &label);
+                    writeln!(out, "    \"{}\" [ {} ]", copy, attribs)?;
+                    index.nodes.insert(
+                        copy.clone(),
+                        IndexNode {
+                            kind: NodeKind::ItemCopy,
+                            item: Some(iid.label(&graph.keys)),
+                            recipe: Some(label.clone()),
+                            merged: Vec::new(),
+                            name: name.to_owned(),
+                            type_: item.and_then(|i| i.type_.as_ref()).map(ToString::to_string),
+                        },
+                    );
+
+                    copy
+                }
+                from => node_name(from),
+            };
+
+            let mut attribs = DotAttribs::default();
+            attribs.set("name", format!("\"{}\"", edge.amount));
+            if let Some(rate) = edge.rate {
+                attribs.set("label", format!("\"{}\"", rate_label(rate)));
+            }
+            if count > 1 {
+                let label = match edge.rate {
+                    Some(rate) => format!("{} ×{}", rate_label(rate), count),
+                    None => format!("×{}", count),
+                };
+
+                attribs.set("label", format!("\"{}\"", label));
+                attribs.set("tooltip", dot_id(&recipe.name));
+            }
+            attribs.apply(&theme.edge);
+            if let Some(diff) = &options.diff {
+                diff.edge_attribs(*rid, &edge, &mut attribs);
+            }
+
+            let to = node_name(edge.to);
+            writeln!(out, "    \"{}\" -> \"{}\" [ {} ]", from, to, attribs)?;
+            index.add_edge(graph, *rid, (from, to), &edge, false);
+        }
+
+        let removed = options
+            .diff
+            .as_ref()
+            .and_then(|diff| diff.removed_edges.get(rid))
+            .into_iter()
+            .flatten();
+        for edge in removed {
+            let mut attribs = DotAttribs::default();
+            attribs.set("name", format!("\"{}\"", edge.amount));
+            attribs.apply(&theme.edge);
+            Change::Removed.edge_style(&mut attribs);
+
+            let (from, to) = (node_name(edge.from), node_name(edge.to));
+            writeln!(out, "    \"{}\" -> \"{}\" [ {} ]", from, to, attribs)?;
+            index.add_edge(graph, *rid, (from, to), edge, true);
+        }
+    }
+
+    if options.rank_by_tier {
+        let mut ranks = BTreeMap::<usize, Vec<&str>>::new();
+        for (iid, item) in &graph.items {
+            if item.source && split.contains(iid) {
+                continue;
+            }
+
+            if let Some(tier) = item.tier {
+                ranks.entry(tier).or_default().push(&item.name);
+            }
+        }
+
+        writeln!(out)?;
+        writeln!(out, "    /* Tiers */")?;
+
+        for (tier, names) in ranks {
+            let names = names
+                .iter()
+                .map(|name| format!("\"{}\"", name))
+                .collect::<Vec<_>>()
+                .join(" ");
+
+            writeln!(out)?;
+            writeln!(out, "    /* Tier {} */", tier)?;
+            writeln!(out, "    {{ rank=same {} }}", names)?;
+        }
+    }
+
+    if let Some(summary) = &options.summary {
+        write_summary(summary, theme, out)?;
+        index.nodes.insert(
+            SUMMARY_NODE.to_owned(),
+            IndexNode {
+                kind: NodeKind::Summary,
+                item: None,
+                recipe: None,
+                merged: Vec::new(),
+                name: "Summary".to_owned(),
+                type_: None,
+            },
+        );
+    }
+
+    writeln!(out, "}}")?;
+
+    Ok(index)
+}
+
+/// Write the passed aggregated graph in the DOT format of graphviz. The
+/// groups are labeled with their number of members, and the edges with the
+/// summed amounts, or rates if they were propagated. If edges are combined,
+/// they are also labeled with the number of recipes they pass through, which
+/// are listed in the tooltip.
+pub fn write_aggregate_dot(
+    graph: &AggregateGraph,
+    options: &DotOptions,
+    out: &mut dyn Write,
+) -> Result<GraphIndex, Error> {
+    if let Some(meta) = &options.meta {
+        meta.write_comments("//", out)?;
+    }
+
+    let theme = &options.theme;
+
+    writeln!(out, "strict digraph DSP {{")?;
+
+    let mut attribs = DotAttribs::default();
+    attribs.apply(&theme.graph);
+    writeln!(out, "    graph [ {} ]", attribs)?;
+
+    writeln!(out)?;
+    writeln!(out, "    /* Groups */")?;
+    writeln!(out)?;
+
+    let (member, defaults, types) = match graph.by {
+        AggregateBy::ItemType => ("item", &theme.item, &theme.item_types),
+        AggregateBy::RecipeType => ("recipe", &theme.recipe, &theme.recipe_types),
+    };
+    for (group, count) in &graph.groups {
+        let plural = if *count == 1 { "" } else { "s" };
+
+        let mut attribs = DotAttribs::default();
+        attribs.set(
+            "label",
+            format!("\"{}\\n({} {}{})\"", group, count, member, plural),
+        );
+        attribs.set("shape", "box");
+        attribs.apply(defaults);
+        if let Some(type_attribs) = types.get(&group.to_uppercase()) {
+            attribs.apply(type_attribs);
+        }
+        attribs.set("members", count);
+
+        writeln!(out, "    \"{}\" [ {} ]", group, attribs)?;
+    }
+
+    writeln!(out)?;
+    writeln!(out, "    /* Flows */")?;
+    writeln!(out)?;
+
+    for ((from, to), edge) in &graph.edges {
+        let mut label = match edge.rate {
+            Some(rate) => rate_label(rate),
+            None => edge.amount.to_string(),
+        };
+        if options.combine_edges && edge.recipes.len() > 1 {
+            label += &format!(" ×{}", edge.recipes.len());
+        }
+
+        let mut attribs = DotAttribs::default();
+        attribs.set("label", format!("\"{}\"", label));
+        attribs.set("name", format!("\"{}\"", edge.amount));
+        if options.combine_edges {
+            attribs.set("tooltip", dot_id(&edge.recipes.values().join(", ")));
+        }
+        attribs.apply(&theme.edge);
+
+        writeln!(out, "    \"{}\" -> \"{}\" [ {} ]", from, to, attribs)?;
+    }
+
+    writeln!(out, "}}")?;
+
+    Ok(graph.index())
+}
+
+/// Merge the edges with the same endpoints into one edge with the summed
+/// amounts and rates, together with the number of merged edges. The edges
+/// keep the order of their first occurrence.
+fn combine_edges<I>(edges: I) -> Vec<(Edge, usize)>
+where
+    I: IntoIterator<Item = Edge>,
+{
+    let mut ret = Vec::<(Edge, usize)>::new();
+
+    for edge in edges {
+        match ret
+            .iter_mut()
+            .find(|(e, _)| e.from == edge.from && e.to == edge.to)
+        {
+            Some((combined, count)) => {
+                combined.amount += edge.amount;
+                combined.rate = match (combined.rate, edge.rate) {
+                    (Some(a), Some(b)) => Some(a + b),
+                    (a, b) => a.or(b),
+                };
+                *count += 1;
+            }
+            None => ret.push((edge, 1)),
+        }
+    }
+
+    ret
+}
+
+/// Write the summary as a node with an HTML table as label. The node uses
+/// the attributes of the items, except for the shape.
+fn write_summary(summary: &Summary, theme: &Theme, out: &mut dyn Write) -> Result<(), Error> {
+    let mut attribs = DotAttribs::default();
+    attribs.apply(&theme.item);
+    attribs.set("shape", "plaintext");
+
+    writeln!(out)?;
+    writeln!(out, "    /* Summary */")?;
+    writeln!(out)?;
+    writeln!(out, "    \"{}\" [ {} label=<", SUMMARY_NODE, attribs)?;
+    writeln!(
+        out,
+        "        <table border=\"0\" cellborder=\"1\" cellspacing=\"0\" cellpadding=\"4\">"
+    )?;
+
+    let sections = [
+        ("Resources", &summary.resources, true),
+        ("Machines", &summary.machines, false),
+    ];
+    for (title, rows, is_rate) in sections {
+        if rows.is_empty() {
+            continue;
+        }
+
+        writeln!(
+            out,
+            "        <tr><td colspan=\"2\"><b>{}</b></td></tr>",
+            title
+        )?;
+        for (name, value) in rows {
+            let value = if is_rate {
+                rate_label(*value)
+            } else {
+                format!("{:.2}", value)
+            };
+
+            writeln!(
+                out,
+                "        <tr><td align=\"left\">{}</td><td align=\"right\">{}</td></tr>",
+                escape_html(name),
+                value
+            )?;
+        }
+    }
+
+    writeln!(out, "        </table>")?;
+    writeln!(out, "    > ]")?;
+    writeln!(out, "    {{ rank=sink \"{}\" }}", SUMMARY_NODE)?;
+
+    Ok(())
+}
+
+/// HTML-like label of a recipe: the name in the header, the inputs on the
+/// left and the outputs on the right, and the crafting time in the footer.
+fn recipe_table(graph: &ProductionGraph, recipe: &RecipeNode) -> String {
+    let cell = |amount: Option<&ItemAmount>| match amount {
+        Some(amount) => {
+            let name = graph.item_name(amount.id).unwrap_or_default();
+
+            format!("{}× {}", amount.amount, escape_html(&abbreviate(name)))
+        }
+        None => String::new(),
+    };
+
+    let mut ret =
+        String::from("<table border=\"0\" cellborder=\"1\" cellspacing=\"0\" cellpadding=\"2\">");
+    let count = if recipe.merged.is_empty() {
+        String::new()
+    } else {
+        format!(" ×{}", recipe.merged.len() + 1)
+    };
+    ret += &format!(
+        "<tr><td colspan=\"2\"><b>{}{}</b></td></tr>",
+        escape_html(&abbreviate(&recipe.name)),
+        count
+    );
+
+    let rows = recipe.inputs.len().max(recipe.outputs.len());
+    for i in 0..rows {
+        ret += &format!(
+            "<tr><td align=\"left\">{}</td><td align=\"right\">{}</td></tr>",
+            cell(recipe.inputs.get(i)),
+            cell(recipe.outputs.get(i))
+        );
+    }
+
+    ret += &format!("<tr><td colspan=\"2\">{}s</td></tr>", recipe.seconds);
+    ret += "</table>";
+
+    ret
+}
+
+/// Shorten long names, so the recipe tables stay narrow.
+fn abbreviate(name: &str) -> String {
+    if name.chars().count() <= MAX_NAME_LEN {
+        return name.to_owned();
+    }
+
+    let mut ret = name.chars().take(MAX_NAME_LEN - 1).collect::<String>();
+    ret.push('…');
+
+    ret
+}
+
+/// Longest name in a recipe table.
+const MAX_NAME_LEN: usize = 20;
+
+pub(crate) fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Write the nodes of all items. Source items are drawn as houses and, if
+/// the icons directory is set, items that have an icon are drawn with their
+/// icon.
+fn write_items(
+    graph: &ProductionGraph,
+    options: &DotOptions,
+    split: &BTreeSet<ItemId>,
+    index: &mut GraphIndex,
+    out: &mut dyn Write,
+) -> Result<(), Error> {
+    writeln!(out)?;
+    writeln!(out, "    /* Items */")?;
+    writeln!(out)?;
+
+    let theme = &options.theme;
+    let max_depth = graph.max_depth();
+    let item_scale = options
+        .size_by
+        .and_then(|_| LogScale::new(graph.items.values().filter_map(|i| i.rate)));
+    let mut missing = 0;
+    for (iid, item) in &graph.items {
+        // Split source items are only drawn as copies next to their consumers.
+        if item.source && split.contains(iid) {
+            continue;
+        }
+
+        let mut attribs = DotAttribs::default();
+        let source = options.source_style && item.source;
+
+        attribs.apply(&theme.item);
+        if let Some(type_attribs) = item.type_.as_ref().and_then(|t| theme.item_type(t)) {
+            attribs.apply(type_attribs);
+        }
+
+        let icon = options.icons_dir.as_deref().zip(item.icon.as_ref());
+        if let Some((icons_dir, icon)) = icon {
+            let icon = Path::join(icons_dir, icon);
+
+            if icon.is_file() {
+                attribs.set("image", format!("\"{}\"", icon.display()));
+                attribs.set("labelloc", "b");
+                attribs.set("fixedsize", "true");
+                attribs.set("width", "1.2");
+                attribs.set("height", "1.2");
+                if !source {
+                    attribs.set("shape", "none");
+                }
+            } else {
+                missing += 1;
+            }
+        }
+
+        if source {
+            attribs.apply(&theme.source);
+        }
+
+        let change = options.diff.as_ref().and_then(|d| d.items.get(iid));
+        let fill = match (change, options.color_by) {
+            (Some(change), _) => Some(change.fill_color().to_owned()),
+            (None, Some(ColorBy::Depth)) => Some(
+                options
+                    .depth_colors
+                    .color(item.origin.depth, max_depth)
+                    .to_string(),
+            ),
+            (None, None) => None,
+        };
+
+        if let Some(fill) = fill {
+            attribs.set("style", "filled");
+            attribs.set("fillcolor", format!("\"{}\"", fill));
+        }
+        if options.color_by == Some(ColorBy::Depth) {
+            attribs.set("depth", item.origin.depth);
+        }
+        if let (Some(rate), Some(scale)) = (item.rate.filter(|rate| *rate > 0.0), item_scale) {
+            let sizes = &options.node_sizes;
+            let width = format!("{:.2}", scale.map(rate, sizes.width));
+
+            attribs.set("width", &width);
+            attribs.set("height", &width);
+            attribs.set(
+                "fontsize",
+                format!("{:.1}", scale.map(rate, sizes.font_size)),
+            );
+            attribs.set("throughput", format!("{:.4}", rate));
+        }
+
+        if attribs.is_empty() {
+            writeln!(out, "    \"{}\"", item.name)?;
+        } else {
+            writeln!(out, "    \"{}\" [ {} ]", item.name, attribs)?;
+        }
+        index.add_item(&item.name, graph, *iid);
+    }
+
+    if missing > 0 {
+        log::warn!("  {} icons not found, using plain labels", missing);
+    }
+
+    Ok(())
+}
+
+/// Label of a rate in items or crafts per minute.
+fn rate_label(rate: f64) -> String {
+    format!("{:.2}/min", rate)
+}
+
+/// Name of the copy of an item that is consumed by the passed recipe.
+fn copy_name(name: &str, recipe: &IdLabel) -> String {
+    format!("{}#{}", name, recipe)
+}
+
+/// Smallest and largest width of the recipe points that are scaled by their
+/// number of machines, in inches.
+const RECIPE_WIDTH: (f64, f64) = (0.05, 0.4);
+
+/// Name of the summary node, chosen to not collide with item names.
+const SUMMARY_NODE: &str = "__summary__";
+
+/* DotAttribs */
+
+/// Attributes of a node or edge, with the values in DOT syntax.
+#[derive(Debug, Default)]
+struct DotAttribs(Vec<(String, String)>);
+
+impl DotAttribs {
+    /// Set the passed attribute, replacing an earlier value. The value has
+    /// to be quoted already if needed.
+    fn set(&mut self, key: &str, value: impl ToString) {
+        let value = value.to_string();
+
+        match self.0.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.0.push((key.to_owned(), value)),
+        }
+    }
+
+    /// Set the attributes of a theme, quoting the values if needed.
+    fn apply(&mut self, attributes: &Attributes) {
+        for (key, value) in attributes.iter() {
+            self.set(key, dot_id(value));
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl Display for DotAttribs {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for (i, (key, value)) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+
+            write!(f, "{}={}", key, value)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Value as DOT identifier, quoted unless it is a plain name or a number.
+fn dot_id(value: &str) -> String {
+    let is_name = value
+        .chars()
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+    let digits = value.strip_prefix('-').unwrap_or(value);
+    let is_number = digits.chars().any(|c| c.is_ascii_digit())
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.matches('.').count() <= 1;
+
+    if is_name || is_number {
+        value.to_owned()
+    } else {
+        format!("\"{}\"", value.replace('"', "\\\""))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{error::EXIT_USAGE, planner::Proliferator};
+
+    use super::*;
+
+    fn data() -> Data {
+        Data::from_lua_source("small", include_str!("../tests/data/small.lua"), None, &[]).unwrap()
+    }
+
+    fn recipe_ids(graph: &ProductionGraph) -> Vec<RecipeId> {
+        graph.recipes.keys().copied().collect()
+    }
+
+    fn dot(graph: &ProductionGraph, options: &DotOptions) -> String {
+        let mut out = Vec::new();
+        write_dot(graph, options, &mut out).unwrap();
+
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn graph_has_the_nodes_and_edges() {
+        let data = data();
+        let graph = GraphBuilder::new(&data)
+            .targets([ItemId(3)])
+            .resolve_deps(true)
+            .build()
+            .unwrap();
+
+        assert_eq!(graph.items[&ItemId(3)].origin.depth, 0);
+        assert_eq!(graph.items[&ItemId(1)].origin.depth, 2);
+        assert_eq!(graph.recipes[&RecipeId(1)].item, ItemId(2));
+
+        let edges = graph.recipe_edges(RecipeId(2)).collect::<Vec<_>>();
+        assert_eq!(edges.len(), 2);
+        assert_eq!(edges[0].from, Node::Item(ItemId(2)));
+        assert_eq!(edges[0].to, Node::Recipe(RecipeId(2)));
+        assert_eq!(edges[0].amount, 2);
+        assert_eq!(edges[1].to, Node::Item(ItemId(3)));
+        assert_eq!(graph.edges().count(), 4);
+    }
+
+    #[test]
+    fn rates_apply_the_proliferator() {
+        let data = data();
+        let mut graph = GraphBuilder::new(&data)
+            .targets([ItemId(3)])
+            .resolve_deps(true)
+            .build()
+            .unwrap();
+        let options = PlanOptions {
+            proliferator: Proliferator::Mk3,
+            ..PlanOptions::default()
+        };
+        graph
+            .propagate_rates(&data, &BTreeMap::from([(ItemId(3), 60.0)]), &options)
+            .unwrap();
+
+        // Every recipe yields a quarter more, so 48 gears are crafted from 96
+        // ingots, which are smelted from 76.8 ore.
+        let rates = graph
+            .edges()
+            .map(|edge| (edge.from, edge.to, edge.rate.unwrap()))
+            .collect::<Vec<_>>();
+        let rate = |from, to| {
+            rates
+                .iter()
+                .find(|(f, t, _)| *f == from && *t == to)
+                .map(|(_, _, rate)| *rate)
+                .unwrap()
+        };
+        let ore = rate(Node::Item(ItemId(1)), Node::Recipe(RecipeId(1)));
+        let ingots = rate(Node::Item(ItemId(2)), Node::Recipe(RecipeId(2)));
+        let gears = rate(Node::Recipe(RecipeId(2)), Node::Item(ItemId(3)));
+        assert!((ore - 76.8).abs() < 1e-9, "{}", ore);
+        assert!((ingots - 96.0).abs() < 1e-9, "{}", ingots);
+        assert!((gears - 60.0).abs() < 1e-9, "{}", gears);
+        assert_eq!(graph.recipes[&RecipeId(2)].rate, Some(48.0));
+    }
+
+    #[test]
+    fn dot_output_has_the_nodes_and_edges() {
+        let data = data();
+        let graph = GraphBuilder::new(&data)
+            .targets([ItemId(3)])
+            .build()
+            .unwrap();
+
+        let dot = dot(&graph, &DotOptions::default());
+        assert!(dot.starts_with("strict digraph DSP {"), "{}", dot);
+        assert!(dot.contains("    \"Gear\"\n"), "{}", dot);
+        assert!(
+            dot.contains("    \"Ingot\" -> \"2\" [ name=\"2\" ]"),
+            "{}",
+            dot
+        );
+        assert!(
+            dot.contains("    \"2\" -> \"Gear\" [ name=\"1\" ]"),
+            "{}",
+            dot
+        );
+        assert!(dot.trim_end().ends_with('}'), "{}", dot);
+    }
+
+    #[test]
+    fn parallel_edges_are_combined() {
+        let source = r#"
+            return {
+              game_items = {
+                { id = 1, name = "Ore", type = "RESOURCE" },
+                { id = 2, name = "Ingot", type = "MATERIAL" },
+              },
+              game_recipes = {
+                { id = 1, name = "Ingot", type = "SMELT", seconds = 1,
+                  inputs = { 1, 1, 1, 2 }, outputs = { 2, 1 } },
+              },
+            }
+        "#;
+        let data = Data::from_lua_source("parallel", source, None, &[]).unwrap();
+        let graph = GraphBuilder::new(&data)
+            .targets([ItemId(2)])
+            .build()
+            .unwrap();
+
+        let combined = combine_edges(graph.recipe_edges(RecipeId(1)));
+        assert_eq!(combined.len(), 2);
+        assert_eq!(combined[0].0.from, Node::Item(ItemId(1)));
+        assert_eq!(combined[0].0.amount, 3);
+        assert_eq!(combined[0].1, 2);
+        assert_eq!(combined[1].1, 1);
+
+        let options = DotOptions {
+            combine_edges: true,
+            ..DotOptions::default()
+        };
+        let combined = dot(&graph, &options);
+        assert_eq!(
+            combined.matches("\"Ore\" -> \"1\"").count(),
+            1,
+            "{}",
+            combined
+        );
+        assert!(combined.contains("label=\"×2\""), "{}", combined);
+
+        let bundled = dot(&graph, &DotOptions::default());
+        assert_eq!(
+            bundled.matches("\"Ore\" -> \"1\"").count(),
+            2,
+            "{}",
+            bundled
+        );
+    }
+
+    #[test]
+    fn builder_needs_targets() {
+        let data = data();
+
+        let err = GraphBuilder::new(&data).build().unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+
+        let err = GraphBuilder::new(&data)
+            .targets([ItemId(3)])
+            .exclude_items([ItemId(3)])
+            .build()
+            .unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+    }
+
+    #[test]
+    fn builder_resolves_dependencies() {
+        let data = data();
+
+        let graph = GraphBuilder::new(&data)
+            .targets([ItemId(3)])
+            .resolve_deps(true)
+            .build()
+            .unwrap();
+        assert_eq!(recipe_ids(&graph), [RecipeId(1), RecipeId(2)]);
+        assert_eq!(graph.items.len(), 3);
+        assert!(graph.items[&ItemId(1)].source);
+
+        let graph = GraphBuilder::new(&data)
+            .targets([ItemId(3)])
+            .resolve_deps(true)
+            .max_depth(0)
+            .build()
+            .unwrap();
+        assert_eq!(recipe_ids(&graph), [RecipeId(2)]);
+    }
+
+    #[test]
+    fn builder_follows_the_direction() {
+        let data = data();
+
+        let graph = GraphBuilder::new(&data)
+            .targets([ItemId(1)])
+            .resolve_deps(true)
+            .direction(Direction::Downstream)
+            .build()
+            .unwrap();
+        assert_eq!(recipe_ids(&graph), [RecipeId(1), RecipeId(2)]);
+    }
+
+    #[test]
+    fn builder_ignores_recipes() {
+        let data = data();
+
+        let graph = GraphBuilder::new(&data)
+            .targets([ItemId(3)])
+            .resolve_deps(true)
+            .ignore_recipes([RecipeId(1)])
+            .build()
+            .unwrap();
+        assert_eq!(recipe_ids(&graph), [RecipeId(2)]);
+        assert!(graph.items[&ItemId(2)].source);
+    }
+}
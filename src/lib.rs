@@ -1,4 +1,20 @@
+pub mod analysis;
 pub mod args;
+pub mod cache;
 pub mod commands;
+pub mod config;
 pub mod data;
+#[cfg(feature = "remote-data")]
+pub mod download;
 pub mod error;
+pub mod format;
+pub mod graph;
+pub mod logging;
+pub mod meta;
+pub mod picker;
+pub mod planner;
+pub mod resolver;
+pub mod selector;
+pub mod solver;
+pub mod table;
+pub mod theme;
@@ -0,0 +1,127 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{Result as IoResult, Write};
+use std::str::FromStr;
+
+use log::kv::{Error as KvError, Key, Value, VisitSource};
+use log::Record;
+use serde_json::{json, Map, Value as JsonValue};
+
+/* LogFormat */
+
+/// Format of the log messages written to stderr.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum LogFormat {
+    Text,
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "text" => Ok(Self::Text),
+            "json" => Ok(Self::Json),
+            s => Err(format!("Unknown log format: {} (expected text or json)", s)),
+        }
+    }
+}
+
+impl Display for LogFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Text => write!(f, "text"),
+            Self::Json => write!(f, "json"),
+        }
+    }
+}
+
+/// Write the passed record as one line of JSON with the level, the message
+/// and the key-values of the record as `fields`.
+pub fn write_json<W: Write>(w: &mut W, record: &Record<'_>) -> IoResult<()> {
+    let mut fields = Fields::default();
+    let _ = record.key_values().visit(&mut fields);
+
+    let event = json!({
+        "level": record.level().as_str(),
+        "message": record.args().to_string().trim(),
+        "fields": fields.0,
+    });
+
+    writeln!(w, "{}", event)
+}
+
+#[derive(Default)]
+struct Fields(Map<String, JsonValue>);
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), KvError> {
+        let value = if let Some(v) = value.to_u64() {
+            json!(v)
+        } else if let Some(v) = value.to_i64() {
+            json!(v)
+        } else if let Some(v) = value.to_f64() {
+            json!(v)
+        } else if let Some(v) = value.to_bool() {
+            json!(v)
+        } else {
+            json!(value.to_string())
+        };
+
+        self.0.insert(key.to_string(), value);
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use log::Level;
+
+    #[test]
+    fn json_records_have_the_fields_and_are_escaped() {
+        let path = r#"C:\data "new".lua"#;
+        let kvs = [
+            ("recipes", Value::from(3u64)),
+            ("ratio", Value::from(0.5)),
+            ("cached", Value::from(true)),
+            ("path", Value::from_display(&path)),
+        ];
+        let mut out = Vec::new();
+        write_json(
+            &mut out,
+            &Record::builder()
+                .level(Level::Warn)
+                .args(format_args!("Load {:?}\n", path))
+                .key_values(&kvs)
+                .build(),
+        )
+        .unwrap();
+        let line = String::from_utf8(out).unwrap();
+
+        assert!(line.ends_with('\n'));
+        assert_eq!(line.lines().count(), 1);
+        assert!(
+            line.contains(r#""path":"C:\\data \"new\".lua""#),
+            "{}",
+            line
+        );
+
+        let event = serde_json::from_str::<JsonValue>(&line).unwrap();
+        assert_eq!(
+            event,
+            json!({
+                "level": "WARN",
+                "message": r#"Load "C:\\data \"new\".lua""#,
+                "fields": {
+                    "recipes": 3,
+                    "ratio": 0.5,
+                    "cached": true,
+                    "path": path,
+                },
+            })
+        );
+    }
+}
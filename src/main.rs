@@ -1,22 +1,76 @@
-use log::LevelFilter;
+use std::env::var_os;
+use std::process::exit;
 
-use dsp_tool::{args::Args, error::Error};
-use structopt::StructOpt;
+use dsp_tool::{
+    args::Args,
+    config::Config,
+    error::EXIT_USAGE,
+    logging::{write_json, LogFormat},
+    selector::SelectorContext,
+    table::Style,
+};
+use structopt::{clap::ErrorKind, StructOpt};
 
-fn main() -> Result<(), Error> {
-    env_logger::Builder::from_default_env()
+fn main() {
+    let matches = match Args::clap().get_matches_safe() {
+        Ok(matches) => matches,
+        Err(err)
+            if matches!(
+                err.kind,
+                ErrorKind::HelpDisplayed | ErrorKind::VersionDisplayed
+            ) =>
+        {
+            err.exit()
+        }
+        Err(err) => {
+            eprintln!("{}", err.message);
+
+            exit(EXIT_USAGE);
+        }
+    };
+    let mut args = Args::from_clap(&matches);
+
+    let mut logger = env_logger::Builder::from_default_env();
+    logger
         .format_level(true)
         .format_module_path(false)
         .format_target(false)
-        .format_timestamp(None)
-        .filter_level(LevelFilter::Info)
-        .init();
+        .format_timestamp(None);
 
-    let Args { command } = Args::from_args();
+    if args.log_format == LogFormat::Json {
+        logger.format(write_json);
+    }
 
-    if let Err(err) = command.exec() {
-        log::error!("Error while executing the command: {}", err);
+    // An explicit RUST_LOG wins over the verbosity flags.
+    if var_os("RUST_LOG").is_none() {
+        logger.filter_level(args.log_level());
     }
 
-    Ok(())
+    logger.init();
+
+    let config = match Config::load(&args) {
+        Ok(config) => config,
+        Err(err) => {
+            log::error!("Error while loading the config: {}", err);
+
+            exit(err.exit_code());
+        }
+    };
+    config.apply(&mut args, &matches);
+
+    let style = Style::new(args.color, args.wide);
+    let ctx = SelectorContext {
+        groups: config.groups,
+        groups_file: args.groups,
+        ignore_files: args.ignore_files,
+        print_ignores: args.print_effective_ignores,
+        allow_empty_match: args.allow_empty_match,
+    };
+    let Args { data, command, .. } = args;
+
+    if let Err(err) = command.exec(&data, &ctx, &style) {
+        log::error!("Error while executing the command: {}", err);
+
+        exit(err.exit_code());
+    }
 }
@@ -0,0 +1,196 @@
+use std::fmt::Display;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Serialize, Serializer};
+
+use crate::{
+    data::{Data, DataSource},
+    error::Error,
+};
+
+/// Describes how an output was generated, so it can be reproduced later.
+///
+/// The same metadata is written by all output formats: as comments at the
+/// top of DOT files and as `meta` object in JSON.
+#[derive(Debug, Clone, Serialize)]
+pub struct Meta {
+    /// Version of the tool.
+    pub version: String,
+
+    /// Data files that were used.
+    pub data: Vec<DataSource>,
+
+    /// Game version of the data, if the data files contain it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub game_version: Option<String>,
+
+    /// Command line options, in the order they were added.
+    #[serde(serialize_with = "serialize_options")]
+    pub options: Vec<(String, String)>,
+
+    /// Time the output was generated (RFC 3339, UTC).
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+}
+
+impl Meta {
+    /// Create the metadata of an output generated from the passed data. The
+    /// current time is only added if `timestamp` is set.
+    pub fn new(data: &Data, timestamp: bool) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_owned(),
+            data: data.sources.clone(),
+            game_version: data.game_version.clone(),
+            options: Vec::new(),
+            timestamp: timestamp.then(now),
+        }
+    }
+
+    /// Add a command line option with its value.
+    pub fn option(mut self, name: &str, value: impl Display) -> Self {
+        self.options.push((name.to_owned(), value.to_string()));
+
+        self
+    }
+
+    /// Add a command line option with multiple values, which are joined by
+    /// commas. Options without values are skipped.
+    pub fn values<I, T>(self, name: &str, values: I) -> Self
+    where
+        I: IntoIterator<Item = T>,
+        T: Display,
+    {
+        let values = values
+            .into_iter()
+            .map(|v| v.to_string())
+            .collect::<Vec<_>>();
+
+        if values.is_empty() {
+            self
+        } else {
+            self.option(name, values.join(","))
+        }
+    }
+
+    /// Write the metadata as comments, each line starting with `prefix`.
+    pub fn write_comments(&self, prefix: &str, out: &mut dyn Write) -> Result<(), Error> {
+        writeln!(out, "{} Generated by dsp-tool {}", prefix, self.version)?;
+
+        for source in &self.data {
+            writeln!(out, "{} data: {} ({})", prefix, source.path, source.hash)?;
+        }
+
+        if let Some(version) = &self.game_version {
+            writeln!(out, "{} game version: {}", prefix, version)?;
+        }
+
+        for (name, value) in &self.options {
+            writeln!(out, "{} --{}: {}", prefix, name, value)?;
+        }
+
+        if let Some(timestamp) = &self.timestamp {
+            writeln!(out, "{} timestamp: {}", prefix, timestamp)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn serialize_options<S>(options: &[(String, String)], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_map(options.iter().map(|(name, value)| (name, value)))
+}
+
+/// Current time in the RFC 3339 format.
+fn now() -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    rfc3339(secs)
+}
+
+/// Format the passed seconds since the Unix epoch in the RFC 3339 format.
+fn rfc3339(secs: u64) -> String {
+    // Convert the days since the epoch to the civil date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (secs / 86400) as i64 + 719_468;
+    let era = days.div_euclid(146_097);
+    let doe = days.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    let time = secs % 86400;
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        time / 3600,
+        time / 60 % 60,
+        time % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn times_are_formatted_as_rfc3339() {
+        assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(rfc3339(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(rfc3339(1_709_251_199), "2024-02-29T23:59:59Z");
+        assert_eq!(rfc3339(4_102_444_800), "2100-01-01T00:00:00Z");
+    }
+
+    #[test]
+    fn comments_list_the_metadata() {
+        let data = Data::from_lua_source(
+            "meta",
+            "return { game_items = {}, game_recipes = {} }",
+            None,
+            &[],
+        )
+        .unwrap();
+        let mut meta = Meta::new(&data, false)
+            .option("items", "Gear")
+            .values("ignore", Vec::<String>::new())
+            .values("types", ["SMELT", "ASSEMBLE"]);
+        meta.data.push(DataSource::new("data.lua", "return {}"));
+
+        let mut out = Vec::new();
+        meta.write_comments("//", &mut out).unwrap();
+        let comments = String::from_utf8(out).unwrap();
+
+        let lines = comments.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines[0],
+            format!("// Generated by dsp-tool {}", env!("CARGO_PKG_VERSION"))
+        );
+        assert!(lines[1].starts_with("// data: data.lua ("), "{}", comments);
+        assert_eq!(
+            lines[2..],
+            ["// --items: Gear", "// --types: SMELT,ASSEMBLE"]
+        );
+
+        let json = serde_json::to_value(&meta).unwrap();
+        assert_eq!(json["options"]["types"], "SMELT,ASSEMBLE");
+        assert!(json.get("timestamp").is_none());
+    }
+
+    #[test]
+    fn sources_are_hashed_with_fnv() {
+        assert_eq!(DataSource::new("a", "").hash, "cbf29ce484222325");
+        assert_eq!(DataSource::new("a", "a").hash, "af63dc4c8601ec8c");
+    }
+}
@@ -0,0 +1,317 @@
+use std::io::{stderr, stdin, BufRead, IsTerminal, Write};
+
+use itertools::Itertools;
+
+use crate::{
+    data::{Data, ItemId},
+    error::Error,
+};
+
+/// Maximum number of matches that are listed for a filter.
+const MAX_MATCHES: usize = 40;
+
+/// Whether the picker can be used, that is if stdin and stderr are both
+/// terminals.
+pub fn is_interactive() -> bool {
+    stdin().is_terminal() && stderr().is_terminal()
+}
+
+/// Let the user pick items on the terminal. A line with text lists the items
+/// whose names fuzzy match the text, grouped by their type. A line with
+/// numbers (like `1,3` or `2-5`) picks the listed items with these numbers,
+/// or drops them if they are already picked, and an empty line finishes.
+///
+/// The prompts are written to stderr, so the output of the command on stdout
+/// stays untouched. Returns the picked items in the order they were picked.
+pub fn pick_items(data: &Data) -> Result<Vec<ItemId>, Error> {
+    let mut picker = Picker::default();
+
+    eprintln!("No items passed, pick them interactively:");
+    eprintln!("  enter a part of the name to list the matching items (* lists all),");
+    eprintln!("  the numbers of the listed items to pick them (like 1,3 or 2-5),");
+    eprintln!("  and an empty line to finish.");
+
+    let mut lines = stdin().lock().lines();
+    loop {
+        eprint!("> ");
+        stderr().flush()?;
+
+        let line = match lines.next() {
+            Some(line) => line?,
+            None => {
+                eprintln!();
+
+                break;
+            }
+        };
+
+        match picker.handle(data, &line) {
+            Some(output) => output.iter().for_each(|line| eprintln!("{}", line)),
+            None => break,
+        }
+    }
+
+    if picker.picked.is_empty() {
+        return Err(Error::usage("No items picked"));
+    }
+
+    Ok(picker.picked)
+}
+
+/// State of [`pick_items`] between the lines of the user.
+#[derive(Debug, Default)]
+struct Picker {
+    /// Picked items, in the order they were picked.
+    picked: Vec<ItemId>,
+
+    /// Items of the last listing, in the order of their numbers.
+    listed: Vec<ItemId>,
+}
+
+impl Picker {
+    /// Handle a line of the user and return the lines to print. Returns
+    /// `None` if the line finishes the picking.
+    fn handle(&mut self, data: &Data, line: &str) -> Option<Vec<String>> {
+        let line = line.trim();
+        if line.is_empty() {
+            return None;
+        }
+
+        let mut output = Vec::new();
+
+        if let Some(numbers) = parse_numbers(line) {
+            for number in numbers {
+                let iid = match number.checked_sub(1).and_then(|i| self.listed.get(i)) {
+                    Some(iid) => *iid,
+                    None => {
+                        output.push(format!("  no listed item has the number {}", number));
+
+                        continue;
+                    }
+                };
+
+                match self.picked.iter().position(|p| *p == iid) {
+                    Some(index) => {
+                        self.picked.remove(index);
+                    }
+                    None => self.picked.push(iid),
+                }
+            }
+
+            if self.picked.is_empty() {
+                output.push("  nothing picked".to_owned());
+            } else {
+                output.push(format!(
+                    "  picked: {}",
+                    self.picked
+                        .iter()
+                        .map(|iid| &data.items[iid].name)
+                        .join(", ")
+                ));
+            }
+
+            return Some(output);
+        }
+
+        self.listed = matches(data, line);
+        if self.listed.is_empty() {
+            output.push(format!("  no item matches {:?}", line));
+
+            return Some(output);
+        }
+
+        let mut number = 0;
+        for (type_, items) in &self.listed.iter().group_by(|iid| &data.items[*iid].type_) {
+            output.push(format!("  {}", type_));
+            for iid in items {
+                number += 1;
+                let mark = if self.picked.contains(iid) { '*' } else { ' ' };
+                output.push(format!(
+                    "  {} {:>3}  {}",
+                    mark, number, data.items[iid].name
+                ));
+            }
+        }
+
+        Some(output)
+    }
+}
+
+/// Items whose names fuzzy match the query (see [`fuzzy_score`]), the best
+/// matches first, grouped by the type of the items.
+fn matches(data: &Data, query: &str) -> Vec<ItemId> {
+    let scored = data
+        .items
+        .iter()
+        .filter_map(|(iid, item)| {
+            let score = if query == "*" {
+                Some(0)
+            } else {
+                fuzzy_score(query, &item.name)
+            };
+
+            score.map(|score| (score, item.name.as_str(), *iid))
+        })
+        .sorted()
+        .take(MAX_MATCHES)
+        .map(|(_, _, iid)| iid)
+        .collect::<Vec<_>>();
+
+    // Order the types by their best match, and keep the order of the matches
+    // inside each type.
+    let mut types = Vec::new();
+    for iid in &scored {
+        let type_ = &data.items[iid].type_;
+        if !types.contains(&type_) {
+            types.push(type_);
+        }
+    }
+
+    types
+        .into_iter()
+        .flat_map(|type_| {
+            scored
+                .iter()
+                .filter(move |iid| data.items[*iid].type_ == *type_)
+                .copied()
+        })
+        .collect()
+}
+
+/// Score of a fuzzy match of the query in the name, lower is better. The
+/// characters of the query have to appear in the name in the same order,
+/// ignoring case and whitespace. The score is the number of characters of
+/// the name that are skipped. `None` if the name does not match.
+fn fuzzy_score(query: &str, name: &str) -> Option<usize> {
+    let name = name.to_lowercase().chars().collect::<Vec<_>>();
+
+    let mut score = 0;
+    let mut pos = 0;
+    for c in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let found = pos + name[pos..].iter().position(|n| *n == c)?;
+
+        // Skipped characters before the first match count less, so names
+        // that start with the query are not preferred too much.
+        score += if pos == 0 {
+            usize::from(found > 0)
+        } else {
+            found - pos
+        };
+        pos = found + 1;
+    }
+
+    Some(score)
+}
+
+/// Parse a line of numbers and inclusive ranges, separated by commas or
+/// whitespace. `None` if the line contains anything else.
+fn parse_numbers(line: &str) -> Option<Vec<usize>> {
+    let mut ret = Vec::new();
+
+    for part in line.split(|c: char| c == ',' || c.is_whitespace()) {
+        if part.is_empty() {
+            continue;
+        }
+
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start = start.trim().parse::<usize>().ok()?;
+                let end = end.trim().parse::<usize>().ok()?;
+
+                ret.extend((start..=end).take(MAX_MATCHES));
+            }
+            None => ret.push(part.parse().ok()?),
+        }
+    }
+
+    Some(ret)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Items of two types, so the listings are grouped.
+    const SOURCE: &str = r#"
+        return {
+          game_items = {
+            { id = 1, name = "Iron Ore", type = "RESOURCE" },
+            { id = 2, name = "Iron Ingot", type = "MATERIAL" },
+            { id = 3, name = "Copper Ore", type = "RESOURCE" },
+            { id = 4, name = "Magnet", type = "MATERIAL" },
+          },
+          game_recipes = {},
+        }
+    "#;
+
+    fn data() -> Data {
+        Data::from_lua_source("picker", SOURCE, None, &[]).unwrap()
+    }
+
+    #[test]
+    fn fuzzy_score_counts_the_skipped_characters() {
+        assert_eq!(fuzzy_score("iron", "Iron Ore"), Some(0));
+        assert_eq!(fuzzy_score("i o", "Iron Ore"), Some(1));
+        assert_eq!(fuzzy_score("ore", "Iron Ore"), Some(4));
+        assert_eq!(fuzzy_score("ore", "Copper Ore"), Some(7));
+        assert_eq!(fuzzy_score("ingot", "Iron Ingot"), Some(5));
+        assert_eq!(fuzzy_score("tongi", "Iron Ingot"), None);
+    }
+
+    #[test]
+    fn numbers_and_ranges_are_parsed() {
+        assert_eq!(parse_numbers("1,3"), Some(vec![1, 3]));
+        assert_eq!(parse_numbers(" 2-4  6"), Some(vec![2, 3, 4, 6]));
+        assert_eq!(parse_numbers("1, 2 - 3"), None);
+        assert_eq!(parse_numbers("2-x"), None);
+        assert_eq!(parse_numbers("iron"), None);
+    }
+
+    #[test]
+    fn matches_are_grouped_by_type() {
+        let data = data();
+
+        assert_eq!(matches(&data, "ore"), vec![ItemId(1), ItemId(3)]);
+
+        // Copper Ore is the best match, so Iron Ore is listed before the
+        // equally good Iron Ingot.
+        assert_eq!(matches(&data, "o"), vec![ItemId(3), ItemId(1), ItemId(2)]);
+        assert_eq!(matches(&data, "*").len(), 4);
+        assert!(matches(&data, "steel").is_empty());
+    }
+
+    #[test]
+    fn numbers_toggle_the_listed_items() {
+        let data = data();
+        let mut picker = Picker::default();
+
+        assert_eq!(
+            picker.handle(&data, "iron").unwrap(),
+            vec![
+                "  MATERIAL",
+                "      1  Iron Ingot",
+                "  RESOURCE",
+                "      2  Iron Ore",
+            ]
+        );
+        assert_eq!(
+            picker.handle(&data, "2,1").unwrap(),
+            vec!["  picked: Iron Ore, Iron Ingot"]
+        );
+        assert_eq!(
+            picker.handle(&data, "1 3").unwrap(),
+            vec!["  no listed item has the number 3", "  picked: Iron Ore"]
+        );
+        assert_eq!(
+            picker.handle(&data, "ore").unwrap(),
+            vec!["  RESOURCE", "  *   1  Iron Ore", "      2  Copper Ore"]
+        );
+        assert_eq!(picker.handle(&data, "1").unwrap(), vec!["  nothing picked"]);
+        assert_eq!(
+            picker.handle(&data, "steel").unwrap(),
+            vec!["  no item matches \"steel\""]
+        );
+        assert_eq!(picker.handle(&data, "  "), None);
+        assert!(picker.picked.is_empty());
+    }
+}
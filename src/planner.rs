@@ -0,0 +1,1311 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::str::FromStr;
+
+use itertools::Itertools;
+
+use crate::{
+    data::{Building, Data, ItemId, Recipe, RecipeId, RecipeType},
+    error::Error,
+    resolver::{PinnedList, PreferBasic, RecipeSelector},
+    selector::Selection,
+    solver::{self, Flow},
+};
+
+/* PlanOptions */
+
+/// Options that control how a production plan is calculated.
+#[derive(Debug)]
+pub struct PlanOptions {
+    /// Recipes and items that are never used. Ignored items are treated as
+    /// resources that are supplied from outside.
+    pub ignore: Selection,
+
+    /// Proliferator that is sprayed on the inputs of all recipes.
+    pub proliferator: Proliferator,
+
+    /// How the sprayed proliferator is used.
+    pub spray_mode: SprayMode,
+
+    /// Buildings that are used for the recipe types. Recipe types without an
+    /// entry use the default building of the data.
+    pub buildings: HashMap<RecipeType, ItemId>,
+
+    /// Selects the recipe that is used for items with multiple producing
+    /// recipes. The first selected recipe is used.
+    pub selector: Box<dyn RecipeSelector>,
+}
+
+impl Default for PlanOptions {
+    fn default() -> Self {
+        Self {
+            ignore: Selection::default(),
+            proliferator: Proliferator::default(),
+            spray_mode: SprayMode::default(),
+            buildings: HashMap::new(),
+            selector: Box::new(PreferBasic),
+        }
+    }
+}
+
+impl PlanOptions {
+    /// Copy of the options that uses the passed recipe selector.
+    pub fn with_selector(&self, selector: Box<dyn RecipeSelector>) -> Self {
+        Self {
+            ignore: self.ignore.clone(),
+            proliferator: self.proliferator,
+            spray_mode: self.spray_mode,
+            buildings: self.buildings.clone(),
+            selector,
+        }
+    }
+
+    /// Get the building that is used to craft the passed recipe.
+    pub fn building<'a>(&self, data: &'a Data, recipe: &Recipe) -> Option<(ItemId, &'a Building)> {
+        match self.buildings.get(&recipe.type_) {
+            Some(bid) => data.buildings.get(bid).map(|b| (*bid, b)),
+            None => data.building_for(&recipe.type_),
+        }
+    }
+
+    /// Crafting speed of the machines of the passed recipe, including the
+    /// proliferator. If there is no building data for the recipe, a speed of
+    /// 1 is assumed.
+    pub fn crafting_speed(&self, data: &Data, recipe: &Recipe) -> f64 {
+        let speed = self
+            .building(data, recipe)
+            .map(|(_, b)| b.speed)
+            .unwrap_or(1.0);
+
+        speed * self.speed_multiplier(recipe)
+    }
+
+    /// Number of machines that are needed to run the passed recipe at the
+    /// passed crafts per minute (see [`PlanOptions::crafting_speed`]).
+    pub fn machines(&self, data: &Data, recipe: &Recipe, crafts: f64) -> f64 {
+        crafts * recipe.seconds / 60.0 / self.crafting_speed(data, recipe)
+    }
+
+    /// Get the spray mode that is effectively used for the passed recipe.
+    ///
+    /// Recipes that can not receive extra products fall back to speedup.
+    pub fn spray_mode(&self, recipe: &Recipe) -> Option<SprayMode> {
+        match (self.proliferator, self.spray_mode) {
+            (Proliferator::None, _) => None,
+            (_, SprayMode::ExtraProducts) if recipe.non_productive => Some(SprayMode::Speedup),
+            (_, mode) => Some(mode),
+        }
+    }
+
+    /// Factor that is applied to the outputs of the passed recipe, including
+    /// the productivity of the recipe itself.
+    pub fn output_multiplier(&self, recipe: &Recipe) -> f64 {
+        let proliferator = match self.spray_mode(recipe) {
+            Some(SprayMode::ExtraProducts) => 1.0 + self.proliferator.extra_products(),
+            _ => 1.0,
+        };
+
+        proliferator * recipe.productivity
+    }
+
+    /// Factor that is applied to the crafting speed of the passed recipe.
+    pub fn speed_multiplier(&self, recipe: &Recipe) -> f64 {
+        match self.spray_mode(recipe) {
+            Some(SprayMode::Speedup) => 1.0 + self.proliferator.speedup(),
+            _ => 1.0,
+        }
+    }
+
+    /// Factor that is applied to the power consumption of the passed recipe.
+    pub fn power_multiplier(&self, recipe: &Recipe) -> f64 {
+        match self.spray_mode(recipe) {
+            Some(_) => 1.0 + self.proliferator.power(),
+            None => 1.0,
+        }
+    }
+}
+
+/* Proliferator */
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum Proliferator {
+    #[default]
+    None,
+    Mk1,
+    Mk2,
+    Mk3,
+}
+
+impl Proliferator {
+    /// Bonus of additional products.
+    pub fn extra_products(&self) -> f64 {
+        match self {
+            Self::None => 0.0,
+            Self::Mk1 => 0.125,
+            Self::Mk2 => 0.2,
+            Self::Mk3 => 0.25,
+        }
+    }
+
+    /// Bonus of crafting speed.
+    pub fn speedup(&self) -> f64 {
+        match self {
+            Self::None => 0.0,
+            Self::Mk1 => 0.25,
+            Self::Mk2 => 0.5,
+            Self::Mk3 => 1.0,
+        }
+    }
+
+    /// Additional power consumption.
+    pub fn power(&self) -> f64 {
+        match self {
+            Self::None => 0.0,
+            Self::Mk1 => 0.3,
+            Self::Mk2 => 0.7,
+            Self::Mk3 => 1.5,
+        }
+    }
+}
+
+impl FromStr for Proliferator {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(Self::None),
+            "mk1" => Ok(Self::Mk1),
+            "mk2" => Ok(Self::Mk2),
+            "mk3" => Ok(Self::Mk3),
+            s => Err(format!(
+                "Unknown proliferator: {} (expected none, mk1, mk2 or mk3)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Proliferator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::None => write!(f, "none"),
+            Self::Mk1 => write!(f, "Mk.I"),
+            Self::Mk2 => write!(f, "Mk.II"),
+            Self::Mk3 => write!(f, "Mk.III"),
+        }
+    }
+}
+
+/* SprayMode */
+
+#[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
+pub enum SprayMode {
+    #[default]
+    ExtraProducts,
+    Speedup,
+}
+
+impl FromStr for SprayMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "extra-products" => Ok(Self::ExtraProducts),
+            "speedup" => Ok(Self::Speedup),
+            s => Err(format!(
+                "Unknown spray mode: {} (expected extra-products or speedup)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for SprayMode {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::ExtraProducts => write!(f, "extra products"),
+            Self::Speedup => write!(f, "production speedup"),
+        }
+    }
+}
+
+/* Objective */
+
+/// Value that is minimized by [`optimize`].
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Objective {
+    /// Sum of all resources in items per minute.
+    RawResources,
+
+    /// Power consumption of all machines.
+    Power,
+}
+
+impl FromStr for Objective {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "raw-resources" => Ok(Self::RawResources),
+            "power" => Ok(Self::Power),
+            s => Err(format!(
+                "Unknown objective: {} (expected raw-resources or power)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for Objective {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::RawResources => write!(f, "raw-resources"),
+            Self::Power => write!(f, "power"),
+        }
+    }
+}
+
+impl Objective {
+    /// Unit of the cost.
+    pub fn unit(&self) -> &'static str {
+        match self {
+            Self::RawResources => "/min",
+            Self::Power => "kW",
+        }
+    }
+}
+
+/* ProductionPlan */
+
+/// Production plan for a set of target items.
+///
+/// All rates of the plan are measured per minute.
+#[derive(Debug, Default)]
+pub struct ProductionPlan {
+    /// Options the plan was calculated with.
+    pub options: PlanOptions,
+
+    /// Crafts per minute of each recipe used by the plan.
+    pub recipes: BTreeMap<RecipeId, f64>,
+
+    /// Items that are not produced by the plan and need to be supplied.
+    pub resources: BTreeMap<ItemId, f64>,
+
+    /// Items that are produced as side effect of the used recipes.
+    pub byproducts: BTreeMap<ItemId, f64>,
+}
+
+impl ProductionPlan {
+    /// Create a new plan that produces the passed items at the passed rates.
+    ///
+    /// If the selected recipes contain a cycle, or a byproduct of one recipe
+    /// is consumed by another, the steady state of all recipes is solved (see
+    /// [`solver::solve`]). Otherwise the demand is propagated recursively.
+    pub fn new(
+        data: &Data,
+        targets: &BTreeMap<ItemId, f64>,
+        options: PlanOptions,
+    ) -> Result<Self, Error> {
+        let mut plan = Self {
+            options,
+            ..Default::default()
+        };
+
+        let selection = plan.select_recipes(data, targets);
+        if selection.needs_solver {
+            log::debug!(
+                recipes = selection.items.len();
+                "  solve the steady state of {} recipes",
+                selection.items.len()
+            );
+            plan.solve(data, targets, &selection.items)?;
+
+            return Ok(plan);
+        }
+
+        let mut stack = Vec::new();
+        for (iid, rate) in targets {
+            plan.add_demand(data, &mut stack, *iid, *rate);
+        }
+
+        Ok(plan)
+    }
+
+    /// Amount of each item that is produced by the recipes of the plan.
+    pub fn production(&self, data: &Data) -> BTreeMap<ItemId, f64> {
+        let mut ret = BTreeMap::<ItemId, f64>::new();
+
+        for (rid, crafts) in &self.recipes {
+            if let Some(recipe) = data.recipes.get(rid) {
+                let multiplier = self.options.output_multiplier(recipe);
+                for output in &recipe.outputs {
+                    *ret.entry(output.id).or_default() +=
+                        crafts * output.amount as f64 * multiplier;
+                }
+            }
+        }
+
+        ret
+    }
+
+    /// Number of machines that are needed to run the passed recipe at the
+    /// planned rate. If there is no building data for the recipe, a crafting
+    /// speed of 1 is assumed.
+    pub fn machines(&self, data: &Data, rid: RecipeId) -> f64 {
+        match (self.recipes.get(&rid), data.recipes.get(&rid)) {
+            (Some(crafts), Some(recipe)) => self.options.machines(data, recipe, *crafts),
+            (_, _) => 0.0,
+        }
+    }
+
+    /// Power consumption of the machines of the passed recipe in kW.
+    ///
+    /// If `idle_power` is set, the idle power of the partially used machine
+    /// is added. Returns `None` if there is no building data for the recipe.
+    pub fn power(&self, data: &Data, rid: RecipeId, idle_power: bool) -> Option<f64> {
+        let recipe = data.recipes.get(&rid)?;
+        let (_, building) = self.options.building(data, recipe)?;
+
+        let machines = self.machines(data, rid);
+        let mut power = machines * building.work_power * self.options.power_multiplier(recipe);
+        if idle_power {
+            power += (machines.ceil() - machines) * building.idle_power;
+        }
+
+        Some(power)
+    }
+
+    /// Cost of the plan for the passed objective. Returns `None` if the power
+    /// is requested and building data is missing for a used recipe.
+    pub fn cost(&self, data: &Data, objective: Objective) -> Option<f64> {
+        match objective {
+            Objective::RawResources => Some(self.resources.values().sum()),
+            Objective::Power => self
+                .recipes
+                .keys()
+                .map(|rid| self.power(data, *rid, false))
+                .sum(),
+        }
+    }
+
+    /// Select the recipe of each item that is needed to produce the targets.
+    fn select_recipes(&self, data: &Data, targets: &BTreeMap<ItemId, f64>) -> RecipeSelection {
+        let mut selection = RecipeSelection::default();
+        let mut visited = BTreeSet::new();
+        let mut stack = Vec::new();
+
+        for iid in targets.keys() {
+            self.visit(data, &mut selection, &mut visited, &mut stack, *iid);
+        }
+
+        let consumed = selection
+            .items
+            .keys()
+            .filter_map(|rid| data.recipes.get(rid))
+            .flat_map(|r| r.inputs.iter().map(|i| i.id))
+            .chain(targets.keys().copied())
+            .collect::<BTreeSet<_>>();
+        let shared = selection.items.iter().any(|(rid, items)| {
+            data.recipes[rid]
+                .outputs
+                .iter()
+                .any(|o| o.id != items[0] && consumed.contains(&o.id))
+        });
+        selection.needs_solver |= shared;
+
+        selection
+    }
+
+    fn visit(
+        &self,
+        data: &Data,
+        selection: &mut RecipeSelection,
+        visited: &mut BTreeSet<ItemId>,
+        stack: &mut Vec<ItemId>,
+        iid: ItemId,
+    ) {
+        if stack.contains(&iid) {
+            selection.needs_solver = true;
+
+            return;
+        }
+
+        if !visited.insert(iid) || self.options.ignore.items.contains(&iid) {
+            return;
+        }
+
+        let ignore = &self.options.ignore.recipes;
+        let (rid, recipe) = match select_recipe(data, ignore, self.options.selector.as_ref(), iid) {
+            Some(selected) => selected,
+            None => return,
+        };
+
+        if let Some(items) = selection.items.get_mut(&rid) {
+            items.push(iid);
+            selection.needs_solver = true;
+
+            return;
+        }
+        selection.items.insert(rid, vec![iid]);
+
+        stack.push(iid);
+        for input in &recipe.inputs {
+            self.visit(data, selection, visited, stack, input.id);
+        }
+        stack.pop();
+    }
+
+    /// Calculate the plan from the steady state of the selected recipes.
+    fn solve(
+        &mut self,
+        data: &Data,
+        targets: &BTreeMap<ItemId, f64>,
+        items: &BTreeMap<RecipeId, Vec<ItemId>>,
+    ) -> Result<(), Error> {
+        let flows = items
+            .keys()
+            .map(|rid| {
+                let recipe = &data.recipes[rid];
+                let multiplier = self.options.output_multiplier(recipe);
+
+                let flow = Flow {
+                    inputs: recipe
+                        .inputs
+                        .iter()
+                        .map(|i| (i.id, i.amount as f64))
+                        .collect(),
+                    outputs: recipe
+                        .outputs
+                        .iter()
+                        .map(|o| (o.id, o.amount as f64 * multiplier))
+                        .collect(),
+                };
+
+                (*rid, flow)
+            })
+            .collect::<BTreeMap<_, _>>();
+
+        let item_name = |iid: ItemId| {
+            data.items
+                .get(&iid)
+                .map(|i| i.name.clone())
+                .unwrap_or_else(|| iid.label(&data.keys).to_string())
+        };
+        let state = solver::solve(&flows, items, targets, item_name)?;
+
+        self.recipes = state
+            .recipes
+            .into_iter()
+            .filter(|(_, crafts)| *crafts > 0.0)
+            .collect();
+        for (iid, rate) in state.surplus {
+            if rate < 0.0 {
+                self.resources.insert(iid, -rate);
+            } else {
+                self.byproducts.insert(iid, rate);
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_demand(&mut self, data: &Data, stack: &mut Vec<ItemId>, iid: ItemId, rate: f64) {
+        let ignore = &self.options.ignore;
+        let selected = if stack.contains(&iid) || ignore.items.contains(&iid) {
+            None
+        } else {
+            select_recipe(data, &ignore.recipes, self.options.selector.as_ref(), iid)
+        };
+
+        let (rid, recipe) = match selected {
+            Some(selected) => selected,
+            None => {
+                *self.resources.entry(iid).or_default() += rate;
+
+                return;
+            }
+        };
+
+        let multiplier = self.options.output_multiplier(recipe);
+        let crafts = rate / (output_amount(recipe, iid) * multiplier);
+        *self.recipes.entry(rid).or_default() += crafts;
+
+        for output in &recipe.outputs {
+            if output.id != iid {
+                *self.byproducts.entry(output.id).or_default() +=
+                    crafts * output.amount as f64 * multiplier;
+            }
+        }
+
+        stack.push(iid);
+        for input in &recipe.inputs {
+            self.add_demand(data, stack, input.id, crafts * input.amount as f64);
+        }
+        stack.pop();
+    }
+}
+
+/* Optimization */
+
+/// Result of [`optimize`].
+#[derive(Debug)]
+pub struct Optimization {
+    /// Plan with the optimized recipes.
+    pub plan: ProductionPlan,
+
+    /// Items whose recipe differs from the default selection.
+    pub choices: Vec<Choice>,
+
+    /// Cost of the plan with the default selection.
+    pub default_cost: f64,
+
+    /// Cost of the optimized plan.
+    pub cost: f64,
+}
+
+/// Recipe that was chosen for an item by [`optimize`].
+#[derive(Debug, Clone, Copy)]
+pub struct Choice {
+    pub item: ItemId,
+    pub recipe: RecipeId,
+
+    /// Recipe that is used by the default selection.
+    pub default: Option<RecipeId>,
+}
+
+/// Choose the recipes that minimize the cost of the plan for the targets.
+///
+/// This is a greedy heuristic: for each item of the plan that can be
+/// produced by multiple recipes, every candidate is evaluated by calculating
+/// the whole plan with it, and the cheapest candidate is kept. This is
+/// repeated until no item changes. Items that can be produced by one of the
+/// `pins` always use the pinned recipe.
+pub fn optimize(
+    data: &Data,
+    targets: &BTreeMap<ItemId, f64>,
+    options: &PlanOptions,
+    pins: &BTreeSet<RecipeId>,
+    objective: Objective,
+) -> Result<Optimization, Error> {
+    let selector = |chosen: &BTreeMap<ItemId, RecipeId>| {
+        let recipes = pins.iter().chain(chosen.values()).copied();
+
+        PinnedList::new(recipes, Box::new(PreferBasic))
+    };
+    let calculate = |chosen: &BTreeMap<ItemId, RecipeId>| {
+        let options = options.with_selector(Box::new(selector(chosen)));
+
+        ProductionPlan::new(data, targets, options)
+    };
+    let missing_power = || {
+        Error::custom(format!(
+            "Unable to optimize for {}: building data is missing for some recipes",
+            objective
+        ))
+    };
+
+    let mut chosen = BTreeMap::<ItemId, RecipeId>::new();
+    let mut plan = calculate(&chosen)?;
+    let default_cost = plan.cost(data, objective).ok_or_else(missing_power)?;
+    let mut cost = default_cost;
+
+    for _ in 0..MAX_PASSES {
+        let mut changed = false;
+
+        let items = plan
+            .recipes
+            .keys()
+            .filter_map(|rid| data.recipes.get(rid))
+            .flat_map(|r| r.outputs.iter().map(|o| o.id))
+            .collect::<BTreeSet<_>>();
+
+        for iid in items {
+            let candidates = candidates(data, &options.ignore.recipes, iid);
+            if candidates.len() < 2 || candidates.iter().any(|rid| pins.contains(rid)) {
+                continue;
+            }
+
+            for rid in candidates {
+                if chosen.get(&iid) == Some(&rid) {
+                    continue;
+                }
+
+                let mut next = chosen.clone();
+                next.insert(iid, rid);
+
+                let next_plan = match calculate(&next) {
+                    Ok(next_plan) => next_plan,
+                    Err(err) => {
+                        log::debug!("  skip recipe {} for item {}: {}", rid, iid, err);
+
+                        continue;
+                    }
+                };
+
+                if let Some(next_cost) = next_plan.cost(data, objective) {
+                    if next_cost < cost - COST_EPSILON {
+                        log::debug!(
+                            "  use recipe {} for item {}: {:.2} {}",
+                            rid,
+                            iid,
+                            next_cost,
+                            objective.unit()
+                        );
+
+                        chosen = next;
+                        plan = next_plan;
+                        cost = next_cost;
+                        changed = true;
+                    }
+                }
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    let default_selector = selector(&BTreeMap::new());
+    let choices = chosen
+        .into_iter()
+        .filter_map(|(item, recipe)| {
+            let default = select_recipe(data, &options.ignore.recipes, &default_selector, item)
+                .map(|(rid, _)| rid);
+
+            (default != Some(recipe)).then_some(Choice {
+                item,
+                recipe,
+                default,
+            })
+        })
+        .collect();
+
+    Ok(Optimization {
+        plan,
+        choices,
+        default_cost,
+        cost,
+    })
+}
+
+/// Recipes that produce the passed item and are not ignored.
+fn candidates(data: &Data, ignore: &BTreeSet<RecipeId>, iid: ItemId) -> Vec<RecipeId> {
+    data.as_output
+        .get(&iid)
+        .into_iter()
+        .flatten()
+        .filter(|rid| !ignore.contains(rid))
+        .filter(|rid| {
+            data.recipes
+                .get(rid)
+                .is_some_and(|r| output_amount(r, iid) * r.productivity > 0.0)
+        })
+        .copied()
+        .sorted()
+        .collect()
+}
+
+/// Maximum number of passes over the items of the plan.
+const MAX_PASSES: usize = 4;
+
+/// Smallest cost difference that counts as improvement.
+const COST_EPSILON: f64 = 1e-6;
+
+/* ShoppingList */
+
+/// How the crafts of a [`ShoppingList`] are rounded.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Rounding {
+    /// Round up the crafts of each recipe, partial crafts are not possible.
+    #[default]
+    Ceil,
+
+    /// Use the exact fractions of crafts.
+    Exact,
+}
+
+impl FromStr for Rounding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ceil" => Ok(Self::Ceil),
+            "exact" => Ok(Self::Exact),
+            s => Err(format!("Unknown rounding: {} (expected ceil or exact)", s)),
+        }
+    }
+}
+
+impl Display for Rounding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Ceil => write!(f, "ceil"),
+            Self::Exact => write!(f, "exact"),
+        }
+    }
+}
+
+/// Total amounts that are needed to craft a fixed number of items once.
+#[derive(Debug, Default)]
+pub struct ShoppingList {
+    /// Number of crafts of each used recipe.
+    pub recipes: BTreeMap<RecipeId, f64>,
+
+    /// Items that are crafted and consumed while producing the targets.
+    pub intermediates: BTreeMap<ItemId, f64>,
+
+    /// Items that are not crafted and need to be supplied.
+    pub resources: BTreeMap<ItemId, f64>,
+
+    /// Items that are left over, either as byproducts or because of
+    /// rounded up crafts.
+    pub leftovers: BTreeMap<ItemId, f64>,
+}
+
+impl ShoppingList {
+    /// Create a new list that crafts the passed amounts of items.
+    ///
+    /// With [`Rounding::Ceil`] the demand of each item is summed up before
+    /// its crafts are rounded up, starting at the targets. Recipe loops and
+    /// byproducts that are consumed by other recipes can only be calculated
+    /// with [`Rounding::Exact`].
+    pub fn new(
+        data: &Data,
+        amounts: &BTreeMap<ItemId, f64>,
+        options: PlanOptions,
+        rounding: Rounding,
+    ) -> Result<Self, Error> {
+        let plan = ProductionPlan {
+            options,
+            ..Default::default()
+        };
+
+        let selection = plan.select_recipes(data, amounts);
+        if rounding == Rounding::Exact {
+            let plan = ProductionPlan::new(data, amounts, plan.options)?;
+
+            return Ok(Self::from_plan(data, amounts, plan));
+        }
+
+        if selection.needs_solver {
+            return Err(Error::custom(
+                "Unable to round up the crafts: the recipes contain a loop or consume byproducts (use exact rounding instead)",
+            ));
+        }
+
+        let recipes = selection
+            .items
+            .iter()
+            .map(|(rid, items)| (items[0], *rid))
+            .collect::<BTreeMap<_, _>>();
+
+        let mut order = Vec::new();
+        let mut visited = BTreeSet::new();
+        for iid in amounts.keys() {
+            sort_items(data, &recipes, &mut visited, &mut order, *iid);
+        }
+
+        let mut ret = Self::default();
+        let mut demand = amounts.clone();
+        for iid in order.into_iter().rev() {
+            let rate = demand.get(&iid).copied().unwrap_or_default();
+            let (rid, recipe) = match recipes
+                .get(&iid)
+                .and_then(|rid| data.recipes.get_key_value(rid))
+            {
+                Some(selected) => selected,
+                None => {
+                    ret.resources.insert(iid, rate);
+
+                    continue;
+                }
+            };
+
+            let multiplier = plan.options.output_multiplier(recipe);
+            let produced = output_amount(recipe, iid) * multiplier;
+            let crafts = (rate / produced - AMOUNT_EPSILON).ceil().max(0.0);
+            ret.recipes.insert(*rid, crafts);
+
+            let target = amounts.get(&iid).copied().unwrap_or_default();
+            if rate > target {
+                ret.intermediates.insert(iid, rate - target);
+            }
+
+            for output in &recipe.outputs {
+                let left = if output.id == iid {
+                    crafts * produced - rate
+                } else {
+                    crafts * output.amount as f64 * multiplier
+                };
+                if left > AMOUNT_EPSILON {
+                    *ret.leftovers.entry(output.id).or_default() += left;
+                }
+            }
+            for input in &recipe.inputs {
+                *demand.entry(input.id).or_default() += crafts * input.amount as f64;
+            }
+        }
+
+        Ok(ret)
+    }
+
+    fn from_plan(data: &Data, amounts: &BTreeMap<ItemId, f64>, plan: ProductionPlan) -> Self {
+        let intermediates = plan
+            .production(data)
+            .into_iter()
+            .map(|(iid, amount)| {
+                let target = amounts.get(&iid).copied().unwrap_or_default();
+                let left = plan.byproducts.get(&iid).copied().unwrap_or_default();
+
+                (iid, amount - target - left)
+            })
+            .filter(|(_, amount)| *amount > AMOUNT_EPSILON)
+            .collect();
+
+        Self {
+            recipes: plan.recipes,
+            intermediates,
+            resources: plan.resources,
+            leftovers: plan.byproducts,
+        }
+    }
+}
+
+/// Tolerance for rounding errors of the amounts.
+const AMOUNT_EPSILON: f64 = 1e-9;
+
+/// Add the passed item to `order` after all items it is crafted from.
+fn sort_items(
+    data: &Data,
+    recipes: &BTreeMap<ItemId, RecipeId>,
+    visited: &mut BTreeSet<ItemId>,
+    order: &mut Vec<ItemId>,
+    iid: ItemId,
+) {
+    if !visited.insert(iid) {
+        return;
+    }
+
+    let recipe = recipes.get(&iid).and_then(|rid| data.recipes.get(rid));
+    for input in recipe.into_iter().flat_map(|r| &r.inputs) {
+        sort_items(data, recipes, visited, order, input.id);
+    }
+
+    order.push(iid);
+}
+
+/// Recipes that are selected to produce the targets.
+#[derive(Default)]
+struct RecipeSelection {
+    /// Items each recipe was selected for, in the order they were visited.
+    items: BTreeMap<RecipeId, Vec<ItemId>>,
+
+    /// The recipes contain a cycle or share items, so the demand can not be
+    /// propagated recursively.
+    needs_solver: bool,
+}
+
+/// Select the recipe that is used to produce the passed item.
+///
+/// Only recipes that actually produce the item are considered. Of the
+/// recipes chosen by the selector, the one with the lowest id is used, so
+/// with [`PreferBasic`] primary (not explicit) recipes are preferred.
+pub fn select_recipe<'a>(
+    data: &'a Data,
+    ignore: &BTreeSet<RecipeId>,
+    selector: &dyn RecipeSelector,
+    iid: ItemId,
+) -> Option<(RecipeId, &'a Recipe)> {
+    let candidates = candidates(data, ignore, iid);
+
+    selector
+        .select(iid, &candidates, data)
+        .into_iter()
+        .filter_map(|rid| data.recipes.get(&rid).map(|r| (rid, r)))
+        .min_by_key(|(rid, _)| *rid)
+}
+
+fn output_amount(recipe: &Recipe, iid: ItemId) -> f64 {
+    recipe
+        .outputs
+        .iter()
+        .filter(|o| o.id == iid)
+        .map(|o| o.amount)
+        .sum::<usize>() as f64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Gears are made of ingots, crops are grown from seeds that are made of
+    /// crops, and fuel is made of refined oil and the hydrogen that refining
+    /// the oil yields as byproduct. Gears can be melted down again, which does
+    /// not accept extra products.
+    const SOURCE: &str = r#"
+        return {
+          game_items = {
+            { id = 1, name = "Ore", type = "RESOURCE" },
+            { id = 2, name = "Ingot", type = "MATERIAL" },
+            { id = 3, name = "Gear", type = "COMPONENT" },
+            { id = 4, name = "Crop", type = "MATERIAL" },
+            { id = 5, name = "Seed", type = "MATERIAL" },
+            { id = 6, name = "Oil", type = "RESOURCE" },
+            { id = 7, name = "Water", type = "RESOURCE" },
+            { id = 8, name = "Refined Oil", type = "MATERIAL" },
+            { id = 9, name = "Hydrogen", type = "MATERIAL" },
+            { id = 10, name = "Fuel", type = "COMPONENT" },
+          },
+          game_recipes = {
+            { id = 1, name = "Ingot", type = "SMELT", seconds = 1, inputs = { 1, 1 }, outputs = { 2, 1 } },
+            { id = 2, name = "Gear", type = "ASSEMBLE", seconds = 2, inputs = { 2, 2 }, outputs = { 3, 1 } },
+            { id = 3, name = "Crop", type = "CHEMICAL", seconds = 1, inputs = { 5, 1 }, outputs = { 4, 2 } },
+            { id = 4, name = "Seed", type = "CHEMICAL", seconds = 1, inputs = { 4, 1 }, outputs = { 5, 1 } },
+            { id = 5, name = "Hydrogen", type = "CHEMICAL", seconds = 1, inputs = { 7, 1 }, outputs = { 9, 1 } },
+            { id = 6, name = "Refined Oil", type = "REFINE", seconds = 1, inputs = { 6, 2 }, outputs = { 8, 1, 9, 1 } },
+            { id = 7, name = "Fuel", type = "ASSEMBLE", seconds = 1, inputs = { 8, 1, 9, 1 }, outputs = { 10, 1 } },
+            { id = 8, name = "Melt Gear", type = "SMELT", seconds = 1, inputs = { 3, 1 }, outputs = { 2, 2 }, explicit = true, non_productive = true },
+          },
+          game_buildings = {
+            { id = 11, name = "Smelter", types = { "SMELT" }, speed = 1, work_power = 360, idle_power = 12 },
+            { id = 12, name = "Assembler", types = { "ASSEMBLE" }, speed = 0.75, work_power = 270, idle_power = 12 },
+            { id = 13, name = "Fast Assembler", types = { "ASSEMBLE" }, speed = 1.5, work_power = 780, idle_power = 18 },
+          },
+        }
+    "#;
+
+    /// Plates are smelted from two ores, or pressed from one ore by slow
+    /// assemblers that need more power.
+    const COMPETING: &str = r#"
+        return {
+          game_items = {
+            { id = 1, name = "Ore", type = "RESOURCE" },
+            { id = 2, name = "Plate", type = "MATERIAL" },
+            { id = 3, name = "Frame", type = "COMPONENT" },
+          },
+          game_recipes = {
+            { id = 1, name = "Plate", type = "SMELT", seconds = 1, inputs = { 1, 2 }, outputs = { 2, 1 } },
+            { id = 2, name = "Pressed Plate", type = "ASSEMBLE", seconds = 4, inputs = { 1, 1 }, outputs = { 2, 1 }, explicit = true },
+            { id = 3, name = "Frame", type = "ASSEMBLE", seconds = 1, inputs = { 2, 1 }, outputs = { 3, 1 } },
+          },
+          game_buildings = {
+            { id = 11, name = "Smelter", types = { "SMELT" }, speed = 1, work_power = 60 },
+            { id = 12, name = "Assembler", types = { "ASSEMBLE" }, speed = 1, work_power = 600 },
+          },
+        }
+    "#;
+
+    /// One craft smelts two ingots, and a gear needs three of them.
+    const SHOPPING: &str = r#"
+        return {
+          game_items = {
+            { id = 1, name = "Ore", type = "RESOURCE" },
+            { id = 2, name = "Ingot", type = "MATERIAL" },
+            { id = 3, name = "Gear", type = "COMPONENT" },
+          },
+          game_recipes = {
+            { id = 1, name = "Ingot", type = "SMELT", seconds = 2, inputs = { 1, 1 }, outputs = { 2, 2 } },
+            { id = 2, name = "Gear", type = "ASSEMBLE", seconds = 1, inputs = { 2, 3 }, outputs = { 3, 1 } },
+          },
+        }
+    "#;
+
+    fn data() -> Data {
+        Data::from_lua_source("planner", SOURCE, None, &[]).unwrap()
+    }
+
+    fn shopping_list(amounts: &[(usize, f64)], rounding: Rounding) -> ShoppingList {
+        let data = Data::from_lua_source("shopping", SHOPPING, None, &[]).unwrap();
+        let amounts = amounts
+            .iter()
+            .map(|(iid, amount)| (ItemId(*iid), *amount))
+            .collect();
+
+        ShoppingList::new(&data, &amounts, PlanOptions::default(), rounding).unwrap()
+    }
+
+    fn amounts<K: Copy>(map: &BTreeMap<K, f64>) -> Vec<(K, f64)> {
+        map.iter().map(|(k, v)| (*k, *v)).collect()
+    }
+
+    fn competing() -> Data {
+        Data::from_lua_source("competing", COMPETING, None, &[]).unwrap()
+    }
+
+    fn frames() -> BTreeMap<ItemId, f64> {
+        BTreeMap::from([(ItemId(3), 60.0)])
+    }
+
+    fn selection(data: &Data, iid: usize) -> RecipeSelection {
+        let plan = ProductionPlan::default();
+
+        plan.select_recipes(data, &BTreeMap::from([(ItemId(iid), 60.0)]))
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "expected {}, got {}",
+            expected,
+            actual
+        );
+    }
+
+    #[test]
+    fn machines_use_the_speed_of_the_building() {
+        let data = data();
+        let gear = &data.recipes[&RecipeId(2)];
+
+        // The default building is the assembler with the lowest id.
+        let options = PlanOptions::default();
+        assert_close(
+            options.machines(&data, gear, 60.0),
+            60.0 * 2.0 / 60.0 / 0.75,
+        );
+
+        let options = PlanOptions {
+            buildings: HashMap::from([(RecipeType::Assemble, ItemId(13))]),
+            ..PlanOptions::default()
+        };
+        assert_close(options.machines(&data, gear, 60.0), 60.0 * 2.0 / 60.0 / 1.5);
+
+        let options = PlanOptions {
+            proliferator: Proliferator::Mk3,
+            spray_mode: SprayMode::Speedup,
+            ..PlanOptions::default()
+        };
+        assert_close(
+            options.machines(&data, gear, 60.0),
+            60.0 * 2.0 / 60.0 / 0.75 / 2.0,
+        );
+    }
+
+    #[test]
+    fn proliferators_multiply_outputs_speed_and_power() {
+        let data = data();
+        let gear = &data.recipes[&RecipeId(2)];
+
+        let cases = [
+            (Proliferator::None, 1.0, 1.0, 1.0),
+            (Proliferator::Mk1, 1.125, 1.25, 1.3),
+            (Proliferator::Mk2, 1.2, 1.5, 1.7),
+            (Proliferator::Mk3, 1.25, 2.0, 2.5),
+        ];
+        for (proliferator, extra_products, speedup, power) in cases {
+            let options = PlanOptions {
+                proliferator,
+                ..PlanOptions::default()
+            };
+            assert_close(options.output_multiplier(gear), extra_products);
+            assert_close(options.speed_multiplier(gear), 1.0);
+            assert_close(options.power_multiplier(gear), power);
+
+            let options = PlanOptions {
+                proliferator,
+                spray_mode: SprayMode::Speedup,
+                ..PlanOptions::default()
+            };
+            assert_close(options.output_multiplier(gear), 1.0);
+            assert_close(options.speed_multiplier(gear), speedup);
+            assert_close(options.power_multiplier(gear), power);
+        }
+    }
+
+    #[test]
+    fn non_productive_recipes_fall_back_to_speedup() {
+        let data = data();
+        let melt = &data.recipes[&RecipeId(8)];
+        let options = PlanOptions {
+            proliferator: Proliferator::Mk3,
+            ..PlanOptions::default()
+        };
+
+        assert_eq!(options.spray_mode(melt), Some(SprayMode::Speedup));
+        assert_close(options.output_multiplier(melt), 1.0);
+        assert_close(options.speed_multiplier(melt), 2.0);
+        assert_eq!(
+            options.spray_mode(&data.recipes[&RecipeId(2)]),
+            Some(SprayMode::ExtraProducts)
+        );
+        assert_eq!(PlanOptions::default().spray_mode(melt), None);
+    }
+
+    #[test]
+    fn chains_are_propagated_recursively() {
+        let data = data();
+
+        let selection = selection(&data, 3);
+        assert!(!selection.needs_solver);
+        assert_eq!(
+            selection.items,
+            BTreeMap::from([
+                (RecipeId(1), vec![ItemId(2)]),
+                (RecipeId(2), vec![ItemId(3)]),
+            ])
+        );
+    }
+
+    #[test]
+    fn cycles_need_the_solver() {
+        let data = data();
+
+        let selection = selection(&data, 4);
+        assert!(selection.needs_solver);
+
+        let targets = BTreeMap::from([(ItemId(4), 60.0)]);
+        let plan = ProductionPlan::new(&data, &targets, PlanOptions::default()).unwrap();
+        assert_close(plan.recipes[&RecipeId(3)], 60.0);
+        assert_close(plan.recipes[&RecipeId(4)], 60.0);
+        assert!(plan.resources.is_empty());
+    }
+
+    #[test]
+    fn shared_byproducts_need_the_solver() {
+        let data = data();
+
+        // Hydrogen is made by its own recipe, but refining the oil yields
+        // hydrogen as well.
+        let selection = selection(&data, 10);
+        assert!(selection.needs_solver);
+        assert_eq!(selection.items[&RecipeId(5)], vec![ItemId(9)]);
+
+        let targets = BTreeMap::from([(ItemId(10), 60.0)]);
+        let plan = ProductionPlan::new(&data, &targets, PlanOptions::default()).unwrap();
+        assert_close(plan.recipes[&RecipeId(6)], 60.0);
+        assert!(!plan.recipes.contains_key(&RecipeId(5)));
+        assert_close(plan.resources[&ItemId(6)], 120.0);
+    }
+
+    #[test]
+    fn power_adds_the_idle_power_of_partially_used_machines() {
+        let data = data();
+        let targets = BTreeMap::from([(ItemId(3), 30.0)]);
+        let plan = ProductionPlan::new(&data, &targets, PlanOptions::default()).unwrap();
+
+        // 30 crafts of 2 s need 4/3 assemblers with a speed of 0.75.
+        let machines = 30.0 * 2.0 / 60.0 / 0.75;
+        assert_close(plan.machines(&data, RecipeId(2)), machines);
+        assert_close(
+            plan.power(&data, RecipeId(2), false).unwrap(),
+            machines * 270.0,
+        );
+        assert_close(
+            plan.power(&data, RecipeId(2), true).unwrap(),
+            machines * 270.0 + (2.0 - machines) * 12.0,
+        );
+    }
+
+    #[test]
+    fn recipes_without_building_data_use_a_speed_of_one() {
+        let data = data();
+        let targets = BTreeMap::from([(ItemId(4), 60.0)]);
+        let plan = ProductionPlan::new(&data, &targets, PlanOptions::default()).unwrap();
+
+        assert_close(plan.machines(&data, RecipeId(3)), 1.0);
+        assert_eq!(plan.power(&data, RecipeId(3), true), None);
+        assert_eq!(plan.cost(&data, Objective::Power), None);
+        assert_eq!(plan.cost(&data, Objective::RawResources), Some(0.0));
+    }
+
+    #[test]
+    fn optimize_minimizes_the_raw_resources() {
+        let data = competing();
+        let options = PlanOptions::default();
+        let optimization = optimize(
+            &data,
+            &frames(),
+            &options,
+            &BTreeSet::new(),
+            Objective::RawResources,
+        )
+        .unwrap();
+
+        assert_close(optimization.default_cost, 120.0);
+        assert_close(optimization.cost, 60.0);
+        assert_eq!(optimization.choices.len(), 1);
+        assert_eq!(optimization.choices[0].item, ItemId(2));
+        assert_eq!(optimization.choices[0].recipe, RecipeId(2));
+        assert_eq!(optimization.choices[0].default, Some(RecipeId(1)));
+        assert!(optimization.plan.recipes.contains_key(&RecipeId(2)));
+        assert!(!optimization.plan.recipes.contains_key(&RecipeId(1)));
+    }
+
+    #[test]
+    fn optimize_minimizes_the_power() {
+        let data = competing();
+        let options = PlanOptions::default();
+        let optimization = optimize(
+            &data,
+            &frames(),
+            &options,
+            &BTreeSet::new(),
+            Objective::Power,
+        )
+        .unwrap();
+
+        // One smelter and one assembler are cheaper than the five assemblers
+        // of the pressed plates.
+        assert_close(optimization.default_cost, 660.0);
+        assert_close(optimization.cost, 660.0);
+        assert!(optimization.choices.is_empty());
+        assert!(optimization.plan.recipes.contains_key(&RecipeId(1)));
+    }
+
+    #[test]
+    fn optimize_keeps_the_pinned_recipes() {
+        let data = competing();
+        let options = PlanOptions::default();
+        let pins = BTreeSet::from([RecipeId(2)]);
+        let optimization = optimize(&data, &frames(), &options, &pins, Objective::Power).unwrap();
+
+        assert_close(optimization.default_cost, 3000.0);
+        assert_close(optimization.cost, 3000.0);
+        assert!(optimization.choices.is_empty());
+        assert!(optimization.plan.recipes.contains_key(&RecipeId(2)));
+        assert!(!optimization.plan.recipes.contains_key(&RecipeId(1)));
+    }
+
+    #[test]
+    fn shopping_list_rounds_up_partial_crafts() {
+        // A gear needs 1.5 crafts of ingots, so two are crafted and one
+        // ingot is left over.
+        let list = shopping_list(&[(3, 1.0)], Rounding::Ceil);
+
+        assert_eq!(
+            amounts(&list.recipes),
+            vec![(RecipeId(1), 2.0), (RecipeId(2), 1.0)]
+        );
+        assert_eq!(amounts(&list.intermediates), vec![(ItemId(2), 3.0)]);
+        assert_eq!(amounts(&list.resources), vec![(ItemId(1), 2.0)]);
+        assert_eq!(amounts(&list.leftovers), vec![(ItemId(2), 1.0)]);
+    }
+
+    #[test]
+    fn shopping_list_uses_exact_crafts() {
+        let list = shopping_list(&[(3, 1.0)], Rounding::Exact);
+
+        assert_eq!(
+            amounts(&list.recipes),
+            vec![(RecipeId(1), 1.5), (RecipeId(2), 1.0)]
+        );
+        assert_eq!(amounts(&list.intermediates), vec![(ItemId(2), 3.0)]);
+        assert_eq!(amounts(&list.resources), vec![(ItemId(1), 1.5)]);
+        assert!(list.leftovers.is_empty());
+    }
+
+    #[test]
+    fn shopping_list_sums_the_demand_before_rounding() {
+        // The ingot of the target and the three of the gear are smelted by
+        // two crafts without leftovers.
+        let list = shopping_list(&[(2, 1.0), (3, 1.0)], Rounding::Ceil);
+
+        assert_eq!(
+            amounts(&list.recipes),
+            vec![(RecipeId(1), 2.0), (RecipeId(2), 1.0)]
+        );
+        assert_eq!(amounts(&list.intermediates), vec![(ItemId(2), 3.0)]);
+        assert_eq!(amounts(&list.resources), vec![(ItemId(1), 2.0)]);
+        assert!(list.leftovers.is_empty());
+    }
+}
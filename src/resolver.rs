@@ -0,0 +1,438 @@
+use std::collections::{BTreeMap, BTreeSet, VecDeque};
+use std::fmt::Debug;
+
+use crate::{
+    data::{Data, ItemId, RecipeId},
+    selector::Selection,
+};
+
+/// Describes how an item or recipe was reached while resolving the
+/// dependencies.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct Origin {
+    /// Root item the entry was reached from first.
+    pub root: ItemId,
+
+    /// Number of recipes between the root item and the entry. Root items and
+    /// the recipes that produce them have a depth of 0.
+    pub depth: usize,
+}
+
+/// Direction the recipes are followed in while resolving the dependencies.
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum Direction {
+    /// Follow the recipes that produce the items, and their inputs.
+    #[default]
+    Upstream,
+
+    /// Follow the recipes that use the items, and their outputs.
+    Downstream,
+}
+
+/* RecipeSelector */
+
+/// Selects which of the recipes that produce an item are used.
+pub trait RecipeSelector: Debug {
+    /// Select the recipes that are used to produce `item` from the passed
+    /// candidates, which are ordered by id.
+    fn select(&self, item: ItemId, candidates: &[RecipeId], data: &Data) -> Vec<RecipeId>;
+}
+
+impl Default for Box<dyn RecipeSelector> {
+    fn default() -> Self {
+        Box::new(AllRecipes)
+    }
+}
+
+/// Use all candidates.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AllRecipes;
+
+impl RecipeSelector for AllRecipes {
+    fn select(&self, _item: ItemId, candidates: &[RecipeId], _data: &Data) -> Vec<RecipeId> {
+        candidates.to_vec()
+    }
+}
+
+/// Use the explicit (alternative) recipes if there are any, and all
+/// candidates otherwise.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreferExplicit;
+
+impl RecipeSelector for PreferExplicit {
+    fn select(&self, _item: ItemId, candidates: &[RecipeId], data: &Data) -> Vec<RecipeId> {
+        prefer(candidates, |rid| {
+            data.recipes.get(rid).is_some_and(|r| r.explicit)
+        })
+    }
+}
+
+/// Use the primary (not explicit) recipes if there are any, and all
+/// candidates otherwise.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PreferBasic;
+
+impl RecipeSelector for PreferBasic {
+    fn select(&self, _item: ItemId, candidates: &[RecipeId], data: &Data) -> Vec<RecipeId> {
+        prefer(candidates, |rid| {
+            data.recipes.get(rid).is_some_and(|r| !r.explicit)
+        })
+    }
+}
+
+/// Use the pinned recipes if any of them is a candidate, and fall back to
+/// another selector otherwise.
+#[derive(Debug, Default)]
+pub struct PinnedList {
+    pub recipes: BTreeSet<RecipeId>,
+    pub fallback: Box<dyn RecipeSelector>,
+}
+
+impl PinnedList {
+    pub fn new<I>(recipes: I, fallback: Box<dyn RecipeSelector>) -> Self
+    where
+        I: IntoIterator<Item = RecipeId>,
+    {
+        Self {
+            recipes: recipes.into_iter().collect(),
+            fallback,
+        }
+    }
+}
+
+impl RecipeSelector for PinnedList {
+    fn select(&self, item: ItemId, candidates: &[RecipeId], data: &Data) -> Vec<RecipeId> {
+        let pinned = candidates
+            .iter()
+            .filter(|rid| self.recipes.contains(rid))
+            .copied()
+            .collect::<Vec<_>>();
+
+        if pinned.is_empty() {
+            self.fallback.select(item, candidates, data)
+        } else {
+            pinned
+        }
+    }
+}
+
+/// Candidates that match the passed predicate, or all candidates if none of
+/// them does.
+fn prefer<F>(candidates: &[RecipeId], f: F) -> Vec<RecipeId>
+where
+    F: Fn(&RecipeId) -> bool,
+{
+    let preferred = candidates
+        .iter()
+        .filter(|rid| f(rid))
+        .copied()
+        .collect::<Vec<_>>();
+
+    if preferred.is_empty() {
+        candidates.to_vec()
+    } else {
+        preferred
+    }
+}
+
+/* Resolver */
+
+/// Collects the recipes that produce a set of items and, optionally, the
+/// recipes of all their dependencies.
+pub struct Resolver<'a> {
+    data: &'a Data,
+    exclude: &'a Selection,
+    resolve_deps: bool,
+    max_depth: Option<usize>,
+    direction: Direction,
+    selector: &'a dyn RecipeSelector,
+
+    pub items: BTreeMap<ItemId, Origin>,
+    pub recipes: BTreeMap<RecipeId, Origin>,
+
+    /// Item each recipe was added for.
+    pub recipe_items: BTreeMap<RecipeId, ItemId>,
+}
+
+impl<'a> Resolver<'a> {
+    pub fn new(data: &'a Data, exclude: &'a Selection, resolve_deps: bool) -> Self {
+        Self {
+            data,
+            exclude,
+            resolve_deps,
+            max_depth: None,
+            direction: Direction::Upstream,
+            selector: &AllRecipes,
+            items: BTreeMap::new(),
+            recipes: BTreeMap::new(),
+            recipe_items: BTreeMap::new(),
+        }
+    }
+
+    /// Only add recipes up to the passed depth while resolving the
+    /// dependencies.
+    pub fn max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.max_depth = max_depth;
+
+        self
+    }
+
+    /// Select the recipes that produce an item with the passed selector. By
+    /// default all recipes are used. Downstream all recipes are used anyway.
+    pub fn selector(mut self, selector: &'a dyn RecipeSelector) -> Self {
+        self.selector = selector;
+
+        self
+    }
+
+    /// Follow the recipes in the passed direction.
+    pub fn direction(mut self, direction: Direction) -> Self {
+        self.direction = direction;
+
+        self
+    }
+
+    /// Add the passed root items, the recipes that produce them and their
+    /// inputs. If dependencies are resolved, the recipes of the inputs are
+    /// added as well, until no new recipe is found or the maximum depth is
+    /// reached. Downstream, the recipes that use the items and their outputs
+    /// are added instead.
+    pub fn resolve<I>(&mut self, roots: I)
+    where
+        I: IntoIterator<Item = ItemId>,
+    {
+        let mut queue = VecDeque::new();
+        for root in roots {
+            let origin = Origin { root, depth: 0 };
+
+            self.items.entry(root).or_insert(origin);
+            queue.push_back((root, origin));
+        }
+
+        let recipes = match self.direction {
+            Direction::Upstream => &self.data.as_output,
+            Direction::Downstream => &self.data.as_input,
+        };
+
+        while let Some((iid, origin)) = queue.pop_front() {
+            if self.max_depth.is_some_and(|max| origin.depth > max) {
+                continue;
+            }
+
+            let mut candidates = recipes
+                .get(&iid)
+                .into_iter()
+                .flatten()
+                .filter(|rid| !self.exclude.recipes.contains(rid))
+                .copied()
+                .collect::<Vec<_>>();
+            candidates.sort();
+            if self.direction == Direction::Upstream {
+                candidates = self.selector.select(iid, &candidates, self.data);
+            }
+
+            for rid in &candidates {
+                if self.recipes.contains_key(rid) {
+                    continue;
+                }
+
+                self.recipes.insert(*rid, origin);
+                self.recipe_items.insert(*rid, iid);
+
+                let recipe = match self.data.recipes.get(rid) {
+                    Some(recipe) => recipe,
+                    None => continue,
+                };
+                log::trace!(
+                    recipe:% = rid,
+                    item:% = iid,
+                    depth = origin.depth;
+                    "  add recipe {} ({}: {}) for item {} at depth {}",
+                    rid,
+                    recipe.name,
+                    recipe.display_with(self.data),
+                    iid,
+                    origin.depth
+                );
+
+                let next = Origin {
+                    root: origin.root,
+                    depth: origin.depth + 1,
+                };
+                let next_items = match self.direction {
+                    Direction::Upstream => &recipe.inputs,
+                    Direction::Downstream => &recipe.outputs,
+                };
+                for next_item in next_items {
+                    if self.exclude.items.contains(&next_item.id) {
+                        log::trace!(item:% = next_item.id; "  skip ignored item {}", next_item.id);
+
+                        continue;
+                    }
+
+                    self.items.entry(next_item.id).or_insert(next);
+
+                    if self.resolve_deps {
+                        queue.push_back((next_item.id, next));
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn item_ids(&self) -> BTreeSet<ItemId> {
+        self.items.keys().copied().collect()
+    }
+
+    pub fn recipe_ids(&self) -> BTreeSet<RecipeId> {
+        self.recipes.keys().copied().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Chain of `count` items, item `n + 1` is made from item `n` by recipe
+    /// `n`, and recipe 0 turns the last item back into the first one.
+    fn chain(count: usize) -> Data {
+        let source = format!(
+            r#"
+                local items, recipes = {{}}, {{}}
+                for i = 1, {count} do
+                  items[#items + 1] = {{ id = i, name = "Item " .. i, type = "COMPONENT" }}
+                end
+                for i = 1, {count} - 1 do
+                  recipes[#recipes + 1] = {{ id = i, name = "Recipe " .. i, type = "ASSEMBLE",
+                    seconds = 1, inputs = {{ i, 1 }}, outputs = {{ i + 1, 1 }} }}
+                end
+                recipes[#recipes + 1] = {{ id = 0, name = "Recipe 0", type = "ASSEMBLE",
+                  seconds = 1, inputs = {{ {count}, 1 }}, outputs = {{ 1, 1 }} }}
+                return {{ game_items = items, game_recipes = recipes }}
+            "#
+        );
+
+        Data::from_lua_source("chain", &source, None, &[]).unwrap()
+    }
+
+    /// Ingot (2) is made from ore (1) by a primary recipe (1), an explicit
+    /// one (2) and another primary one (3).
+    fn alternatives() -> Data {
+        let source = r#"
+            return {
+              game_items = {
+                { id = 1, name = "Ore", type = "RESOURCE" },
+                { id = 2, name = "Ingot", type = "MATERIAL" },
+              },
+              game_recipes = {
+                { id = 1, name = "Ingot", type = "SMELT", seconds = 1,
+                  inputs = { 1, 1 }, outputs = { 2, 1 } },
+                { id = 2, name = "Fast Ingot", type = "SMELT", seconds = 1, explicit = true,
+                  inputs = { 1, 2 }, outputs = { 2, 2 } },
+                { id = 3, name = "Slow Ingot", type = "SMELT", seconds = 2,
+                  inputs = { 1, 1 }, outputs = { 2, 1 } },
+              },
+            }
+        "#;
+
+        Data::from_lua_source("alternatives", source, None, &[]).unwrap()
+    }
+
+    #[test]
+    fn selectors_choose_the_recipes() {
+        let data = alternatives();
+        let candidates = [RecipeId(1), RecipeId(2), RecipeId(3)];
+        let select = |selector: &dyn RecipeSelector| selector.select(ItemId(2), &candidates, &data);
+
+        assert_eq!(select(&AllRecipes), candidates);
+        assert_eq!(select(&PreferExplicit), [RecipeId(2)]);
+        assert_eq!(select(&PreferBasic), [RecipeId(1), RecipeId(3)]);
+        assert_eq!(
+            select(&PinnedList::new([RecipeId(3)], Box::new(PreferExplicit))),
+            [RecipeId(3)]
+        );
+        assert_eq!(
+            select(&PinnedList::new([RecipeId(4)], Box::new(PreferExplicit))),
+            [RecipeId(2)]
+        );
+
+        let primary = [RecipeId(1)];
+        assert_eq!(PreferExplicit.select(ItemId(2), &primary, &data), primary);
+    }
+
+    #[test]
+    fn resolver_uses_the_selector() {
+        let data = alternatives();
+        let exclude = Selection::default();
+
+        let mut resolver = Resolver::new(&data, &exclude, true);
+        resolver.resolve([ItemId(2)]);
+        assert_eq!(resolver.recipes.len(), 3);
+
+        let mut resolver = Resolver::new(&data, &exclude, true).selector(&PreferExplicit);
+        resolver.resolve([ItemId(2)]);
+        assert_eq!(resolver.recipe_ids(), BTreeSet::from([RecipeId(2)]));
+    }
+
+    #[test]
+    fn long_chains_do_not_overflow_the_stack() {
+        let data = chain(20_000);
+        let exclude = Selection::default();
+
+        let mut resolver = Resolver::new(&data, &exclude, true);
+        resolver.resolve([ItemId(20_000)]);
+
+        assert_eq!(resolver.items.len(), 20_000);
+        assert_eq!(resolver.recipes.len(), 20_000);
+    }
+
+    #[test]
+    fn origins_record_root_and_depth() {
+        let data = chain(4);
+        let exclude = Selection::default();
+
+        let mut resolver = Resolver::new(&data, &exclude, true);
+        resolver.resolve([ItemId(4)]);
+
+        let origin = |depth| Origin {
+            root: ItemId(4),
+            depth,
+        };
+        assert_eq!(resolver.items[&ItemId(4)], origin(0));
+        assert_eq!(resolver.items[&ItemId(3)], origin(1));
+        assert_eq!(resolver.items[&ItemId(1)], origin(3));
+        assert_eq!(resolver.recipes[&RecipeId(3)], origin(0));
+        assert_eq!(resolver.recipes[&RecipeId(0)], origin(3));
+        assert_eq!(resolver.recipe_items[&RecipeId(0)], ItemId(1));
+    }
+
+    #[test]
+    fn without_deps_only_the_producers_are_added() {
+        let data = chain(4);
+        let exclude = Selection::default();
+
+        let mut resolver = Resolver::new(&data, &exclude, false);
+        resolver.resolve([ItemId(4)]);
+
+        assert_eq!(resolver.recipe_ids(), BTreeSet::from([RecipeId(3)]));
+        assert_eq!(resolver.item_ids(), BTreeSet::from([ItemId(3), ItemId(4)]));
+    }
+
+    #[test]
+    fn excluded_entries_are_skipped() {
+        let data = chain(4);
+        let exclude = Selection {
+            items: BTreeSet::from([ItemId(2)]),
+            recipes: BTreeSet::new(),
+        };
+
+        let mut resolver = Resolver::new(&data, &exclude, true);
+        resolver.resolve([ItemId(4)]);
+
+        assert_eq!(
+            resolver.recipe_ids(),
+            BTreeSet::from([RecipeId(2), RecipeId(3)])
+        );
+        assert!(!resolver.items.contains_key(&ItemId(2)));
+    }
+}
@@ -0,0 +1,1621 @@
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::read_to_string;
+use std::io::{stdin, IsTerminal, Read};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::OnceLock;
+
+use itertools::Itertools;
+use regex::{Regex, RegexBuilder};
+
+use crate::{
+    config::{parse_file, Groups},
+    data::{normalize_name, Data, IdLabel, ItemId, ItemType, Keys, RecipeId, RecipeType, TechId},
+    error::Error,
+};
+
+/* SelectorContext */
+
+/// Settings of the selector parser, taken from the command line and the
+/// config. They are passed to the parse functions, so every caller (like
+/// the requests of `serve`) can use its own settings.
+#[derive(Debug, Default, Clone)]
+pub struct SelectorContext {
+    /// Named selector groups that are referenced as `@name`.
+    pub groups: Groups,
+
+    /// File with more groups (`--groups`), which override the groups of the
+    /// same name. Like the ignore files it is read whenever selectors are
+    /// parsed, so `--watch` picks up its changes.
+    pub groups_file: Option<PathBuf>,
+
+    /// Files whose selectors are added to the ignores of every command, see
+    /// [`parse_ignore`].
+    pub ignore_files: Vec<PathBuf>,
+
+    /// Print the merged ignores of the command line and the ignore files to
+    /// stderr.
+    pub print_ignores: bool,
+
+    /// Report patterns that do not match any item or recipe as a warning
+    /// instead of an error.
+    pub allow_empty_match: bool,
+}
+
+impl SelectorContext {
+    /// The groups together with the groups of the groups file.
+    fn groups(&self) -> Result<Cow<'_, Groups>, Error> {
+        let path = match &self.groups_file {
+            Some(path) => path,
+            None => return Ok(Cow::Borrowed(&self.groups)),
+        };
+
+        log::debug!("Load selector groups from {:#?}", path);
+        let mut groups = self.groups.clone();
+        groups.extend(parse_file::<Groups>(path)?);
+
+        Ok(Cow::Owned(groups))
+    }
+}
+
+/* Selection */
+
+/// Items and recipes that were selected by a list of selectors. Item and
+/// recipe ids are independent, so they are kept apart.
+#[derive(Debug, Default, Clone)]
+pub struct Selection {
+    pub items: BTreeSet<ItemId>,
+    pub recipes: BTreeSet<RecipeId>,
+}
+
+impl Selection {
+    pub fn len(&self) -> usize {
+        self.items.len() + self.recipes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty() && self.recipes.is_empty()
+    }
+
+    pub fn extend(&mut self, other: Selection) {
+        self.items.extend(other.items);
+        self.recipes.extend(other.recipes);
+    }
+
+    /// Entries that are selected by both selections.
+    pub fn intersection(&self, other: &Selection) -> Selection {
+        Selection {
+            items: self.items.intersection(&other.items).copied().collect(),
+            recipes: self.recipes.intersection(&other.recipes).copied().collect(),
+        }
+    }
+}
+
+/// Parse the passed selectors into item (and recipe) ids.
+///
+/// A selector is an id, a name, a glob pattern or one of the type keywords.
+/// Ids have to exist in the data, unknown ids are reported together. Names
+/// that contain `*` or `?` are matched case-insensitively against all names,
+/// the wildcards can be escaped with a backslash (`\\*`). The `re:` prefix
+/// selects all names that match a case-insensitive regular expression. Unprefixed names
+/// prefer items over recipes, the `item:`, `recipe:` and `type:` prefixes
+/// force one interpretation. Recipes are only selected if `items_only` is not
+/// set.
+///
+/// A `-` reads the selectors from stdin, one per line, and `@name` is
+/// replaced by the selectors of the named group.
+///
+/// A selector with a leading `!` (or `-`) is negated: the entries it selects
+/// are removed from the entries of all other selectors, independent of the
+/// order of the selectors.
+pub fn parse_ids(
+    data: &Data,
+    ctx: &SelectorContext,
+    items: &[String],
+    items_only: bool,
+) -> Result<Selection, Error> {
+    Ok(Selectors::parse(data, ctx, items, items_only)?.finish())
+}
+
+/// Entries of a list of selectors, before the negated selectors are
+/// removed.
+#[derive(Debug, Default)]
+struct Selectors {
+    selected: Selection,
+
+    /// Negated selectors with the entries they select.
+    negated: Vec<(String, Selection)>,
+}
+
+impl Selectors {
+    fn parse(
+        data: &Data,
+        ctx: &SelectorContext,
+        items: &[String],
+        items_only: bool,
+    ) -> Result<Self, Error> {
+        Self::parse_split(data, ctx, &split_selectors(items)?, items_only)
+    }
+
+    /// Parse selectors that are already split by [`split_selectors`].
+    fn parse_split(
+        data: &Data,
+        ctx: &SelectorContext,
+        items: &[String],
+        items_only: bool,
+    ) -> Result<Self, Error> {
+        let mut ret = Self::default();
+        let mut invalid = Vec::<String>::new();
+
+        let groups = ctx.groups()?;
+        let items = expand_groups(&groups, &expand_stdin(items)?)?;
+        for item in &items {
+            if let Some(negated) = negation(item) {
+                let selectors = Self::parse_split(data, ctx, &[negated.to_owned()], items_only)?;
+                if !selectors.negated.is_empty() {
+                    return Err(Error::usage(format!(
+                        "Selectors can only be negated once: {}",
+                        item
+                    )));
+                }
+
+                ret.negated.push((item.clone(), selectors.selected));
+
+                continue;
+            }
+
+            ret.selected
+                .extend(parse_selector(data, ctx, item, items_only, &mut invalid)?);
+        }
+
+        if !invalid.is_empty() {
+            return Err(Error::usage(format!(
+                "Unknown ids: {}",
+                invalid.iter().join(", ")
+            )));
+        }
+
+        Ok(ret)
+    }
+
+    fn extend(&mut self, other: Self) {
+        self.selected.extend(other.selected);
+        self.negated.extend(other.negated);
+    }
+
+    /// Remove the entries of the negated selectors from the selected
+    /// entries. Negated selectors that do not match any selected entry are
+    /// reported as warning.
+    fn finish(self) -> Selection {
+        let Self {
+            mut selected,
+            negated,
+        } = self;
+        if negated.is_empty() {
+            return selected;
+        }
+
+        let positive = selected.clone();
+        for (selector, selection) in &negated {
+            if selection.intersection(&positive).is_empty() {
+                log::warn!(
+                    "Negated selector {} does not remove anything, none of its entries is selected",
+                    selector
+                );
+            }
+
+            selected.items.retain(|i| !selection.items.contains(i));
+            selected.recipes.retain(|r| !selection.recipes.contains(r));
+        }
+
+        log::debug!(
+            "  {} negated selectors remove {} entries, selected items: {}; recipes: {}",
+            negated.len(),
+            positive.len() - selected.len(),
+            selected.items.iter().join(", "),
+            selected.recipes.iter().join(", ")
+        );
+
+        selected
+    }
+}
+
+/// Get the selector that is negated by the passed selector, if it starts
+/// with `!` or `-`. A single `-` reads from stdin and is not a negation.
+fn negation(selector: &str) -> Option<&str> {
+    selector
+        .strip_prefix('!')
+        .or_else(|| selector.strip_prefix('-').filter(|rest| !rest.is_empty()))
+}
+
+/// Parse a single selector that is not negated, see [`parse_ids`]. Ids that
+/// do not exist are added to `invalid`.
+fn parse_selector(
+    data: &Data,
+    ctx: &SelectorContext,
+    item: &str,
+    items_only: bool,
+    invalid: &mut Vec<String>,
+) -> Result<Selection, Error> {
+    let (kinds, name) = match item.split_once(':') {
+        Some(("item", name)) => (Kinds::ITEMS, name),
+        Some(("recipe", _)) if items_only => {
+            return Err(Error::usage(format!(
+                "Only items can be selected here: {}",
+                item
+            )));
+        }
+        Some(("recipe", name)) => (Kinds::RECIPES, name),
+        _ if items_only => (Kinds::ITEMS, item),
+        _ => (Kinds::ALL, item),
+    };
+
+    if let Some(ids) = parse_id_list(name, &data.keys)? {
+        return select_ids(data, &ids, kinds, invalid);
+    }
+
+    let is_type = matches!(item.split_once(':'), Some(("type", _)));
+    if let Some(pattern) = name.strip_prefix("re:").filter(|_| !is_type) {
+        let pattern = Pattern::regex(pattern)?;
+
+        return select_pattern(data, ctx, item, &pattern, kinds);
+    }
+
+    if let Some(pattern) = Pattern::glob(name).filter(|_| !is_type) {
+        return select_pattern(data, ctx, item, &pattern, kinds);
+    }
+
+    let mut ret = Selection::default();
+    match item.split_once(':') {
+        Some(("item", name)) => {
+            ret.items.insert(parse_item(data, &unescape(name))?);
+        }
+        Some(("recipe", name)) => {
+            ret.recipes.insert(parse_recipe(data, &unescape(name))?);
+        }
+        Some(("type", keyword)) => match parse_type(data, keyword, items_only)? {
+            Some(selection) => ret.extend(selection),
+            None => {
+                return Err(Error::usage(format!(
+                    "Invalid or unknown type: {}",
+                    keyword
+                )))
+            }
+        },
+        _ => ret.extend(parse_unprefixed(data, &unescape(item), items_only)?),
+    }
+
+    Ok(ret)
+}
+
+/// Parse the ignored items and recipes of a command: the passed selectors
+/// and the selectors of the ignore files of the context, in the order of the
+/// files. Selectors of a file that fail are reported with the file and line.
+pub fn parse_ignore(
+    data: &Data,
+    ctx: &SelectorContext,
+    ignore: &[String],
+    items_only: bool,
+) -> Result<Selection, Error> {
+    let mut ret = Selectors::parse(data, ctx, ignore, items_only)?;
+
+    for path in &ctx.ignore_files {
+        let selectors = read_selector_file(path)?;
+        log::debug!(
+            path:% = path.display();
+            "  read {} ignores from {:#?}",
+            selectors.len(),
+            path
+        );
+
+        for (line, selector) in selectors {
+            let selectors =
+                Selectors::parse(data, ctx, std::slice::from_ref(&selector), items_only)
+                    .map_err(|err| Error::usage(format!("{}:{}: {}", path.display(), line, err)))?;
+            ret.extend(selectors);
+        }
+    }
+    let ret = ret.finish();
+
+    if ctx.print_ignores {
+        print_selection("Effective ignores", data, &ret);
+    }
+
+    Ok(ret)
+}
+
+/// Parse the passed item selectors and remove the items of the selectors of
+/// the exclude files, as if they were passed negated (`!selector`) in the
+/// order of the files. Selectors of a file that fail are reported with the
+/// file and line.
+pub fn parse_items(
+    data: &Data,
+    ctx: &SelectorContext,
+    items: &[String],
+    exclude_files: &[PathBuf],
+) -> Result<Selection, Error> {
+    let mut ret = Selectors::parse(data, ctx, items, true)?;
+
+    for (origin, selector) in file_selectors(exclude_files, true)? {
+        let origin = origin.unwrap_or_default();
+        let mut selectors = Selectors::parse_split(data, ctx, &[selector], true)
+            .map_err(|err| Error::usage(format!("{}: {}", origin, err)))?;
+        for (selector, _) in &mut selectors.negated {
+            *selector = format!("{} ({})", selector, origin);
+        }
+
+        ret.extend(selectors);
+    }
+
+    Ok(ret.finish())
+}
+
+/// Entries of a single selector, see [`parse_each`].
+#[derive(Debug)]
+pub struct SelectorMatch {
+    pub selector: String,
+
+    /// File and line of a selector of an ignore file.
+    pub origin: Option<String>,
+
+    /// Whether the selector is negated. The selection contains the entries it
+    /// removes from the other selectors.
+    pub negated: bool,
+
+    pub selection: Result<Selection, Error>,
+}
+
+/// Parse each of the passed selectors on its own, to report what every
+/// selector matches. The selectors of the passed files are added, negated if
+/// `negate_files` is set (like the exclude files of [`parse_items`]).
+pub fn parse_each(
+    data: &Data,
+    ctx: &SelectorContext,
+    items: &[String],
+    items_only: bool,
+    files: &[PathBuf],
+    negate_files: bool,
+) -> Result<Vec<SelectorMatch>, Error> {
+    let mut selectors = split_selectors(items)?
+        .into_iter()
+        .map(|selector| (None, selector))
+        .collect::<Vec<_>>();
+    selectors.extend(file_selectors(files, negate_files)?);
+
+    Ok(selectors
+        .into_iter()
+        .map(|(origin, selector)| {
+            let negated = negation(&selector);
+            let selection = Selectors::parse_split(
+                data,
+                ctx,
+                &[negated.unwrap_or(&selector).to_owned()],
+                items_only,
+            )
+            .map(Selectors::finish);
+
+            SelectorMatch {
+                negated: negated.is_some(),
+                selection,
+                origin,
+                selector,
+            }
+        })
+        .collect())
+}
+
+/// Read the selectors of the passed files, split like the selectors of the
+/// command line, with the file and line they are read from. With `negate`
+/// every selector is negated.
+fn file_selectors(paths: &[PathBuf], negate: bool) -> Result<Vec<(Option<String>, String)>, Error> {
+    let mut ret = Vec::new();
+
+    for path in paths {
+        for (line, selector) in read_selector_file(path)? {
+            let origin = format!("{}:{}", path.display(), line);
+            for selector in split_selectors(&[selector])? {
+                let selector = if negate {
+                    format!("!{}", selector)
+                } else {
+                    selector
+                };
+
+                ret.push((Some(origin.clone()), selector));
+            }
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Read the selectors of a file, one per line, with their line numbers.
+/// Blank lines and lines that start with `#` are skipped.
+pub fn read_selector_file(path: &Path) -> Result<Vec<(usize, String)>, Error> {
+    let content = read_to_string(path)
+        .map_err(|err| Error::usage(format!("Unable to read {:#?}: {}", path, err)))?;
+
+    Ok(content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+        .map(|(i, line)| (i, line.to_owned()))
+        .collect())
+}
+
+/// Print the items and recipes of the passed selection to stderr.
+fn print_selection(title: &str, data: &Data, selection: &Selection) {
+    eprintln!(
+        "{} ({} items, {} recipes):",
+        title,
+        selection.items.len(),
+        selection.recipes.len()
+    );
+    for iid in &selection.items {
+        let name = data.items.get(iid).map_or("", |i| i.name.as_str());
+        eprintln!(
+            "    item {:<10} {}",
+            iid.label(&data.keys).to_string(),
+            name
+        );
+    }
+    for rid in &selection.recipes {
+        let name = data.recipes.get(rid).map_or("", |r| r.name.as_str());
+        eprintln!(
+            "    recipe {:<8} {}",
+            rid.label(&data.keys).to_string(),
+            name
+        );
+    }
+}
+
+/// Maximum depth of nested group references.
+const MAX_GROUP_DEPTH: usize = 16;
+
+/// Replace the `@name` selectors with the selectors of the groups.
+fn expand_groups(groups: &Groups, items: &[String]) -> Result<Vec<String>, Error> {
+    let mut ret = Vec::with_capacity(items.len());
+    expand_groups_into(groups, items, &mut Vec::new(), &mut ret)?;
+
+    Ok(ret)
+}
+
+fn expand_groups_into<'a>(
+    groups: &'a Groups,
+    items: &'a [String],
+    stack: &mut Vec<&'a str>,
+    ret: &mut Vec<String>,
+) -> Result<(), Error> {
+    for item in items {
+        let name = match item.strip_prefix('@') {
+            Some(name) => name,
+            None => {
+                ret.push(item.clone());
+
+                continue;
+            }
+        };
+
+        if stack.contains(&name) {
+            return Err(Error::usage(format!(
+                "Selector group @{} references itself (@{} -> @{})",
+                name,
+                stack.iter().join(" -> @"),
+                name
+            )));
+        }
+
+        if stack.len() >= MAX_GROUP_DEPTH {
+            return Err(Error::usage(format!(
+                "Selector groups are nested deeper than {} levels (@{})",
+                MAX_GROUP_DEPTH,
+                stack.iter().join(" -> @")
+            )));
+        }
+
+        let group = groups.get(name).ok_or_else(|| {
+            Error::usage(format!(
+                "Unknown selector group: @{} (defined groups: {})",
+                name,
+                if groups.is_empty() {
+                    "none".into()
+                } else {
+                    groups.keys().map(|name| format!("@{}", name)).join(", ")
+                }
+            ))
+        })?;
+        log::debug!("  expand @{} to {}", name, group.iter().join(", "));
+
+        stack.push(name);
+        expand_groups_into(groups, group, stack, ret)?;
+        stack.pop();
+    }
+
+    Ok(())
+}
+
+/// Split comma separated lists of selectors into single selectors, e.g.
+/// `Iron Ingot, Copper Ingot` into `Iron Ingot` and `Copper Ingot`. The
+/// entries are trimmed, and empty entries are an error. Commas of names are
+/// escaped with a backslash (`\\,`) or by quoting the name with double
+/// quotes. Selectors that are a list of ids (like `recipe:1-3,7`) are not
+/// split.
+pub fn split_selectors(selectors: &[String]) -> Result<Vec<String>, Error> {
+    let mut ret = Vec::with_capacity(selectors.len());
+
+    for selector in selectors {
+        let ids = negation(selector).unwrap_or(selector);
+        let ids = ids
+            .strip_prefix("item:")
+            .or_else(|| ids.strip_prefix("recipe:"))
+            .unwrap_or(ids);
+        if !selector.contains([',', '"'])
+            || matches!(parse_id_list(ids, &Keys::default()), Ok(Some(_)))
+        {
+            ret.push(selector.clone());
+
+            continue;
+        }
+
+        let mut entries = vec![String::new()];
+        let mut quoted = false;
+        let mut chars = selector.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (c, chars.peek()) {
+                ('\\', Some(',')) => {
+                    entries.last_mut().unwrap().push(',');
+                    chars.next();
+                }
+                ('"', _) => quoted = !quoted,
+                (',', _) if !quoted => entries.push(String::new()),
+                (c, _) => entries.last_mut().unwrap().push(c),
+            }
+        }
+
+        if quoted {
+            return Err(Error::usage(format!(
+                "Selector list {:?} has an unterminated quote",
+                selector
+            )));
+        }
+
+        for entry in entries {
+            let entry = entry.trim();
+            if entry.is_empty() {
+                return Err(Error::usage(format!(
+                    "Selector list {:?} contains an empty entry",
+                    selector
+                )));
+            }
+
+            ret.push(entry.to_owned());
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Replace the `-` selectors with the selectors read from stdin.
+fn expand_stdin(items: &[String]) -> Result<Vec<String>, Error> {
+    let mut ret = Vec::with_capacity(items.len());
+    let mut stdin_read = false;
+
+    for item in items {
+        if item != "-" {
+            ret.push(item.clone());
+        } else if !stdin_read {
+            ret.extend(read_stdin()?.iter().cloned());
+            stdin_read = true;
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Read the selectors from stdin. Stdin is only read once, later calls get
+/// the same selectors.
+fn read_stdin() -> Result<&'static [String], Error> {
+    static SELECTORS: OnceLock<Vec<String>> = OnceLock::new();
+
+    if let Some(selectors) = SELECTORS.get() {
+        return Ok(selectors);
+    }
+
+    let mut stdin = stdin();
+    if stdin.is_terminal() {
+        return Err(Error::usage(
+            "Selector - reads from stdin, but stdin is a terminal (pipe the selectors into the command, one per line)",
+        ));
+    }
+
+    let mut content = String::new();
+    stdin.read_to_string(&mut content)?;
+
+    let selectors = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(ToOwned::to_owned)
+        .collect::<Vec<_>>();
+    log::info!(selectors = selectors.len(); "  read {} selectors from stdin", selectors.len());
+
+    Ok(SELECTORS.get_or_init(|| selectors))
+}
+
+/// Kinds of entries a selector may select.
+#[derive(Debug, Clone, Copy)]
+struct Kinds {
+    items: bool,
+    recipes: bool,
+}
+
+impl Kinds {
+    const ALL: Self = Self {
+        items: true,
+        recipes: true,
+    };
+    const ITEMS: Self = Self {
+        items: true,
+        recipes: false,
+    };
+    const RECIPES: Self = Self {
+        items: false,
+        recipes: true,
+    };
+
+    fn name(&self) -> &'static str {
+        match (self.items, self.recipes) {
+            (true, false) => "item",
+            (false, true) => "recipe",
+            _ => "item or recipe",
+        }
+    }
+}
+
+/// Id or inclusive range of ids.
+#[derive(Debug, Clone, Copy)]
+enum IdRange {
+    Id(usize),
+    Range(usize, usize),
+}
+
+/// Parse a comma separated list of ids and id ranges (`1101,1104,1110-1120`).
+/// The passed string keys (`mymod:super_magnet`) are single ids. Returns
+/// `None` if the selector is not such a list.
+fn parse_id_list(selector: &str, keys: &Keys) -> Result<Option<Vec<IdRange>>, Error> {
+    let mut ret = Vec::new();
+
+    if let Ok(id) = ItemId::parse(selector.trim(), keys) {
+        return Ok(Some(vec![IdRange::Id(id.0)]));
+    }
+
+    for segment in selector.split(',').map(str::trim) {
+        if let Ok(id) = ItemId::parse(segment, keys) {
+            ret.push(IdRange::Id(id.0));
+
+            continue;
+        }
+
+        let (start, end) = match segment.split_once('-') {
+            Some((start, end)) => (start.trim(), end.trim()),
+            None => return Ok(None),
+        };
+        let start = match usize::from_str(start) {
+            Ok(start) => start,
+            Err(_) => return Ok(None),
+        };
+        let end = usize::from_str(end).map_err(|_| {
+            Error::usage(format!(
+                "Invalid id range: {} (the end is not a number)",
+                segment
+            ))
+        })?;
+
+        if start > end {
+            return Err(Error::usage(format!(
+                "Invalid id range: {} (the start is greater than the end)",
+                segment
+            )));
+        }
+
+        ret.push(IdRange::Range(start, end));
+    }
+
+    Ok(Some(ret))
+}
+
+/// Select the passed ids. Single ids that do not exist are added to
+/// `invalid` (with their kind), ranges only select the ids that exist and warn about the gaps.
+fn select_ids(
+    data: &Data,
+    ids: &[IdRange],
+    kinds: Kinds,
+    invalid: &mut Vec<String>,
+) -> Result<Selection, Error> {
+    let mut ret = Selection::default();
+
+    for range in ids {
+        let (start, end) = match *range {
+            IdRange::Id(id) => (id, id),
+            IdRange::Range(start, end) => (start, end),
+        };
+
+        let items = data
+            .items
+            .keys()
+            .filter(|iid| kinds.items && (start..=end).contains(&iid.0))
+            .copied()
+            .collect::<BTreeSet<_>>();
+        let recipes = data
+            .recipes
+            .keys()
+            .filter(|rid| kinds.recipes && (start..=end).contains(&rid.0))
+            .copied()
+            .collect::<BTreeSet<_>>();
+
+        if let Some(iid) = items.iter().find(|iid| recipes.contains(&RecipeId(iid.0))) {
+            return Err(Error::usage(format!(
+                "Ambiguous id: {id} (matches item:{id} and recipe:{id}, select one of them)",
+                id = iid.label(&data.keys)
+            )));
+        }
+
+        let matched = items.len() + recipes.len();
+        match range {
+            IdRange::Id(id) if matched == 0 => invalid.push(format!(
+                "{} {}",
+                kinds.name(),
+                ItemId(*id).label(&data.keys)
+            )),
+            IdRange::Id(_) => (),
+            IdRange::Range(..) if matched == 0 => {
+                return Err(Error::usage(format!(
+                    "Id range {}-{} does not match any {}",
+                    start,
+                    end,
+                    kinds.name()
+                )));
+            }
+            IdRange::Range(..) => {
+                let gaps = end - start + 1 - matched;
+                if gaps > 0 {
+                    log::warn!(
+                        start,
+                        end,
+                        matched,
+                        missing = gaps;
+                        "Id range {}-{} matches {} ids, {} ids do not exist",
+                        start,
+                        end,
+                        matched,
+                        gaps
+                    );
+                }
+            }
+        }
+
+        ret.items.extend(items);
+        ret.recipes.extend(recipes);
+    }
+
+    Ok(ret)
+}
+
+/// Pattern that is matched against the names of the items and recipes.
+#[derive(Debug)]
+enum Pattern {
+    Glob(Vec<GlobToken>),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum GlobToken {
+    Char(char),
+    AnyChar,
+    AnyString,
+}
+
+impl Pattern {
+    /// Parse the passed name as glob pattern. Returns `None` if the name does
+    /// not contain any unescaped wildcard.
+    fn glob(name: &str) -> Option<Self> {
+        let mut tokens = Vec::new();
+        let mut chars = name.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '*' => tokens.push(GlobToken::AnyString),
+                '?' => tokens.push(GlobToken::AnyChar),
+                '\\' => match chars.next() {
+                    Some(c @ ('*' | '?' | '\\')) => tokens.push(GlobToken::Char(c)),
+                    Some(c) => tokens.extend([GlobToken::Char('\\'), GlobToken::Char(c)]),
+                    None => tokens.push(GlobToken::Char('\\')),
+                },
+                c => tokens.push(GlobToken::Char(c)),
+            }
+        }
+
+        if tokens.iter().all(|t| matches!(t, GlobToken::Char(_))) {
+            return None;
+        }
+
+        let tokens = tokens
+            .into_iter()
+            .map(|t| match t {
+                GlobToken::Char(c) => GlobToken::Char(lowercase(c)),
+                t => t,
+            })
+            .collect();
+
+        Some(Self::Glob(tokens))
+    }
+
+    fn regex(pattern: &str) -> Result<Self, Error> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .map_err(|err| Error::usage(format!("Invalid regex: {}\n{}", pattern, err)))?;
+
+        Ok(Self::Regex(regex))
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Glob(tokens) => {
+                let name = name.chars().map(lowercase).collect::<Vec<_>>();
+
+                glob_matches(tokens, &name)
+            }
+            Self::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+fn lowercase(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// Match the glob tokens against the whole name. A `*` that does not lead to
+/// a match is extended by one character at a time, starting at the last `*`.
+fn glob_matches(tokens: &[GlobToken], name: &[char]) -> bool {
+    let (mut t, mut n) = (0, 0);
+    let mut backtrack = None;
+
+    while n < name.len() {
+        match tokens.get(t) {
+            Some(GlobToken::AnyString) => {
+                backtrack = Some((t, n));
+                t += 1;
+            }
+            Some(GlobToken::AnyChar) => {
+                t += 1;
+                n += 1;
+            }
+            Some(GlobToken::Char(c)) if *c == name[n] => {
+                t += 1;
+                n += 1;
+            }
+            _ => match backtrack {
+                Some((bt, bn)) => {
+                    backtrack = Some((bt, bn + 1));
+                    t = bt + 1;
+                    n = bn + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    tokens[t..].iter().all(|t| *t == GlobToken::AnyString)
+}
+
+/// Select all items (and recipes) whose name matches the passed pattern. A
+/// pattern that does not match anything is an error, unless empty matches
+/// are allowed by the context.
+fn select_pattern(
+    data: &Data,
+    ctx: &SelectorContext,
+    selector: &str,
+    pattern: &Pattern,
+    kinds: Kinds,
+) -> Result<Selection, Error> {
+    let mut ret = Selection::default();
+
+    let mut item_names = Vec::new();
+    if kinds.items {
+        for (name, ids) in &data.item_by_name {
+            if pattern.matches(name) {
+                item_names.push(name.as_str());
+                ret.items.extend(ids);
+            }
+        }
+    }
+
+    let mut recipe_names = Vec::new();
+    if kinds.recipes {
+        for (name, ids) in &data.recipes_by_name {
+            if pattern.matches(name) {
+                recipe_names.push(name.as_str());
+                ret.recipes.extend(ids);
+            }
+        }
+    }
+
+    if ret.is_empty() {
+        let message = format!("Pattern {} does not match any {}", selector, kinds.name());
+        if !ctx.allow_empty_match {
+            return Err(Error::usage(message));
+        }
+
+        log::warn!(selector; "{}", message);
+    }
+
+    if !item_names.is_empty() {
+        log::info!(
+            selector,
+            items = ret.items.len();
+            "  {} matches {} items: {}",
+            selector,
+            ret.items.len(),
+            item_names.iter().sorted().join(", ")
+        );
+    }
+
+    if !recipe_names.is_empty() {
+        log::info!(
+            selector,
+            recipes = ret.recipes.len();
+            "  {} matches {} recipes: {}",
+            selector,
+            ret.recipes.len(),
+            recipe_names.iter().sorted().join(", ")
+        );
+    }
+
+    Ok(ret)
+}
+
+/// Remove the backslashes of escaped wildcards from a name that is not a
+/// pattern.
+fn unescape(name: &str) -> String {
+    let mut ret = String::with_capacity(name.len());
+    let mut chars = name.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (c, chars.peek()) {
+            ('\\', Some(next @ ('*' | '?' | '\\'))) => {
+                ret.push(*next);
+                chars.next();
+            }
+            (c, _) => ret.push(c),
+        }
+    }
+
+    ret
+}
+
+/// Get the items with the passed name. If no item has exactly this name, the
+/// normalized name is used.
+fn items_by_name<'a>(data: &'a Data, name: &str) -> Option<&'a Vec<ItemId>> {
+    data.item_by_name
+        .get(name)
+        .or_else(|| data.item_by_normalized_name.get(&normalize_name(name)))
+}
+
+/// Get the recipes with the passed name. If no recipe has exactly this name,
+/// the normalized name is used.
+fn recipes_by_name<'a>(data: &'a Data, name: &str) -> Option<&'a Vec<RecipeId>> {
+    data.recipes_by_name
+        .get(name)
+        .or_else(|| data.recipes_by_normalized_name.get(&normalize_name(name)))
+}
+
+fn parse_unprefixed(data: &Data, item: &str, items_only: bool) -> Result<Selection, Error> {
+    let items = data.item_by_name.get(item);
+    let recipes = data.recipes_by_name.get(item).filter(|_| !items_only);
+
+    // Exact names win over normalized ones, e.g. an item whose name only
+    // differs in case from a recipe.
+    let (items, recipes) = match (items, recipes) {
+        (None, None) => (
+            items_by_name(data, item),
+            recipes_by_name(data, item).filter(|_| !items_only),
+        ),
+        found => found,
+    };
+
+    let mut ret = Selection::default();
+    match (items, recipes) {
+        (Some(items), recipes) => {
+            let id = unique(
+                "item",
+                item,
+                items.iter().map(|iid| (*iid, iid.label(&data.keys))),
+            )?;
+
+            if let Some(recipes) = recipes {
+                let recipes = recipes.iter().map(|rid| rid.label(&data.keys));
+                log::warn!(
+                    selector = item,
+                    item:% = id.label(&data.keys),
+                    recipes:% = recipes.clone().join(",");
+                    "{} matches item {} and recipe {}, using the item (select the recipe with recipe:{})",
+                    item,
+                    id.label(&data.keys),
+                    recipes.clone().join(", "),
+                    item
+                );
+            }
+
+            ret.items.insert(id);
+        }
+        (None, Some(recipes)) => {
+            let id = unique(
+                "recipe",
+                item,
+                recipes.iter().map(|rid| (*rid, rid.label(&data.keys))),
+            )?;
+
+            ret.recipes.insert(id);
+        }
+        // Recipe names select nothing if only items are allowed.
+        (None, None) if recipes_by_name(data, item).is_some() => (),
+        (None, None) => match parse_type(data, item, items_only)? {
+            Some(selection) => ret = selection,
+            None => return Err(unknown(data, "item", item, items_only)),
+        },
+    }
+
+    Ok(ret)
+}
+
+fn parse_item(data: &Data, item: &str) -> Result<ItemId, Error> {
+    match items_by_name(data, item) {
+        Some(ids) => unique(
+            "item",
+            item,
+            ids.iter().map(|iid| (*iid, iid.label(&data.keys))),
+        ),
+        None => Err(unknown(data, "item", item, true)),
+    }
+}
+
+fn parse_recipe(data: &Data, recipe: &str) -> Result<RecipeId, Error> {
+    match recipes_by_name(data, recipe) {
+        Some(ids) => unique(
+            "recipe",
+            recipe,
+            ids.iter().map(|rid| (*rid, rid.label(&data.keys))),
+        ),
+        None => Err(unknown(data, "recipe", recipe, false)),
+    }
+}
+
+/// Get the ids selected by one of the type keywords, or `None` if the
+/// passed string is not a keyword. Recipe type keywords fail if only items
+/// can be selected.
+fn parse_type(data: &Data, keyword: &str, items_only: bool) -> Result<Option<Selection>, Error> {
+    let mut ret = Selection::default();
+
+    if let Some(recipe_type) = RecipeType::from_keyword(keyword) {
+        if items_only {
+            return Err(Error::usage(format!(
+                "{} is a recipe type, but only items can be selected here",
+                keyword
+            )));
+        }
+
+        ret.recipes.extend(
+            data.recipes
+                .iter()
+                .filter(|(_, r)| r.type_ == recipe_type)
+                .map(|(rid, _)| *rid),
+        );
+        log::info!(
+            selector = keyword;
+            "  recipe type {} selects {} recipes",
+            recipe_type,
+            ret.recipes.len()
+        );
+
+        return Ok(Some(ret));
+    }
+
+    let item_type = match keyword.to_lowercase().as_str() {
+        "all" => {
+            ret.items.extend(data.items.keys());
+
+            if !items_only {
+                ret.recipes.extend(data.recipes.keys());
+            }
+
+            return Ok(Some(ret));
+        }
+        "explicit" if !items_only => {
+            ret.recipes.extend(
+                data.recipes
+                    .iter()
+                    .filter(|(_, r)| r.explicit)
+                    .map(|(rid, _)| *rid),
+            );
+
+            return Ok(Some(ret));
+        }
+        "advanced" if !items_only => {
+            ret.recipes.extend(
+                ADVANCED_RECIPES
+                    .iter()
+                    .filter_map(|name| data.recipes_by_name.get(*name))
+                    .flatten(),
+            );
+
+            return Ok(Some(ret));
+        }
+        "material" => ItemType::Material,
+        "matrix" => ItemType::Matrix,
+        "product" => ItemType::Product,
+        "production" => ItemType::Production,
+        "resource" => ItemType::Resource,
+        "component" => ItemType::Component,
+        "logistics" => ItemType::Logistics,
+        _ => return Ok(None),
+    };
+
+    ret.items.extend(
+        data.items
+            .iter()
+            .filter(|(_, i)| i.type_ == item_type)
+            .map(|(iid, _)| *iid),
+    );
+
+    Ok(Some(ret))
+}
+
+/// Get up to three item (and recipe) names that are similar to the passed
+/// name, the most similar first. Case, punctuation and whitespace are ignored
+/// when comparing the names.
+pub fn similar_names(data: &Data, name: &str, items_only: bool) -> Vec<String> {
+    let name = normalize_name(name);
+    let max_distance = (name.chars().count() / 3).max(2);
+
+    let recipes = data.recipes_by_name.keys().filter(|_| !items_only);
+
+    data.item_by_name
+        .keys()
+        .chain(recipes)
+        .unique()
+        .filter_map(|candidate| {
+            let distance = distance(&name, &normalize_name(candidate));
+
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .sorted()
+        .take(3)
+        .map(|(_, candidate)| candidate.clone())
+        .collect()
+}
+
+/// Create the error for a name that does not match any item or recipe.
+fn unknown(data: &Data, kind: &str, name: &str, items_only: bool) -> Error {
+    let similar = similar_names(data, name, items_only);
+    if similar.is_empty() {
+        return Error::usage(format!("Invalid or unknown {}: {}", kind, name));
+    }
+
+    Error::usage(format!(
+        "Invalid or unknown {}: {}, did you mean {}?",
+        kind,
+        name,
+        similar.iter().map(|s| format!("'{}'", s)).join(", ")
+    ))
+}
+
+/// Levenshtein distance of the passed strings.
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+
+        for (j, cb) in b.iter().enumerate() {
+            let cost = if ca == *cb { prev } else { prev + 1 };
+            prev = row[j + 1];
+            row[j + 1] = cost.min(row[j] + 1).min(prev + 1);
+        }
+    }
+
+    row[b.len()]
+}
+
+/// Parse the passed targets, given as `<item>=<rate>` with the rate in items
+/// per minute and an optional `/min` suffix. Rates of items that are selected
+/// multiple times are summed up. Rates have to be positive and finite.
+pub fn parse_targets(
+    data: &Data,
+    ctx: &SelectorContext,
+    targets: &[String],
+) -> Result<BTreeMap<ItemId, f64>, Error> {
+    let mut ret = BTreeMap::new();
+
+    for target in targets {
+        let (item, rate) = target.rsplit_once('=').ok_or_else(|| {
+            Error::usage(format!(
+                "Invalid target (expected <item>=<rate>[/min]): {}",
+                target
+            ))
+        })?;
+        let rate = rate
+            .trim()
+            .trim_end_matches("/min")
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|rate| rate.is_finite() && *rate > 0.0)
+            .ok_or_else(|| {
+                Error::usage(format!(
+                    "Invalid rate for target (expected a positive number): {}",
+                    target
+                ))
+            })?;
+
+        for iid in parse_ids(data, ctx, &[item.trim().to_owned()], true)?.items {
+            *ret.entry(iid).or_default() += rate;
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Parse the passed machine counts, given as `<recipe>=<machines>` or as
+/// files that contain one such line per recipe. Counts of recipes that are
+/// selected multiple times are summed up.
+pub fn parse_machines(
+    data: &Data,
+    ctx: &SelectorContext,
+    machines: &[String],
+) -> Result<BTreeMap<RecipeId, f64>, Error> {
+    let mut ret = BTreeMap::new();
+
+    for machine in machines {
+        let path = Path::new(machine);
+        if path.is_file() {
+            let content = read_to_string(path)?;
+            let lines = content
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>();
+
+            for (rid, count) in parse_machines(data, ctx, &lines)? {
+                *ret.entry(rid).or_default() += count;
+            }
+
+            continue;
+        }
+
+        let (recipe, count) = machine.rsplit_once('=').ok_or_else(|| {
+            Error::usage(format!(
+                "Invalid machine count (expected <recipe>=<machines>): {}",
+                machine
+            ))
+        })?;
+        let count = count
+            .trim()
+            .parse::<f64>()
+            .ok()
+            .filter(|count| *count >= 0.0)
+            .ok_or_else(|| Error::usage(format!("Invalid number of machines: {}", machine)))?;
+
+        // Unprefixed names are recipes here, even if an item has the same name.
+        let recipe = recipe.trim();
+        let selector = if recipe.contains(':') {
+            recipe.to_owned()
+        } else {
+            format!("recipe:{}", recipe)
+        };
+        for rid in parse_ids(data, ctx, &[selector], false)?.recipes {
+            *ret.entry(rid).or_default() += count;
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Parse the passed techs, given as ids, names or files that contain one tech
+/// per line. Techs with multiple levels share the same name, so a name selects
+/// all of them.
+pub fn parse_techs(data: &Data, techs: &[String]) -> Result<BTreeSet<TechId>, Error> {
+    let mut ret = BTreeSet::new();
+
+    for tech in techs {
+        let path = Path::new(tech);
+        if path.is_file() {
+            let content = read_to_string(path)?;
+            let lines = content
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#'))
+                .map(ToOwned::to_owned)
+                .collect::<Vec<_>>();
+
+            ret.extend(parse_techs(data, &lines)?);
+
+            continue;
+        }
+
+        let tech = tech.trim();
+        let matches = data
+            .techs
+            .iter()
+            .filter(|(tid, t)| t.name == tech || tid.0.to_string() == tech)
+            .map(|(tid, _)| *tid)
+            .collect::<Vec<_>>();
+
+        if matches.is_empty() {
+            return Err(Error::usage(format!("Invalid or unknown tech: {}", tech)));
+        }
+
+        ret.extend(matches);
+    }
+
+    Ok(ret)
+}
+
+const ADVANCED_RECIPES: &[&str] = &[
+    "Casimir Crystal (Advanced)",
+    "Organic Crystal (Original)",
+    "Crystal Silicon (Advanced)",
+    "Photon Combiner (Advanced)",
+    "Space Warper (Advanced)",
+    "Particle Container (Advanced)",
+    "Graphene (Advanced)",
+    "Carbon Nanotube (Advanced)",
+    "Diamond (Advanced)",
+];
+
+/// Get the only id a name refers to, or fail if the name is ambiguous. The
+/// ids are passed with their labels for the error.
+fn unique<T, I>(kind: &str, name: &str, ids: I) -> Result<T, Error>
+where
+    T: Copy,
+    I: IntoIterator<Item = (T, IdLabel)>,
+{
+    let ids = ids.into_iter().collect::<Vec<_>>();
+
+    match ids.as_slice() {
+        [(id, _)] => Ok(*id),
+        ids => Err(Error::usage(format!(
+            "Ambiguous name: {} (matches {}, select one of them by id)",
+            name,
+            ids.iter()
+                .map(|(_, label)| format!("{}:{}", kind, label))
+                .join(", ")
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::EXIT_USAGE;
+
+    use super::*;
+
+    fn data() -> Data {
+        Data::from_lua_source("small", include_str!("../tests/data/small.lua"), None, &[]).unwrap()
+    }
+
+    fn parse(data: &Data, selectors: &[&str]) -> Result<Selection, Error> {
+        let selectors = selectors.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+
+        parse_ids(data, &SelectorContext::default(), &selectors, false)
+    }
+
+    #[test]
+    fn shared_id_resolves_by_prefix() {
+        let data = data();
+
+        let items = parse(&data, &["item:1"]).unwrap();
+        assert_eq!(items.items, BTreeSet::from([ItemId(1)]));
+        assert!(items.recipes.is_empty());
+
+        let recipes = parse(&data, &["recipe:1"]).unwrap();
+        assert!(recipes.items.is_empty());
+        assert_eq!(recipes.recipes, BTreeSet::from([RecipeId(1)]));
+    }
+
+    #[test]
+    fn shared_id_without_prefix_is_ambiguous() {
+        let data = data();
+
+        let err = parse(&data, &["1"]).unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+        assert!(err.to_string().contains("Ambiguous id"));
+    }
+
+    #[test]
+    fn duplicate_names_are_ambiguous() {
+        let source = r#"
+            return {
+              game_items = {
+                { id = 1, name = "Magnet", type = "COMPONENT" },
+                { id = 2, name = "Magnet", type = "COMPONENT" },
+                { id = 3, name = "Coil", type = "COMPONENT" },
+              },
+              game_recipes = {},
+            }
+        "#;
+        let data = Data::from_lua_source("duplicates", source, None, &[]).unwrap();
+        assert_eq!(data.item_by_name["Magnet"], [ItemId(1), ItemId(2)]);
+
+        let err = parse(&data, &["Magnet"]).unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+        assert!(err.to_string().contains("item:1, item:2"), "{}", err);
+
+        let selection = parse(&data, &["item:2", "Coil"]).unwrap();
+        assert_eq!(selection.items, BTreeSet::from([ItemId(2), ItemId(3)]));
+    }
+
+    #[test]
+    fn distance_counts_the_edits() {
+        assert_eq!(distance("gear", "gear"), 0);
+        assert_eq!(distance("gear", "gears"), 1);
+        assert_eq!(distance("gaer", "gear"), 2);
+        assert_eq!(distance("", "ore"), 3);
+    }
+
+    #[test]
+    fn unknown_names_suggest_similar_names() {
+        let data = data();
+
+        assert_eq!(similar_names(&data, "ingto", false), ["Ingot"]);
+        assert_eq!(similar_names(&data, "GEAR!", false), ["Gear"]);
+        assert!(similar_names(&data, "Circuit Board", false).is_empty());
+
+        let err = parse(&data, &["Gaer"]).unwrap_err();
+        assert!(err.to_string().contains("did you mean 'Gear'?"), "{}", err);
+    }
+
+    #[test]
+    fn regex_selects_matching_names() {
+        let data = data();
+
+        let selection = parse(&data, &["re:^(ore|GEAR)$"]).unwrap();
+        assert_eq!(selection.items, BTreeSet::from([ItemId(1), ItemId(3)]));
+        assert_eq!(selection.recipes, BTreeSet::from([RecipeId(2)]));
+
+        let selection = parse(&data, &["item:re:^(ore|GEAR)$"]).unwrap();
+        assert_eq!(selection.items, BTreeSet::from([ItemId(1), ItemId(3)]));
+        assert!(selection.recipes.is_empty());
+
+        let selection = parse(&data, &["recipe:re:^g"]).unwrap();
+        assert!(selection.items.is_empty());
+        assert_eq!(selection.recipes, BTreeSet::from([RecipeId(2)]));
+
+        let err = parse(&data, &["re:("]).unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+        assert!(err.to_string().contains("Invalid regex"), "{}", err);
+    }
+
+    #[test]
+    fn empty_matches_fail_unless_allowed() {
+        let data = data();
+        let selectors = vec!["Gear".to_owned(), "re:^magnet$".to_owned()];
+
+        let err = parse(&data, &["re:^magnet$"]).unwrap_err();
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+
+        let ctx = SelectorContext {
+            allow_empty_match: true,
+            ..SelectorContext::default()
+        };
+        let selection = parse_ids(&data, &ctx, &selectors, false).unwrap();
+        assert_eq!(selection.items, BTreeSet::from([ItemId(3)]));
+    }
+
+    #[test]
+    fn groups_file_is_read_on_every_parse() {
+        let data = data();
+        let path =
+            std::env::temp_dir().join(format!("dsp-tool-groups-{}.toml", std::process::id()));
+        let ctx = SelectorContext {
+            groups: Groups::from([
+                ("metal".to_owned(), vec!["Ingot".to_owned()]),
+                ("parts".to_owned(), vec!["Ore".to_owned()]),
+            ]),
+            groups_file: Some(path.clone()),
+            ..SelectorContext::default()
+        };
+        let parse = |selector: &str| parse_ids(&data, &ctx, &[selector.to_owned()], true);
+
+        std::fs::write(&path, "parts = [\"Gear\"]").unwrap();
+        assert_eq!(parse("@parts").unwrap().items, BTreeSet::from([ItemId(3)]));
+        assert_eq!(parse("@metal").unwrap().items, BTreeSet::from([ItemId(2)]));
+
+        std::fs::write(&path, "parts = [\"@metal\", \"Gear\"]").unwrap();
+        let selection = parse("@parts");
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(
+            selection.unwrap().items,
+            BTreeSet::from([ItemId(2), ItemId(3)])
+        );
+
+        assert!(parse("@parts").is_err());
+    }
+
+    #[test]
+    fn exclude_files_negate_item_selectors() {
+        let data = data();
+        let ctx = SelectorContext::default();
+        let path =
+            std::env::temp_dir().join(format!("dsp-tool-exclude-{}.txt", std::process::id()));
+        let items = vec!["*".to_owned()];
+        let files = vec![path.clone()];
+
+        std::fs::write(&path, "# comment\nGear\nre:^In\n").unwrap();
+        let selection = parse_items(&data, &ctx, &items, &files);
+        let matches = parse_each(&data, &ctx, &items, true, &files, true);
+
+        std::fs::write(&path, "Gear\nunknown item\n").unwrap();
+        let err = parse_items(&data, &ctx, &items, &files).unwrap_err();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(selection.unwrap().items, BTreeSet::from([ItemId(1)]));
+
+        let matches = matches.unwrap();
+        assert_eq!(matches.len(), 3);
+        assert!(matches[1].negated);
+        assert_eq!(matches[1].selector, "!Gear");
+        assert_eq!(
+            matches[1].origin.as_deref(),
+            Some(format!("{}:2", path.display()).as_str())
+        );
+
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+        assert!(
+            err.to_string()
+                .starts_with(&format!("{}:2: ", path.display())),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn targets_need_positive_rates() {
+        let data = data();
+        let ctx = SelectorContext::default();
+        let targets = |targets: &[&str]| {
+            let targets = targets.iter().map(|t| t.to_string()).collect::<Vec<_>>();
+
+            parse_targets(&data, &ctx, &targets)
+        };
+
+        let parsed = targets(&["Gear=60", "Gear = 1.5/min", "Ingot=0.25"]).unwrap();
+        assert_eq!(
+            parsed,
+            BTreeMap::from([(ItemId(2), 0.25), (ItemId(3), 61.5)])
+        );
+
+        for target in [
+            "Gear=-5", "Gear=0", "Gear=NaN", "Gear=inf", "Gear=x", "Gear",
+        ] {
+            let err = targets(&[target]).unwrap_err();
+            assert_eq!(err.exit_code(), EXIT_USAGE, "{}", target);
+        }
+    }
+
+    #[test]
+    fn shared_name_prefers_the_item() {
+        let data = data();
+
+        let selection = parse(&data, &["Ingot"]).unwrap();
+        assert_eq!(selection.items, BTreeSet::from([ItemId(2)]));
+        assert!(selection.recipes.is_empty());
+
+        let selection = parse(&data, &["recipe:Ingot"]).unwrap();
+        assert_eq!(selection.recipes, BTreeSet::from([RecipeId(1)]));
+    }
+}
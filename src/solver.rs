@@ -0,0 +1,370 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::{
+    data::{ItemId, RecipeId},
+    error::Error,
+};
+
+/// Inputs and outputs of a recipe per craft. The outputs already include the
+/// productivity of the recipe.
+#[derive(Debug, Clone, Default)]
+pub struct Flow {
+    pub inputs: Vec<(ItemId, f64)>,
+    pub outputs: Vec<(ItemId, f64)>,
+}
+
+impl Flow {
+    fn produced(&self, iid: ItemId) -> f64 {
+        amount(&self.outputs, iid)
+    }
+
+    fn net(&self, iid: ItemId) -> f64 {
+        amount(&self.outputs, iid) - amount(&self.inputs, iid)
+    }
+}
+
+/// Solution of the flow-balance equations.
+#[derive(Debug, Clone, Default)]
+pub struct SteadyState {
+    /// Crafts per minute of each recipe.
+    pub recipes: BTreeMap<RecipeId, f64>,
+
+    /// Items per minute that are left over after all inputs and targets are
+    /// satisfied. Negative amounts need to be supplied from outside, positive
+    /// amounts are byproducts.
+    pub surplus: BTreeMap<ItemId, f64>,
+}
+
+/// Solve the steady-state of the passed recipes, so that the targets are
+/// produced at their rates (items per minute).
+///
+/// Each recipe is driven by one of its candidate items, initially the first
+/// one it produces. For each driven item the net production of all recipes
+/// has to match its target, or zero if it is not a target. If multiple
+/// recipes drive the same item, they produce equal amounts of it. All other
+/// items are free, so they become resources or byproducts.
+///
+/// The system is solved repeatedly until it is stable:
+/// - If a candidate item is still missing, a recipe that may produce it is
+///   driven by it instead, which only increases the crafts of that recipe.
+/// - If a recipe would have to run backwards, because its item is already
+///   produced as byproduct of other recipes, the recipe is not used.
+///
+/// Fails if the system has no unique solution, if it does not converge, or if
+/// a target can not be produced. Items that are produced as byproduct beyond
+/// their demand (see [`overproduced`]) are reported as warning.
+pub fn solve<F>(
+    flows: &BTreeMap<RecipeId, Flow>,
+    candidates: &BTreeMap<RecipeId, Vec<ItemId>>,
+    targets: &BTreeMap<ItemId, f64>,
+    item_name: F,
+) -> Result<SteadyState, Error>
+where
+    F: Fn(ItemId) -> String,
+{
+    let candidates = candidates
+        .iter()
+        .filter_map(|(rid, items)| {
+            let flow = flows.get(rid)?;
+            let items = items
+                .iter()
+                .filter(|iid| flow.net(**iid) > 0.0)
+                .copied()
+                .collect::<Vec<_>>();
+
+            (!items.is_empty()).then_some((*rid, items))
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let mut drivers = candidates
+        .iter()
+        .map(|(rid, items)| (*rid, items[0]))
+        .collect::<BTreeMap<_, _>>();
+    let mut unused = BTreeSet::new();
+
+    let max_iterations = 2 * candidates.values().map(Vec::len).sum::<usize>() + 1;
+    for _ in 0..max_iterations {
+        let crafts = solve_drivers(flows, &drivers, targets)?;
+
+        if let Some((rid, _)) = crafts.iter().find(|(_, crafts)| **crafts < -EPSILON) {
+            log::debug!(
+                recipe:% = rid;
+                "  recipe {} is not needed, {} is produced as byproduct",
+                rid,
+                item_name(drivers[rid])
+            );
+            drivers.remove(rid);
+            unused.insert(*rid);
+
+            continue;
+        }
+
+        let state = steady_state(flows, crafts, targets);
+
+        let driven = drivers.values().copied().collect::<BTreeSet<_>>();
+        let missing = state.surplus.iter().find_map(|(iid, rate)| {
+            if *rate >= 0.0 || driven.contains(iid) {
+                return None;
+            }
+
+            candidates
+                .iter()
+                .filter(|(rid, _)| !unused.contains(rid))
+                .find(|(_, items)| items.contains(iid))
+                .map(|(rid, _)| (*rid, *iid))
+        });
+
+        match missing {
+            Some((rid, iid)) => {
+                drivers.insert(rid, iid);
+            }
+            None => {
+                if let Some((iid, _)) = targets
+                    .keys()
+                    .filter_map(|iid| state.surplus.get(iid).map(|rate| (iid, rate)))
+                    .find(|(_, rate)| **rate < 0.0)
+                {
+                    return Err(Error::custom(format!(
+                        "Unable to solve the production: {} can not be produced",
+                        item_name(*iid)
+                    )));
+                }
+
+                for (iid, rate) in overproduced(flows, targets, &state) {
+                    log::warn!(
+                        item:% = iid;
+                        "{} is overproduced as byproduct, {:.2} /min are left over",
+                        item_name(iid),
+                        rate
+                    );
+                }
+
+                return Ok(state);
+            }
+        }
+    }
+
+    Err(Error::custom(
+        "Unable to solve the production: the rates do not converge",
+    ))
+}
+
+/// Items that are left over although they are needed by the targets or by
+/// the inputs of the recipes, with their surplus. Their byproduct production
+/// alone exceeds the demand, so the recipes that would produce them are not
+/// used and the surplus has nowhere to go. Byproducts that nothing consumes
+/// are not included.
+fn overproduced(
+    flows: &BTreeMap<RecipeId, Flow>,
+    targets: &BTreeMap<ItemId, f64>,
+    state: &SteadyState,
+) -> Vec<(ItemId, f64)> {
+    state
+        .surplus
+        .iter()
+        .filter(|(iid, rate)| {
+            **rate > 0.0
+                && (targets.contains_key(iid)
+                    || flows.iter().any(|(rid, flow)| {
+                        state.recipes.get(rid).is_some_and(|crafts| *crafts > 0.0)
+                            && amount(&flow.inputs, **iid) > 0.0
+                    }))
+        })
+        .map(|(iid, rate)| (*iid, *rate))
+        .collect()
+}
+
+/// Solve the equations of the driven items. Recipes without a driver are not
+/// used.
+fn solve_drivers(
+    flows: &BTreeMap<RecipeId, Flow>,
+    drivers: &BTreeMap<RecipeId, ItemId>,
+    targets: &BTreeMap<ItemId, f64>,
+) -> Result<BTreeMap<RecipeId, f64>, Error> {
+    let recipes = drivers.keys().copied().collect::<Vec<_>>();
+    let column = |rid: &RecipeId| recipes.iter().position(|r| r == rid).unwrap();
+
+    let mut driven = BTreeMap::<ItemId, Vec<RecipeId>>::new();
+    for (rid, iid) in drivers {
+        driven.entry(*iid).or_default().push(*rid);
+    }
+
+    let mut matrix = Vec::with_capacity(recipes.len());
+    let mut rhs = Vec::with_capacity(recipes.len());
+    for (iid, rids) in &driven {
+        matrix.push(recipes.iter().map(|rid| flows[rid].net(*iid)).collect());
+        rhs.push(targets.get(iid).copied().unwrap_or_default());
+
+        let first = rids[0];
+        for rid in &rids[1..] {
+            let mut row = vec![0.0; recipes.len()];
+            row[column(&first)] = flows[&first].produced(*iid);
+            row[column(rid)] = -flows[rid].produced(*iid);
+
+            matrix.push(row);
+            rhs.push(0.0);
+        }
+    }
+
+    let crafts = gauss(matrix, rhs).ok_or_else(|| {
+        Error::custom(
+            "Unable to solve the production: the recipes do not have a unique steady state",
+        )
+    })?;
+
+    if crafts.iter().any(|crafts| !crafts.is_finite()) {
+        return Err(Error::custom(
+            "Unable to solve the production: the rates are not finite",
+        ));
+    }
+
+    Ok(recipes.into_iter().zip(crafts).collect())
+}
+
+/// Calculate the surplus of all items for the passed crafts.
+fn steady_state(
+    flows: &BTreeMap<RecipeId, Flow>,
+    crafts: BTreeMap<RecipeId, f64>,
+    targets: &BTreeMap<ItemId, f64>,
+) -> SteadyState {
+    let mut ret = SteadyState::default();
+    for (iid, rate) in targets {
+        *ret.surplus.entry(*iid).or_default() -= rate;
+    }
+
+    for (rid, flow) in flows {
+        let crafts = crafts.get(rid).copied().unwrap_or_default().max(0.0);
+        ret.recipes.insert(*rid, crafts);
+
+        for (iid, amount) in &flow.outputs {
+            *ret.surplus.entry(*iid).or_default() += crafts * amount;
+        }
+        for (iid, amount) in &flow.inputs {
+            *ret.surplus.entry(*iid).or_default() -= crafts * amount;
+        }
+    }
+    ret.surplus.retain(|_, rate| rate.abs() > EPSILON);
+
+    ret
+}
+
+/// Solve the linear system `matrix * x = rhs` using Gaussian elimination with
+/// partial pivoting. Returns `None` if the system is singular.
+fn gauss(mut matrix: Vec<Vec<f64>>, mut rhs: Vec<f64>) -> Option<Vec<f64>> {
+    let n = rhs.len();
+
+    for col in 0..n {
+        let pivot =
+            (col..n).max_by(|a, b| matrix[*a][col].abs().total_cmp(&matrix[*b][col].abs()))?;
+        if matrix[pivot][col].abs() < EPSILON {
+            return None;
+        }
+
+        matrix.swap(col, pivot);
+        rhs.swap(col, pivot);
+
+        let (pivot_rows, rows) = matrix.split_at_mut(col + 1);
+        let pivot_row = &pivot_rows[col];
+        for (i, row) in rows.iter_mut().enumerate() {
+            let factor = row[col] / pivot_row[col];
+            if factor == 0.0 {
+                continue;
+            }
+
+            for (value, pivot) in row[col..].iter_mut().zip(&pivot_row[col..]) {
+                *value -= factor * pivot;
+            }
+            rhs[col + 1 + i] -= factor * rhs[col];
+        }
+    }
+
+    let mut x = vec![0.0; n];
+    for row in (0..n).rev() {
+        let sum = (row + 1..n).map(|k| matrix[row][k] * x[k]).sum::<f64>();
+        x[row] = (rhs[row] - sum) / matrix[row][row];
+    }
+
+    Some(x)
+}
+
+fn amount(amounts: &[(ItemId, f64)], iid: ItemId) -> f64 {
+    amounts
+        .iter()
+        .filter(|(id, _)| *id == iid)
+        .map(|(_, amount)| amount)
+        .sum()
+}
+
+/// Tolerance for rounding errors.
+const EPSILON: f64 = 1e-9;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flow(inputs: &[(usize, f64)], outputs: &[(usize, f64)]) -> Flow {
+        let ids = |amounts: &[(usize, f64)]| {
+            amounts
+                .iter()
+                .map(|(iid, amount)| (ItemId(*iid), *amount))
+                .collect()
+        };
+
+        Flow {
+            inputs: ids(inputs),
+            outputs: ids(outputs),
+        }
+    }
+
+    #[test]
+    fn byproduct_overproduction_is_reported() {
+        // Recipe 1 makes ore (1) into 2 X (2), 1 Y (3) and 1 W (5), recipe 2
+        // makes ore into Y and recipe 3 makes 1 X and 0.1 Y into Z (4).
+        let flows = BTreeMap::from([
+            (
+                RecipeId(1),
+                flow(&[(1, 1.0)], &[(2, 2.0), (3, 1.0), (5, 1.0)]),
+            ),
+            (RecipeId(2), flow(&[(1, 1.0)], &[(3, 1.0)])),
+            (RecipeId(3), flow(&[(2, 1.0), (3, 0.1)], &[(4, 1.0)])),
+        ]);
+        let candidates = BTreeMap::from([
+            (RecipeId(1), vec![ItemId(2), ItemId(3)]),
+            (RecipeId(2), vec![ItemId(3)]),
+            (RecipeId(3), vec![ItemId(4)]),
+        ]);
+        let targets = BTreeMap::from([(ItemId(4), 1.0)]);
+
+        let state = solve(&flows, &candidates, &targets, |iid| iid.to_string()).unwrap();
+        assert!((state.recipes[&RecipeId(1)] - 0.5).abs() < EPSILON);
+        assert_eq!(state.recipes[&RecipeId(2)], 0.0);
+        assert!((state.surplus[&ItemId(3)] - 0.4).abs() < EPSILON);
+        assert!((state.surplus[&ItemId(5)] - 0.5).abs() < EPSILON);
+
+        // Y is needed by recipe 3 but left over, W is a plain byproduct.
+        let overproduced = overproduced(&flows, &targets, &state);
+        assert_eq!(overproduced.len(), 1);
+        assert_eq!(overproduced[0].0, ItemId(3));
+        assert!((overproduced[0].1 - 0.4).abs() < EPSILON);
+    }
+
+    #[test]
+    fn balanced_loops_are_not_overproduced() {
+        // Recipe 1 makes 2 Y (3) out of ore (1) and 1 Y, recipe 2 makes Y
+        // into Z (4).
+        let flows = BTreeMap::from([
+            (RecipeId(1), flow(&[(1, 1.0), (3, 1.0)], &[(3, 2.0)])),
+            (RecipeId(2), flow(&[(3, 1.0)], &[(4, 1.0)])),
+        ]);
+        let candidates = BTreeMap::from([
+            (RecipeId(1), vec![ItemId(3)]),
+            (RecipeId(2), vec![ItemId(4)]),
+        ]);
+        let targets = BTreeMap::from([(ItemId(4), 2.0)]);
+
+        let state = solve(&flows, &candidates, &targets, |iid| iid.to_string()).unwrap();
+        assert!((state.recipes[&RecipeId(1)] - 2.0).abs() < EPSILON);
+        assert!((state.surplus[&ItemId(1)] + 2.0).abs() < EPSILON);
+        assert!(overproduced(&flows, &targets, &state).is_empty());
+    }
+}
@@ -0,0 +1,377 @@
+use std::env::var_os;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{stdout, IsTerminal};
+use std::str::FromStr;
+
+use crate::data::{ItemType, RecipeType};
+
+/* ColorChoice */
+
+/// Whether the tables are printed with colors.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum ColorChoice {
+    /// Use colors if stdout is a terminal and `NO_COLOR` is not set.
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            s => Err(format!(
+                "Unknown color choice: {} (expected auto, always or never)",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for ColorChoice {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            Self::Auto => write!(f, "auto"),
+            Self::Always => write!(f, "always"),
+            Self::Never => write!(f, "never"),
+        }
+    }
+}
+
+/* Style */
+
+/// How the tables are printed to stdout. The default style prints the tables
+/// without colors and does not truncate them.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Style {
+    color: bool,
+    width: Option<usize>,
+}
+
+impl Style {
+    /// Style for the passed color choice. Tables are only truncated to the
+    /// width of the terminal if stdout is a terminal and `wide` is not set.
+    pub fn new(color: ColorChoice, wide: bool) -> Self {
+        let is_terminal = stdout().is_terminal();
+
+        let color = match color {
+            ColorChoice::Auto => is_terminal && var_os("NO_COLOR").is_none(),
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+        };
+
+        let width = if wide || !is_terminal {
+            None
+        } else {
+            terminal_size::terminal_size().map(|(w, _)| w.0 as usize)
+        };
+
+        Self { color, width }
+    }
+}
+
+/* Color */
+
+/// Colors of the table cells.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum Color {
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    Gray,
+}
+
+impl Color {
+    fn code(&self) -> &'static str {
+        match self {
+            Self::Red => "31",
+            Self::Green => "32",
+            Self::Yellow => "33",
+            Self::Blue => "34",
+            Self::Magenta => "35",
+            Self::Cyan => "36",
+            Self::Gray => "90",
+        }
+    }
+
+    /// Color of the passed item type.
+    pub fn item_type(type_: &ItemType) -> Option<Self> {
+        match type_ {
+            ItemType::Resource => Some(Self::Green),
+            ItemType::Material => Some(Self::Yellow),
+            ItemType::Component => Some(Self::Cyan),
+            ItemType::Product => Some(Self::Blue),
+            ItemType::Production => Some(Self::Magenta),
+            ItemType::Logistics => Some(Self::Gray),
+            ItemType::Matrix => Some(Self::Red),
+            ItemType::Unknown(_) => None,
+        }
+    }
+
+    /// Color of the passed recipe type.
+    pub fn recipe_type(type_: &RecipeType) -> Option<Self> {
+        match type_ {
+            RecipeType::Smelt => Some(Self::Yellow),
+            RecipeType::Assemble => Some(Self::Blue),
+            RecipeType::Chemical => Some(Self::Cyan),
+            RecipeType::Refine => Some(Self::Green),
+            RecipeType::Particle => Some(Self::Magenta),
+            RecipeType::Research => Some(Self::Red),
+            RecipeType::Fractionate => Some(Self::Gray),
+            RecipeType::Unknown(_) => None,
+        }
+    }
+}
+
+/* Cell */
+
+/// Cell of a table, optionally with a color.
+#[derive(Debug, Clone)]
+pub struct Cell {
+    text: String,
+    color: Option<Color>,
+}
+
+impl Cell {
+    pub fn new<T: ToString>(text: T) -> Self {
+        Self {
+            text: text.to_string(),
+            color: None,
+        }
+    }
+
+    pub fn colored<T: ToString>(text: T, color: Option<Color>) -> Self {
+        Self {
+            text: text.to_string(),
+            color,
+        }
+    }
+}
+
+impl From<String> for Cell {
+    fn from(text: String) -> Self {
+        Self::new(text)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(text: &str) -> Self {
+        Self::new(text)
+    }
+}
+
+/* Table */
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+enum Align {
+    Left,
+    Right,
+}
+
+/// Column of a table. Left aligned columns are truncated if the table does
+/// not fit into the terminal.
+#[derive(Debug, Clone)]
+pub struct Column {
+    title: String,
+    align: Align,
+}
+
+impl Column {
+    pub fn left<T: ToString>(title: T) -> Self {
+        Self {
+            title: title.to_string(),
+            align: Align::Left,
+        }
+    }
+
+    pub fn right<T: ToString>(title: T) -> Self {
+        Self {
+            title: title.to_string(),
+            align: Align::Right,
+        }
+    }
+}
+
+/// Table with aligned columns that is printed to stdout. The header is
+/// underlined and skipped if all column titles are empty.
+#[derive(Debug, Clone)]
+pub struct Table {
+    columns: Vec<Column>,
+    rows: Vec<Vec<Cell>>,
+    indent: usize,
+}
+
+/// Separator between the columns.
+const GAP: usize = 2;
+
+impl Table {
+    pub fn new<I>(columns: I) -> Self
+    where
+        I: IntoIterator<Item = Column>,
+    {
+        Self {
+            columns: columns.into_iter().collect(),
+            rows: Vec::new(),
+            indent: 0,
+        }
+    }
+
+    /// Indent all lines of the table by the passed number of spaces.
+    pub fn indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+
+        self
+    }
+
+    /// Add a row. Missing cells are left empty.
+    pub fn row<I, T>(&mut self, cells: I)
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<Cell>,
+    {
+        self.rows.push(cells.into_iter().map(Into::into).collect());
+    }
+
+    /// Print the table to stdout using the passed style.
+    pub fn print(&self, style: &Style) {
+        print!("{}", self.render(style.color, style.width));
+    }
+
+    fn render(&self, color: bool, width: Option<usize>) -> String {
+        let widths = self.widths(width);
+        let has_header = self.columns.iter().any(|c| !c.title.is_empty());
+
+        let mut ret = String::new();
+        if has_header {
+            let titles = self
+                .columns
+                .iter()
+                .map(|c| Cell::new(&c.title))
+                .collect::<Vec<_>>();
+            self.render_line(&mut ret, &titles, &widths, color, true);
+
+            let underline = self
+                .columns
+                .iter()
+                .zip(&widths)
+                .map(|(c, w)| Cell::new(if c.title.is_empty() { "" } else { "-" }.repeat(*w)))
+                .collect::<Vec<_>>();
+            self.render_line(&mut ret, &underline, &widths, false, false);
+        }
+
+        for row in &self.rows {
+            self.render_line(&mut ret, row, &widths, color, false);
+        }
+
+        ret
+    }
+
+    fn render_line(
+        &self,
+        ret: &mut String,
+        cells: &[Cell],
+        widths: &[usize],
+        color: bool,
+        bold: bool,
+    ) {
+        let mut line = " ".repeat(self.indent);
+
+        for (i, (column, width)) in self.columns.iter().zip(widths).enumerate() {
+            let cell = cells.get(i);
+            let text = truncate(cell.map(|c| c.text.as_str()).unwrap_or(""), *width);
+            let padding = " ".repeat(width - text.chars().count());
+            let is_last = i + 1 == self.columns.len();
+
+            if i > 0 {
+                line += &" ".repeat(GAP);
+            }
+
+            if column.align == Align::Right {
+                line += &padding;
+            }
+
+            match (color, bold, cell.and_then(|c| c.color)) {
+                (true, true, _) => line += &format!("\x1b[1m{}\x1b[0m", text),
+                (true, false, Some(c)) => line += &format!("\x1b[{}m{}\x1b[0m", c.code(), text),
+                _ => line += &text,
+            }
+
+            if column.align == Align::Left && !is_last {
+                line += &padding;
+            }
+        }
+
+        ret.push_str(line.trim_end());
+        ret.push('\n');
+    }
+
+    /// Width of the columns. If the table is wider than `max`, the widest
+    /// left aligned column is shrunk until it fits.
+    fn widths(&self, max: Option<usize>) -> Vec<usize> {
+        let mut widths = self
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(i, column)| {
+                self.rows
+                    .iter()
+                    .filter_map(|row| row.get(i))
+                    .map(|cell| cell.text.chars().count())
+                    .chain([column.title.chars().count()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+
+        let max = match max {
+            Some(max) => max,
+            None => return widths,
+        };
+
+        let total = |widths: &[usize]| {
+            self.indent + widths.iter().sum::<usize>() + GAP * widths.len().saturating_sub(1)
+        };
+
+        while total(&widths) > max {
+            let widest = widths
+                .iter()
+                .enumerate()
+                .filter(|(i, w)| self.columns[*i].align == Align::Left && **w > MIN_WIDTH)
+                .max_by_key(|(_, w)| **w)
+                .map(|(i, _)| i);
+
+            match widest {
+                Some(i) => widths[i] -= 1,
+                None => break,
+            }
+        }
+
+        widths
+    }
+}
+
+/// Smallest width a column is truncated to.
+const MIN_WIDTH: usize = 8;
+
+/// Truncate the passed text to `width` characters, ending in `…` if it was
+/// truncated.
+fn truncate(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_owned();
+    }
+
+    let mut ret = text
+        .chars()
+        .take(width.saturating_sub(1))
+        .collect::<String>();
+    ret.push('…');
+
+    ret
+}
@@ -0,0 +1,440 @@
+use std::collections::BTreeMap;
+use std::fmt::{Formatter, Result as FmtResult};
+use std::fs::read_to_string;
+
+use serde::{
+    de::{Error as DeError, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+use crate::{
+    data::{ItemType, RecipeType},
+    error::Error,
+    graph::{DepthColors, RecipeFlag, Rgb},
+};
+
+/// Themes that are embedded into the binary and can be selected by name.
+pub const PRESETS: &[(&str, &str)] = &[
+    ("default", include_str!("../themes/default.toml")),
+    ("dark", include_str!("../themes/dark.toml")),
+];
+
+/* Theme */
+
+/// Styling of the production graph: the attributes of the nodes and edges,
+/// and the colors of the highlighted items.
+///
+/// Themes are loaded from TOML files (see `themes/default.toml` for the
+/// built-in theme). Entries that are missing in a theme fall back to the
+/// built-in theme, so the loaded theme is always complete and is used as is
+/// by the graph writers.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    /// Attributes of the graph.
+    pub graph: Attributes,
+
+    /// Attributes of all items.
+    pub item: Attributes,
+
+    /// Attributes of all recipes.
+    pub recipe: Attributes,
+
+    /// Attributes of all edges.
+    pub edge: Attributes,
+
+    /// Attributes of the items of a type, applied after [`Theme::item`].
+    pub item_types: BTreeMap<String, Attributes>,
+
+    /// Attributes of the recipes of a type, applied after [`Theme::recipe`].
+    pub recipe_types: BTreeMap<String, Attributes>,
+
+    /// Attributes of the recipes with a flag, by the name of the flag (see
+    /// [`RecipeFlag::name`]). Applied after the recipe types.
+    pub recipe_flags: BTreeMap<String, Attributes>,
+
+    /// Attributes of the items that are not produced by any recipe of the
+    /// graph.
+    pub source: Attributes,
+
+    /// Attributes of the copies of the items that are consumed by many
+    /// recipes.
+    pub copy: Attributes,
+
+    /// Gradient of the nodes that are colored by depth.
+    pub depth_colors: DepthColors,
+}
+
+impl Theme {
+    /// Whether the passed theme is the path of a theme file instead of the
+    /// name of a built-in theme.
+    pub fn is_file(theme: &str) -> bool {
+        !PRESETS.iter().any(|(name, _)| *name == theme)
+    }
+
+    /// Load the built-in theme with the passed name (see [`PRESETS`]), or
+    /// the theme file with the passed path.
+    pub fn load(theme: &str) -> Result<Self, Error> {
+        if let Some((_, source)) = PRESETS.iter().find(|(name, _)| *name == theme) {
+            return Self::parse(source, theme);
+        }
+
+        log::info!("Load theme from {:#?}", theme);
+        let source = read_to_string(theme)
+            .map_err(|err| Error::custom(format!("Unable to read theme {:#?}: {}", theme, err)))?;
+
+        Self::parse(&source, theme)
+    }
+
+    fn parse(source: &str, name: &str) -> Result<Self, Error> {
+        let file = toml::from_str::<ThemeFile>(source)
+            .map_err(|err| Error::usage(format!("Invalid theme {:#?}: {}", name, err)))?;
+
+        for key in file.unknown_keys() {
+            log::warn!("Unknown key in theme {:#?}: {}", name, key);
+        }
+
+        let mut theme = Self::default();
+        theme.apply(file);
+
+        Ok(theme)
+    }
+
+    /// Attributes of the items of the passed type.
+    pub fn item_type(&self, type_: &ItemType) -> Option<&Attributes> {
+        self.item_types.get(&type_.to_string().to_uppercase())
+    }
+
+    /// Attributes of the recipes of the passed type.
+    pub fn recipe_type(&self, type_: &RecipeType) -> Option<&Attributes> {
+        self.recipe_types.get(&type_.to_string().to_uppercase())
+    }
+
+    fn apply(&mut self, file: ThemeFile) {
+        self.graph.extend(file.graph);
+        self.item.extend(file.item);
+        self.recipe.extend(file.recipe);
+        self.edge.extend(file.edge);
+
+        for (type_, attributes) in file.item_types {
+            self.item_types
+                .entry(type_.to_uppercase())
+                .or_default()
+                .extend(attributes);
+        }
+        for (type_, attributes) in file.recipe_types {
+            self.recipe_types
+                .entry(type_.to_uppercase())
+                .or_default()
+                .extend(attributes);
+        }
+
+        for (flag, attributes) in file.recipe_flags {
+            self.recipe_flags
+                .entry(flag.to_lowercase())
+                .or_default()
+                .extend(attributes);
+        }
+
+        self.source.extend(file.highlight.source);
+        self.copy.extend(file.highlight.copy);
+
+        if let Some(start) = file.depth_colors.start {
+            self.depth_colors.start = start;
+        }
+        if let Some(end) = file.depth_colors.end {
+            self.depth_colors.end = end;
+        }
+    }
+}
+
+impl Default for Theme {
+    /// The built-in theme, see `themes/default.toml`.
+    fn default() -> Self {
+        let file = toml::from_str::<ThemeFile>(PRESETS[0].1).expect("Invalid built-in theme");
+
+        let mut theme = Self {
+            graph: Attributes::default(),
+            item: Attributes::default(),
+            recipe: Attributes::default(),
+            edge: Attributes::default(),
+            item_types: BTreeMap::new(),
+            recipe_types: BTreeMap::new(),
+            recipe_flags: BTreeMap::new(),
+            source: Attributes::default(),
+            copy: Attributes::default(),
+            depth_colors: DepthColors::default(),
+        };
+        theme.apply(file);
+
+        theme
+    }
+}
+
+/* ThemeFile */
+
+/// Content of a theme file, all entries are optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ThemeFile {
+    graph: Attributes,
+    item: Attributes,
+    recipe: Attributes,
+    edge: Attributes,
+    item_types: BTreeMap<String, Attributes>,
+    recipe_types: BTreeMap<String, Attributes>,
+    recipe_flags: BTreeMap<String, Attributes>,
+    highlight: HighlightFile,
+    depth_colors: DepthColorsFile,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct HighlightFile {
+    source: Attributes,
+    copy: Attributes,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct DepthColorsFile {
+    start: Option<Rgb>,
+    end: Option<Rgb>,
+
+    #[serde(flatten)]
+    unknown: BTreeMap<String, toml::Value>,
+}
+
+impl ThemeFile {
+    fn unknown_keys(&self) -> impl Iterator<Item = String> + '_ {
+        let highlight = self
+            .highlight
+            .unknown
+            .keys()
+            .map(|k| format!("highlight.{}", k));
+        let depth_colors = self
+            .depth_colors
+            .unknown
+            .keys()
+            .map(|k| format!("depth_colors.{}", k));
+
+        let recipe_flags = self
+            .recipe_flags
+            .keys()
+            .filter(|flag| {
+                !RecipeFlag::ALL
+                    .iter()
+                    .any(|f| f.name().eq_ignore_ascii_case(flag))
+            })
+            .map(|flag| format!("recipe_flags.{}", flag));
+
+        self.unknown
+            .keys()
+            .cloned()
+            .chain(highlight)
+            .chain(depth_colors)
+            .chain(recipe_flags)
+    }
+}
+
+/* Attributes */
+
+/// Graphviz attributes, in the order they were defined.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct Attributes(Vec<(String, String)>);
+
+impl Attributes {
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str)> {
+        self.0.iter().map(|(k, v)| (k.as_str(), v.as_str()))
+    }
+
+    pub fn get(&self, key: &str) -> Option<&str> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    /// Set the passed attribute, replacing an earlier value.
+    pub fn set(&mut self, key: &str, value: &str) {
+        match self.0.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => value.clone_into(v),
+            None => self.0.push((key.to_owned(), value.to_owned())),
+        }
+    }
+
+    /// Set all attributes of `other`.
+    pub fn extend(&mut self, other: Attributes) {
+        for (key, value) in &other.0 {
+            self.set(key, value);
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Attributes {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(AttributesVisitor)
+    }
+}
+
+struct AttributesVisitor;
+
+impl<'de> Visitor<'de> for AttributesVisitor {
+    type Value = Attributes;
+
+    fn expecting(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "a table of graphviz attributes")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+        let mut ret = Attributes::default();
+
+        while let Some((key, value)) = map.next_entry::<String, toml::Value>()? {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Integer(i) => i.to_string(),
+                toml::Value::Float(f) => f.to_string(),
+                toml::Value::Boolean(b) => b.to_string(),
+                _ => {
+                    return Err(A::Error::custom(format!(
+                        "invalid value of attribute {} (expected a string, number or boolean)",
+                        key
+                    )))
+                }
+            };
+
+            ret.set(&key, &value);
+        }
+
+        Ok(ret)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        data::{Data, ItemId},
+        error::EXIT_USAGE,
+        graph::{write_dot, DotOptions, GraphBuilder},
+    };
+
+    /// Theme with an entry in each table and an unknown key on each level.
+    const SOURCE: &str = r##"
+        colors = "none"
+
+        [graph]
+        bgcolor = "black"
+
+        [item]
+        fontname = "Sans"
+
+        [item_types.material]
+        color = "orange"
+
+        [recipe_types.SMELT]
+        shape = "box"
+
+        [recipe_flags.Explicit]
+        penwidth = 2
+
+        [recipe_flags.fast]
+        color = "red"
+
+        [highlight.source]
+        fillcolor = "#000000"
+        width = 2
+
+        [highlight.target]
+        color = "red"
+
+        [depth_colors]
+        start = "#ff0000"
+        middle = "#00ff00"
+    "##;
+
+    #[test]
+    fn theme_is_merged_with_the_built_in_theme() {
+        let theme = Theme::parse(SOURCE, "test").unwrap();
+
+        assert_eq!(theme.graph.get("rankdir"), Some("LR"));
+        assert_eq!(theme.graph.get("bgcolor"), Some("black"));
+        assert_eq!(theme.item.get("fontname"), Some("Sans"));
+        assert_eq!(theme.item_types["MATERIAL"].get("color"), Some("orange"));
+        assert_eq!(theme.recipe_types["SMELT"].get("shape"), Some("box"));
+        assert_eq!(theme.recipe_flags["explicit"].get("penwidth"), Some("2"));
+        assert_eq!(theme.recipe_flags["explicit"].get("peripheries"), Some("2"));
+        assert_eq!(theme.source.get("shape"), Some("house"));
+        assert_eq!(theme.source.get("fillcolor"), Some("#000000"));
+        assert_eq!(theme.source.get("width"), Some("2"));
+        assert_eq!(theme.depth_colors.start, Rgb(0xff, 0x00, 0x00));
+        assert_eq!(theme.depth_colors.end, Rgb(0x9f, 0xc5, 0xe8));
+    }
+
+    #[test]
+    fn unknown_keys_are_reported() {
+        let file = toml::from_str::<ThemeFile>(SOURCE).unwrap();
+
+        assert_eq!(
+            file.unknown_keys().collect::<Vec<_>>(),
+            vec![
+                "colors",
+                "highlight.target",
+                "depth_colors.middle",
+                "recipe_flags.fast"
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_attributes_are_usage_errors() {
+        let err = Theme::parse("[item]\ncolor = [1, 2]", "test").unwrap_err();
+
+        assert_eq!(err.exit_code(), EXIT_USAGE);
+    }
+
+    #[test]
+    fn dot_output_uses_the_theme() {
+        let data =
+            Data::from_lua_source("small", include_str!("../tests/data/small.lua"), None, &[])
+                .unwrap();
+        let graph = GraphBuilder::new(&data)
+            .targets([ItemId(3)])
+            .resolve_deps(true)
+            .build()
+            .unwrap();
+        let options = DotOptions {
+            theme: Theme::parse(SOURCE, "test").unwrap(),
+            source_style: true,
+            ..DotOptions::default()
+        };
+
+        let mut out = Vec::new();
+        write_dot(&graph, &options, &mut out).unwrap();
+        let dot = String::from_utf8(out).unwrap();
+
+        assert!(
+            dot.contains("graph [ rankdir=LR bgcolor=black ]"),
+            "{}",
+            dot
+        );
+        assert!(
+            dot.contains(
+                r##""Ore" [ fontname=Sans shape=house style=filled fillcolor="#000000" width=2 ]"##
+            ),
+            "{}",
+            dot
+        );
+        assert!(
+            dot.contains(r#""Ingot" [ fontname=Sans color=orange ]"#),
+            "{}",
+            dot
+        );
+        assert!(dot.contains(r#""Gear" [ fontname=Sans ]"#), "{}", dot);
+        assert!(dot.contains(r#""1" [ label="1" shape=box "#), "{}", dot);
+        assert!(dot.contains(r#""2" [ label="1" shape=point "#), "{}", dot);
+    }
+}
@@ -0,0 +1,12 @@
+-- Item 1 and recipe 1 share an id, and item 2 and recipe 1 share a name.
+return {
+  game_items = {
+    { id = 1, name = "Ore", type = "RESOURCE" },
+    { id = 2, name = "Ingot", type = "MATERIAL" },
+    { id = 3, name = "Gear", type = "COMPONENT" },
+  },
+  game_recipes = {
+    { id = 1, name = "Ingot", type = "SMELT", seconds = 1, inputs = { 1, 1 }, outputs = { 2, 1 } },
+    { id = 2, name = "Gear", type = "ASSEMBLE", seconds = 1, inputs = { 2, 2 }, outputs = { 3, 1 } },
+  },
+}
@@ -0,0 +1 @@
+return { game_items = {
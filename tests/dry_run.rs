@@ -0,0 +1,60 @@
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join(name)
+}
+
+/// Run `create-production-graph --dry-run` for the passed item selectors on
+/// the small data file and return the report of the tool.
+fn dry_run(items: &[&str]) -> String {
+    let mut cmd = Command::new(env!("CARGO_BIN_EXE_dsp-tool"));
+    cmd.args(["--no-config", "--no-cache", "--quiet", "--data"])
+        .arg(fixture("small.lua"))
+        .args(["create-production-graph", "--no-interactive", "--dry-run"]);
+    for item in items {
+        cmd.args(["--items", item]);
+    }
+
+    let output = cmd.output().expect("failed to run dsp-tool");
+    assert!(output.status.success(), "{:?}", output);
+
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[test]
+fn negation_reports_removed_entries() {
+    let report = dry_run(&["Gear,Ingot", "!re:^(Gear|Ore)$", "!Ore"]);
+    let lines = report.lines().collect::<Vec<_>>();
+
+    assert_eq!(lines[0], "--items Gear: 1 item, 0 recipes (Gear)");
+    assert_eq!(lines[1], "--items Ingot: 1 item, 0 recipes (Ingot)");
+    assert_eq!(
+        lines[2],
+        "--items !re:^(Gear|Ore)$: removes 1 item, 0 recipes (Gear)"
+    );
+    assert_eq!(
+        lines[3],
+        "--items !Ore: removes nothing, none of its entries is selected"
+    );
+    assert_eq!(lines[5], "Graph: 3 nodes (2 items, 1 recipe), 2 edges");
+}
+
+#[test]
+fn plural_of_multiple_entries() {
+    let report = dry_run(&["*"]);
+
+    assert!(
+        report.starts_with("--items *: 3 items, 0 recipes (Ore, Ingot, Gear)"),
+        "{}",
+        report
+    );
+    assert!(
+        report.ends_with("Graph: 5 nodes (3 items, 2 recipes), 4 edges\n"),
+        "{}",
+        report
+    );
+}
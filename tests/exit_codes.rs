@@ -0,0 +1,98 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use dsp_tool::error::{EXIT_DATA, EXIT_IO, EXIT_USAGE};
+
+fn fixture(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("data")
+        .join(name)
+}
+
+/// Run `create-production-graph` for the passed items on the data file and
+/// return the exit code of the tool.
+fn run(data: &Path, items: &str) -> i32 {
+    exit_code(
+        data,
+        &[
+            "create-production-graph",
+            "--no-interactive",
+            "--items",
+            items,
+        ],
+    )
+}
+
+/// Run the tool with the passed arguments on the data file and return its
+/// exit code.
+fn exit_code(data: &Path, args: &[&str]) -> i32 {
+    let output = Command::new(env!("CARGO_BIN_EXE_dsp-tool"))
+        .args(["--no-config", "--no-cache", "--quiet", "--data"])
+        .arg(data)
+        .args(args)
+        .output()
+        .expect("failed to run dsp-tool");
+
+    output
+        .status
+        .code()
+        .expect("dsp-tool was killed by a signal")
+}
+
+#[test]
+fn missing_data_file() {
+    assert_eq!(run(&fixture("does_not_exist.lua"), "Gear"), EXIT_IO);
+}
+
+#[test]
+fn lua_syntax_error() {
+    assert_eq!(run(&fixture("syntax_error.lua"), "Gear"), EXIT_DATA);
+}
+
+#[test]
+fn unknown_selector() {
+    assert_eq!(run(&fixture("small.lua"), "Nope"), EXIT_USAGE);
+}
+
+#[test]
+fn empty_selection_after_negation() {
+    assert_eq!(run(&fixture("small.lua"), "Gear,!Gear"), EXIT_USAGE);
+}
+
+#[test]
+fn success() {
+    assert_eq!(run(&fixture("small.lua"), "Gear"), 0);
+}
+
+#[test]
+fn invalid_plan_targets() {
+    let data = fixture("small.lua");
+    for target in ["Gear=-5", "Gear=0", "Gear=NaN", "Gear=inf"] {
+        assert_eq!(
+            exit_code(&data, &["plan", "-t", target]),
+            EXIT_USAGE,
+            "{}",
+            target
+        );
+    }
+
+    assert_eq!(exit_code(&data, &["plan"]), EXIT_USAGE);
+    assert_eq!(exit_code(&data, &["plan", "-t", "Gear=60"]), 0);
+}
+
+#[test]
+fn closed_stdout() {
+    // The script is larger than the pipe buffer, so writing it fails once the
+    // reading end is closed.
+    let mut child = Command::new(env!("CARGO_BIN_EXE_dsp-tool"))
+        .args(["--no-config", "completions", "bash"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .expect("failed to run dsp-tool");
+    drop(child.stdout.take());
+
+    let status = child.wait().expect("failed to wait for dsp-tool");
+    assert_eq!(status.code(), Some(0));
+}